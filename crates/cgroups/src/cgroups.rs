@@ -1,21 +1,75 @@
 use crate::error::{CGroupsError, Result};
 use crate::filesystem::{FileSystem, RealFileSystem};
 use melon_common::log;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Directory the cgroups are created in, unless the builder is given another one
+pub const BASE_CGROUP_PATH: &str = "/sys/fs/cgroup/melon";
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// How long a forced removal waits for the killed processes to exit
+const KILL_TIMEOUT: Duration = Duration::from_secs(1);
+const KILL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The cgroup hierarchy mounted at `/sys/fs/cgroup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CGroupVersion {
+    /// Separate hierarchies per controller, or a hybrid setup
+    V1,
+    /// The unified hierarchy
+    V2,
+}
+
+/// Detects the cgroup version of the host, `None` if no cgroup filesystem is mounted.
+pub fn detect_version() -> Option<CGroupVersion> {
+    detect_version_with(&RealFileSystem)
+}
+
+/// Fails unless the host has the unified cgroup v2 hierarchy, which is all this module supports.
+pub fn require_v2() -> Result<()> {
+    match detect_version() {
+        Some(CGroupVersion::V2) => Ok(()),
+        Some(CGroupVersion::V1) => Err(CGroupsError::V2Required(format!(
+            "{} is a cgroup v1 hierarchy",
+            CGROUP_ROOT
+        ))),
+        None => Err(CGroupsError::V2Required(format!(
+            "no cgroup filesystem is mounted at {}",
+            CGROUP_ROOT
+        ))),
+    }
+}
 
-const BASE_CGROUP_PATH: &str = "/sys/fs/cgroup/melon";
+/// Only the root of the unified hierarchy lists its controllers in `cgroup.controllers`.
+pub(crate) fn detect_version_with(fs: &dyn FileSystem) -> Option<CGroupVersion> {
+    let root = Path::new(CGROUP_ROOT);
+    if fs.exists(&root.join("cgroup.controllers")) {
+        Some(CGroupVersion::V2)
+    } else if fs.exists(root) {
+        Some(CGroupVersion::V1)
+    } else {
+        None
+    }
+}
 
 /// # CGroups V2 Management Module
 ///
 /// This module provides a high-level interface for managing Linux Control Groups (cgroups).
 /// It allows for easy creation and manipulation of cgroups, including setting CPU, memory,
-/// and I/O constraints, as well as adding processes to these groups.
+/// swap and I/O constraints, as well as adding processes to these groups.
 #[derive(Default)]
 pub struct CGroupsBuilder {
     name: Option<String>,
+    base_path: Option<PathBuf>,
     cpus: Option<String>,
+    cpu_weight: Option<u64>,
     memory: Option<u64>,
+    swap: Option<u64>,
     io: Option<String>,
+    force_remove: bool,
     fs: Option<Box<dyn FileSystem>>,
 }
 
@@ -34,30 +88,60 @@ impl CGroupsBuilder {
         self
     }
 
+    /// Sets the directory the cgroup is created in, e.g. a delegated subtree inside a container.
+    pub fn with_base_path<P: Into<PathBuf>>(mut self, base_path: P) -> Self {
+        self.base_path = Some(base_path.into());
+        self
+    }
+
     pub fn with_cpu(mut self, cpus: &str) -> Self {
         self.cpus = Some(cpus.to_string());
         self
     }
 
+    /// Sets the share of CPU time the group gets under contention, from 1 to 10000 (100 is the kernel default).
+    pub fn with_cpu_weight(mut self, weight: u64) -> Self {
+        self.cpu_weight = Some(weight);
+        self
+    }
+
     pub fn with_memory(mut self, memory_bytes: u64) -> Self {
         self.memory = Some(memory_bytes);
         self
     }
 
+    /// Limits the swap the group may use, 0 keeps it from swapping at all.
+    pub fn with_swap(mut self, swap_bytes: u64) -> Self {
+        self.swap = Some(swap_bytes);
+        self
+    }
+
     pub fn with_io(mut self, io: &str) -> Self {
         self.io = Some(io.to_string());
         self
     }
 
+    /// Kills processes that are left in the cgroup when it is dropped, instead of leaving it behind.
+    pub fn with_force_remove(mut self, force_remove: bool) -> Self {
+        self.force_remove = force_remove;
+        self
+    }
+
     pub fn build(self) -> Result<CGroups> {
         let name = self
             .name
             .ok_or_else(|| CGroupsError::InvalidCGroupName("Group name is required".to_string()))?;
         Ok(CGroups {
             name,
+            base_path: self
+                .base_path
+                .unwrap_or_else(|| PathBuf::from(BASE_CGROUP_PATH)),
             cpus: self.cpus,
+            cpu_weight: self.cpu_weight,
             memory: self.memory,
+            swap: self.swap,
             io: self.io,
+            force_remove: self.force_remove,
             fs: self.fs.unwrap_or_else(|| Box::new(RealFileSystem)),
         })
     }
@@ -66,12 +150,20 @@ impl CGroupsBuilder {
 pub struct CGroups {
     /// The cgroup name
     name: String,
+    /// The directory the cgroup is created in
+    base_path: PathBuf,
     /// The allocated CPUs, eg. 0,1,4
     cpus: Option<String>,
+    /// The CPU weight
+    cpu_weight: Option<u64>,
     /// The memory in bytes
     memory: Option<u64>,
+    /// The swap in bytes
+    swap: Option<u64>,
     /// The io limits
     io: Option<String>,
+    /// Whether dropping the cgroup kills its remaining processes
+    force_remove: bool,
     /// Filesystem for testing
     fs: Box<dyn FileSystem>,
 }
@@ -82,26 +174,62 @@ impl CGroups {
         &self.name
     }
 
+    /// Get the directory the cgroup is created in
+    pub fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
     /// Get the allocated CPUs
     pub fn cpus(&self) -> Option<&str> {
         self.cpus.as_deref()
     }
 
+    /// Get the CPU weight
+    pub fn cpu_weight(&self) -> Option<u64> {
+        self.cpu_weight
+    }
+
     /// Get the memory in bytes
     pub fn memory(&self) -> Option<u64> {
         self.memory
     }
 
+    /// Get the swap in bytes
+    pub fn swap(&self) -> Option<u64> {
+        self.swap
+    }
+
     /// Get the io limits
     pub fn io(&self) -> Option<&str> {
         self.io.as_deref()
     }
 }
 
+// the filesystem is left out, it's only swapped for tests
+impl std::fmt::Debug for CGroups {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CGroups")
+            .field("name", &self.name)
+            .field("base_path", &self.base_path)
+            .field("cpus", &self.cpus)
+            .field("cpu_weight", &self.cpu_weight)
+            .field("memory", &self.memory)
+            .field("swap", &self.swap)
+            .field("io", &self.io)
+            .field("force_remove", &self.force_remove)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Drop for CGroups {
     fn drop(&mut self) {
         // todo: handle errors
-        match self.remove() {
+        let removed = if self.force_remove {
+            self.remove_force()
+        } else {
+            self.remove()
+        };
+        match removed {
             Ok(_) => {
                 log!(info, "Removed cgroup {}", self.name);
             }
@@ -134,7 +262,7 @@ impl CGroups {
 
     #[tracing::instrument(level = "info", name = "Create new cgroup" skip(self))]
     pub fn create(&self) -> Result<()> {
-        let path = PathBuf::from(BASE_CGROUP_PATH).join(&self.name);
+        let path = self.path();
         self.fs.create_dir_all(&path).map_err(|e| {
             let error_msg = format!("Failed to create directory at {:?}: {}", path, e);
             log!(error, "{}", error_msg);
@@ -145,7 +273,11 @@ impl CGroups {
         if self.cpus.is_some() {
             controllers.push("+cpuset");
         }
-        if self.memory.is_some() {
+        if self.cpu_weight.is_some() {
+            controllers.push("+cpu");
+        }
+        // swap is accounted by the memory controller
+        if self.memory.is_some() || self.swap.is_some() {
             controllers.push("+memory");
         }
         if self.io.is_some() {
@@ -198,6 +330,24 @@ impl CGroups {
                 })?;
         }
 
+        if let Some(weight) = self.cpu_weight {
+            self.fs
+                .write(&path.join("cpu.weight"), weight.to_string().as_bytes())
+                .map_err(|e| {
+                    log!(error, "Could not write CPU weight {}: {}", weight, e);
+                    CGroupsError::CGroupWriteFailed(e)
+                })?;
+        }
+
+        if let Some(swap) = self.swap {
+            self.fs
+                .write(&path.join("memory.swap.max"), swap.to_string().as_bytes())
+                .map_err(|e| {
+                    log!(error, "Could not write swap {}: {}", swap, e);
+                    CGroupsError::CGroupWriteFailed(e)
+                })?;
+        }
+
         if let Some(io) = &self.io {
             self.fs
                 .write(&path.join("io.max"), io.as_bytes())
@@ -212,18 +362,57 @@ impl CGroups {
 
     #[tracing::instrument(level = "info", name = "Add process to cgroup" skip(self))]
     pub fn add_process(&self, pid: u32) -> Result<()> {
-        let path = PathBuf::from(BASE_CGROUP_PATH)
-            .join(&self.name)
-            .join("cgroup.procs");
+        let path = self.path().join("cgroup.procs");
         self.fs
             .append(&path, format!("{}\n", pid).as_bytes())
             .map_err(CGroupsError::AddProcessFailed)?;
         Ok(())
     }
 
+    /// Returns how many processes of the cgroup were killed by the OOM killer.
+    ///
+    /// Reads the `oom_kill` counter from `memory.events`.
+    pub fn oom_kill_count(&self) -> Result<u64> {
+        let events = self.read_file("memory.events")?;
+        Ok(find_key(&events, "oom_kill").unwrap_or(0))
+    }
+
+    /// Returns the highest memory usage of the cgroup in bytes.
+    ///
+    /// Reads `memory.peak`, which is available since Linux 5.19.
+    pub fn memory_peak(&self) -> Result<u64> {
+        let peak = self.read_file("memory.peak")?;
+        peak.trim().parse().map_err(|_| {
+            CGroupsError::CGroupReadFailed(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid memory.peak value {}", peak.trim()),
+            ))
+        })
+    }
+
+    /// Returns the CPU time used by all processes of the cgroup in microseconds.
+    ///
+    /// Reads the `usage_usec` counter from `cpu.stat`.
+    pub fn cpu_usage_usec(&self) -> Result<u64> {
+        let stat = self.read_file("cpu.stat")?;
+        find_key(&stat, "usage_usec").ok_or_else(|| {
+            CGroupsError::CGroupReadFailed(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "No usage_usec in cpu.stat",
+            ))
+        })
+    }
+
+    fn read_file(&self, file: &str) -> Result<String> {
+        let path = self.path().join(file);
+        self.fs
+            .read_to_string(&path)
+            .map_err(CGroupsError::CGroupReadFailed)
+    }
+
     #[tracing::instrument(level = "info", name = "Remove cgroup" skip(self))]
     pub fn remove(&self) -> Result<()> {
-        let path = PathBuf::from(BASE_CGROUP_PATH).join(&self.name);
+        let path = self.path();
 
         if !self.fs.exists(&path) {
             log!(error, "Cgroup path does not exist {:?}", path);
@@ -248,6 +437,50 @@ impl CGroups {
         Ok(())
     }
 
+    /// Kills the processes left in the cgroup, e.g. orphans of the job, and removes it.
+    ///
+    /// Writes to `cgroup.kill`, which exists since Linux 5.14, and sends SIGKILL to every
+    /// process in `cgroup.procs` on older kernels. Blocks for up to a second while the
+    /// processes die, async callers should run it, or drop the cgroup, on a blocking thread.
+    #[tracing::instrument(level = "info", name = "Force remove cgroup" skip(self))]
+    pub fn remove_force(&self) -> Result<()> {
+        let path = self.path();
+        if self.fs.exists(&path) && self.has_running_processes(&path)? {
+            if let Err(e) = self.fs.write(&path.join("cgroup.kill"), b"1") {
+                log!(
+                    warn,
+                    "Could not write cgroup.kill of {}, kill its processes one by one: {}",
+                    self.name,
+                    e
+                );
+                self.kill_processes(&path)?;
+            }
+            let deadline = Instant::now() + KILL_TIMEOUT;
+            while self.has_running_processes(&path)? && Instant::now() < deadline {
+                std::thread::sleep(KILL_POLL_INTERVAL);
+            }
+        }
+        self.remove()
+    }
+
+    fn kill_processes(&self, path: &Path) -> Result<()> {
+        let procs = self
+            .fs
+            .read_to_string(&path.join("cgroup.procs"))
+            .map_err(CGroupsError::CGroupReadFailed)?;
+        for pid in procs.split_whitespace().filter_map(|pid| pid.parse().ok()) {
+            if let Err(e) = kill(Pid::from_raw(pid), Signal::SIGKILL) {
+                log!(warn, "Could not kill process {}: {}", pid, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// The directory of the cgroup
+    fn path(&self) -> PathBuf {
+        self.base_path.join(&self.name)
+    }
+
     fn process_exists(&self, pid: i32) -> bool {
         let proc_stat_path = PathBuf::from(format!("/proc/{}/stat", pid));
         self.fs.exists(&proc_stat_path)
@@ -272,3 +505,12 @@ impl CGroups {
         Ok(false)
     }
 }
+
+/// Finds the value of a key in a flat keyed cgroup file like `memory.events` or `cpu.stat`.
+fn find_key(content: &str, key: &str) -> Option<u64> {
+    content
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .find(|(k, _)| *k == key)
+        .and_then(|(_, value)| value.trim().parse().ok())
+}
@@ -2,7 +2,7 @@
 mod tests {
     use crate::error::CGroupsError;
     use crate::filesystem::FileSystem;
-    use crate::CGroups;
+    use crate::{detect_version_with, CGroupVersion, CGroups};
     use std::collections::HashMap;
     use std::io::{Error, ErrorKind, Result};
     use std::path::Path;
@@ -38,9 +38,12 @@ mod tests {
         }
 
         fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
-            let path = path.to_path_buf();
             let mut files = self.files.lock().unwrap();
-            files.insert(path, contents.to_vec());
+            // like the kernel, killing the cgroup ends all processes
+            if path.ends_with("cgroup.kill") {
+                files.retain(|k, _| !k.starts_with("/proc"));
+            }
+            files.insert(path.to_path_buf(), contents.to_vec());
             Ok(())
         }
 
@@ -102,12 +105,37 @@ mod tests {
             .with_cpu("0-1")
             .with_memory(1024 * 1024)
             .with_io("8:0 rbps=1048576")
+            .with_swap(0)
+            .with_cpu_weight(200)
             .build()
             .unwrap();
         assert_eq!(cgroup.name(), "test_cgroup");
+        assert_eq!(cgroup.base_path(), Path::new("/sys/fs/cgroup/melon"));
         assert_eq!(cgroup.cpus(), Some("0-1"));
         assert_eq!(cgroup.memory(), Some(1024 * 1024));
         assert_eq!(cgroup.io(), Some("8:0 rbps=1048576"));
+        assert_eq!(cgroup.swap(), Some(0));
+        assert_eq!(cgroup.cpu_weight(), Some(200));
+    }
+
+    #[test]
+    fn test_detect_cgroup_version() {
+        let mock_fs = setup_mock_fs();
+        assert_eq!(detect_version_with(&mock_fs), None);
+
+        mock_fs.create_dir_all(Path::new("/sys/fs/cgroup")).unwrap();
+        mock_fs
+            .create_dir_all(Path::new("/sys/fs/cgroup/memory"))
+            .unwrap();
+        assert_eq!(detect_version_with(&mock_fs), Some(CGroupVersion::V1));
+
+        mock_fs
+            .write(
+                Path::new("/sys/fs/cgroup/cgroup.controllers"),
+                b"cpuset cpu io memory pids",
+            )
+            .unwrap();
+        assert_eq!(detect_version_with(&mock_fs), Some(CGroupVersion::V2));
     }
 
     #[test]
@@ -204,6 +232,107 @@ mod tests {
             .is_err());
     }
 
+    #[test]
+    fn test_cgroup_creation_with_swap_and_cpu_weight() {
+        let mock_fs = setup_mock_fs();
+        let cgroup = CGroups::build()
+            .name("test_cgroup")
+            .with_cpu("0-1")
+            .with_cpu_weight(200)
+            .with_swap(0)
+            .with_fs(mock_fs.clone())
+            .build()
+            .unwrap();
+
+        assert!(cgroup.create().is_ok());
+
+        let swap_content = String::from_utf8(
+            mock_fs
+                .read(Path::new(
+                    "/sys/fs/cgroup/melon/test_cgroup/memory.swap.max",
+                ))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(swap_content, "0");
+        let weight_content = String::from_utf8(
+            mock_fs
+                .read(Path::new("/sys/fs/cgroup/melon/test_cgroup/cpu.weight"))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(weight_content, "200");
+        // the swap limit needs the memory controller even without a memory limit
+        let controllers_content = String::from_utf8(
+            mock_fs
+                .read(Path::new(
+                    "/sys/fs/cgroup/melon/test_cgroup/cgroup.subtree_control",
+                ))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(controllers_content, "+cpuset +cpu +memory");
+        assert!(mock_fs
+            .read(Path::new("/sys/fs/cgroup/melon/test_cgroup/memory.max"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_cgroup_creation_in_base_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let cgroup = CGroups::build()
+            .name("test_cgroup")
+            .with_base_path(dir.path())
+            .with_cpu("0-1")
+            .with_memory(1024 * 1024)
+            .build()
+            .unwrap();
+
+        cgroup.create().unwrap();
+
+        let path = dir.path().join("test_cgroup");
+        assert_eq!(
+            std::fs::read_to_string(path.join("cpuset.cpus")).unwrap(),
+            "0-1"
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.join("memory.max")).unwrap(),
+            "1048576"
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.join("cgroup.subtree_control")).unwrap(),
+            "+cpuset +memory"
+        );
+    }
+
+    #[test]
+    fn test_nested_base_path() {
+        let mock_fs = setup_mock_fs();
+        let base_path = Path::new("/sys/fs/cgroup/session/melon");
+        let cgroup = CGroups::build()
+            .name("test_cgroup")
+            .with_base_path(base_path)
+            .with_fs(mock_fs.clone())
+            .build()
+            .unwrap();
+
+        cgroup.create().unwrap();
+        mock_fs
+            .write(&base_path.join("test_cgroup/cgroup.procs"), b"")
+            .unwrap();
+        cgroup.add_process(1234).unwrap();
+        assert_eq!(
+            mock_fs
+                .read_to_string(&base_path.join("test_cgroup/cgroup.procs"))
+                .unwrap(),
+            "1234\n"
+        );
+        assert!(!mock_fs.exists(Path::new("/sys/fs/cgroup/melon/test_cgroup")));
+
+        assert!(cgroup.remove().is_ok());
+        assert!(!mock_fs.exists(&base_path.join("test_cgroup")));
+    }
+
     #[test]
     fn test_add_process() {
         let mock_fs = setup_mock_fs();
@@ -227,6 +356,70 @@ mod tests {
         assert_eq!(procs_content, "1234\n5678\n");
     }
 
+    #[test]
+    fn test_oom_kill_count() {
+        let mock_fs = setup_mock_fs();
+        let cgroup = CGroups::build()
+            .name("test_cgroup")
+            .with_fs(mock_fs.clone())
+            .with_memory(1024 * 1024)
+            .build()
+            .unwrap();
+
+        cgroup.create().unwrap();
+        mock_fs
+            .write(
+                Path::new("/sys/fs/cgroup/melon/test_cgroup/memory.events"),
+                b"low 0\nhigh 0\nmax 3\noom 1\noom_kill 1\noom_group_kill 0\n",
+            )
+            .unwrap();
+
+        assert_eq!(cgroup.oom_kill_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_oom_kill_count_missing_events() {
+        let mock_fs = setup_mock_fs();
+        let cgroup = CGroups::build()
+            .name("test_cgroup")
+            .with_fs(mock_fs.clone())
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            cgroup.oom_kill_count(),
+            Err(CGroupsError::CGroupReadFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_resource_usage() {
+        let mock_fs = setup_mock_fs();
+        let cgroup = CGroups::build()
+            .name("test_cgroup")
+            .with_fs(mock_fs.clone())
+            .with_memory(1024 * 1024)
+            .build()
+            .unwrap();
+
+        cgroup.create().unwrap();
+        mock_fs
+            .write(
+                Path::new("/sys/fs/cgroup/melon/test_cgroup/memory.peak"),
+                b"524288\n",
+            )
+            .unwrap();
+        mock_fs
+            .write(
+                Path::new("/sys/fs/cgroup/melon/test_cgroup/cpu.stat"),
+                b"usage_usec 2500000\nuser_usec 2000000\nsystem_usec 500000\n",
+            )
+            .unwrap();
+
+        assert_eq!(cgroup.memory_peak().unwrap(), 524288);
+        assert_eq!(cgroup.cpu_usage_usec().unwrap(), 2_500_000);
+    }
+
     #[test]
     fn test_cgroup_creation_failure() {
         struct FailingMockFileSystem;
@@ -348,6 +541,42 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_remove_force_kills_running_processes() {
+        let mock_fs = setup_mock_fs();
+        setup_cgroup(&mock_fs, "test_cgroup");
+        mock_fs.set_running_processes(vec![1000, 2000]);
+        let cgroup = CGroups::build()
+            .name("test_cgroup")
+            .with_fs(mock_fs.clone())
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            cgroup.remove(),
+            Err(CGroupsError::CGroupHasRunningProcesses)
+        ));
+        assert!(cgroup.remove_force().is_ok());
+        assert!(!mock_fs.exists(Path::new("/proc/1000/stat")));
+        assert!(!mock_fs.exists(Path::new("/sys/fs/cgroup/melon/test_cgroup")));
+    }
+
+    #[test]
+    fn test_drop_with_force_remove() {
+        let mock_fs = setup_mock_fs();
+        setup_cgroup(&mock_fs, "test_cgroup");
+        mock_fs.set_running_processes(vec![1000]);
+        let cgroup = CGroups::build()
+            .name("test_cgroup")
+            .with_force_remove(true)
+            .with_fs(mock_fs.clone())
+            .build()
+            .unwrap();
+
+        drop(cgroup);
+        assert!(!mock_fs.exists(Path::new("/sys/fs/cgroup/melon/test_cgroup")));
+    }
+
     #[test]
     fn test_remove_failed() {
         #[derive(Clone)]
@@ -540,6 +769,34 @@ mod tests {
             assert!(matches!(result, Err(CGroupsError::CGroupWriteFailed(_))));
         }
 
+        // Test memory.swap.max write failure
+        {
+            mock_fs.set_fail_on("memory.swap.max");
+            let cgroup = CGroups::build()
+                .name("test_cgroup")
+                .with_swap(0)
+                .with_fs(mock_fs.clone())
+                .build()
+                .unwrap();
+
+            let result = cgroup.create();
+            assert!(matches!(result, Err(CGroupsError::CGroupWriteFailed(_))));
+        }
+
+        // Test cpu.weight write failure
+        {
+            mock_fs.set_fail_on("cpu.weight");
+            let cgroup = CGroups::build()
+                .name("test_cgroup")
+                .with_cpu_weight(200)
+                .with_fs(mock_fs.clone())
+                .build()
+                .unwrap();
+
+            let result = cgroup.create();
+            assert!(matches!(result, Err(CGroupsError::CGroupWriteFailed(_))));
+        }
+
         // Test io.max write failure
         {
             mock_fs.set_fail_on("io.max");
@@ -41,6 +41,9 @@ pub enum CGroupsError {
 
     #[error("Some processes are not finished")]
     CGroupHasRunningProcesses,
+
+    #[error("cgroup v2 required, {0}")]
+    V2Required(String),
 }
 
 impl From<io::Error> for CGroupsError {
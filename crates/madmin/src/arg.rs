@@ -0,0 +1,36 @@
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(
+        short = 'a',
+        long = "api_endpoint",
+        default_value = "http://[::1]:8080"
+    )]
+    pub api_endpoint: String,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Requests that work on the jobs of any user, the token has to belong to an admin.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Stop a job and mark it as cancelled, whoever owns it
+    Kill {
+        /// The job id
+        job: u64,
+    },
+
+    /// Stop a running job and put it back into the queue, whoever owns it
+    Requeue {
+        /// The job id
+        job: u64,
+    },
+}
@@ -0,0 +1,57 @@
+mod arg;
+use arg::{Args, Command};
+use clap::Parser;
+use melon_client::{proto, Client, Code};
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let (job_id, result, done) = match args.command {
+        Command::Kill { job } => {
+            let request = tonic::Request::new(proto::AdminJobRequest { job_id: job });
+            (
+                job,
+                client.scheduler().admin_kill_job(request).await,
+                "Killed",
+            )
+        }
+        Command::Requeue { job } => {
+            let request = tonic::Request::new(proto::AdminJobRequest { job_id: job });
+            (
+                job,
+                client.scheduler().admin_requeue_job(request).await,
+                "Requeued",
+            )
+        }
+    };
+    match result {
+        Ok(_) => println!("{} job {}", done, job_id),
+        Err(e) => {
+            match e.code() {
+                Code::NotFound => eprintln!("Unknown job id {}", job_id),
+                Code::PermissionDenied | Code::FailedPrecondition => {
+                    eprintln!("{}", e.message())
+                }
+                _ => eprintln!("Unknown error: {}", e.message()),
+            }
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
@@ -1,4 +1,6 @@
 use clap::Parser;
+use melon_common::WarnSignal;
+use std::str::FromStr;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -11,8 +13,117 @@ pub struct Args {
     )]
     pub api_endpoint: String,
 
-    /// Script path
-    pub script: String,
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// CPUs per node, overrides the #MBATCH -c directive
+    #[arg(short = 'c', long = "cpus")]
+    pub cpus: Option<u32>,
+
+    /// Memory per node, e.g. 4G, overrides the #MBATCH -m directive
+    #[arg(short = 'm', long = "mem", value_parser = crate::parse_memory)]
+    pub mem: Option<u64>,
+
+    /// Time limit, e.g. 0-06:00 or minutes, overrides the #MBATCH -t directive
+    #[arg(short = 't', long = "time", value_parser = crate::parse_time_limit)]
+    pub time: Option<u32>,
+
+    /// GPUs per node, overrides the #MBATCH -g directive
+    #[arg(short = 'g', long = "gpus")]
+    pub gpus: Option<u32>,
+
+    /// Number of nodes the job runs on at the same time, overrides the #MBATCH -N directive
+    #[arg(short = 'N', long = "nodes", value_parser = clap::value_parser!(u32).range(1..))]
+    pub nodes: Option<u32>,
+
+    /// Scheduling priority (0-255), overrides the #MBATCH -p directive
+    #[arg(short = 'p', long = "priority")]
+    pub priority: Option<u8>,
+
+    /// Comma-separated job ids that must complete first, overrides the #MBATCH -d directive
+    #[arg(short = 'd', long = "depend", value_delimiter = ',')]
+    pub depend: Vec<u64>,
+
+    /// Partition to schedule the job in, overrides the #MBATCH -P directive
+    #[arg(short = 'P', long = "partition")]
+    pub partition: Option<String>,
+
+    /// Comma-separated labels the node has to have, e.g. ssd,avx512, overrides the #MBATCH -C directive
+    #[arg(short = 'C', long = "constraint", value_delimiter = ',')]
+    pub constraints: Vec<String>,
+
+    /// Environment variable for the job as KEY=VALUE, can be repeated, overrides #MBATCH -e
+    #[arg(short = 'e', long = "env")]
+    pub env: Vec<String>,
+
+    /// Working directory of the job, overrides the #MBATCH -D directive
+    #[arg(short = 'D', long = "chdir")]
+    pub chdir: Option<String>,
+
+    /// Job name shown by mqueue and mshow, overrides the #MBATCH -J directive
+    #[arg(short = 'J', long = "name")]
+    pub name: Option<String>,
+
+    /// Seconds the job gets to exit after SIGTERM before it is killed, overrides #MBATCH --grace
+    #[arg(long = "grace")]
+    pub grace: Option<u32>,
+
+    /// Cancel the job if it hasn't started this long after submission, e.g. 0-02:00 or minutes, overrides #MBATCH --deadline
+    #[arg(long = "deadline", value_parser = crate::parse_time_limit)]
+    pub deadline: Option<u32>,
+
+    /// Signal sent once the job used a share of its time limit, e.g. USR1@90, overrides #MBATCH --signal
+    #[arg(long = "signal", value_parser = WarnSignal::from_str)]
+    pub signal: Option<WarnSignal>,
+
+    /// File the job's stdout is written to, %j is replaced by the job id and %u by the user, overrides #MBATCH -o
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<String>,
+
+    /// File the job's stderr is written to, overrides #MBATCH --error
+    #[arg(long = "error")]
+    pub error: Option<String>,
+
+    /// Times a failed job is put back into the queue, overrides #MBATCH --requeue
+    #[arg(long = "requeue")]
+    pub requeue: Option<u32>,
+
+    /// Submit even if no registered node is large enough, e.g. for nodes that join later
+    #[arg(short = 'f', long = "force")]
+    pub force: bool,
+
+    /// Require CPUs, memory and time in the script or on the command line, ignore ~/.melon/defaults
+    #[arg(long = "strict")]
+    pub strict: bool,
+
+    /// Submit this many copies of the job at once, each finds its index in MELON_ARRAY_TASK_ID
+    #[arg(long = "array", value_parser = clap::value_parser!(u32).range(1..))]
+    pub array: Option<u32>,
+
+    /// Only check whether the scheduler would accept the job, don't queue it
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Wait until the job finished, exit with 0 if it completed and with its exit code otherwise
+    #[arg(long = "wait")]
+    pub wait: bool,
+
+    /// Seconds to wait at most, the job keeps running afterwards
+    #[arg(long = "wait-timeout", requires = "wait")]
+    pub wait_timeout: Option<u64>,
+
+    /// Send the script along with the job, for nodes that don't see the submitting host's files
+    #[arg(long = "ship")]
+    pub ship: bool,
+
+    /// Command to run as the job, instead of a script, e.g. --wrap "echo hi"
+    #[arg(long = "wrap", conflicts_with = "script")]
+    pub wrap: Option<String>,
+
+    /// Script path, - reads the script from stdin
+    #[arg(required_unless_present = "wrap")]
+    pub script: Option<String>,
 
     /// Script arguments
     #[arg(trailing_var_arg = true)]
@@ -1,56 +1,322 @@
 mod arg;
 use anyhow::{anyhow, Result};
-use melon_common::RequestedResources;
+use melon_common::proto::JobSubmission;
+use melon_common::script::{is_executable, read_shebang};
+pub use melon_common::utils::parse_memory;
+use melon_common::{JobStatus, RequestedResources, WarnSignal};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Retries of a job that uses `--requeue` without a count
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Environment variable that holds the index of a task of a job array
+pub const ARRAY_TASK_ENV: &str = "MELON_ARRAY_TASK_ID";
+
+/// Interpreter of the script that `--wrap` puts around a command
+const WRAP_SHELL: &str = "#!/bin/sh";
+
+/// All settings that can be given through `#MBATCH` directives.
+#[derive(Debug, Clone)]
+pub struct Directives {
+    /// The requested resources
+    pub req_res: RequestedResources,
+
+    /// Scheduling priority (`-p`), if set
+    pub priority: Option<u8>,
+
+    /// Jobs that have to complete before this one may start (`-d`)
+    pub dependencies: Vec<u64>,
+
+    /// Partition to schedule the job in (`-P`), if set
+    pub partition: Option<String>,
+
+    /// Labels the node has to have (`-C`)
+    pub constraints: Vec<String>,
+
+    /// Extra environment variables of the job (`-e KEY=VALUE`, repeatable)
+    pub env: HashMap<String, String>,
+
+    /// Working directory of the job (`-D`), if set
+    pub work_dir: Option<String>,
+
+    /// Job name (`-J`), if set
+    pub name: Option<String>,
+
+    /// Seconds between SIGTERM and SIGKILL on timeout or cancellation (`--grace`), if set
+    pub grace_secs: Option<u32>,
+
+    /// How often a failed job is put back into the queue (`--requeue [N]`), if set
+    pub max_retries: Option<u32>,
+
+    /// Minutes after submission in which the job has to start (`--deadline`), if set
+    pub begin_deadline_mins: Option<u32>,
+
+    /// Signal sent once the job used a share of its time limit (`--signal USR1@90`), if set
+    pub warn_signal: Option<WarnSignal>,
+
+    /// File the job's stdout is written to (`-o`, `%j` is the job id, `%u` the user), if set
+    pub output_path: Option<String>,
+
+    /// File the job's stderr is written to (`--error`), if set
+    pub error_path: Option<String>,
+}
+
+/// Resources given on the command line, they take precedence over the `#MBATCH` directives.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceOverrides {
+    /// CPUs per node (`-c`)
+    pub cpu_count: Option<u32>,
+
+    /// Memory per node in bytes (`-m`)
+    pub memory: Option<u64>,
+
+    /// Time limit in minutes (`-t`)
+    pub time: Option<u32>,
+
+    /// GPUs per node (`-g`)
+    pub gpu_count: Option<u32>,
+
+    /// Number of nodes (`-N`)
+    pub node_count: Option<u32>,
+}
+
+/// Resources used when neither the command line nor the script sets them.
+///
+/// Read from the user's defaults file, see [`read_resource_defaults`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceDefaults {
+    /// CPUs per node (`cpus`)
+    pub cpu_count: Option<u32>,
+
+    /// Memory per node in bytes (`mem`)
+    pub memory: Option<u64>,
+
+    /// Time limit in minutes (`time`)
+    pub time: Option<u32>,
+}
+
+/// Returns the path of the defaults file `~/.melon/defaults`.
+pub fn default_resources_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join(".melon").join("defaults")
+}
+
+/// Reads the resource defaults from a file of `key = value` lines, e.g.
+///
+/// ```text
+/// cpus = 1
+/// mem = 1G
+/// time = 60
+/// ```
+///
+/// Values take the same formats as the `#MBATCH` directives, lines starting with `#` are ignored.
+/// A missing file means there are no defaults.
+pub fn read_resource_defaults(path: &Path) -> Result<ResourceDefaults> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(ResourceDefaults::default())
+        }
+        Err(e) => return Err(anyhow!("Could not read {}: {}", path.display(), e)),
+    };
+
+    let mut defaults = ResourceDefaults::default();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || anyhow!("Invalid line {} in {}: {}", index + 1, path.display(), line);
+        let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+        let value = value.trim();
+        match key.trim() {
+            "cpus" => defaults.cpu_count = Some(value.parse().map_err(|_| invalid())?),
+            "mem" => defaults.memory = Some(parse_memory(value).map_err(|_| invalid())?),
+            "time" => defaults.time = Some(parse_time_limit(value).map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(defaults)
+}
+
+/// Checks that the script exists and can be started, i.e. is executable or has a `#!` line.
+///
+/// The nodes may see a different filesystem, they check the script again before starting it.
+pub fn check_script(path: &Path) -> Result<()> {
+    let metadata = std::fs::metadata(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => anyhow!("Script {} does not exist", path.display()),
+        _ => anyhow!("Could not read script {}: {}", path.display(), e),
+    })?;
+    if !metadata.is_file() {
+        return Err(anyhow!("Script {} is not a file", path.display()));
+    }
+    if !is_executable(path)? && read_shebang(path)?.is_none() {
+        return Err(anyhow!(
+            "Script {} is neither executable nor starts with a #! line, try chmod +x",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a script read from stdin can be started, i.e. starts with a `#!` line.
+pub fn check_script_body(body: &[u8]) -> Result<()> {
+    if !body.starts_with(b"#!") {
+        return Err(anyhow!("The script from stdin has to start with a #! line"));
+    }
+    Ok(())
+}
+
+/// Turns the command of `--wrap` into a script that runs it with `sh`.
+pub fn wrap_command(command: &str) -> String {
+    format!("{}\n{}\n", WRAP_SHELL, command)
+}
 
 pub fn parse_mbatch_comments(path: &str) -> Result<RequestedResources> {
+    parse_mbatch_directives(path).map(|directives| directives.req_res)
+}
+
+pub fn parse_mbatch_directives(path: &str) -> Result<Directives> {
+    parse_mbatch_directives_with(
+        path,
+        &ResourceOverrides::default(),
+        &ResourceDefaults::default(),
+    )
+}
+
+/// Parses the `#MBATCH` directives of a script, resources given in `overrides` replace theirs.
+///
+/// A resource that is required by the scheduler may be missing from the script if it is overridden
+/// or has a value in `defaults`.
+pub fn parse_mbatch_directives_with(
+    path: &str,
+    overrides: &ResourceOverrides,
+    defaults: &ResourceDefaults,
+) -> Result<Directives> {
     let file = File::open(path)?;
-    let reader = BufReader::new(file);
+    parse_mbatch_directives_from(BufReader::new(file), overrides, defaults)
+}
 
+/// Parses the `#MBATCH` directives of a script that is read from `reader`, e.g. stdin.
+///
+/// See [parse_mbatch_directives_with].
+pub fn parse_mbatch_directives_from(
+    reader: impl BufRead,
+    overrides: &ResourceOverrides,
+    defaults: &ResourceDefaults,
+) -> Result<Directives> {
     let mut cpu_count: Option<u32> = None;
     let mut memory: Option<u64> = None;
     let mut time_limit_mins: Option<u32> = None;
+    let mut gpu_count: u32 = 0;
+    let mut node_count: u32 = 1;
+    let mut priority: Option<u8> = None;
+    let mut dependencies: Vec<u64> = Vec::new();
+    let mut partition: Option<String> = None;
+    let mut constraints: Vec<String> = Vec::new();
+    let mut env: HashMap<String, String> = HashMap::new();
+    let mut work_dir: Option<String> = None;
+    let mut name: Option<String> = None;
+    let mut grace_secs: Option<u32> = None;
+    let mut max_retries: Option<u32> = None;
+    let mut begin_deadline_mins: Option<u32> = None;
+    let mut warn_signal: Option<WarnSignal> = None;
+    let mut output_path: Option<String> = None;
+    let mut error_path: Option<String> = None;
 
     for line in reader.lines() {
         let line = line?;
         if line.starts_with("#MBATCH") {
             let parts: Vec<&str> = line.split_whitespace().collect();
+            // the only directive whose value is optional
+            if parts.get(1) == Some(&"--requeue") {
+                let value = match parts.get(2) {
+                    Some(count) => count
+                        .parse::<u32>()
+                        .map_err(|_| anyhow!("Invalid retry count {}", count))?,
+                    None => DEFAULT_MAX_RETRIES,
+                };
+                max_retries = Some(value);
+                continue;
+            }
             if parts.len() < 3 {
                 continue;
             }
             match parts[1] {
                 "-c" => cpu_count = parts[2].parse().ok(),
-                "-m" => {
-                    if let Some(mem_str) = parts[2].strip_suffix('G') {
-                        memory = mem_str.parse::<u64>().ok().map(|m| m * 1024 * 1024 * 1024);
-                    } else if let Some(mem_str) = parts[2].strip_suffix('M') {
-                        memory = mem_str.parse::<u64>().ok().map(|m| m * 1024 * 1024);
-                    } else {
-                        // invalid or missing suffix
-                        return Err(anyhow!("Unsupported memory suffix in {}", parts[2]));
-                    }
+                "-m" => memory = Some(parse_memory(parts[2])?),
+                "-t" => time_limit_mins = Some(parse_time_limit(parts[2])?),
+                "-g" => {
+                    gpu_count = parts[2]
+                        .parse::<u32>()
+                        .map_err(|_| anyhow!("Invalid GPU count {}", parts[2]))?;
+                }
+                "-N" => {
+                    node_count = parse_node_count(parts[2])?;
+                }
+                "-p" => {
+                    let value = parts[2]
+                        .parse::<u8>()
+                        .map_err(|_| anyhow!("Invalid priority {}", parts[2]))?;
+                    priority = Some(value);
                 }
-                "-t" => {
-                    // Assuming time format is D-HH:MM
-                    let time_parts: Vec<&str> = parts[2].split(&['-', ':']).collect();
-                    if time_parts.len() == 3 {
-                        let days: u32 = time_parts[0].parse()?;
-                        let hours: u32 = time_parts[1].parse()?;
-                        let minutes: u32 = time_parts[2].parse()?;
-                        time_limit_mins = Some(days * 24 * 60 + hours * 60 + minutes);
-                    }
+                "-d" => {
+                    dependencies = parse_dependency_list(parts[2])?;
                 }
+                "-P" => partition = Some(parts[2].to_string()),
+                "-C" => constraints = parse_constraint_list(parts[2]),
+                "-D" => work_dir = Some(parts[2].to_string()),
+                "-J" => name = Some(parts[2].to_string()),
+                "-e" => {
+                    let (key, value) = parse_env_var(parts[2])?;
+                    env.insert(key, value);
+                }
+                "--grace" => {
+                    let value = parts[2]
+                        .parse::<u32>()
+                        .map_err(|_| anyhow!("Invalid grace period {}", parts[2]))?;
+                    grace_secs = Some(value);
+                }
+                "--deadline" => begin_deadline_mins = Some(parse_time_limit(parts[2])?),
+                "--signal" => warn_signal = Some(parts[2].parse().map_err(|e| anyhow!("{}", e))?),
+                "-o" => output_path = Some(parts[2].to_string()),
+                "--error" => error_path = Some(parts[2].to_string()),
                 _ => {}
             }
         }
     }
 
+    let cpu_count = overrides.cpu_count.or(cpu_count).or(defaults.cpu_count);
+    let memory = overrides.memory.or(memory).or(defaults.memory);
+    let time_limit_mins = overrides.time.or(time_limit_mins).or(defaults.time);
+    let gpu_count = overrides.gpu_count.unwrap_or(gpu_count);
+    let node_count = overrides.node_count.unwrap_or(node_count);
+
     if let (Some(cpu_count), Some(memory), Some(time)) = (cpu_count, memory, time_limit_mins) {
-        Ok(RequestedResources {
-            cpu_count,
-            memory,
-            time,
+        Ok(Directives {
+            req_res: RequestedResources {
+                cpu_count,
+                memory,
+                time,
+                gpu_count,
+                node_count,
+            },
+            priority,
+            dependencies,
+            partition,
+            constraints,
+            env,
+            work_dir,
+            name,
+            grace_secs,
+            max_retries,
+            begin_deadline_mins,
+            warn_signal,
+            output_path,
+            error_path,
         })
     } else {
         Err(anyhow!(
@@ -59,6 +325,99 @@ pub fn parse_mbatch_comments(path: &str) -> Result<RequestedResources> {
     }
 }
 
+/// Parses a time limit in minutes, in one of the formats `MM`, `MM:SS`, `HH:MM:SS`,
+/// `D-HH`, `D-HH:MM` or `D-HH:MM:SS`.
+///
+/// Seconds are rounded up to the next full minute.
+pub fn parse_time_limit(value: &str) -> Result<u32> {
+    let invalid = || anyhow!("Invalid time limit {}", value);
+    let number = |part: &str| part.parse::<u32>().map_err(|_| invalid());
+
+    let (days, clock) = match value.split_once('-') {
+        Some((days, clock)) => (Some(number(days)?), clock),
+        None => (None, value),
+    };
+    let fields = clock.split(':').map(number).collect::<Result<Vec<u32>>>()?;
+
+    let (days, hours, minutes, seconds) = match (days, fields.as_slice()) {
+        (None, [m]) => (0, 0, *m, 0),
+        (None, [m, s]) => (0, 0, *m, *s),
+        (None, [h, m, s]) => (0, *h, *m, *s),
+        (Some(d), [h]) => (d, *h, 0, 0),
+        (Some(d), [h, m]) => (d, *h, *m, 0),
+        (Some(d), [h, m, s]) => (d, *h, *m, *s),
+        _ => return Err(invalid()),
+    };
+
+    let total_secs =
+        (days as u64 * 24 + hours as u64) * 3600 + minutes as u64 * 60 + seconds as u64;
+    u32::try_from(total_secs.div_ceil(60)).map_err(|_| invalid())
+}
+
+/// Parses the number of nodes a job runs on, at least one.
+pub fn parse_node_count(value: &str) -> Result<u32> {
+    match value.parse::<u32>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(anyhow!("Invalid node count {}", value)),
+    }
+}
+
+/// Parses a comma-separated list of job ids, e.g. `12,15`.
+pub fn parse_dependency_list(list: &str) -> Result<Vec<u64>> {
+    list.split(',')
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.trim()
+                .parse::<u64>()
+                .map_err(|_| anyhow!("Invalid dependency job id {}", id))
+        })
+        .collect()
+}
+
+/// Parses a comma-separated list of node labels, e.g. `ssd,avx512`.
+pub fn parse_constraint_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses an environment variable given as `KEY=VALUE`.
+pub fn parse_env_var(var: &str) -> Result<(String, String)> {
+    match var.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(anyhow!(
+            "Invalid environment variable {}, expected KEY=VALUE",
+            var
+        )),
+    }
+}
+
+/// Copies a submission into the `count` tasks of a job array, numbered from 0.
+pub fn array_submissions(submission: JobSubmission, count: u32) -> Vec<JobSubmission> {
+    (0..count)
+        .map(|task| {
+            let mut task_submission = submission.clone();
+            task_submission
+                .env
+                .insert(ARRAY_TASK_ENV.to_string(), task.to_string());
+            task_submission
+        })
+        .collect()
+}
+
+/// Exit code of `mbatch --wait` for a finished job.
+///
+/// 0 if the job completed, the exit code of its script if it failed with one, 1 otherwise.
+pub fn wait_exit_code(status: &JobStatus, exit_code: Option<i32>) -> i32 {
+    match (status, exit_code) {
+        (JobStatus::Completed, _) => 0,
+        (_, Some(code)) if code != 0 => code,
+        _ => 1,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -71,6 +430,48 @@ mod tests {
         file
     }
 
+    #[test]
+    fn test_check_script() {
+        let file = create_temp_file("#!/bin/bash\necho hello");
+        assert!(check_script(file.path()).is_ok());
+
+        // temp files are not executable
+        let file = create_temp_file("echo hello");
+        let err = check_script(file.path()).unwrap_err();
+        assert!(err.to_string().contains("chmod +x"));
+
+        let err = check_script(Path::new("/does/not/exist.sh")).unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+        assert!(check_script(Path::new("/tmp")).is_err());
+    }
+
+    #[test]
+    fn test_check_script_body() {
+        assert!(check_script_body(b"#!/bin/bash\necho hello\n").is_ok());
+        let err = check_script_body(b"echo hello\n").unwrap_err();
+        assert!(err.to_string().contains("#! line"));
+    }
+
+    #[test]
+    fn test_wrap_command() {
+        let script = wrap_command("echo hi && sleep 1");
+        assert_eq!(script, "#!/bin/sh\necho hi && sleep 1\n");
+        assert!(check_script_body(script.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_directives_from_a_body() {
+        let body = "#!/bin/sh\n#MBATCH -c 2\n#MBATCH -m 1G\n#MBATCH -t 10\n#MBATCH -J piped\n";
+        let directives = parse_mbatch_directives_from(
+            body.as_bytes(),
+            &ResourceOverrides::default(),
+            &ResourceDefaults::default(),
+        )
+        .unwrap();
+        assert_eq!(directives.req_res.cpu_count, 2);
+        assert_eq!(directives.name.as_deref(), Some("piped"));
+    }
+
     #[test]
     fn test_parse_valid_input() {
         let content = r#"
@@ -87,6 +488,169 @@ echo "Hello, World!"
         assert_eq!(result.time, 2190);
     }
 
+    #[test]
+    fn test_parse_gpu_count() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -g 2";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_comments(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.gpu_count, 2);
+    }
+
+    #[test]
+    fn test_parse_node_count() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -N 4";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_comments(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.node_count, 4);
+
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_comments(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.node_count, 1);
+    }
+
+    #[test]
+    fn test_parse_constraints() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 60\n#MBATCH -C ssd,avx512";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.constraints, vec!["ssd", "avx512"]);
+
+        assert_eq!(parse_constraint_list("ssd, ,rack-a"), vec!["ssd", "rack-a"]);
+        assert!(parse_constraint_list("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_deadline() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 60\n#MBATCH --deadline 0-02:00";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.begin_deadline_mins, Some(120));
+
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 60\n#MBATCH --deadline soon";
+        let file = create_temp_file(content);
+        assert!(parse_mbatch_directives(file.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 60\n#MBATCH --signal USR1@90";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        let warn_signal = result.warn_signal.unwrap();
+        assert_eq!(warn_signal.signal, "USR1");
+        assert_eq!(warn_signal.percent, 90);
+
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 60\n#MBATCH --signal KILL@90";
+        let file = create_temp_file(content);
+        assert!(parse_mbatch_directives(file.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_output_paths() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 60\n#MBATCH -o /data/out.%j\n#MBATCH --error logs/err.%j\n#MBATCH -e KEY=value";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.output_path.as_deref(), Some("/data/out.%j"));
+        assert_eq!(result.error_path.as_deref(), Some("logs/err.%j"));
+        assert_eq!(result.env.get("KEY").map(String::as_str), Some("value"));
+    }
+
+    #[test]
+    fn test_overrides_replace_directives() {
+        let content = "#MBATCH -c 2\n#MBATCH -t 0-02:00\n#MBATCH -g 1";
+        let file = create_temp_file(content);
+        let overrides = ResourceOverrides {
+            cpu_count: Some(8),
+            memory: Some(1024),
+            gpu_count: Some(0),
+            ..Default::default()
+        };
+
+        let result = parse_mbatch_directives_with(
+            file.path().to_str().unwrap(),
+            &overrides,
+            &ResourceDefaults::default(),
+        )
+        .unwrap();
+
+        // the missing memory directive is given on the command line
+        assert_eq!(result.req_res.cpu_count, 8);
+        assert_eq!(result.req_res.memory, 1024);
+        assert_eq!(result.req_res.time, 120);
+        assert_eq!(result.req_res.gpu_count, 0);
+        assert_eq!(result.req_res.node_count, 1);
+    }
+
+    #[test]
+    fn test_defaults_fill_missing_resources() {
+        let content = "#MBATCH -c 2";
+        let file = create_temp_file(content);
+        let overrides = ResourceOverrides {
+            time: Some(30),
+            ..Default::default()
+        };
+        let defaults = ResourceDefaults {
+            cpu_count: Some(1),
+            memory: Some(1024),
+            time: Some(60),
+        };
+
+        let result =
+            parse_mbatch_directives_with(file.path().to_str().unwrap(), &overrides, &defaults)
+                .unwrap();
+
+        // only the memory is missing from both the script and the command line
+        assert_eq!(result.req_res.cpu_count, 2);
+        assert_eq!(result.req_res.memory, 1024);
+        assert_eq!(result.req_res.time, 30);
+    }
+
+    #[test]
+    fn test_read_resource_defaults() {
+        let file = create_temp_file("# for quick tests\ncpus = 1\nmem = 1G\n\ntime=0-01:00");
+        let defaults = read_resource_defaults(file.path()).unwrap();
+        assert_eq!(
+            defaults,
+            ResourceDefaults {
+                cpu_count: Some(1),
+                memory: Some(1024 * 1024 * 1024),
+                time: Some(60),
+            }
+        );
+
+        let file = create_temp_file("mem = 1G");
+        let defaults = read_resource_defaults(file.path()).unwrap();
+        assert_eq!(defaults.cpu_count, None);
+
+        let missing = read_resource_defaults(Path::new("/does/not/exist")).unwrap();
+        assert_eq!(missing, ResourceDefaults::default());
+    }
+
+    #[test]
+    fn test_read_invalid_resource_defaults() {
+        for content in ["cpus = many", "mem 1G", "gpus = 1", "time = 1h"] {
+            let file = create_temp_file(content);
+            let err = read_resource_defaults(file.path()).unwrap_err();
+            assert!(err.to_string().contains("line 1"), "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_node_count() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -N 0";
+        let file = create_temp_file(content);
+        assert!(parse_mbatch_comments(file.path().to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_missing_gpu_count_defaults_to_zero() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_comments(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.gpu_count, 0);
+    }
+
     #[test]
     fn test_parse_memory_in_mb() {
         let content = "#MBATCH -c 2\n#MBATCH -m 512M\n#MBATCH -t 0-01:00";
@@ -95,9 +659,26 @@ echo "Hello, World!"
         assert_eq!(result.memory, 512 * 1024 * 1024);
     }
 
+    #[test]
+    fn test_parse_memory_units() {
+        assert_eq!(parse_memory("8GB").unwrap(), 8 * 1024 * 1024 * 1024);
+        assert_eq!(parse_memory("512k").unwrap(), 512 * 1024);
+        assert_eq!(parse_memory("2T").unwrap(), 2 * 1024 * 1024 * 1024 * 1024);
+        assert_eq!(parse_memory("1073741824").unwrap(), 1073741824);
+        assert_eq!(parse_memory("64mb").unwrap(), 64 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_parse_invalid_memory() {
+        assert!(parse_memory("G").is_err());
+        assert!(parse_memory("-1G").is_err());
+        assert!(parse_memory("4.5G").is_err());
+        assert!(parse_memory("99999999999T").is_err());
+    }
+
     #[test]
     fn test_parse_invalid_memory_suffix() {
-        let content = "#MBATCH -c 2\n#MBATCH -m 512K\n#MBATCH -t 0-01:00";
+        let content = "#MBATCH -c 2\n#MBATCH -m 512X\n#MBATCH -t 0-01:00";
         let file = create_temp_file(content);
         let result = parse_mbatch_comments(file.path().to_str().unwrap());
         assert!(result.is_err());
@@ -121,10 +702,30 @@ echo "Hello, World!"
 
     #[test]
     fn test_parse_invalid_time_format() {
-        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 1:30";
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 1:30:00:00";
         let file = create_temp_file(content);
         let result = parse_mbatch_comments(file.path().to_str().unwrap());
-        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("1:30:00:00"));
+    }
+
+    #[test]
+    fn test_parse_time_limit_formats() {
+        assert_eq!(parse_time_limit("90").unwrap(), 90);
+        assert_eq!(parse_time_limit("10:30").unwrap(), 11);
+        assert_eq!(parse_time_limit("02:15:00").unwrap(), 135);
+        assert_eq!(parse_time_limit("1-12").unwrap(), 2160);
+        assert_eq!(parse_time_limit("1-12:30").unwrap(), 2190);
+        assert_eq!(parse_time_limit("1-12:30:01").unwrap(), 2191);
+    }
+
+    #[test]
+    fn test_parse_invalid_time_limit() {
+        assert!(parse_time_limit("").is_err());
+        assert!(parse_time_limit("1-").is_err());
+        assert!(parse_time_limit("a-12").is_err());
+        assert!(parse_time_limit("1:2:3:4").is_err());
+        assert!(parse_time_limit("-5").is_err());
+        assert!(parse_time_limit("1h").is_err());
     }
 
     #[test]
@@ -161,4 +762,158 @@ echo "Hello"
         assert_eq!(result.memory, 4 * 1024 * 1024 * 1024);
         assert_eq!(result.time, 120);
     }
+
+    #[test]
+    fn test_parse_priority() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -p 80";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.priority, Some(80));
+        assert_eq!(result.req_res.cpu_count, 2);
+    }
+
+    #[test]
+    fn test_parse_missing_priority() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.priority, None);
+    }
+
+    #[test]
+    fn test_parse_dependencies() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -d 12,15";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.dependencies, vec![12, 15]);
+    }
+
+    #[test]
+    fn test_parse_invalid_dependencies() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -d 12,abc";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_partition() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -P short";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.partition, Some("short".to_string()));
+    }
+
+    #[test]
+    fn test_parse_missing_partition() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.partition, None);
+    }
+
+    #[test]
+    fn test_parse_env() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -e MODE=fast\n#MBATCH -e URL=http://x?a=b";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.env.len(), 2);
+        assert_eq!(result.env["MODE"], "fast");
+        assert_eq!(result.env["URL"], "http://x?a=b");
+    }
+
+    #[test]
+    fn test_parse_invalid_env() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -e MODE";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_work_dir() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -D /data/run";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.work_dir, Some("/data/run".to_string()));
+    }
+
+    #[test]
+    fn test_parse_name() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -J training";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.name, Some("training".to_string()));
+    }
+
+    #[test]
+    fn test_parse_grace() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH --grace 30";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.grace_secs, Some(30));
+    }
+
+    #[test]
+    fn test_parse_invalid_grace() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH --grace 10s";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_requeue() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH --requeue 5";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.max_retries, Some(5));
+    }
+
+    #[test]
+    fn test_parse_requeue_without_count() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH --requeue";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap()).unwrap();
+        assert_eq!(result.max_retries, Some(DEFAULT_MAX_RETRIES));
+    }
+
+    #[test]
+    fn test_parse_invalid_requeue() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH --requeue many";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_array_submissions_are_numbered() {
+        let submission = JobSubmission {
+            script_path: "/tmp/job.sh".to_string(),
+            ..Default::default()
+        };
+        let tasks = array_submissions(submission, 3);
+        assert_eq!(tasks.len(), 3);
+        for (i, task) in tasks.iter().enumerate() {
+            assert_eq!(task.script_path, "/tmp/job.sh");
+            assert_eq!(task.env.get(ARRAY_TASK_ENV), Some(&i.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_wait_exit_code() {
+        assert_eq!(wait_exit_code(&JobStatus::Completed, Some(0)), 0);
+        assert_eq!(wait_exit_code(&JobStatus::Failed, Some(3)), 3);
+        assert_eq!(wait_exit_code(&JobStatus::Failed, None), 1);
+        assert_eq!(wait_exit_code(&JobStatus::Timeout, None), 1);
+        assert_eq!(wait_exit_code(&JobStatus::Cancelled, Some(0)), 1);
+    }
+
+    #[test]
+    fn test_parse_invalid_priority() {
+        let content = "#MBATCH -c 2\n#MBATCH -m 4G\n#MBATCH -t 0-02:00\n#MBATCH -p 300";
+        let file = create_temp_file(content);
+        let result = parse_mbatch_directives(file.path().to_str().unwrap());
+        assert!(result.is_err());
+    }
 }
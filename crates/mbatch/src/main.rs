@@ -1,34 +1,238 @@
 use arg::Args;
 use clap::Parser;
+use std::collections::HashMap;
+use std::io::Read;
+use std::time::{Duration, Instant};
 mod arg;
 use anyhow::Result;
-use mbatch::parse_mbatch_comments;
-use melon_common::proto::melon_scheduler_client::MelonSchedulerClient;
+use mbatch::{
+    array_submissions, check_script, check_script_body, default_resources_path, parse_env_var,
+    parse_mbatch_directives_from, parse_mbatch_directives_with, parse_memory, parse_time_limit,
+    read_resource_defaults, wait_exit_code, wrap_command, ResourceDefaults, ResourceOverrides,
+};
+use melon_client::{Client, Code};
 use melon_common::proto::JobSubmission;
+use melon_common::JobStatus;
+
+/// How often `--wait` checks the jobs
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Exit code once `--wait-timeout` ran out, like timeout(1) uses it
+const WAIT_TIMEOUT_EXIT_CODE: i32 = 124;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
-    let mut client = MelonSchedulerClient::connect(args.api_endpoint).await?;
-    let script_path = std::path::Path::new(&args.script);
-    // convert to absolute path if relative
-    let absolute_script_path = if script_path.is_relative() {
-        std::env::current_dir()?.join(script_path)
-    } else {
-        script_path.to_path_buf()
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    // scripts from the command line or stdin are always sent along, the node runs them from the scratch directory
+    let (script_path, script_contents) = match (args.wrap, args.script.as_deref()) {
+        (Some(command), _) => (
+            "wrap".to_string(),
+            Some(wrap_command(&command).into_bytes()),
+        ),
+        (None, Some("-")) => {
+            let mut body = Vec::new();
+            std::io::stdin().read_to_end(&mut body)?;
+            if let Err(e) = check_script_body(&body) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            ("stdin".to_string(), Some(body))
+        }
+        (None, script_path) => {
+            let script_path = std::path::Path::new(script_path.unwrap_or_default());
+            // convert to absolute path if relative
+            let absolute_script_path = if script_path.is_relative() {
+                std::env::current_dir()?.join(script_path)
+            } else {
+                script_path.to_path_buf()
+            };
+            if let Err(e) = check_script(&absolute_script_path) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            let contents = if args.ship {
+                Some(std::fs::read(&absolute_script_path)?)
+            } else {
+                None
+            };
+            (
+                absolute_script_path.to_string_lossy().into_owned(),
+                contents,
+            )
+        }
     };
 
-    let res = parse_mbatch_comments(&absolute_script_path.to_string_lossy())?;
+    let overrides = ResourceOverrides {
+        cpu_count: args.cpus,
+        memory: args.mem,
+        time: args.time,
+        gpu_count: args.gpus,
+        node_count: args.nodes,
+    };
+    let defaults = if args.strict {
+        ResourceDefaults::default()
+    } else {
+        read_resource_defaults(&default_resources_path())?
+    };
+    let directives = match &script_contents {
+        Some(body) => parse_mbatch_directives_from(body.as_slice(), &overrides, &defaults)?,
+        None => parse_mbatch_directives_with(&script_path, &overrides, &defaults)?,
+    };
+    let priority = args.priority.or(directives.priority);
+    let partition = args.partition.or(directives.partition);
+    let constraints = if args.constraints.is_empty() {
+        directives.constraints
+    } else {
+        args.constraints
+    };
+    let name = args.name.or(directives.name);
+    let grace_secs = args.grace.or(directives.grace_secs);
+    let begin_deadline_mins = args.deadline.or(directives.begin_deadline_mins);
+    let warn_signal = args.signal.or(directives.warn_signal);
+    let max_retries = args.requeue.or(directives.max_retries).unwrap_or(0);
+    let dependencies = if args.depend.is_empty() {
+        directives.dependencies
+    } else {
+        args.depend
+    };
+    // relative directories are resolved against the submission directory
+    let work_dir = args
+        .chdir
+        .or(directives.work_dir)
+        .map(|dir| std::env::current_dir().map(|cwd| cwd.join(dir)))
+        .transpose()?
+        .map(|dir| dir.to_string_lossy().into_owned());
+    // relative output files are resolved against the job's working directory
+    let base_dir = match &work_dir {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => std::env::current_dir()?,
+    };
+    let output_path = args
+        .output
+        .or(directives.output_path)
+        .map(|path| base_dir.join(path).to_string_lossy().into_owned());
+    let error_path = args
+        .error
+        .or(directives.error_path)
+        .map(|path| base_dir.join(path).to_string_lossy().into_owned());
+    let mut env = directives.env;
+    for var in &args.env {
+        let (key, value) = parse_env_var(var)?;
+        env.insert(key, value);
+    }
     let req = JobSubmission {
         user: whoami::username(),
-        script_path: absolute_script_path.to_string_lossy().into_owned(),
-        req_res: Some(res.into()),
+        script_path,
+        req_res: Some(directives.req_res.into()),
         script_args: args.script_args,
+        priority: priority.map(u32::from),
+        dependencies,
+        partition,
+        force: args.force,
+        env,
+        work_dir,
+        name,
+        grace_secs,
+        max_retries,
+        dry_run: args.dry_run,
+        interactive: false,
+        constraints,
+        begin_deadline_mins,
+        warn_signal: warn_signal.as_ref().map(ToString::to_string),
+        output_path,
+        error_path,
+        parent_job_id: None,
+        script_contents,
+    };
+    // a dry run only checks a single task
+    let responses = match args.array.filter(|_| !args.dry_run) {
+        Some(count) => client.submit_batch(array_submissions(req, count)).await,
+        None => client.submit(req).await.map(|response| vec![response]),
     };
-    let request = tonic::Request::new(req);
-    let response = client.submit_job(request).await?;
+    let responses = match responses {
+        Ok(responses) => responses,
+        Err(e) if matches!(e.code(), Code::InvalidArgument | Code::ResourceExhausted) => {
+            eprintln!("Job rejected: {}", e.message());
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if args.dry_run {
+        println!("{}", responses[0].summary);
+        return Ok(());
+    }
+    match responses.as_slice() {
+        [response] => println!("Started job with id: {:?}", response.job_id),
+        [first, .., last] => println!("Started jobs with ids: {}-{}", first.job_id, last.job_id),
+        [] => {}
+    }
 
-    println!("Started job with id: {:?}", response.get_ref().job_id);
+    if args.wait {
+        let job_ids: Vec<u64> = responses.iter().map(|response| response.job_id).collect();
+        let timeout = args.wait_timeout.map(Duration::from_secs);
+        let code = wait_for_jobs(&mut client, &job_ids, timeout).await?;
+        std::process::exit(code);
+    }
     Ok(())
 }
+
+/// Polls the jobs until all of them finished and prints their status changes.
+///
+/// Returns the exit code of the first job that didn't complete, 0 if all did, or
+/// [WAIT_TIMEOUT_EXIT_CODE] once `timeout` ran out.
+async fn wait_for_jobs(
+    client: &mut Client,
+    job_ids: &[u64],
+    timeout: Option<Duration>,
+) -> Result<i32> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut statuses: HashMap<u64, JobStatus> = HashMap::new();
+    let mut exit_codes: HashMap<u64, i32> = HashMap::new();
+    loop {
+        for &job_id in job_ids {
+            if exit_codes.contains_key(&job_id) {
+                continue;
+            }
+            let (status, exit_code) = client.status(job_id).await?;
+            let finished = status.is_finished();
+            if statuses.get(&job_id) != Some(&status) {
+                let name: String = status.clone().into();
+                match exit_code.filter(|_| finished) {
+                    Some(code) => eprintln!("Job {} is {} (exit code {})", job_id, name, code),
+                    None => eprintln!("Job {} is {}", job_id, name),
+                }
+            }
+            if finished {
+                exit_codes.insert(job_id, wait_exit_code(&status, exit_code));
+            }
+            statuses.insert(job_id, status);
+        }
+        if exit_codes.len() == job_ids.len() {
+            let code = job_ids
+                .iter()
+                .map(|job_id| exit_codes[job_id])
+                .find(|code| *code != 0)
+                .unwrap_or(0);
+            return Ok(code);
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            eprintln!("Stopped waiting, the jobs keep running");
+            return Ok(WAIT_TIMEOUT_EXIT_CODE);
+        }
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
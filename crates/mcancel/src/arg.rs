@@ -11,7 +11,15 @@ pub struct Args {
     )]
     pub api_endpoint: String,
 
-    /// The job id
-    #[arg()]
-    pub job: u64,
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// Job ids or ranges of them, e.g. `10 11 12` or `10-20`
+    #[arg(required_unless_present = "all_mine")]
+    pub jobs: Vec<String>,
+
+    /// Cancel all of your pending and running jobs
+    #[arg(long = "all-mine", conflicts_with = "jobs")]
+    pub all_mine: bool,
 }
@@ -4,6 +4,40 @@ use melon_common::RequestedResources;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
+/// Longest range of job ids that can be given at once
+pub const MAX_RANGE_LEN: u64 = 1000;
+
+/// Expands job ids and ranges like `10-20` (both ends included), dropping duplicates.
+pub fn parse_job_ids(values: &[String]) -> Result<Vec<u64>> {
+    let mut job_ids: Vec<u64> = vec![];
+    for value in values {
+        let number = |part: &str| {
+            part.parse::<u64>()
+                .map_err(|_| anyhow!("Invalid job id {}", value))
+        };
+        let (first, last) = match value.split_once('-') {
+            Some((first, last)) => (number(first)?, number(last)?),
+            None => (number(value)?, number(value)?),
+        };
+        if first > last {
+            return Err(anyhow!("Invalid job range {}", value));
+        }
+        if last - first >= MAX_RANGE_LEN {
+            return Err(anyhow!(
+                "Job range {} is longer than {} jobs",
+                value,
+                MAX_RANGE_LEN
+            ));
+        }
+        for job_id in first..=last {
+            if !job_ids.contains(&job_id) {
+                job_ids.push(job_id);
+            }
+        }
+    }
+    Ok(job_ids)
+}
+
 pub fn parse_mbatch_comments(path: &str) -> Result<RequestedResources> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
@@ -51,6 +85,8 @@ pub fn parse_mbatch_comments(path: &str) -> Result<RequestedResources> {
             cpu_count,
             memory,
             time,
+            gpu_count: 0,
+            node_count: 1,
         })
     } else {
         Err(anyhow!(
@@ -65,6 +101,28 @@ mod tests {
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    fn ids(values: &[&str]) -> Result<Vec<u64>> {
+        parse_job_ids(&values.iter().map(|v| v.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_job_ids_and_ranges() {
+        assert_eq!(ids(&["10", "11", "12"]).unwrap(), vec![10, 11, 12]);
+        assert_eq!(ids(&["3-5", "1"]).unwrap(), vec![3, 4, 5, 1]);
+        assert_eq!(ids(&["7-7"]).unwrap(), vec![7]);
+        // overlapping ranges cancel every job once
+        assert_eq!(ids(&["1-3", "2-4"]).unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_parse_invalid_job_ids() {
+        assert!(ids(&["abc"]).is_err());
+        assert!(ids(&["5-3"]).is_err());
+        assert!(ids(&["1-"]).is_err());
+        assert!(ids(&["-1"]).is_err());
+        assert!(ids(&["1-5000"]).is_err());
+    }
+
     fn create_temp_file(content: &str) -> NamedTempFile {
         let mut file = NamedTempFile::new().unwrap();
         writeln!(file, "{}", content).unwrap();
@@ -1,25 +1,90 @@
 mod arg;
 use arg::Args;
 use clap::Parser;
-use melon_common::proto::{self, melon_scheduler_client::MelonSchedulerClient};
+use mcancel::parse_job_ids;
+use melon_client::{Client, Code};
+use melon_common::JobStatus;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let job_id = args.job;
     let user = whoami::username();
+    let requested = if args.all_mine {
+        None
+    } else {
+        match parse_job_ids(&args.jobs) {
+            Ok(job_ids) => Some(job_ids),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let job_ids = if let Some(job_ids) = requested {
+        job_ids
+    } else {
+        let jobs = client.list(Some(&user)).await?;
+        jobs.iter()
+            .filter(|job| {
+                matches!(
+                    job.status,
+                    JobStatus::Pending | JobStatus::Held | JobStatus::Running
+                )
+            })
+            .map(|job| job.id)
+            .collect()
+    };
+    if job_ids.is_empty() {
+        println!("No jobs to cancel");
+        return Ok(());
+    }
 
-    let mut client = MelonSchedulerClient::connect(args.api_endpoint).await?;
-    let request = tonic::Request::new(proto::CancelJobRequest { job_id, user });
-    match client.cancel_job(request).await {
-        Ok(_) => println!("Successfully canceled job {}", job_id),
-        Err(e) => match e.code() {
-            tonic::Code::NotFound => println!("Unknown job id {}", job_id),
-            tonic::Code::PermissionDenied => {
-                println!("Not authorized to cancel job id {}", job_id)
+    // all cancellations are sent at once, the results are printed in order
+    let handles: Vec<_> = job_ids
+        .iter()
+        .map(|&job_id| {
+            let mut client = client.clone();
+            let user = user.clone();
+            tokio::spawn(async move { client.cancel(job_id, &user).await })
+        })
+        .collect();
+
+    let mut cancelled = 0;
+    for (job_id, handle) in job_ids.iter().zip(handles) {
+        match handle.await? {
+            Ok(_) => {
+                cancelled += 1;
+                println!("Successfully canceled job {}", job_id)
             }
-            _ => println!("Unknown error!"),
-        },
+            Err(e) => match e.code() {
+                Code::NotFound => println!("Unknown job id {}", job_id),
+                Code::PermissionDenied => {
+                    println!("Not authorized to cancel job id {}", job_id)
+                }
+                _ => println!("Could not cancel job {}: {}", job_id, e.message()),
+            },
+        }
+    }
+    if job_ids.len() > 1 {
+        println!("Canceled {} of {} jobs", cancelled, job_ids.len());
+    }
+    if cancelled < job_ids.len() {
+        std::process::exit(1);
     }
 
     Ok(())
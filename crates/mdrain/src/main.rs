@@ -0,0 +1,39 @@
+mod arg;
+use arg::Args;
+use clap::Parser;
+use melon_client::{proto, Client, Code};
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let node_id = args.node;
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let request = tonic::Request::new(proto::DrainNodeRequest {
+        node_id: node_id.clone(),
+    });
+    match client.scheduler().drain_node(request).await {
+        Ok(_) => println!(
+            "Draining node {}, it will be removed once its running jobs have finished",
+            node_id
+        ),
+        Err(e) => match e.code() {
+            Code::NotFound => println!("Unknown node id {}", node_id),
+            _ => println!("Unknown error!"),
+        },
+    }
+
+    Ok(())
+}
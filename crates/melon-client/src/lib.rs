@@ -0,0 +1,230 @@
+use melon_common::auth::{self, SchedulerClient, TokenInterceptor};
+use melon_common::{Job, JobStatus};
+use std::time::Duration;
+
+pub use melon_common::auth::ConnectError;
+pub use melon_common::proto;
+pub use tonic::{Code, Status};
+
+/// How long [Client::connect] waits for the scheduler, unless set otherwise
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Turns an endpoint into a URL the scheduler can be reached at.
+///
+/// Bare addresses like `[::1]:8080` get an `http://` prefix, URLs are returned as they are.
+pub fn endpoint_url(endpoint: &str) -> String {
+    if endpoint.contains("://") {
+        endpoint.to_string()
+    } else {
+        format!("http://{}", endpoint)
+    }
+}
+
+/// Async client of the scheduler.
+///
+/// Covers the calls most tools need, [Client::scheduler] gives access to all the others.
+/// Cloning is cheap, the clones share the connection.
+#[derive(Clone, Debug)]
+pub struct Client {
+    inner: SchedulerClient,
+}
+
+/// Settings of a connection to the scheduler, see [Client::builder].
+#[derive(Clone, Debug)]
+pub struct ClientBuilder {
+    endpoint: String,
+    timeout: Duration,
+    interceptor: TokenInterceptor,
+}
+
+impl ClientBuilder {
+    /// Gives up connecting after `timeout` instead of [DEFAULT_TIMEOUT].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Authenticates with `token` instead of the one from [auth::TOKEN_ENV].
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.interceptor = TokenInterceptor::new(token);
+        self
+    }
+
+    pub async fn connect(self) -> Result<Client, ConnectError> {
+        let endpoint = endpoint_url(&self.endpoint);
+        let connect = auth::connect_with_token(endpoint.clone(), self.interceptor);
+        match tokio::time::timeout(self.timeout, connect).await {
+            Ok(Ok(inner)) => Ok(Client { inner }),
+            Ok(Err(source)) => Err(ConnectError::Transport { endpoint, source }),
+            Err(_) => Err(ConnectError::Timeout {
+                endpoint,
+                timeout: self.timeout,
+            }),
+        }
+    }
+}
+
+impl Client {
+    /// Starts the settings of a connection to the scheduler at `endpoint`.
+    ///
+    /// The endpoint may be a bare address, see [endpoint_url]. Without further settings the
+    /// connection uses the token from [auth::TOKEN_ENV] and gives up after [DEFAULT_TIMEOUT].
+    pub fn builder(endpoint: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            endpoint: endpoint.into(),
+            timeout: DEFAULT_TIMEOUT,
+            interceptor: TokenInterceptor::from_env(),
+        }
+    }
+
+    /// Connects to the scheduler at `endpoint` with the default settings.
+    pub async fn connect(endpoint: impl Into<String>) -> Result<Self, ConnectError> {
+        Self::builder(endpoint).connect().await
+    }
+
+    /// Returns the underlying gRPC client, for the calls this one doesn't wrap.
+    pub fn scheduler(&mut self) -> &mut SchedulerClient {
+        &mut self.inner
+    }
+
+    /// Submits a job and returns its id, or what was validated for a dry run.
+    pub async fn submit(
+        &mut self,
+        submission: proto::JobSubmission,
+    ) -> Result<proto::MasterJobResponse, Status> {
+        let request = tonic::Request::new(submission);
+        Ok(self.inner.submit_job(request).await?.into_inner())
+    }
+
+    /// Submits several jobs in one request, they are either all queued or none is.
+    ///
+    /// Returns the responses in the order of the submissions, the jobs get consecutive ids.
+    pub async fn submit_batch(
+        &mut self,
+        submissions: Vec<proto::JobSubmission>,
+    ) -> Result<Vec<proto::MasterJobResponse>, Status> {
+        let request = tonic::Request::new(proto::JobBatch { submissions });
+        Ok(self
+            .inner
+            .submit_jobs(request)
+            .await?
+            .into_inner()
+            .responses)
+    }
+
+    /// Cancels a job of `user`.
+    pub async fn cancel(&mut self, job_id: u64, user: &str) -> Result<(), Status> {
+        let request = tonic::Request::new(proto::CancelJobRequest {
+            job_id,
+            user: user.to_string(),
+        });
+        self.inner.cancel_job(request).await?;
+        Ok(())
+    }
+
+    /// Extends the time limit of a job of `user` by `extension_mins`.
+    pub async fn extend(
+        &mut self,
+        job_id: u64,
+        user: &str,
+        extension_mins: u32,
+    ) -> Result<(), Status> {
+        let request = tonic::Request::new(proto::ExtendJobRequest {
+            job_id,
+            user: user.to_string(),
+            extension_mins,
+        });
+        self.inner.extend_job(request).await?;
+        Ok(())
+    }
+
+    /// Lists the queued and running jobs, only those of `user` if given.
+    pub async fn list(&mut self, user: Option<&str>) -> Result<Vec<Job>, Status> {
+        self.list_submitted(user, None, None).await
+    }
+
+    /// Lists the jobs submitted at or after `since` and before `until`, given as unix times.
+    pub async fn list_submitted(
+        &mut self,
+        user: Option<&str>,
+        since: Option<u64>,
+        until: Option<u64>,
+    ) -> Result<Vec<Job>, Status> {
+        let response = match user {
+            Some(user) => {
+                let request = tonic::Request::new(proto::ListUserJobsRequest {
+                    user: user.to_string(),
+                    submitted_since: since,
+                    submitted_until: until,
+                });
+                self.inner.list_user_jobs(request).await?
+            }
+            None => {
+                let request = tonic::Request::new(proto::ListJobsRequest {
+                    submitted_since: since,
+                    submitted_until: until,
+                });
+                self.inner.list_jobs(request).await?
+            }
+        };
+        Ok(response.get_ref().jobs.iter().map(Job::from).collect())
+    }
+
+    /// Returns a job, including finished ones.
+    pub async fn info(&mut self, job_id: u64) -> Result<Job, Status> {
+        let request = tonic::Request::new(proto::GetJobInfoRequest { job_id });
+        let response = self.inner.get_job_info(request).await?;
+        Ok(response.get_ref().into())
+    }
+
+    /// Returns only the status of a job and, once finished, its exit code.
+    ///
+    /// Cheaper than [Client::info] for polling a job until it finished.
+    pub async fn status(&mut self, job_id: u64) -> Result<(JobStatus, Option<i32>), Status> {
+        let request = tonic::Request::new(proto::JobStatusRequest { job_id });
+        let response = self.inner.get_job_status(request).await?.into_inner();
+        Ok((
+            JobStatus::from_i32_or_failed(response.status),
+            response.exit_code,
+        ))
+    }
+
+    /// Lists the compute nodes and their resources.
+    pub async fn nodes(&mut self) -> Result<Vec<proto::NodeDetails>, Status> {
+        let response = self.inner.list_nodes(tonic::Request::new(())).await?;
+        Ok(response.into_inner().nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_url() {
+        assert_eq!(endpoint_url("[::1]:8080"), "http://[::1]:8080");
+        assert_eq!(endpoint_url("10.0.0.5:8080"), "http://10.0.0.5:8080");
+        assert_eq!(endpoint_url("http://[::1]:8080"), "http://[::1]:8080");
+        assert_eq!(endpoint_url("https://melon:443"), "https://melon:443");
+    }
+
+    #[tokio::test]
+    async fn test_connect_refused() {
+        let endpoint = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+
+        let err = Client::builder(endpoint.clone())
+            .with_timeout(Duration::from_secs(5))
+            .connect()
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, ConnectError::Transport { .. }));
+        assert_eq!(
+            err.to_string().split(": ").next().unwrap(),
+            format!("Scheduler unreachable at http://{}", endpoint)
+        );
+    }
+}
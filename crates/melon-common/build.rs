@@ -1,6 +1,10 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // submissions are handed to the scheduler's admission hook as JSON
+    let serde = "#[derive(serde::Serialize, serde::Deserialize)] #[serde(default)]";
     tonic_build::configure()
         .protoc_arg("--experimental_allow_proto3_optional")
+        .type_attribute("melon.JobSubmission", serde)
+        .type_attribute("melon.RequestedResources", serde)
         .compile(&["../../proto/melon.proto"], &["../../proto"])?;
     Ok(())
 }
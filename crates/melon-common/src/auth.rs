@@ -0,0 +1,139 @@
+use crate::proto::melon_scheduler_client::MelonSchedulerClient;
+use crate::proto::melon_worker_client::MelonWorkerClient;
+use std::fmt;
+use std::time::Duration;
+use tonic::metadata::MetadataValue;
+use tonic::service::{interceptor::InterceptedService, Interceptor};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+
+/// Environment variable that holds the token for the scheduler RPCs
+pub const TOKEN_ENV: &str = "MELON_TOKEN";
+
+/// Environment variable that holds the token the workers expect from the scheduler
+pub const WORKER_TOKEN_ENV: &str = "MELON_WORKER_TOKEN";
+
+/// Client of the scheduler that sends a bearer token with every request.
+pub type SchedulerClient = MelonSchedulerClient<InterceptedService<Channel, TokenInterceptor>>;
+
+/// Client of a worker that sends the worker token with every request.
+pub type WorkerClient = MelonWorkerClient<InterceptedService<Channel, TokenInterceptor>>;
+
+/// Adds an `authorization: Bearer <token>` header to outgoing requests.
+///
+/// Without a token, requests are sent as they are, which only works if the scheduler runs without authentication.
+#[derive(Clone, Debug, Default)]
+pub struct TokenInterceptor {
+    token: Option<String>,
+}
+
+impl TokenInterceptor {
+    pub fn new(token: Option<String>) -> Self {
+        Self { token }
+    }
+
+    /// Reads the token from [TOKEN_ENV], if set.
+    pub fn from_env() -> Self {
+        Self::new(std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty()))
+    }
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.token {
+            let value = MetadataValue::try_from(format!("Bearer {}", token))
+                .map_err(|_| Status::unauthenticated("Token contains invalid characters"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }
+}
+
+/// Connects to the scheduler, authenticated with the token from [TOKEN_ENV].
+pub async fn connect(
+    endpoint: impl Into<String>,
+) -> Result<SchedulerClient, tonic::transport::Error> {
+    connect_with_token(endpoint, TokenInterceptor::from_env()).await
+}
+
+/// The scheduler could not be reached.
+#[derive(Debug)]
+pub enum ConnectError {
+    /// No connection within the timeout
+    Timeout { endpoint: String, timeout: Duration },
+    /// The connection failed, e.g. because it was refused
+    Transport {
+        endpoint: String,
+        source: tonic::transport::Error,
+    },
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectError::Timeout { endpoint, timeout } => write!(
+                f,
+                "Scheduler unreachable at {} (no connection after {}s)",
+                endpoint,
+                timeout.as_secs()
+            ),
+            ConnectError::Transport { endpoint, source } => {
+                write!(f, "Scheduler unreachable at {}: {}", endpoint, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Timeout { .. } => None,
+            ConnectError::Transport { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Connects to the scheduler with the given token interceptor.
+pub async fn connect_with_token(
+    endpoint: impl Into<String>,
+    interceptor: TokenInterceptor,
+) -> Result<SchedulerClient, tonic::transport::Error> {
+    let channel = Endpoint::from_shared(endpoint.into())?.connect().await?;
+    Ok(MelonSchedulerClient::with_interceptor(channel, interceptor))
+}
+
+/// Returns the token of a request's `authorization: Bearer <token>` header.
+pub fn bearer_token<T>(request: &Request<T>) -> Option<&str> {
+    request
+        .metadata()
+        .get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interceptor_adds_token() {
+        let mut interceptor = TokenInterceptor::new(Some("secret".to_string()));
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(bearer_token(&request), Some("secret"));
+    }
+
+    #[test]
+    fn test_interceptor_without_token() {
+        let mut interceptor = TokenInterceptor::default();
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(bearer_token(&request), None);
+    }
+
+    #[test]
+    fn test_invalid_token() {
+        let mut interceptor = TokenInterceptor::new(Some("line\nbreak".to_string()));
+        let status = interceptor.call(Request::new(())).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    }
+}
@@ -1,8 +1,13 @@
 use proto::JobSubmission;
-use std::time::Instant;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use utils::get_current_timestamp;
+pub mod auth;
 pub mod configuration;
 pub mod error;
+pub mod script;
 pub mod telemetry;
 use serde::{Deserialize, Serialize};
 pub mod utils;
@@ -11,6 +16,12 @@ pub mod proto {
     tonic::include_proto!("melon");
 }
 
+/// Priority assigned to jobs that don't request one explicitly
+pub const DEFAULT_PRIORITY: u8 = 50;
+
+/// Partition of jobs that don't request one explicitly
+pub const DEFAULT_PARTITION: &str = "default";
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Job {
     /// The unique ID, created by the scheduler
@@ -42,6 +53,84 @@ pub struct Job {
 
     /// The id of the compute node that is working on this job
     pub assigned_node: Option<String>,
+
+    /// The other nodes of a multi-node job, `assigned_node` is the first one
+    pub extra_nodes: Vec<String>,
+
+    /// Scheduling priority, higher values are assigned first
+    pub priority: u8,
+
+    /// IDs of jobs that have to complete successfully before this job can start
+    pub dependencies: Vec<u64>,
+
+    /// The partition the job is scheduled in
+    pub partition: String,
+
+    /// Labels a node needs to run the job, e.g. `ssd`
+    pub constraints: Vec<String>,
+
+    /// Extra environment variables of the job process
+    pub env: HashMap<String, String>,
+
+    /// Directory the job process is started in, the worker's if unset
+    pub work_dir: Option<String>,
+
+    /// Name given by the user
+    pub name: Option<String>,
+
+    /// Seconds the job gets to exit after SIGTERM before it is killed, the worker's default if unset
+    pub grace_secs: Option<u32>,
+
+    /// Minutes after submission in which the job has to start, it is cancelled otherwise
+    pub begin_deadline_mins: Option<u32>,
+
+    /// Signal the job gets once it used a share of its time limit, the worker's default if unset
+    pub warn_signal: Option<WarnSignal>,
+
+    /// File the standard output is written to, see [utils::expand_output_path], the worker's log directory if unset
+    pub output_path: Option<String>,
+
+    /// File the standard error is written to, like `output_path`
+    pub error_path: Option<String>,
+
+    /// Peak memory usage in bytes, once finished and if measured
+    pub max_rss_bytes: Option<u64>,
+
+    /// CPU time in seconds, once finished and if measured
+    pub cpu_seconds: Option<f64>,
+
+    /// How often the job is put back into the queue after failing
+    pub max_retries: u32,
+
+    /// How often the job has been put back into the queue so far
+    pub retry_count: u32,
+
+    /// Earlier runs of the job that failed, oldest first
+    pub attempts: Vec<JobAttempt>,
+
+    /// Exit code of the job process, once finished and if it exited normally
+    pub exit_code: Option<i32>,
+
+    /// Why the job did not complete, e.g. the end of its error output
+    pub message: Option<String>,
+
+    /// Whether the job's process is run for a client that attaches to it, see `mrun`
+    pub interactive: bool,
+
+    /// The job whose allocation this step runs in, steps hold no resources of their own
+    pub parent_job_id: Option<u64>,
+
+    /// The script itself if it was sent along, the node runs it instead of reading `script_path`,
+    /// only kept until the job finished
+    #[serde(skip)]
+    pub script_contents: Option<Vec<u8>>,
+
+    /// Secret the client of an interactive job attaches with, only known to the scheduler
+    #[serde(skip)]
+    pub attach_token: Option<String>,
+
+    /// Place of a pending job in the queue, only set when a single job is looked up
+    pub queue_position: Option<QueuePosition>,
 }
 
 impl Job {
@@ -63,12 +152,197 @@ impl Job {
             stop_time: None,
             status: JobStatus::Pending,
             assigned_node: None,
+            extra_nodes: Vec::new(),
+            priority: DEFAULT_PRIORITY,
+            dependencies: Vec::new(),
+            partition: DEFAULT_PARTITION.to_string(),
+            constraints: Vec::new(),
+            env: HashMap::new(),
+            work_dir: None,
+            name: None,
+            grace_secs: None,
+            begin_deadline_mins: None,
+            warn_signal: None,
+            output_path: None,
+            error_path: None,
+            max_rss_bytes: None,
+            cpu_seconds: None,
+            max_retries: 0,
+            retry_count: 0,
+            attempts: Vec::new(),
+            exit_code: None,
+            message: None,
+            interactive: false,
+            parent_job_id: None,
+            script_contents: None,
+            attach_token: None,
+            queue_position: None,
         }
     }
 
     pub fn extend_time(&mut self, extension_in_mins: u32) {
         self.req_res.time += extension_in_mins;
     }
+
+    /// Returns the job name, or the file name of the script if none was given.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or_else(|| {
+            self.script_path
+                .rsplit('/')
+                .next()
+                .unwrap_or(&self.script_path)
+        })
+    }
+
+    /// Returns all nodes the job runs on, `assigned_node` first.
+    pub fn nodes(&self) -> impl Iterator<Item = &String> {
+        self.assigned_node.iter().chain(&self.extra_nodes)
+    }
+
+    /// Whether a part of the job runs on the given node.
+    pub fn runs_on(&self, node_id: &str) -> bool {
+        self.nodes().any(|node| node == node_id)
+    }
+
+    /// Whether the job is a step that runs within the allocation of another job.
+    pub fn is_step(&self) -> bool {
+        self.parent_job_id.is_some()
+    }
+
+    /// Whether a pending job has missed its begin deadline at `now` (seconds since the epoch).
+    pub fn begin_deadline_passed(&self, now: u64) -> bool {
+        self.begin_deadline_mins
+            .is_some_and(|mins| now >= self.submit_time + mins as u64 * 60)
+    }
+
+    /// Whether the job has retries left after a failure.
+    pub fn can_retry(&self) -> bool {
+        self.retry_count < self.max_retries
+    }
+
+    /// Records the current run as a failed attempt and resets the job to pending.
+    pub fn requeue(&mut self, status: JobStatus) {
+        let node_id = self.nodes().cloned().collect::<Vec<_>>().join(",");
+        self.assigned_node = None;
+        self.extra_nodes.clear();
+        self.attempts.push(JobAttempt {
+            node_id,
+            start_time: self.start_time.take(),
+            stop_time: get_current_timestamp(),
+            status,
+        });
+        self.retry_count += 1;
+        self.status = JobStatus::Pending;
+        self.max_rss_bytes = None;
+        self.cpu_seconds = None;
+        self.exit_code = None;
+        self.message = None;
+    }
+}
+
+/// Signals a job can ask for with [WarnSignal]
+pub const WARN_SIGNALS: &[&str] = &["HUP", "INT", "QUIT", "USR1", "USR2", "TERM"];
+
+/// A signal that is sent to a job once it used a share of its time limit, e.g. to write a checkpoint.
+///
+/// Written as `<signal>@<percent>`, e.g. `USR1@90`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct WarnSignal {
+    /// Name of the signal without the `SIG` prefix, one of [WARN_SIGNALS]
+    pub signal: String,
+
+    /// Share of the time limit after which the signal is sent, between 1 and 99
+    pub percent: u32,
+}
+
+impl WarnSignal {
+    /// Returns how far into a time limit of `budget` the signal is due.
+    pub fn due_after(&self, budget: Duration) -> Duration {
+        budget * self.percent / 100
+    }
+}
+
+impl FromStr for WarnSignal {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (signal, percent) = value
+            .split_once('@')
+            .ok_or_else(|| format!("Invalid signal {}, expected e.g. USR1@90", value))?;
+        let signal = signal.trim().to_ascii_uppercase();
+        let signal = signal.strip_prefix("SIG").unwrap_or(&signal);
+        if !WARN_SIGNALS.contains(&signal) {
+            return Err(format!(
+                "Unsupported signal {}, expected one of {}",
+                signal,
+                WARN_SIGNALS.join(", ")
+            ));
+        }
+        let percent = percent
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|percent| (1..100).contains(percent))
+            .ok_or_else(|| format!("Invalid percentage in {}, expected 1 to 99", value))?;
+        Ok(Self {
+            signal: signal.to_string(),
+            percent,
+        })
+    }
+}
+
+impl fmt::Display for WarnSignal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}@{}", self.signal, self.percent)
+    }
+}
+
+/// Where a pending job waits in the queue.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct QueuePosition {
+    /// 1-based place among the pending jobs, in the order they are scheduled
+    pub position: u32,
+
+    /// Number of pending jobs
+    pub length: u32,
+}
+
+/// A finished run of a job that was retried afterwards.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct JobAttempt {
+    /// The node the job ran on, comma-separated for a multi-node job
+    pub node_id: String,
+
+    /// Start time, if the job got to start
+    pub start_time: Option<u64>,
+
+    /// Stop time
+    pub stop_time: u64,
+
+    /// The status the run ended with
+    pub status: JobStatus,
+}
+
+impl From<&JobAttempt> for proto::JobAttempt {
+    fn from(attempt: &JobAttempt) -> Self {
+        proto::JobAttempt {
+            node_id: attempt.node_id.clone(),
+            start_time: attempt.start_time,
+            stop_time: attempt.stop_time,
+            status: proto::JobStatus::from(attempt.status.clone()).into(),
+        }
+    }
+}
+
+impl From<&proto::JobAttempt> for JobAttempt {
+    fn from(attempt: &proto::JobAttempt) -> Self {
+        JobAttempt {
+            node_id: attempt.node_id.clone(),
+            start_time: attempt.start_time,
+            stop_time: attempt.stop_time,
+            status: JobStatus::from_i32_or_failed(attempt.status),
+        }
+    }
 }
 
 impl From<&Job> for proto::Job {
@@ -84,6 +358,32 @@ impl From<&Job> for proto::Job {
             stop_time: job.stop_time,
             status: proto::JobStatus::from(job.status.clone()).into(),
             assigned_node: job.assigned_node.clone().unwrap_or_default(),
+            extra_nodes: job.extra_nodes.clone(),
+            priority: job.priority as u32,
+            dependencies: job.dependencies.clone(),
+            partition: job.partition.clone(),
+            constraints: job.constraints.clone(),
+            env: job.env.clone(),
+            work_dir: job.work_dir.clone(),
+            name: job.name.clone(),
+            grace_secs: job.grace_secs,
+            begin_deadline_mins: job.begin_deadline_mins,
+            warn_signal: job.warn_signal.as_ref().map(ToString::to_string),
+            output_path: job.output_path.clone(),
+            error_path: job.error_path.clone(),
+            max_rss_bytes: job.max_rss_bytes,
+            cpu_seconds: job.cpu_seconds,
+            max_retries: job.max_retries,
+            retry_count: job.retry_count,
+            attempts: job.attempts.iter().map(Into::into).collect(),
+            exit_code: job.exit_code,
+            message: job.message.clone(),
+            interactive: job.interactive,
+            parent_job_id: job.parent_job_id,
+            queue_position: job.queue_position.map(|queue| queue.position),
+            queue_length: job.queue_position.map(|queue| queue.length),
+            // only sent for a single job
+            script_contents: None,
         }
     }
 }
@@ -99,12 +399,40 @@ impl From<&proto::Job> for Job {
             submit_time: job.submit_time,
             start_time: job.start_time,
             stop_time: job.stop_time,
-            status: JobStatus::from(job.status()),
+            status: JobStatus::from_i32_or_failed(job.status),
             assigned_node: if job.assigned_node.is_empty() {
                 None
             } else {
                 Some(job.assigned_node.clone())
             },
+            extra_nodes: job.extra_nodes.clone(),
+            priority: job.priority.min(u8::MAX as u32) as u8,
+            dependencies: job.dependencies.clone(),
+            partition: job.partition.clone(),
+            constraints: job.constraints.clone(),
+            env: job.env.clone(),
+            work_dir: job.work_dir.clone(),
+            name: job.name.clone(),
+            grace_secs: job.grace_secs,
+            begin_deadline_mins: job.begin_deadline_mins,
+            warn_signal: job.warn_signal.as_deref().and_then(|s| s.parse().ok()),
+            output_path: job.output_path.clone(),
+            error_path: job.error_path.clone(),
+            max_rss_bytes: job.max_rss_bytes,
+            cpu_seconds: job.cpu_seconds,
+            max_retries: job.max_retries,
+            retry_count: job.retry_count,
+            attempts: job.attempts.iter().map(Into::into).collect(),
+            exit_code: job.exit_code,
+            message: job.message.clone(),
+            interactive: job.interactive,
+            parent_job_id: job.parent_job_id,
+            script_contents: job.script_contents.clone(),
+            attach_token: None,
+            queue_position: job
+                .queue_position
+                .zip(job.queue_length)
+                .map(|(position, length)| QueuePosition { position, length }),
         }
     }
 }
@@ -116,6 +444,24 @@ impl From<&mut Job> for JobSubmission {
             script_path: val.script_path.clone(),
             req_res: Some(val.req_res.into()),
             script_args: val.script_args.clone(),
+            priority: Some(val.priority as u32),
+            dependencies: val.dependencies.clone(),
+            partition: Some(val.partition.clone()),
+            force: false,
+            env: val.env.clone(),
+            work_dir: val.work_dir.clone(),
+            name: val.name.clone(),
+            grace_secs: val.grace_secs,
+            max_retries: val.max_retries,
+            dry_run: false,
+            interactive: val.interactive,
+            constraints: val.constraints.clone(),
+            begin_deadline_mins: val.begin_deadline_mins,
+            warn_signal: val.warn_signal.as_ref().map(ToString::to_string),
+            output_path: val.output_path.clone(),
+            error_path: val.error_path.clone(),
+            parent_job_id: val.parent_job_id,
+            script_contents: val.script_contents.clone(),
         }
     }
 }
@@ -128,6 +474,16 @@ impl From<&mut Job> for proto::JobAssignment {
             script_path: val.script_path.clone(),
             req_res: Some(val.req_res.into()),
             script_args: val.script_args.clone(),
+            env: val.env.clone(),
+            work_dir: val.work_dir.clone(),
+            grace_secs: val.grace_secs,
+            interactive: val.interactive,
+            attach_token: val.attach_token.clone().unwrap_or_default(),
+            warn_signal: val.warn_signal.as_ref().map(ToString::to_string),
+            output_path: val.output_path.clone(),
+            error_path: val.error_path.clone(),
+            parent_job_id: val.parent_job_id,
+            script_contents: val.script_contents.clone(),
         }
     }
 }
@@ -138,6 +494,15 @@ pub struct RequestedResources {
     pub cpu_count: u32,
     pub memory: u64,
     pub time: u32,
+    #[serde(default)]
+    pub gpu_count: u32,
+    /// Number of nodes the job runs on, the resources above are per node
+    #[serde(default = "default_node_count")]
+    pub node_count: u32,
+}
+
+fn default_node_count() -> u32 {
+    1
 }
 
 impl From<RequestedResources> for proto::RequestedResources {
@@ -146,6 +511,8 @@ impl From<RequestedResources> for proto::RequestedResources {
             cpu_count: req_res.cpu_count,
             memory: req_res.memory,
             time: req_res.time,
+            gpu_count: req_res.gpu_count,
+            node_count: req_res.node_count,
         }
     }
 }
@@ -156,6 +523,8 @@ impl From<&mut RequestedResources> for proto::RequestedResources {
             cpu_count: req_res.cpu_count,
             memory: req_res.memory,
             time: req_res.time,
+            gpu_count: req_res.gpu_count,
+            node_count: req_res.node_count,
         }
     }
 }
@@ -166,18 +535,28 @@ impl From<proto::RequestedResources> for RequestedResources {
             cpu_count: res.cpu_count,
             memory: res.memory,
             time: res.time,
+            gpu_count: res.gpu_count,
+            node_count: res.node_count.max(1),
         }
     }
 }
 
 impl RequestedResources {
-    pub fn new(cpu_count: u32, memory: u64, time: u32) -> Self {
+    pub fn new(cpu_count: u32, memory: u64, time: u32, gpu_count: u32) -> Self {
         Self {
             cpu_count,
             memory,
             time,
+            gpu_count,
+            node_count: 1,
         }
     }
+
+    /// Spreads the job over `node_count` nodes with the same resources each.
+    pub fn with_node_count(mut self, node_count: u32) -> Self {
+        self.node_count = node_count.max(1);
+        self
+    }
 }
 
 /// Available Resources on a worker node.
@@ -185,17 +564,43 @@ impl RequestedResources {
 pub struct NodeResources {
     pub cpu_count: u32,
     pub memory: u64,
+    pub gpu_count: u32,
 }
 
 impl NodeResources {
-    pub fn new(cpu_count: u32, memory: u64) -> Self {
-        Self { cpu_count, memory }
+    pub fn new(cpu_count: u32, memory: u64, gpu_count: u32) -> Self {
+        Self {
+            cpu_count,
+            memory,
+            gpu_count,
+        }
     }
 
     pub fn empty() -> Self {
         Self {
             cpu_count: 0,
             memory: 0,
+            gpu_count: 0,
+        }
+    }
+}
+
+impl From<&NodeResources> for proto::NodeResources {
+    fn from(res: &NodeResources) -> Self {
+        Self {
+            cpu_count: res.cpu_count,
+            memory: res.memory,
+            gpu_count: res.gpu_count,
+        }
+    }
+}
+
+impl From<proto::NodeResources> for NodeResources {
+    fn from(res: proto::NodeResources) -> Self {
+        Self {
+            cpu_count: res.cpu_count,
+            memory: res.memory,
+            gpu_count: res.gpu_count,
         }
     }
 }
@@ -207,6 +612,11 @@ pub enum JobStatus {
     Pending,
     Running,
     Timeout,
+    Cancelled,
+    /// Pending, but kept from being scheduled until it is released
+    Held,
+    /// Killed for exceeding its requested memory
+    OutOfMemory,
 }
 
 impl From<JobStatus> for proto::JobStatus {
@@ -217,6 +627,9 @@ impl From<JobStatus> for proto::JobStatus {
             JobStatus::Pending => proto::JobStatus::Pending,
             JobStatus::Running => proto::JobStatus::Running,
             JobStatus::Timeout => proto::JobStatus::Timeout,
+            JobStatus::Cancelled => proto::JobStatus::Cancelled,
+            JobStatus::Held => proto::JobStatus::Held,
+            JobStatus::OutOfMemory => proto::JobStatus::OutOfMemory,
         }
     }
 }
@@ -228,16 +641,32 @@ impl From<JobStatus> for i32 {
     }
 }
 
-impl From<i32> for JobStatus {
-    fn from(value: i32) -> Self {
-        match value {
-            x if x == proto::JobStatus::Completed as i32 => JobStatus::Completed,
-            x if x == proto::JobStatus::Failed as i32 => JobStatus::Failed,
-            x if x == proto::JobStatus::Pending as i32 => JobStatus::Pending,
-            x if x == proto::JobStatus::Running as i32 => JobStatus::Running,
-            x if x == proto::JobStatus::Timeout as i32 => JobStatus::Timeout,
-            _ => panic!("Invalid JobStatus value: {}", value),
-        }
+/// Fails for values that are unknown to this version.
+impl TryFrom<i32> for JobStatus {
+    type Error = prost::UnknownEnumValue;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        proto::JobStatus::try_from(value).map(JobStatus::from)
+    }
+}
+
+impl JobStatus {
+    /// Whether the job has ended and won't change its status anymore.
+    pub fn is_finished(&self) -> bool {
+        !matches!(
+            self,
+            JobStatus::Pending | JobStatus::Running | JobStatus::Held
+        )
+    }
+
+    /// Converts a status received from elsewhere, treating unknown values as failed.
+    ///
+    /// Lets peers running a newer version report states this build doesn't know.
+    pub fn from_i32_or_failed(value: i32) -> Self {
+        JobStatus::try_from(value).unwrap_or_else(|e| {
+            crate::log!(warn, "Unknown job status {}, treat as failed", e.0);
+            JobStatus::Failed
+        })
     }
 }
 
@@ -249,6 +678,9 @@ impl From<proto::JobStatus> for JobStatus {
             proto::JobStatus::Pending => JobStatus::Pending,
             proto::JobStatus::Running => JobStatus::Running,
             proto::JobStatus::Timeout => JobStatus::Timeout,
+            proto::JobStatus::Cancelled => JobStatus::Cancelled,
+            proto::JobStatus::Held => JobStatus::Held,
+            proto::JobStatus::OutOfMemory => JobStatus::OutOfMemory,
         }
     }
 }
@@ -261,6 +693,77 @@ impl From<JobStatus> for String {
             JobStatus::Pending => "Pending".to_string(),
             JobStatus::Running => "Running".to_string(),
             JobStatus::Timeout => "Timeout".to_string(),
+            JobStatus::Cancelled => "Cancelled".to_string(),
+            JobStatus::Held => "Held".to_string(),
+            JobStatus::OutOfMemory => "OutOfMemory".to_string(),
+        }
+    }
+}
+
+/// Nodes that only run jobs of some users during a time window.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Reservation {
+    /// Unique ID, assigned by the scheduler
+    pub id: u64,
+
+    /// Name given by the admin, e.g. the class it is for
+    pub name: String,
+
+    /// Ids of the reserved nodes
+    pub node_ids: Vec<String>,
+
+    /// Users whose jobs may run on the reserved nodes during the window
+    pub users: Vec<String>,
+
+    /// Unix timestamp in seconds at which the reservation starts
+    pub start_time: u64,
+
+    /// Unix timestamp in seconds at which the reservation ends
+    pub end_time: u64,
+
+    /// Admin that created the reservation
+    pub created_by: String,
+}
+
+impl Reservation {
+    /// Whether a job of `user` that runs on `node_id` from `start` to `end` conflicts with
+    /// the reservation.
+    pub fn blocks(&self, node_id: &str, user: &str, start: u64, end: u64) -> bool {
+        self.node_ids.iter().any(|id| id == node_id)
+            && !self.users.iter().any(|u| u == user)
+            && self.overlaps(start, end)
+    }
+
+    /// Whether the window of the reservation overlaps the time from `start` to `end`.
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        start < self.end_time && self.start_time < end
+    }
+}
+
+impl From<&proto::Reservation> for Reservation {
+    fn from(reservation: &proto::Reservation) -> Self {
+        Self {
+            id: reservation.id,
+            name: reservation.name.clone(),
+            node_ids: reservation.node_ids.clone(),
+            users: reservation.users.clone(),
+            start_time: reservation.start_time,
+            end_time: reservation.end_time,
+            created_by: reservation.created_by.clone(),
+        }
+    }
+}
+
+impl From<&Reservation> for proto::Reservation {
+    fn from(reservation: &Reservation) -> Self {
+        Self {
+            id: reservation.id,
+            name: reservation.name.clone(),
+            node_ids: reservation.node_ids.clone(),
+            users: reservation.users.clone(),
+            start_time: reservation.start_time,
+            end_time: reservation.end_time,
+            created_by: reservation.created_by.clone(),
         }
     }
 }
@@ -285,6 +788,12 @@ pub struct Node {
 
     /// Reachability status
     pub status: NodeStatus,
+
+    /// Most jobs the worker runs at once, as reported with its heartbeat
+    pub max_jobs: Option<u32>,
+
+    /// Features of the node that jobs can ask for, e.g. `ssd`
+    pub labels: Vec<String>,
 }
 
 impl Node {
@@ -296,9 +805,22 @@ impl Node {
             status,
             used_resources: NodeResources::empty(),
             last_heartbeat: Instant::now(),
+            max_jobs: None,
+            labels: Vec::new(),
         }
     }
 
+    /// Sets the features of the node.
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Whether the node has all labels a job asks for.
+    pub fn has_labels(&self, constraints: &[String]) -> bool {
+        constraints.iter().all(|label| self.labels.contains(label))
+    }
+
     pub fn set_status(&mut self, status: NodeStatus) {
         self.status = status;
     }
@@ -307,12 +829,14 @@ impl Node {
     pub fn reduce_avail_resources(&mut self, res: &RequestedResources) {
         self.used_resources.cpu_count += res.cpu_count;
         self.used_resources.memory += res.memory;
+        self.used_resources.gpu_count += res.gpu_count;
     }
 
     /// Free up available resources
     pub fn free_avail_resource(&mut self, res: &RequestedResources) {
         self.used_resources.cpu_count -= res.cpu_count;
         self.used_resources.memory -= res.memory;
+        self.used_resources.gpu_count -= res.gpu_count;
     }
 
     /// Update heartbeat
@@ -325,6 +849,32 @@ impl Node {
 pub enum NodeStatus {
     Available,
     Offline,
+    /// Accepts no new jobs, removed once its running jobs have finished
+    Draining,
+    /// Registered, but gets no jobs until its validation job succeeded
+    Validating,
+}
+
+impl From<NodeStatus> for proto::NodeStatus {
+    fn from(status: NodeStatus) -> Self {
+        match status {
+            NodeStatus::Available => proto::NodeStatus::Available,
+            NodeStatus::Offline => proto::NodeStatus::Offline,
+            NodeStatus::Draining => proto::NodeStatus::Draining,
+            NodeStatus::Validating => proto::NodeStatus::Validating,
+        }
+    }
+}
+
+impl From<proto::NodeStatus> for NodeStatus {
+    fn from(status: proto::NodeStatus) -> Self {
+        match status {
+            proto::NodeStatus::Available => NodeStatus::Available,
+            proto::NodeStatus::Offline => NodeStatus::Offline,
+            proto::NodeStatus::Draining => NodeStatus::Draining,
+            proto::NodeStatus::Validating => NodeStatus::Validating,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -334,11 +884,44 @@ pub struct JobResult {
 
     /// The job status (either completed or failed)
     pub status: JobStatus,
+
+    /// Peak memory usage in bytes, if measured
+    pub max_rss_bytes: Option<u64>,
+
+    /// CPU time in seconds, if measured
+    pub cpu_seconds: Option<f64>,
+
+    /// Exit code of the job process, if it exited normally
+    pub exit_code: Option<i32>,
+
+    /// Why the job did not complete
+    pub message: Option<String>,
 }
 
 impl JobResult {
     pub fn new(id: u64, status: JobStatus) -> Self {
-        Self { id, status }
+        Self {
+            id,
+            status,
+            max_rss_bytes: None,
+            cpu_seconds: None,
+            exit_code: None,
+            message: None,
+        }
+    }
+
+    /// Attaches the measured resource usage of the job.
+    pub fn with_usage(mut self, max_rss_bytes: Option<u64>, cpu_seconds: Option<f64>) -> Self {
+        self.max_rss_bytes = max_rss_bytes;
+        self.cpu_seconds = cpu_seconds;
+        self
+    }
+
+    /// Attaches how the job process ended and why the job did not complete.
+    pub fn with_exit(mut self, exit_code: Option<i32>, message: Option<String>) -> Self {
+        self.exit_code = exit_code;
+        self.message = message;
+        self
     }
 }
 
@@ -347,16 +930,18 @@ impl From<JobResult> for proto::JobResult {
         proto::JobResult {
             job_id: result.id,
             status: (proto::JobStatus::from(result.status)).into(),
+            max_rss_bytes: result.max_rss_bytes,
+            cpu_seconds: result.cpu_seconds,
+            exit_code: result.exit_code,
+            message: result.message,
+            node_id: String::new(),
         }
     }
 }
 
 impl From<proto::JobResult> for JobResult {
     fn from(result: proto::JobResult) -> Self {
-        JobResult {
-            id: result.job_id,
-            status: JobStatus::from(result.status),
-        }
+        (&result).into()
     }
 }
 
@@ -364,7 +949,11 @@ impl From<&proto::JobResult> for JobResult {
     fn from(result: &proto::JobResult) -> Self {
         JobResult {
             id: result.job_id,
-            status: JobStatus::from(result.status),
+            status: JobStatus::from_i32_or_failed(result.status),
+            max_rss_bytes: result.max_rss_bytes,
+            cpu_seconds: result.cpu_seconds,
+            exit_code: result.exit_code,
+            message: result.message.clone(),
         }
     }
 }
@@ -378,9 +967,42 @@ mod tests {
         #[test]
         fn job_conversion_roundtrip(id in 0u64.., user in ".*", script_path in ".*",
             script_args in proptest::collection::vec(".*", 0..10),
-            cpu_count in 1u32..16, memory in 0u64..(1 << 30), time in 0u32..) {
-            let req_res = RequestedResources::new(cpu_count, memory, time);
-            let job = Job::new(id, user, script_path, script_args, req_res);
+            dependencies in proptest::collection::vec(0u64.., 0..10),
+            cpu_count in 1u32..16, memory in 0u64..(1 << 30), time in 0u32.., gpu_count in 0u32..8,
+            node_count in 1u32..8, extra_nodes in proptest::collection::vec("[a-z]{1,10}", 0..4),
+            priority in 0u8.., partition in "[a-z]{1,10}",
+            constraints in proptest::collection::vec("[a-z0-9]{1,10}", 0..4),
+            env in proptest::collection::hash_map("[A-Z_]{1,10}", ".*", 0..5),
+            work_dir in proptest::option::of(".*"), name in proptest::option::of(".*"),
+            grace_secs in proptest::option::of(0u32..), begin_deadline_mins in proptest::option::of(0u32..),
+            warn_percent in proptest::option::of(1u32..100),
+            output_path in proptest::option::of("/[a-z%]{1,20}"), error_path in proptest::option::of("/[a-z%]{1,20}"),
+            max_rss_bytes in proptest::option::of(0u64..), cpu_seconds in proptest::option::of(0f64..1e9),
+            max_retries in 0u32..10, retry_count in 0u32..10, parent_job_id in proptest::option::of(0u64..),
+            exit_code in proptest::option::of(any::<i32>()), message in proptest::option::of(".*")) {
+            let req_res = RequestedResources::new(cpu_count, memory, time, gpu_count)
+                .with_node_count(node_count);
+            let mut job = Job::new(id, user, script_path, script_args, req_res);
+            job.extra_nodes = extra_nodes;
+            job.priority = priority;
+            job.dependencies = dependencies;
+            job.partition = partition;
+            job.constraints = constraints;
+            job.env = env;
+            job.work_dir = work_dir;
+            job.name = name;
+            job.grace_secs = grace_secs;
+            job.begin_deadline_mins = begin_deadline_mins;
+            job.output_path = output_path;
+            job.error_path = error_path;
+            job.warn_signal = warn_percent.map(|percent| WarnSignal { signal: "USR1".to_string(), percent });
+            job.max_rss_bytes = max_rss_bytes;
+            job.cpu_seconds = cpu_seconds;
+            job.max_retries = max_retries;
+            job.retry_count = retry_count;
+            job.parent_job_id = parent_job_id;
+            job.exit_code = exit_code;
+            job.message = message;
 
             let proto_job: proto::Job = (&job).into();
             let converted_job: Job = (&proto_job).into();
@@ -392,23 +1014,151 @@ mod tests {
             assert_eq!(job.req_res.cpu_count, converted_job.req_res.cpu_count);
             assert_eq!(job.req_res.memory, converted_job.req_res.memory);
             assert_eq!(job.req_res.time, converted_job.req_res.time);
+            assert_eq!(job.req_res.gpu_count, converted_job.req_res.gpu_count);
+            assert_eq!(job.req_res.node_count, converted_job.req_res.node_count);
+            assert_eq!(job.extra_nodes, converted_job.extra_nodes);
+            assert_eq!(job.constraints, converted_job.constraints);
+            assert_eq!(job.priority, converted_job.priority);
+            assert_eq!(job.dependencies, converted_job.dependencies);
+            assert_eq!(job.partition, converted_job.partition);
+            assert_eq!(job.env, converted_job.env);
+            assert_eq!(job.work_dir, converted_job.work_dir);
+            assert_eq!(job.name, converted_job.name);
+            assert_eq!(job.grace_secs, converted_job.grace_secs);
+            assert_eq!(job.begin_deadline_mins, converted_job.begin_deadline_mins);
+            assert_eq!(job.warn_signal, converted_job.warn_signal);
+            assert_eq!(job.output_path, converted_job.output_path);
+            assert_eq!(job.error_path, converted_job.error_path);
+            assert_eq!(job.parent_job_id, converted_job.parent_job_id);
+            assert_eq!(job.max_rss_bytes, converted_job.max_rss_bytes);
+            assert_eq!(job.cpu_seconds, converted_job.cpu_seconds);
+            assert_eq!(job.max_retries, converted_job.max_retries);
+            assert_eq!(job.retry_count, converted_job.retry_count);
+            assert_eq!(job.exit_code, converted_job.exit_code);
+            assert_eq!(job.message, converted_job.message);
         }
 
         #[test]
-        fn resource_reduction_and_free(cpu_count in 1u32..16, memory in 0u64..(1 << 30), time in 0u32..) {
+        fn resource_reduction_and_free(cpu_count in 1u32..16, memory in 0u64..(1 << 30), time in 0u32.., gpu_count in 0u32..8) {
             let mut node = Node::new("node-1".to_string(), "127.0.0.1".to_string(),
-            NodeResources::new(cpu_count, memory), NodeStatus::Available);
+            NodeResources::new(cpu_count, memory, gpu_count), NodeStatus::Available);
 
-            let req_res = RequestedResources::new(cpu_count / 2, memory / 2, time);
+            let req_res = RequestedResources::new(cpu_count / 2, memory / 2, time, gpu_count / 2);
             node.reduce_avail_resources(&req_res);
 
             assert!(node.used_resources.cpu_count <= node.avail_resources.cpu_count);
             assert!(node.used_resources.memory <= node.avail_resources.memory);
+            assert!(node.used_resources.gpu_count <= node.avail_resources.gpu_count);
 
             node.free_avail_resource(&req_res);
 
             assert_eq!(node.used_resources.cpu_count, 0);
             assert_eq!(node.used_resources.memory, 0);
+            assert_eq!(node.used_resources.gpu_count, 0);
         }
     }
+
+    #[test]
+    fn display_name_falls_back_to_script_name() {
+        let req_res = RequestedResources::new(1, 1024, 10, 0);
+        let mut job = Job::new(
+            1,
+            "user".into(),
+            "/home/user/run.sh".into(),
+            vec![],
+            req_res,
+        );
+        assert_eq!(job.display_name(), "run.sh");
+
+        job.name = Some("training".into());
+        assert_eq!(job.display_name(), "training");
+    }
+
+    #[test]
+    fn job_status_from_unknown_value() {
+        assert_eq!(JobStatus::try_from(6).unwrap(), JobStatus::Held);
+        assert!(JobStatus::try_from(42).is_err());
+        assert_eq!(JobStatus::from_i32_or_failed(42), JobStatus::Failed);
+    }
+
+    #[test]
+    fn requeue_records_attempt() {
+        let req_res = RequestedResources::new(1, 1024, 10, 0);
+        let mut job = Job::new(1, "user".into(), "job.sh".into(), vec![], req_res);
+        job.max_retries = 1;
+        job.status = JobStatus::Running;
+        job.assigned_node = Some("node-1".to_string());
+        job.start_time = Some(100);
+        assert!(job.can_retry());
+
+        job.requeue(JobStatus::Failed);
+
+        assert!(!job.can_retry());
+        assert_eq!(job.retry_count, 1);
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.assigned_node, None);
+        assert_eq!(job.start_time, None);
+        assert_eq!(job.attempts.len(), 1);
+        assert_eq!(job.attempts[0].node_id, "node-1");
+        assert_eq!(job.attempts[0].start_time, Some(100));
+        assert_eq!(job.attempts[0].status, JobStatus::Failed);
+
+        let proto_job: proto::Job = (&job).into();
+        let converted_job: Job = (&proto_job).into();
+        assert_eq!(job.attempts, converted_job.attempts);
+    }
+
+    #[test]
+    fn requeue_releases_all_nodes() {
+        let req_res = RequestedResources::new(1, 1024, 10, 0).with_node_count(2);
+        let mut job = Job::new(1, "user".into(), "job.sh".into(), vec![], req_res);
+        job.max_retries = 1;
+        job.status = JobStatus::Running;
+        job.assigned_node = Some("node-1".to_string());
+        job.extra_nodes = vec!["node-2".to_string()];
+        assert!(job.runs_on("node-2"));
+
+        job.requeue(JobStatus::Failed);
+
+        assert_eq!(job.nodes().count(), 0);
+        assert!(!job.runs_on("node-2"));
+        assert_eq!(job.attempts[0].node_id, "node-1,node-2");
+    }
+
+    #[test]
+    fn reservation_blocks_other_users_during_window() {
+        let reservation = Reservation {
+            id: 1,
+            name: "class".to_string(),
+            node_ids: vec!["node-1".to_string()],
+            users: vec!["student".to_string()],
+            start_time: 1000,
+            end_time: 2000,
+            created_by: "root".to_string(),
+        };
+
+        assert!(reservation.blocks("node-1", "other", 1500, 1600));
+        // a job that would still run when the reservation starts
+        assert!(reservation.blocks("node-1", "other", 500, 1001));
+        assert!(!reservation.blocks("node-1", "other", 500, 1000));
+        assert!(!reservation.blocks("node-1", "other", 2000, 3000));
+        assert!(!reservation.blocks("node-1", "student", 1500, 1600));
+        assert!(!reservation.blocks("node-2", "other", 1500, 1600));
+    }
+
+    #[test]
+    fn parse_warn_signal() {
+        let warn: WarnSignal = "usr1@90".parse().unwrap();
+        assert_eq!(warn.to_string(), "USR1@90");
+        assert_eq!(
+            warn.due_after(Duration::from_secs(600)),
+            Duration::from_secs(540)
+        );
+        assert_eq!("SIGUSR2@50".parse::<WarnSignal>().unwrap().signal, "USR2");
+
+        assert!("USR1".parse::<WarnSignal>().is_err());
+        assert!("KILL@90".parse::<WarnSignal>().is_err());
+        assert!("USR1@100".parse::<WarnSignal>().is_err());
+        assert!("USR1@0".parse::<WarnSignal>().is_err());
+    }
 }
@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Longest `#!` line that is looked at, like the kernel's limit
+const MAX_SHEBANG_LEN: u64 = 256;
+
+/// Whether the file at `path` has an execute bit set.
+///
+/// Always true on platforms without execute bits.
+pub fn is_executable(path: &Path) -> std::io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(path.metadata()?.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.metadata().map(|_| true)
+    }
+}
+
+/// Reads the interpreter of a script from its `#!` line, see [parse_shebang].
+pub fn read_shebang(path: &Path) -> std::io::Result<Option<Vec<String>>> {
+    let file = File::open(path)?;
+    let mut line = Vec::new();
+    BufReader::new(file)
+        .take(MAX_SHEBANG_LEN)
+        .read_until(b'\n', &mut line)?;
+    Ok(parse_shebang(&String::from_utf8_lossy(&line)))
+}
+
+/// Parses a `#!` line into the interpreter and its optional argument.
+///
+/// Like the kernel, everything after the interpreter is passed as a single argument.
+pub fn parse_shebang(line: &str) -> Option<Vec<String>> {
+    let command = line.strip_prefix("#!")?.trim();
+    let mut parts = command.splitn(2, char::is_whitespace);
+    let interpreter = parts.next().filter(|i| !i.is_empty())?;
+    let mut command = vec![interpreter.to_string()];
+    if let Some(arg) = parts.next().map(str::trim).filter(|a| !a.is_empty()) {
+        command.push(arg.to_string());
+    }
+    Some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shebang() {
+        assert_eq!(
+            parse_shebang("#!/bin/bash\n"),
+            Some(vec!["/bin/bash".to_string()])
+        );
+        assert_eq!(
+            parse_shebang("#! /usr/bin/env python3 -u\n"),
+            Some(vec!["/usr/bin/env".to_string(), "python3 -u".to_string()])
+        );
+        assert_eq!(parse_shebang("#!\n"), None);
+        assert_eq!(parse_shebang("echo hello\n"), None);
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+use std::str::FromStr;
 use tracing::subscriber::set_global_default;
 use tracing::Subscriber;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
@@ -15,24 +17,103 @@ macro_rules! log {
     }
 }
 
+/// How the daemons write their log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log pipelines
+    Json,
+    /// Bunyan-style JSON objects, as written by earlier versions
+    Bunyan,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            "bunyan" => Ok(LogFormat::Bunyan),
+            _ => Err(format!(
+                "Unknown log format {}, expected text, json or bunyan",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+            LogFormat::Bunyan => "bunyan",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Builds the subscriber of a daemon that writes to `sink`.
+///
+/// `log_level` takes the same directives as `RUST_LOG`, e.g. `debug` or `melond=debug,info`.
+/// Without it, `RUST_LOG` is used, and `info` if that isn't set either.
 pub fn get_subscriber<Sink>(
     name: String,
-    env_filter: String,
+    log_level: Option<String>,
+    format: LogFormat,
     sink: Sink,
-) -> impl Subscriber + Send + Sync
+) -> Box<dyn Subscriber + Send + Sync>
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
-    let env_filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
-    let formatting_layer = BunyanFormattingLayer::new(name, sink);
-    Registry::default()
-        .with(env_filter)
-        .with(JsonStorageLayer)
-        .with(formatting_layer)
+    let env_filter = match log_level {
+        Some(log_level) => EnvFilter::new(log_level),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let registry = Registry::default().with(env_filter);
+    match format {
+        // the output usually ends up in journald or a file, which don't render colors
+        LogFormat::Text => Box::new(
+            registry.with(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(sink)
+                    .with_ansi(false),
+            ),
+        ),
+        LogFormat::Json => Box::new(
+            registry.with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_span_list(false)
+                    .with_writer(sink),
+            ),
+        ),
+        LogFormat::Bunyan => Box::new(
+            registry
+                .with(JsonStorageLayer)
+                .with(BunyanFormattingLayer::new(name, sink)),
+        ),
+    }
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to set logger");
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("text".parse(), Ok(LogFormat::Text));
+        assert_eq!("JSON".parse(), Ok(LogFormat::Json));
+        assert_eq!("bunyan".parse(), Ok(LogFormat::Bunyan));
+        assert!("yaml".parse::<LogFormat>().is_err());
+        assert_eq!(LogFormat::default().to_string(), "text");
+    }
+}
@@ -1,4 +1,8 @@
+use std::fmt;
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 
 pub fn get_current_timestamp() -> u64 {
     SystemTime::now()
@@ -6,3 +10,178 @@ pub fn get_current_timestamp() -> u64 {
         .expect("Time went backwards")
         .as_secs()
 }
+
+/// Units of memory sizes with their power of 1024, largest first.
+const MEMORY_UNITS: [(&str, u32); 5] = [("P", 50), ("T", 40), ("G", 30), ("M", 20), ("K", 10)];
+
+/// A memory size that [parse_memory] can't make sense of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMemoryError(String);
+
+impl fmt::Display for ParseMemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseMemoryError {}
+
+/// Parses a memory size in bytes, e.g. `4G`, `512MB`, `2GiB` or `1073741824`.
+///
+/// The units `K`, `M`, `G`, `T` and `P` are powers of 1024, in any case and optionally
+/// followed by `B`, `i` or `iB`. A plain number is taken as bytes.
+pub fn parse_memory(value: &str) -> Result<u64, ParseMemoryError> {
+    let digits_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(digits_end);
+
+    let suffix = suffix.to_ascii_uppercase();
+    let unit = ["IB", "I", "B"]
+        .iter()
+        .find_map(|tail| suffix.strip_suffix(tail).filter(|unit| !unit.is_empty()))
+        .unwrap_or(&suffix);
+    let shift = match unit {
+        "" | "B" => 0,
+        _ => MEMORY_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, shift)| *shift)
+            .ok_or_else(|| ParseMemoryError(format!("Unsupported memory suffix in {}", value)))?,
+    };
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| ParseMemoryError(format!("Invalid memory size {}", value)))?;
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| ParseMemoryError(format!("Memory size {} is too large", value)))
+}
+
+/// Formats a memory size with the largest unit that keeps it exact, e.g. `8G` for 8 GiB.
+///
+/// The result is understood by [parse_memory], sizes that aren't a multiple of 1 KiB stay in bytes.
+pub fn format_memory(bytes: u64) -> String {
+    MEMORY_UNITS
+        .iter()
+        .find(|(_, shift)| bytes > 0 && bytes.is_multiple_of(1u64 << shift))
+        .map(|(unit, shift)| format!("{}{}", bytes >> shift, unit))
+        .unwrap_or_else(|| bytes.to_string())
+}
+
+/// Expands the placeholders of an output path template, `%j` to the job id and `%u` to the user.
+///
+/// `%%` stands for a literal `%`. Fails on other placeholders and on paths that aren't absolute.
+pub fn expand_output_path(template: &str, job_id: u64, user: &str) -> Result<String, String> {
+    let mut path = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('j') => path.push_str(&job_id.to_string()),
+            Some('u') => path.push_str(user),
+            Some('%') => path.push('%'),
+            Some(other) => {
+                return Err(format!("Unknown placeholder %{} in {}", other, template));
+            }
+            None => return Err(format!("Incomplete placeholder at the end of {}", template)),
+        }
+    }
+    if !Path::new(&path).is_absolute() {
+        return Err(format!("Output path {} is not absolute", template));
+    }
+    Ok(path)
+}
+
+/// Waits until the process is asked to stop, either by Ctrl-C (SIGINT) or SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal(SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_parse_memory_units() {
+        assert_eq!(parse_memory("8G").unwrap(), 8 << 30);
+        assert_eq!(parse_memory("8gb").unwrap(), 8 << 30);
+        assert_eq!(parse_memory("8GiB").unwrap(), 8 << 30);
+        assert_eq!(parse_memory("512Ki").unwrap(), 512 << 10);
+        assert_eq!(parse_memory("1P").unwrap(), 1 << 50);
+        assert_eq!(parse_memory("100B").unwrap(), 100);
+        assert_eq!(parse_memory("100").unwrap(), 100);
+    }
+
+    #[test]
+    fn test_parse_invalid_memory() {
+        assert!(parse_memory("").is_err());
+        assert!(parse_memory("G").is_err());
+        assert!(parse_memory("4X").is_err());
+        assert!(parse_memory("4iB").is_err());
+        assert!(parse_memory("4.5G").is_err());
+        assert!(parse_memory("-1G").is_err());
+        assert!(parse_memory("99999P").is_err());
+    }
+
+    #[test]
+    fn test_format_memory() {
+        assert_eq!(format_memory(8589934592), "8G");
+        assert_eq!(format_memory(1536 << 20), "1536M");
+        assert_eq!(format_memory(1 << 40), "1T");
+        assert_eq!(format_memory(1000), "1000");
+        assert_eq!(format_memory(0), "0");
+    }
+
+    #[test]
+    fn test_expand_output_path() {
+        assert_eq!(
+            expand_output_path("/data/%u/out.%j", 42, "alice").unwrap(),
+            "/data/alice/out.42"
+        );
+        assert_eq!(
+            expand_output_path("/tmp/100%%.log", 1, "bob").unwrap(),
+            "/tmp/100%.log"
+        );
+        assert!(expand_output_path("/tmp/out.%x", 1, "bob").is_err());
+        assert!(expand_output_path("/tmp/out.%", 1, "bob").is_err());
+        assert!(expand_output_path("out.%j", 1, "bob").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_format_memory_round_trip(bytes in any::<u64>()) {
+            prop_assert_eq!(parse_memory(&format_memory(bytes)).unwrap(), bytes);
+        }
+
+        #[test]
+        fn test_parse_memory_round_trip(number in 1u64..1 << 13, unit in "[kmgtpKMGTP](i?[bB])?") {
+            let bytes = parse_memory(&format!("{}{}", number, unit)).unwrap();
+            prop_assert_eq!(parse_memory(&format_memory(bytes)).unwrap(), bytes);
+        }
+    }
+}
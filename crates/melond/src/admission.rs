@@ -0,0 +1,82 @@
+use melon_common::log;
+use melon_common::proto;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tonic::Status;
+
+/// Runs the site's admission hook on a submission and returns the submission to queue.
+///
+/// The hook gets the submission as JSON on stdin. It accepts it by exiting with 0 and may
+/// print a changed submission as JSON to stdout, which replaces the original one. Any other
+/// exit rejects the submission, with the hook's stderr as the reason.
+#[allow(clippy::result_large_err)] // handed to the client as is
+pub async fn admit(
+    hook: &str,
+    timeout: Duration,
+    sub: &proto::JobSubmission,
+) -> Result<proto::JobSubmission, Status> {
+    let input = serde_json::to_vec(sub).map_err(|e| {
+        log!(
+            error,
+            "Could not serialize submission for the admission hook: {}",
+            e
+        );
+        Status::internal("Failed to run the admission hook")
+    })?;
+    let mut child = Command::new(hook)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // a hook that runs into the timeout is killed
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            log!(error, "Could not run admission hook {}: {}", hook, e);
+            Status::internal("Failed to run the admission hook")
+        })?;
+
+    // written concurrently, the hook may answer before it read everything
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    tokio::spawn(async move {
+        // a hook that doesn't look at the submission closes stdin early
+        let _ = stdin.write_all(&input).await;
+    });
+
+    let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            log!(error, "Admission hook {} failed: {}", hook, e);
+            return Err(Status::internal("Failed to run the admission hook"));
+        }
+        Err(_) => {
+            log!(warn, "Admission hook {} timed out", hook);
+            return Err(Status::unavailable(format!(
+                "Admission hook did not answer within {}s",
+                timeout.as_secs()
+            )));
+        }
+    };
+
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(Status::invalid_argument(if reason.is_empty() {
+            "Rejected by the admission hook".to_string()
+        } else {
+            reason
+        }));
+    }
+    if output.stdout.iter().all(u8::is_ascii_whitespace) {
+        return Ok(sub.clone());
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| {
+        log!(
+            warn,
+            "Admission hook {} printed an invalid submission: {}",
+            hook,
+            e
+        );
+        Status::internal("Admission hook returned an invalid submission")
+    })
+}
@@ -1,17 +1,26 @@
+use crate::events::JobEventSender;
+use crate::monitoring;
 use crate::settings::Settings;
 use axum::extract::State;
 use axum::http::Method;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
 use axum::{routing::get, Router};
-use melon_common::proto::melon_scheduler_client::MelonSchedulerClient;
+use melon_common::auth;
+use melon_common::proto;
+use melon_common::JobStatus;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use serde::Serialize;
 use serde_json::json;
+use std::convert::Infallible;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use thiserror::Error;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
 use tower_http::cors::{Any, CorsLayer};
 
 #[derive(Error, Debug)]
@@ -42,13 +51,48 @@ impl IntoResponse for JobError {
     }
 }
 
+/// Handle to the process-wide Prometheus recorder.
+static PROMETHEUS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Installs the Prometheus recorder on first use and returns its handle.
+fn prometheus_handle() -> PrometheusHandle {
+    PROMETHEUS
+        .get_or_init(|| {
+            let handle = PrometheusBuilder::new()
+                .install_recorder()
+                .expect("Failed to install Prometheus recorder");
+            monitoring::describe_metrics();
+            handle
+        })
+        .clone()
+}
+
+#[derive(Clone)]
+struct ApiState {
+    settings: Arc<Settings>,
+    metrics: PrometheusHandle,
+    events: Option<JobEventSender>,
+}
+
 pub struct Api {
     settings: Settings,
+    metrics: PrometheusHandle,
+    events: Option<JobEventSender>,
 }
 
 impl Api {
     pub fn new(settings: Settings) -> Self {
-        Self { settings }
+        Self {
+            settings,
+            metrics: prometheus_handle(),
+            events: None,
+        }
+    }
+
+    /// Streams the job state transitions of the scheduler at `/api/events`.
+    pub fn with_events(mut self, events: JobEventSender) -> Self {
+        self.events = Some(events);
+        self
     }
     pub fn router(&self) -> Router {
         let cors = CorsLayer::new()
@@ -59,8 +103,15 @@ impl Api {
         Router::new()
             .route("/api/jobs", get(get_jobs))
             .route("/api/health", get(health_check))
+            .route("/api/live", get(live_check))
+            .route("/api/events", get(get_events))
+            .route("/metrics", get(get_metrics))
             .layer(cors)
-            .with_state(Arc::new(self.settings.clone()))
+            .with_state(ApiState {
+                settings: Arc::new(self.settings.clone()),
+                metrics: self.metrics.clone(),
+                events: self.events.clone(),
+            })
     }
 
     pub async fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -73,22 +124,72 @@ impl Api {
     }
 }
 
-async fn get_jobs(
-    State(settings): State<Arc<Settings>>,
-) -> Result<Json<Vec<melon_common::Job>>, JobError> {
+async fn get_jobs(State(state): State<ApiState>) -> Result<Json<Vec<melon_common::Job>>, JobError> {
+    let settings = state.settings;
     println!("Get job from api at {:?}", settings.application.port);
 
-    let mut client =
-        MelonSchedulerClient::connect(format!("http://[::1]:{}", settings.application.port))
-            .await?;
+    let mut client = auth::connect(format!("http://[::1]:{}", settings.application.port)).await?;
 
-    let request = tonic::Request::new(());
+    let request = tonic::Request::new(proto::ListJobsRequest::default());
     let response = client.list_jobs(request).await?;
 
     let jobs = response.into_inner().jobs;
     Ok(Json(jobs.into_iter().map(|job| (&job).into()).collect()))
 }
 
-async fn health_check() -> &'static str {
+/// Readiness of the API, see [health_check].
+#[derive(Debug, Serialize)]
+struct Health {
+    status: &'static str,
+    scheduler_reachable: bool,
+    node_count: usize,
+    pending_jobs: usize,
+}
+
+/// Checks that the scheduler answers, a scheduler that can't be reached yields a 503.
+async fn health_check(State(state): State<ApiState>) -> Result<Json<Health>, JobError> {
+    let mut client =
+        auth::connect(format!("http://[::1]:{}", state.settings.application.port)).await?;
+
+    let nodes = client.list_nodes(tonic::Request::new(())).await?;
+    let jobs = client
+        .list_jobs(tonic::Request::new(proto::ListJobsRequest::default()))
+        .await?;
+    let pending_jobs = jobs
+        .get_ref()
+        .jobs
+        .iter()
+        .filter(|job| JobStatus::try_from(job.status).is_ok_and(|s| s == JobStatus::Pending))
+        .count();
+
+    Ok(Json(Health {
+        status: "ok",
+        scheduler_reachable: true,
+        node_count: nodes.get_ref().nodes.len(),
+        pending_jobs,
+    }))
+}
+
+/// Answers as long as the API itself is up, whatever the state of the scheduler.
+async fn live_check() -> &'static str {
     "Ok"
 }
+
+/// Pushes every job state transition as a server-sent event, without replaying past ones.
+async fn get_events(
+    State(state): State<ApiState>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let events = state.events.ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+
+    // subscribers that fall behind skip the missed events
+    let stream = BroadcastStream::new(events.subscribe())
+        .filter_map(|event| event.ok())
+        .filter_map(|event| Event::default().event("job").json_data(event).ok())
+        .map(Ok);
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn get_metrics(State(state): State<ApiState>) -> String {
+    state.metrics.render()
+}
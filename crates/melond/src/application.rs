@@ -1,5 +1,8 @@
-use crate::{Result, Scheduler, Settings};
+use crate::{
+    audit::AuditLog, auth::AuthInterceptor, events::JobEventSender, Result, Scheduler, Settings,
+};
 use melon_common::{log, proto::melon_scheduler_server::MelonSchedulerServer};
+use std::future::Future;
 use tokio::net::TcpListener;
 use tonic::transport::{server::Router, Server};
 
@@ -13,6 +16,10 @@ pub struct Application {
     port: u16,
     /// Listener
     listener: TcpListener,
+    /// Job state transitions of the scheduler
+    events: JobEventSender,
+    /// Handle to the scheduler, used to shut it down
+    scheduler: Scheduler,
 }
 
 impl Application {
@@ -34,26 +41,57 @@ impl Application {
         let mut scheduler = Scheduler::new(&settings);
         scheduler.start().await?;
         scheduler.start_health_polling().await?;
-        let server = Server::builder().add_service(MelonSchedulerServer::new(scheduler));
+        let events = scheduler.job_events();
+        if let Some(path) = &settings.audit.path {
+            log!(info, "Writing the audit log to {}", path);
+            AuditLog::open(path, &settings.audit)?.spawn(events.subscribe());
+        }
+        if !settings.auth.enabled {
+            log!(
+                warn,
+                "Authentication is disabled, clients are trusted to name their user"
+            );
+        }
+        let auth = AuthInterceptor::new(&settings.auth);
+        let server = Server::builder().add_service(MelonSchedulerServer::with_interceptor(
+            scheduler.clone(),
+            auth,
+        ));
 
         Ok(Self {
             settings,
             server,
             port,
             listener,
+            events,
+            scheduler,
         })
     }
 
     pub async fn run_until_stopped(self) -> Result<()> {
+        self.run_until_shutdown(std::future::pending()).await
+    }
+
+    /// Serves until `signal` resolves, then shuts down the scheduler.
+    pub async fn run_until_shutdown<F>(self, signal: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
         self.server
-            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(
-                self.listener,
-            ))
+            .serve_with_incoming_shutdown(
+                tokio_stream::wrappers::TcpListenerStream::new(self.listener),
+                signal,
+            )
             .await?;
+        self.scheduler.shutdown().await;
         Ok(())
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
+
+    pub fn job_events(&self) -> JobEventSender {
+        self.events.clone()
+    }
 }
@@ -0,0 +1,16 @@
+use clap::Parser;
+use melon_common::telemetry::LogFormat;
+use std::str::FromStr;
+
+// the settings themselves are read from the configuration files
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// Log level or RUST_LOG-style directives, e.g. debug (RUST_LOG or info if unset)
+    #[arg(long = "log_level", alias = "log-level")]
+    pub log_level: Option<String>,
+
+    /// Format of the log lines: text, json or bunyan
+    #[arg(long = "log_format", alias = "log-format", default_value_t = LogFormat::Text, value_parser = LogFormat::from_str)]
+    pub log_format: LogFormat,
+}
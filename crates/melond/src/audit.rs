@@ -0,0 +1,194 @@
+use crate::events::JobEvent;
+use crate::settings::AuditSettings;
+use melon_common::log;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+/// Append-only audit log of job events, one JSON object per line.
+///
+/// Unlike the tracing logs it is meant to be read by tools, e.g. for post-mortems.
+/// The file is rotated once it would grow beyond the configured size.
+#[derive(Debug)]
+pub struct AuditLog {
+    /// Path of the current file
+    path: PathBuf,
+
+    /// Current file, opened for appending
+    file: File,
+
+    /// Bytes in the current file
+    size: u64,
+
+    /// Size in bytes after which the file is rotated
+    max_bytes: u64,
+
+    /// Number of rotated files that are kept
+    max_files: u32,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path`, existing entries are kept.
+    pub fn open(path: &str, settings: &AuditSettings) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_bytes: settings.max_bytes,
+            max_files: settings.max_files,
+        })
+    }
+
+    /// Appends an event as a single line.
+    pub fn write(&mut self, event: &JobEvent) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        let len = line.len() as u64;
+
+        // a single oversized line still goes into a file of its own
+        if self.size > 0 && self.size + len > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(&line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Moves the current file to `<path>.1`, shifting older files up and dropping the oldest.
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (1..self.max_files).rev() {
+            match fs::rename(self.rotated_path(index), self.rotated_path(index + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    /// Writes all events of `rx` until the channel is closed.
+    ///
+    /// The scheduler never waits for the audit log: events it falls behind on and events
+    /// that can't be written are skipped with a warning.
+    pub fn spawn(mut self, mut rx: broadcast::Receiver<JobEvent>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = self.write(&event) {
+                            log!(
+                                warn,
+                                "Could not write event of job {} to the audit log: {}",
+                                event.job_id,
+                                e
+                            );
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        log!(warn, "Audit log fell behind, skipped {} events", skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+fn open_append(path: &PathBuf) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use melon_common::{Job, RequestedResources};
+    use tempdir::TempDir;
+
+    fn event(job_id: u64) -> JobEvent {
+        let job = Job::new(
+            job_id,
+            "alice".to_string(),
+            "/home/alice/job.sh".to_string(),
+            vec![],
+            RequestedResources::new(1, 1024, 10, 0),
+        );
+        JobEvent::new(&job)
+    }
+
+    fn settings(max_bytes: u64, max_files: u32) -> AuditSettings {
+        AuditSettings {
+            path: None,
+            max_bytes,
+            max_files,
+        }
+    }
+
+    #[test]
+    fn test_appends_json_lines() {
+        let dir = TempDir::new("audit").unwrap();
+        let path = dir.path().join("audit.log");
+        let path = path.to_str().unwrap();
+
+        let mut log = AuditLog::open(path, &settings(1024 * 1024, 1)).unwrap();
+        log.write(&event(1)).unwrap();
+        // reopening keeps the earlier entries
+        let mut log = AuditLog::open(path, &settings(1024 * 1024, 1)).unwrap();
+        log.write(&event(2)).unwrap();
+
+        let content = fs::read_to_string(path).unwrap();
+        let ids: Vec<u64> = content
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .map(|value| {
+                assert_eq!(value["user"], "alice");
+                value["job_id"].as_u64().unwrap()
+            })
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_rotates_by_size() {
+        let dir = TempDir::new("audit").unwrap();
+        let path = dir.path().join("audit.log");
+        let line_len = serde_json::to_vec(&event(1)).unwrap().len() as u64 + 1;
+
+        // every file holds two events, only two rotated files are kept
+        let mut log = AuditLog::open(path.to_str().unwrap(), &settings(2 * line_len, 2)).unwrap();
+        for job_id in 1..=7 {
+            log.write(&event(job_id)).unwrap();
+        }
+
+        let job_ids = |suffix: &str| -> Vec<u64> {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            fs::read_to_string(file)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .map(|value| value["job_id"].as_u64().unwrap())
+                .collect()
+        };
+        assert_eq!(job_ids(""), vec![7]);
+        assert_eq!(job_ids(".1"), vec![5, 6]);
+        assert_eq!(job_ids(".2"), vec![3, 4]);
+        assert!(!dir.path().join("audit.log.3").exists());
+    }
+}
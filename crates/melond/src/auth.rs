@@ -0,0 +1,70 @@
+use crate::settings::AuthSettings;
+use melon_common::auth::bearer_token;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+/// The user a request was authenticated as, stored in the request extensions.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedUser(pub String);
+
+/// Checks the bearer token of every scheduler RPC against the configured tokens.
+///
+/// Authenticated requests carry their user as [AuthenticatedUser].
+/// If authentication is disabled, all requests pass unchanged.
+#[derive(Clone, Debug)]
+pub struct AuthInterceptor {
+    /// Users by token, `None` if authentication is disabled
+    tokens: Option<Arc<HashMap<String, String>>>,
+}
+
+impl AuthInterceptor {
+    pub fn new(settings: &AuthSettings) -> Self {
+        let tokens = settings.enabled.then(|| Arc::new(settings.tokens.clone()));
+        Self { tokens }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let Some(tokens) = &self.tokens else {
+            return Ok(request);
+        };
+        let user = bearer_token(&request)
+            .and_then(|token| tokens.get(token))
+            .ok_or_else(|| Status::unauthenticated("Missing or invalid token"))?
+            .clone();
+        request.extensions_mut().insert(AuthenticatedUser(user));
+        Ok(request)
+    }
+}
+
+/// Returns the user a request was authenticated as.
+///
+/// Falls back to the user the client claims to be if authentication is disabled.
+pub fn request_user<T>(request: &Request<T>, claimed: &str) -> String {
+    request
+        .extensions()
+        .get::<AuthenticatedUser>()
+        .map(|user| user.0.clone())
+        .unwrap_or_else(|| claimed.to_string())
+}
+
+/// Returns the admin a request was authenticated as.
+///
+/// Admin requests are only accepted with authentication enabled, a claimed user is never trusted.
+#[allow(clippy::result_large_err)] // handed to the client as is
+pub fn request_admin<T>(request: &Request<T>, admins: &[String]) -> Result<String, Status> {
+    let user = request
+        .extensions()
+        .get::<AuthenticatedUser>()
+        .ok_or_else(|| Status::permission_denied("Admin requests need authentication"))?;
+    if !admins.contains(&user.0) {
+        return Err(Status::permission_denied(format!(
+            "User {} is not an admin",
+            user.0
+        )));
+    }
+    Ok(user.0.clone())
+}
@@ -1,10 +1,18 @@
 use crate::error::Result;
 use directories::ProjectDirs;
-use melon_common::{log, Job, JobStatus, RequestedResources};
-use rusqlite::{params, Connection, Result as SqliteResult};
+use melon_common::{log, Job, JobStatus, Node, NodeResources, NodeStatus, RequestedResources};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{
+    params, params_from_iter,
+    types::{Type, Value},
+    Connection, MappedRows, OptionalExtension, Result as SqliteResult, Row,
+};
+use serde::de::DeserializeOwned;
 use serde_json;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::{
     sync::{mpsc, Mutex, Notify},
     task::JoinHandle,
@@ -12,10 +20,55 @@ use tokio::{
 
 use crate::settings::DatabaseSettings;
 
+/// Number of pooled connections used for reads
+const READ_POOL_SIZE: u32 = 4;
+
+/// Values of the `state` column of the nodes table
+const NODE_ACTIVE: &str = "active";
+const NODE_DRAINING: &str = "draining";
+const NODE_REJECTED: &str = "rejected";
+
+/// Jobs read from the database.
+#[derive(Debug, Default)]
+pub struct StoredJobs {
+    /// All jobs that could be read
+    pub jobs: Vec<Job>,
+
+    /// Number of rows that were skipped because they could not be parsed
+    pub skipped: usize,
+}
+
+/// Which jobs to list, fields that are unset don't filter.
+#[derive(Debug, Default, Clone)]
+pub struct JobFilter {
+    /// Only jobs of this user
+    pub user: Option<String>,
+
+    /// Only jobs submitted at or after this unix time
+    pub submitted_since: Option<u64>,
+
+    /// Only jobs submitted before this unix time
+    pub submitted_until: Option<u64>,
+}
+
+impl JobFilter {
+    /// Whether a job passes the filter, for the jobs that are not in the database yet.
+    pub fn matches(&self, job: &Job) -> bool {
+        self.user.as_ref().is_none_or(|user| &job.user == user)
+            && self
+                .submitted_since
+                .is_none_or(|since| job.submit_time >= since)
+            && self
+                .submitted_until
+                .is_none_or(|until| job.submit_time < until)
+    }
+}
+
 /// Dedicated Database Reader and Writer
 ///
 /// Receives finished [Job]s from the Scheduler and writes them to the database.
 /// Stops its operation when it receives a shutdown request.
+/// Reads go through a connection pool, separate from the writer's own connection.
 #[derive(Debug)]
 pub struct DatabaseHandler {
     /// Finished Job Receiver
@@ -27,18 +80,28 @@ pub struct DatabaseHandler {
     /// Thread Shutdown Notifier
     notifier: Arc<Notify>,
 
-    /// Database Path
-    db_path: String,
+    /// Connection owned by the writer task
+    writer: Arc<Mutex<Connection>>,
+
+    /// Connections for reads
+    pool: Pool<SqliteConnectionManager>,
 }
 
 impl DatabaseHandler {
     #[tracing::instrument(level = "debug", name = "Create new DatabaseWriter", skip(rx))]
     pub fn new(rx: mpsc::Receiver<Job>, settings: &DatabaseSettings) -> Result<Self> {
+        let busy_timeout = Duration::from_millis(settings.busy_timeout_ms);
+        let writer = initialize_database(&settings.path, busy_timeout)?;
+        let manager = SqliteConnectionManager::file(&settings.path)
+            .with_init(move |conn| configure_connection(conn, busy_timeout));
+        let pool = Pool::builder().max_size(READ_POOL_SIZE).build(manager)?;
+
         Ok(Self {
             rx: Arc::new(Mutex::new(rx)),
             notifier: Arc::new(Notify::new()),
             handle: None,
-            db_path: settings.path.clone(),
+            writer: Arc::new(Mutex::new(writer)),
+            pool,
         })
     }
 
@@ -47,12 +110,22 @@ impl DatabaseHandler {
         self.notifier.notify_one();
     }
 
+    /// Shuts down the writer and stores all jobs that are still queued.
+    pub async fn close(&self) {
+        self.shutdown();
+        // the writer task holds the connection until it's done
+        let conn = self.writer.lock().await;
+        let mut rx = self.rx.lock().await;
+        while let Ok(job) = rx.try_recv() {
+            store_finished_job(&conn, &job);
+        }
+    }
+
     #[tracing::instrument(level = "debug", name = "Create DatabaseWriter thread", skip(self))]
     pub fn run(&mut self) -> Result<()> {
         let notifier = self.notifier.clone();
         let rx = self.rx.clone();
-        let conn = initialize_database(&self.db_path)?;
-        let conn = Arc::new(Mutex::new(conn));
+        let conn = self.writer.clone();
 
         let handle = tokio::spawn(async move {
             let span = tracing::span!(tracing::Level::DEBUG, "DatabaseWriter Thread");
@@ -69,11 +142,7 @@ impl DatabaseHandler {
                     }
                     Some(job) = rx.recv() => {
                         log!(debug, "Receive new finished job with id {}", job.id);
-
-                        // TODO: retry on transient errors
-                        if let Err(e) = insert_finished_job(&conn, &job) {
-                            log!(error, "Error storing finished job with id {}: {}", job.id, e);
-                        }
+                        store_finished_job(&conn, &job);
                     }
                 }
             }
@@ -85,33 +154,31 @@ impl DatabaseHandler {
 
     #[tracing::instrument(level = "debug", name = "Get job from database", skip(self), fields(job_id = %job_id))]
     pub fn get_job_opt(&self, job_id: u64) -> Result<Option<Job>> {
-        let conn = Connection::open(self.db_path.clone())?;
+        let conn = self.pool.get()?;
 
         let mut stmt = conn.prepare("SELECT * FROM jobs WHERE id = ?")?;
-        let mut job_iter = stmt.query_map(params![job_id], |row| {
-            Ok(Job {
-                id: row.get(0)?,
-                user: row.get(1)?,
-                script_path: row.get(2)?,
-                script_args: serde_json::from_str(&row.get::<_, String>(3)?).unwrap(),
-                req_res: RequestedResources {
-                    cpu_count: row.get(4)?,
-                    memory: row.get(5)?,
-                    time: row.get(6)?,
-                },
-                submit_time: row.get(7)?,
-                start_time: row.get(8)?,
-                stop_time: row.get(9)?,
-                status: JobStatus::from(row.get::<_, i32>(10)?),
-                assigned_node: row.get(11)?,
-            })
-        })?;
+        let mut job_iter = stmt.query_map(params![job_id], job_from_row)?;
 
         Ok(job_iter.next().transpose()?)
     }
 
+    /// Reads only the status and exit code of a finished job.
+    #[tracing::instrument(level = "debug", name = "Get job status from database", skip(self), fields(job_id = %job_id))]
+    pub fn get_job_status(&self, job_id: u64) -> Result<Option<(JobStatus, Option<i32>)>> {
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare("SELECT status, exit_code FROM jobs WHERE id = ?")?;
+        let status = stmt
+            .query_row(params![job_id], |row| {
+                Ok((status_column(row, 0)?, row.get(1)?))
+            })
+            .optional()?;
+
+        Ok(status)
+    }
+
     pub fn get_highest_job_id(&self) -> Result<u64> {
-        let conn = Connection::open(self.db_path.clone())?;
+        let conn = self.pool.get()?;
 
         let mut stmt = conn.prepare("SELECT MAX(id) FROM jobs")?;
         let max_id: Option<u64> = stmt.query_row([], |row| row.get(0))?;
@@ -120,43 +187,256 @@ impl DatabaseHandler {
     }
 
     #[tracing::instrument(level = "debug", name = "Get all jobs from database", skip(self))]
-    pub fn get_all_jobs(&self) -> Result<Vec<Job>> {
-        let conn = Connection::open(self.db_path.clone())?;
+    pub fn get_all_jobs(&self) -> Result<StoredJobs> {
+        let conn = self.pool.get()?;
 
         let mut stmt = conn.prepare("SELECT * FROM jobs")?;
-        let job_iter = stmt.query_map([], |row| {
-            Ok(Job {
-                id: row.get(0)?,
-                user: row.get(1)?,
-                script_path: row.get(2)?,
-                script_args: serde_json::from_str(&row.get::<_, String>(3)?).unwrap(),
-                req_res: RequestedResources {
-                    cpu_count: row.get(4)?,
-                    memory: row.get(5)?,
-                    time: row.get(6)?,
-                },
-                submit_time: row.get(7)?,
-                start_time: row.get(8)?,
-                stop_time: row.get(9)?,
-                status: JobStatus::from(row.get::<_, i32>(10)?),
-                assigned_node: row.get(11)?,
-            })
-        })?;
+        let job_iter = stmt.query_map([], job_from_row)?;
+
+        Ok(collect_jobs(job_iter))
+    }
+
+    /// Reads the jobs that pass the filter, the conditions are left to the indexes.
+    #[tracing::instrument(level = "debug", name = "Get filtered jobs from database", skip(self))]
+    pub fn get_jobs(&self, filter: &JobFilter) -> Result<StoredJobs> {
+        let conn = self.pool.get()?;
+
+        let mut conditions = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+        if let Some(user) = &filter.user {
+            conditions.push("user = ?");
+            values.push(Value::Text(user.clone()));
+        }
+        if let Some(since) = filter.submitted_since {
+            conditions.push("submit_time >= ?");
+            values.push(Value::Integer(since as i64));
+        }
+        if let Some(until) = filter.submitted_until {
+            conditions.push("submit_time < ?");
+            values.push(Value::Integer(until as i64));
+        }
+        let mut sql = "SELECT * FROM jobs".to_string();
+        if !conditions.is_empty() {
+            sql = format!("{} WHERE {}", sql, conditions.join(" AND "));
+        }
+
+        let mut stmt = conn.prepare(&sql)?;
+        let job_iter = stmt.query_map(params_from_iter(values), job_from_row)?;
+
+        Ok(collect_jobs(job_iter))
+    }
+
+    #[tracing::instrument(level = "debug", name = "Get jobs of node from database", skip(self))]
+    pub fn get_jobs_by_node(&self, node_id: &str) -> Result<StoredJobs> {
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare("SELECT * FROM jobs WHERE assigned_node = ?")?;
+        let job_iter = stmt.query_map(params![node_id], job_from_row)?;
+
+        Ok(collect_jobs(job_iter))
+    }
 
-        let jobs: SqliteResult<Vec<Job>> = job_iter.collect();
-        Ok(jobs?)
+    /// Stores a node registration, replacing an earlier one of the same node.
+    ///
+    /// Registrations are rare, so they are written right away instead of through the writer.
+    /// A draining node is stored as such, a registration clears an earlier rejection.
+    #[tracing::instrument(level = "debug", name = "Store node in database", skip(self, node), fields(node_id = %node.id))]
+    pub fn store_node(&self, node: &Node) -> Result<()> {
+        let conn = self.pool.get()?;
+        let labels = serde_json::to_string(&node.labels)?;
+        let state = match node.status {
+            NodeStatus::Draining => NODE_DRAINING,
+            _ => NODE_ACTIVE,
+        };
+
+        conn.execute(
+            "INSERT OR REPLACE INTO nodes (id, endpoint, cpu_count, memory, gpu_count, labels, state) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                node.id,
+                node.endpoint,
+                node.avail_resources.cpu_count,
+                node.avail_resources.memory,
+                node.avail_resources.gpu_count,
+                labels,
+                state,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Marks a node whose validation failed, it gets no jobs after a restart either.
+    #[tracing::instrument(level = "debug", name = "Reject node in database", skip(self))]
+    pub fn reject_node(&self, node_id: &str) -> Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE nodes SET state = ?1 WHERE id = ?2",
+            params![NODE_REJECTED, node_id],
+        )?;
+        Ok(())
+    }
+
+    /// Forgets a node, e.g. once it has been drained.
+    #[tracing::instrument(level = "debug", name = "Remove node from database", skip(self))]
+    pub fn remove_node(&self, node_id: &str) -> Result<()> {
+        let conn = self.pool.get()?;
+        conn.execute("DELETE FROM nodes WHERE id = ?1", params![node_id])?;
+        Ok(())
+    }
+
+    /// Reads the IDs of the nodes whose validation failed.
+    #[tracing::instrument(level = "debug", name = "Get rejected nodes from database", skip(self))]
+    pub fn get_rejected_nodes(&self) -> Result<Vec<String>> {
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare("SELECT id FROM nodes WHERE state = ?1")?;
+        let ids = stmt
+            .query_map(params![NODE_REJECTED], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<String>>>()?;
+
+        Ok(ids)
+    }
+
+    /// Reads all registered nodes, they are offline until they send a heartbeat again.
+    ///
+    /// Draining nodes stay draining.
+    #[tracing::instrument(level = "debug", name = "Get nodes from database", skip(self))]
+    pub fn get_all_nodes(&self) -> Result<Vec<Node>> {
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, endpoint, cpu_count, memory, gpu_count, labels, state FROM nodes",
+        )?;
+        let mut nodes = Vec::new();
+        for row in stmt.query_map([], node_from_row)? {
+            match row {
+                Ok(node) => nodes.push(node),
+                Err(e) => {
+                    log!(warn, "Skip malformed node row: {}", e);
+                }
+            }
+        }
+
+        Ok(nodes)
+    }
+}
+
+/// Writes a finished job to the database, logging any error.
+fn store_finished_job(conn: &Connection, job: &Job) {
+    // TODO: retry on transient errors
+    if let Err(e) = insert_finished_job(conn, job) {
+        log!(
+            error,
+            "Error storing finished job with id {}: {}",
+            job.id,
+            e
+        );
+    }
+}
+
+/// Collects all readable jobs, logging and counting the rows that can't be parsed.
+fn collect_jobs<F>(rows: MappedRows<'_, F>) -> StoredJobs
+where
+    F: FnMut(&Row<'_>) -> SqliteResult<Job>,
+{
+    let mut stored = StoredJobs::default();
+    for row in rows {
+        match row {
+            Ok(job) => stored.jobs.push(job),
+            Err(e) => {
+                log!(warn, "Skip malformed job row: {}", e);
+                stored.skipped += 1;
+            }
+        }
     }
+    stored
+}
+
+/// Reads a column that holds JSON.
+fn json_column<T: DeserializeOwned>(row: &Row, index: usize) -> SqliteResult<T> {
+    let value: String = row.get(index)?;
+    serde_json::from_str(&value)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+}
+
+/// Reads the job status column, unknown values are read as failed.
+fn status_column(row: &Row, index: usize) -> SqliteResult<JobStatus> {
+    let value: i32 = row.get(index)?;
+    Ok(JobStatus::from_i32_or_failed(value))
+}
+
+fn job_from_row(row: &Row) -> SqliteResult<Job> {
+    Ok(Job {
+        id: row.get(0)?,
+        user: row.get(1)?,
+        script_path: row.get(2)?,
+        script_args: json_column(row, 3)?,
+        req_res: RequestedResources {
+            cpu_count: row.get(4)?,
+            memory: row.get(5)?,
+            time: row.get(6)?,
+            gpu_count: row.get(14)?,
+            node_count: row.get(27)?,
+        },
+        submit_time: row.get(7)?,
+        start_time: row.get(8)?,
+        stop_time: row.get(9)?,
+        status: status_column(row, 10)?,
+        assigned_node: row.get(11)?,
+        extra_nodes: json_column(row, 28)?,
+        priority: row.get(12)?,
+        dependencies: json_column(row, 13)?,
+        partition: row.get(15)?,
+        constraints: json_column(row, 30)?,
+        env: json_column(row, 16)?,
+        work_dir: row.get(17)?,
+        name: row.get(18)?,
+        grace_secs: row.get(19)?,
+        begin_deadline_mins: row.get(31)?,
+        warn_signal: row
+            .get::<_, Option<String>>(32)?
+            .and_then(|signal| signal.parse().ok()),
+        output_path: row.get(33)?,
+        error_path: row.get(34)?,
+        parent_job_id: row.get(35)?,
+        script_contents: row.get(36)?,
+        max_rss_bytes: row.get(20)?,
+        cpu_seconds: row.get(21)?,
+        max_retries: row.get(22)?,
+        retry_count: row.get(23)?,
+        attempts: json_column(row, 24)?,
+        exit_code: row.get(25)?,
+        message: row.get(26)?,
+        interactive: row.get(29)?,
+        attach_token: None,
+        queue_position: None,
+    })
+}
+
+fn node_from_row(row: &Row) -> SqliteResult<Node> {
+    let resources = NodeResources::new(row.get(2)?, row.get(3)?, row.get(4)?);
+    let status = match row.get::<_, String>(6)?.as_str() {
+        NODE_DRAINING => NodeStatus::Draining,
+        _ => NodeStatus::Offline,
+    };
+    Ok(Node::new(row.get(0)?, row.get(1)?, resources, status).with_labels(json_column(row, 5)?))
 }
 
 #[tracing::instrument(level = "debug", name = "Insert finished job", skip(conn, job), fields(job_id = %job.id))]
 fn insert_finished_job(conn: &Connection, job: &Job) -> Result<()> {
     let script_args = serde_json::to_string(&job.script_args)?;
+    let dependencies = serde_json::to_string(&job.dependencies)?;
+    let env = serde_json::to_string(&job.env)?;
+    let attempts = serde_json::to_string(&job.attempts)?;
+    let extra_nodes = serde_json::to_string(&job.extra_nodes)?;
+    let constraints = serde_json::to_string(&job.constraints)?;
     let status: i32 = job.status.clone().into();
 
     conn.execute(
         "INSERT INTO jobs \
-         (id, user, script_path, script_args, cpu_count, memory, time, submit_time, start_time, stop_time, status, assigned_node) \
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+         (id, user, script_path, script_args, cpu_count, memory, time, submit_time, start_time, stop_time, status, assigned_node, priority, dependencies, gpu_count, partition, env, work_dir, name, grace_secs, max_rss_bytes, cpu_seconds, max_retries, retry_count, attempts, exit_code, message, node_count, extra_nodes, interactive, constraints, begin_deadline_mins, warn_signal, output_path, error_path, parent_job_id, script_contents) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37)",
         params![
             job.id,
             job.user,
@@ -170,21 +450,198 @@ fn insert_finished_job(conn: &Connection, job: &Job) -> Result<()> {
             job.stop_time.expect("No stop time set"),
             status,
             job.assigned_node,
+            job.priority,
+            dependencies,
+            job.req_res.gpu_count,
+            job.partition,
+            env,
+            job.work_dir,
+            job.name,
+            job.grace_secs,
+            job.max_rss_bytes,
+            job.cpu_seconds,
+            job.max_retries,
+            job.retry_count,
+            attempts,
+            job.exit_code,
+            job.message,
+            job.req_res.node_count,
+            extra_nodes,
+            job.interactive,
+            constraints,
+            job.begin_deadline_mins,
+            job.warn_signal.as_ref().map(ToString::to_string),
+            job.output_path,
+            job.error_path,
+            job.parent_job_id,
+            job.script_contents,
         ],
     )?;
 
     Ok(())
 }
 
+/// A step from one schema version to the next.
+enum Migration {
+    /// Adds a column to the jobs table, skipped if the column already exists
+    AddColumn {
+        column: &'static str,
+        definition: &'static str,
+    },
+    /// Adds a column to the nodes table, skipped if the column already exists
+    AddNodeColumn {
+        column: &'static str,
+        definition: &'static str,
+    },
+    /// Runs an arbitrary statement, which has to be safe to run again
+    Sql(&'static str),
+}
+
+/// Schema changes on top of the initial jobs table, in the order they are applied.
+///
+/// A database at version `n` (stored in `PRAGMA user_version`) has the first `n` steps applied.
+/// Only ever append to this list.
+const MIGRATIONS: &[Migration] = &[
+    Migration::AddColumn {
+        column: "priority",
+        definition: "INTEGER NOT NULL DEFAULT 50",
+    },
+    Migration::AddColumn {
+        column: "dependencies",
+        definition: "TEXT NOT NULL DEFAULT '[]'",
+    },
+    Migration::AddColumn {
+        column: "gpu_count",
+        definition: "INTEGER NOT NULL DEFAULT 0",
+    },
+    Migration::AddColumn {
+        column: "partition",
+        definition: "TEXT NOT NULL DEFAULT 'default'",
+    },
+    Migration::AddColumn {
+        column: "env",
+        definition: "TEXT NOT NULL DEFAULT '{}'",
+    },
+    Migration::AddColumn {
+        column: "work_dir",
+        definition: "TEXT",
+    },
+    Migration::AddColumn {
+        column: "name",
+        definition: "TEXT",
+    },
+    Migration::Sql("CREATE INDEX IF NOT EXISTS idx_jobs_user ON jobs(user)"),
+    Migration::Sql("CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status)"),
+    Migration::AddColumn {
+        column: "grace_secs",
+        definition: "INTEGER",
+    },
+    Migration::AddColumn {
+        column: "max_rss_bytes",
+        definition: "INTEGER",
+    },
+    Migration::AddColumn {
+        column: "cpu_seconds",
+        definition: "REAL",
+    },
+    Migration::AddColumn {
+        column: "max_retries",
+        definition: "INTEGER NOT NULL DEFAULT 0",
+    },
+    Migration::AddColumn {
+        column: "retry_count",
+        definition: "INTEGER NOT NULL DEFAULT 0",
+    },
+    Migration::AddColumn {
+        column: "attempts",
+        definition: "TEXT NOT NULL DEFAULT '[]'",
+    },
+    Migration::AddColumn {
+        column: "exit_code",
+        definition: "INTEGER",
+    },
+    Migration::AddColumn {
+        column: "message",
+        definition: "TEXT",
+    },
+    Migration::AddColumn {
+        column: "node_count",
+        definition: "INTEGER NOT NULL DEFAULT 1",
+    },
+    Migration::AddColumn {
+        column: "extra_nodes",
+        definition: "TEXT NOT NULL DEFAULT '[]'",
+    },
+    Migration::Sql("CREATE INDEX IF NOT EXISTS idx_jobs_assigned_node ON jobs(assigned_node)"),
+    Migration::AddColumn {
+        column: "interactive",
+        definition: "INTEGER NOT NULL DEFAULT 0",
+    },
+    Migration::AddColumn {
+        column: "constraints",
+        definition: "TEXT NOT NULL DEFAULT '[]'",
+    },
+    Migration::AddColumn {
+        column: "begin_deadline_mins",
+        definition: "INTEGER",
+    },
+    Migration::AddColumn {
+        column: "warn_signal",
+        definition: "TEXT",
+    },
+    Migration::AddColumn {
+        column: "output_path",
+        definition: "TEXT",
+    },
+    Migration::AddColumn {
+        column: "error_path",
+        definition: "TEXT",
+    },
+    Migration::AddColumn {
+        column: "parent_job_id",
+        definition: "INTEGER",
+    },
+    Migration::Sql(
+        "CREATE TABLE IF NOT EXISTS nodes (
+            id TEXT PRIMARY KEY,
+            endpoint TEXT NOT NULL,
+            cpu_count INTEGER NOT NULL,
+            memory INTEGER NOT NULL,
+            gpu_count INTEGER NOT NULL,
+            labels TEXT NOT NULL DEFAULT '[]'
+        )",
+    ),
+    Migration::Sql("CREATE INDEX IF NOT EXISTS idx_jobs_submit_time ON jobs(submit_time)"),
+    Migration::AddNodeColumn {
+        column: "state",
+        definition: "TEXT NOT NULL DEFAULT 'active'",
+    },
+    Migration::AddColumn {
+        column: "script_contents",
+        definition: "BLOB",
+    },
+];
+
+/// Prepares a connection to share the database with the other ones.
+///
+/// In WAL mode the pooled readers work alongside the writer, the busy timeout covers the
+/// short moments in which one still has to wait for another, e.g. during checkpoints.
+fn configure_connection(conn: &Connection, busy_timeout: Duration) -> SqliteResult<()> {
+    conn.busy_timeout(busy_timeout)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    Ok(())
+}
+
 #[tracing::instrument(level = "debug", name = "Initialise database")]
-fn initialize_database(db_path: &str) -> Result<Connection> {
+fn initialize_database(db_path: &str, busy_timeout: Duration) -> Result<Connection> {
     let db_path = PathBuf::from(db_path);
 
     if let Some(parent) = db_path.parent() {
         std::fs::create_dir_all(parent)?;
     }
 
-    let conn = Connection::open(db_path)?;
+    let mut conn = Connection::open(db_path)?;
+    configure_connection(&conn, busy_timeout)?;
 
     conn.execute(
         "CREATE TABLE IF NOT EXISTS jobs (
@@ -203,10 +660,49 @@ fn initialize_database(db_path: &str) -> Result<Connection> {
             )",
         [],
     )?;
+    migrate(&mut conn)?;
 
     Ok(conn)
 }
 
+/// Brings the schema up to the latest version, one transaction per step.
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        match migration {
+            Migration::AddColumn { column, definition } => {
+                ensure_column(&tx, "jobs", column, definition)?
+            }
+            Migration::AddNodeColumn { column, definition } => {
+                ensure_column(&tx, "nodes", column, definition)?
+            }
+            Migration::Sql(sql) => tx.execute_batch(sql)?,
+        }
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+        log!(info, "Migrated database to schema version {}", index + 1);
+    }
+
+    Ok(())
+}
+
+/// Adds a column to a table if it does not exist yet.
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))?;
+    let columns: SqliteResult<Vec<String>> = stmt.query_map([], |row| row.get(0))?.collect();
+
+    if !columns?.iter().any(|name| name == column) {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Get the path to the production databse
 pub fn get_prod_database_path() -> String {
     let proj_dirs = ProjectDirs::from("com", "MelonOrganization", "Melon")
@@ -217,3 +713,328 @@ pub fn get_prod_database_path() -> String {
         .expect("Path contains invalid Unicode")
         .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    /// Opens a database in a new temporary directory, which is removed once dropped.
+    fn test_db() -> (TempDir, DatabaseHandler) {
+        let dir = TempDir::new("melon-db").unwrap();
+        let settings = DatabaseSettings {
+            path: dir.path().join("melon.db").to_str().unwrap().to_string(),
+            busy_timeout_ms: 5000,
+        };
+        // the writer task isn't started, nothing is sent to it
+        let (_tx, rx) = mpsc::channel(1);
+        let db = DatabaseHandler::new(rx, &settings).unwrap();
+        (dir, db)
+    }
+
+    #[test]
+    fn test_migrate_old_database() {
+        let dir = TempDir::new("melon-db").unwrap();
+        let path = dir.path().join("melon.db");
+        let path = path.to_str().unwrap();
+
+        // schema of the first release, before any migration
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE jobs (
+                id INTEGER PRIMARY KEY,
+                user TEXT NOT NULL,
+                script_path TEXT NOT NULL,
+                script_args TEXT NOT NULL,
+                cpu_count INTEGER NOT NULL,
+                memory INTEGER NOT NULL,
+                time INTEGER NOT NULL,
+                submit_time INTEGER NOT NULL,
+                start_time INTEGER,
+                stop_time INTEGER NOT NULL,
+                status INTEGER NOT NULL,
+                assigned_node TEXT
+            );
+            INSERT INTO jobs VALUES (7, 'alice', '/home/alice/run.sh', '[\"-v\"]', 4, 1024, 60, 100, 110, 120, 0, 'node-1');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let conn = initialize_database(path, Duration::from_secs(5)).unwrap();
+        let version: usize = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+
+        let job = conn
+            .query_row("SELECT * FROM jobs WHERE id = 7", [], job_from_row)
+            .unwrap();
+        assert_eq!(job.user, "alice");
+        assert_eq!(job.script_args, vec!["-v".to_string()]);
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.assigned_node, Some("node-1".to_string()));
+        assert_eq!(job.priority, 50);
+        assert_eq!(job.partition, "default");
+        assert!(job.env.is_empty());
+        assert!(job.constraints.is_empty());
+        assert_eq!(job.name, None);
+    }
+
+    #[tokio::test]
+    async fn test_skip_malformed_rows() {
+        let (_dir, db) = test_db();
+        let conn = db.pool.get().unwrap();
+        conn.execute_batch(
+            "INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status)
+             VALUES (1, 'alice', 'a.sh', '[]', 1, 1024, 10, 100, 120, 0);
+             INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status)
+             VALUES (2, 'alice', 'b.sh', 'not json', 1, 1024, 10, 100, 120, 0);
+             INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status)
+             VALUES (3, 'alice', 'c.sh', '[]', 1, 1024, 10, 100, 120, 99);",
+        )
+        .unwrap();
+
+        let stored = db.get_all_jobs().unwrap();
+        assert_eq!(stored.jobs.len(), 2);
+        assert_eq!(stored.skipped, 1);
+
+        // unknown status values are read as failed
+        let job = db.get_job_opt(3).unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+
+        assert!(db.get_job_opt(1).unwrap().is_some());
+        assert!(db.get_job_opt(2).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_jobs_by_node() {
+        let (_dir, db) = test_db();
+        let conn = db.pool.get().unwrap();
+        conn.execute_batch(
+            "INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status, assigned_node)
+             VALUES (1, 'alice', 'a.sh', '[]', 1, 1024, 10, 100, 120, 0, 'node-1');
+             INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status, assigned_node)
+             VALUES (2, 'bob', 'b.sh', '[]', 1, 1024, 10, 100, 120, 0, 'node-2');
+             INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status)
+             VALUES (3, 'bob', 'c.sh', '[]', 1, 1024, 10, 100, 120, 4);",
+        )
+        .unwrap();
+
+        let stored = db.get_jobs_by_node("node-1").unwrap();
+        let ids: Vec<u64> = stored.jobs.iter().map(|job| job.id).collect();
+        assert_eq!(ids, vec![1]);
+
+        let plan: String = conn
+            .query_row(
+                "EXPLAIN QUERY PLAN SELECT * FROM jobs WHERE assigned_node = 'node-1'",
+                [],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(plan.contains("idx_jobs_assigned_node"), "{}", plan);
+    }
+
+    #[tokio::test]
+    async fn test_get_jobs_by_submit_time() {
+        let (_dir, db) = test_db();
+        let conn = db.pool.get().unwrap();
+        conn.execute_batch(
+            "INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status)
+             VALUES (1, 'alice', 'a.sh', '[]', 1, 1024, 10, 100, 120, 0);
+             INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status)
+             VALUES (2, 'bob', 'b.sh', '[]', 1, 1024, 10, 200, 220, 0);
+             INSERT INTO jobs (id, user, script_path, script_args, cpu_count, memory, time, submit_time, stop_time, status)
+             VALUES (3, 'alice', 'c.sh', '[]', 1, 1024, 10, 300, 320, 0);",
+        )
+        .unwrap();
+
+        let ids = |filter: JobFilter| {
+            let mut ids: Vec<u64> = db
+                .get_jobs(&filter)
+                .unwrap()
+                .jobs
+                .iter()
+                .map(|job| job.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(JobFilter::default()), vec![1, 2, 3]);
+        let since = JobFilter {
+            submitted_since: Some(200),
+            ..Default::default()
+        };
+        assert_eq!(ids(since.clone()), vec![2, 3]);
+        let window = JobFilter {
+            submitted_until: Some(300),
+            ..since
+        };
+        assert_eq!(ids(window), vec![2]);
+        let mine = JobFilter {
+            user: Some("alice".to_string()),
+            submitted_until: Some(300),
+            ..Default::default()
+        };
+        assert_eq!(ids(mine), vec![1]);
+
+        let plan: String = conn
+            .query_row(
+                "EXPLAIN QUERY PLAN SELECT * FROM jobs WHERE submit_time >= 200 AND submit_time < 300",
+                [],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(plan.contains("idx_jobs_submit_time"), "{}", plan);
+    }
+
+    #[test]
+    fn test_read_while_writing() {
+        let (dir, db) = test_db();
+        let path = dir.path().join("melon.db");
+        let writer = initialize_database(path.to_str().unwrap(), Duration::from_secs(5)).unwrap();
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for id in 1..=200 {
+                    let mut job = Job::new(
+                        id,
+                        "alice".to_string(),
+                        "/home/alice/job.sh".to_string(),
+                        vec![],
+                        RequestedResources::new(1, 1024, 10, 0),
+                    );
+                    job.status = JobStatus::Completed;
+                    job.stop_time = Some(200);
+                    insert_finished_job(&writer, &job).unwrap();
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..200 {
+                    db.get_all_jobs().unwrap();
+                }
+            });
+        });
+
+        assert_eq!(db.get_all_jobs().unwrap().jobs.len(), 200);
+        let conn = db.pool.get().unwrap();
+        let mode: String = conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn test_script_contents_are_kept() {
+        let (_dir, db) = test_db();
+        let conn = db.pool.get().unwrap();
+
+        let mut job = Job::new(
+            1,
+            "alice".to_string(),
+            "stdin".to_string(),
+            vec![],
+            RequestedResources::new(1, 1024, 10, 0),
+        );
+        job.status = JobStatus::Completed;
+        job.stop_time = Some(200);
+        job.script_contents = Some(b"#!/bin/sh\necho hi\n".to_vec());
+        insert_finished_job(&conn, &job).unwrap();
+
+        let stored = db.get_job_opt(1).unwrap().unwrap();
+        assert_eq!(stored.script_contents, job.script_contents);
+    }
+
+    #[tokio::test]
+    async fn test_store_nodes() {
+        let (_dir, db) = test_db();
+
+        let resources = NodeResources::new(8, 1024, 1);
+        let node = Node::new(
+            "node-1".to_string(),
+            "http://[::1]:8082".to_string(),
+            resources,
+            NodeStatus::Available,
+        )
+        .with_labels(vec!["ssd".to_string()]);
+        db.store_node(&node).unwrap();
+
+        // a node that registers again replaces its old entry
+        let mut node = node;
+        node.avail_resources.cpu_count = 16;
+        db.store_node(&node).unwrap();
+
+        let nodes = db.get_all_nodes().unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].id, "node-1");
+        assert_eq!(nodes[0].endpoint, "http://[::1]:8082");
+        let res = &nodes[0].avail_resources;
+        assert_eq!((res.cpu_count, res.memory, res.gpu_count), (16, 1024, 1));
+        assert_eq!(nodes[0].labels, vec!["ssd".to_string()]);
+        assert_eq!(nodes[0].status, NodeStatus::Offline);
+    }
+
+    #[tokio::test]
+    async fn test_node_state_is_kept() {
+        let (_dir, db) = test_db();
+
+        let resources = NodeResources::new(8, 1024, 1);
+        let node = |id: &str, status| {
+            Node::new(
+                id.to_string(),
+                format!("http://{}", id),
+                resources.clone(),
+                status,
+            )
+        };
+        db.store_node(&node("draining", NodeStatus::Draining))
+            .unwrap();
+        db.store_node(&node("rejected", NodeStatus::Validating))
+            .unwrap();
+        db.store_node(&node("retired", NodeStatus::Draining))
+            .unwrap();
+        db.reject_node("rejected").unwrap();
+        db.remove_node("retired").unwrap();
+
+        let mut nodes = db.get_all_nodes().unwrap();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        let states: Vec<(&str, NodeStatus)> = nodes
+            .iter()
+            .map(|node| (node.id.as_str(), node.status.clone()))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                ("draining", NodeStatus::Draining),
+                ("rejected", NodeStatus::Offline)
+            ]
+        );
+        assert_eq!(
+            db.get_rejected_nodes().unwrap(),
+            vec!["rejected".to_string()]
+        );
+
+        // registering again clears the rejection
+        db.store_node(&node("rejected", NodeStatus::Available))
+            .unwrap();
+        assert!(db.get_rejected_nodes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migrate_is_idempotent() {
+        let dir = TempDir::new("melon-db").unwrap();
+        let path = dir.path().join("melon.db");
+        let path = path.to_str().unwrap();
+
+        drop(initialize_database(path, Duration::from_secs(5)).unwrap());
+        let mut conn = initialize_database(path, Duration::from_secs(5)).unwrap();
+
+        // columns added by an earlier version without a schema version are kept
+        conn.pragma_update(None, "user_version", 0).unwrap();
+        migrate(&mut conn).unwrap();
+
+        let version: usize = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+}
@@ -16,6 +16,9 @@ pub enum Error {
 
     #[from]
     SerdeJsonError(serde_json::Error),
+
+    #[from]
+    PoolError(r2d2::Error),
 }
 
 impl core::fmt::Display for Error {
@@ -0,0 +1,55 @@
+use melon_common::{utils::get_current_timestamp, Job, JobStatus};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// How many events a slow subscriber may fall behind before it misses some
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// A job changed its state or got its time limit extended.
+#[derive(Clone, Debug, Serialize)]
+pub struct JobEvent {
+    /// The job id
+    pub job_id: u64,
+
+    /// The new job status
+    pub status: JobStatus,
+
+    /// Owner of the job
+    pub user: String,
+
+    /// Ids of the nodes the job runs or ran on, empty while it waits
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nodes: Vec<String>,
+
+    /// Minutes the time limit was extended by, only set for extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension_mins: Option<u32>,
+
+    /// Unix timestamp of the transition in seconds
+    pub timestamp: u64,
+}
+
+impl JobEvent {
+    /// Creates an event for the current status of the job.
+    pub fn new(job: &Job) -> Self {
+        Self {
+            job_id: job.id,
+            status: job.status.clone(),
+            user: job.user.clone(),
+            nodes: job.nodes().cloned().collect(),
+            extension_mins: None,
+            timestamp: get_current_timestamp(),
+        }
+    }
+
+    /// Creates an event for an extension of the job's time limit.
+    pub fn extension(job: &Job, extension_mins: u32) -> Self {
+        Self {
+            extension_mins: Some(extension_mins),
+            ..Self::new(job)
+        }
+    }
+}
+
+/// Sending half of the job event channel, subscribers only see events sent after they joined.
+pub type JobEventSender = broadcast::Sender<JobEvent>;
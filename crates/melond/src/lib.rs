@@ -1,7 +1,12 @@
+pub mod admission;
 pub mod api;
 pub mod application;
+pub mod audit;
+pub mod auth;
 pub mod db;
 pub mod error;
+pub mod events;
+pub mod monitoring;
 pub mod scheduler;
 pub mod settings;
 
@@ -1,26 +1,36 @@
+mod arg;
+use arg::Args;
+use clap::Parser;
 use melon_common::{
     configuration::get_configuration,
     log,
     telemetry::{get_subscriber, init_subscriber},
+    utils::shutdown_signal,
 };
 use melond::{db::get_prod_database_path, Api, Settings};
 use melond::{Application, Result};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
     let mut settings: Settings = get_configuration().expect("Failed to read configuration.");
     if settings.database.path.is_empty() {
         settings.database.path = get_prod_database_path();
     }
 
-    let subscriber = get_subscriber("melond".into(), "info".into(), std::io::stdout);
+    let subscriber = get_subscriber(
+        "melond".into(),
+        args.log_level,
+        args.log_format,
+        std::io::stdout,
+    );
     init_subscriber(subscriber);
 
     let application = Application::build(settings.clone()).await?;
 
     #[cfg(feature = "api")]
     {
-        let api = Api::new(settings.clone());
+        let api = Api::new(settings.clone()).with_events(application.job_events());
         tokio::spawn(async move {
             if let Err(e) = api.start().await {
                 log!(error, "API Server error: {}", e);
@@ -28,6 +38,7 @@ async fn main() -> Result<()> {
         });
     }
 
-    application.run_until_stopped().await?;
+    application.run_until_shutdown(shutdown_signal()).await?;
+    log!(info, "Scheduler stopped");
     Ok(())
 }
@@ -0,0 +1,61 @@
+//! Metrics of the scheduler.
+//!
+//! The values are recorded through the [metrics] facade and exported by the API
+//! in the Prometheus format.
+use melon_common::{JobStatus, Node, NodeStatus};
+use metrics::{counter, describe_counter, describe_gauge, gauge};
+use std::collections::HashMap;
+
+pub const PENDING_JOBS: &str = "melon_pending_jobs";
+pub const RUNNING_JOBS: &str = "melon_running_jobs";
+pub const NODES: &str = "melon_nodes";
+pub const OFFLINE_NODES: &str = "melon_offline_nodes";
+pub const CPUS_ALLOCATED: &str = "melon_cpus_allocated";
+pub const CPUS_AVAILABLE: &str = "melon_cpus_available";
+pub const FINISHED_JOBS: &str = "melon_jobs_finished_total";
+
+/// Registers the descriptions of all metrics.
+pub fn describe_metrics() {
+    describe_gauge!(PENDING_JOBS, "Jobs waiting to be scheduled");
+    describe_gauge!(RUNNING_JOBS, "Jobs running on a node");
+    describe_gauge!(NODES, "Registered compute nodes");
+    describe_gauge!(
+        OFFLINE_NODES,
+        "Registered compute nodes that missed their heartbeats"
+    );
+    describe_gauge!(CPUS_ALLOCATED, "CPUs allocated to running jobs");
+    describe_gauge!(CPUS_AVAILABLE, "CPUs free for new jobs");
+    describe_counter!(FINISHED_JOBS, "Finished jobs by final status");
+}
+
+/// Updates the job and node gauges from the current scheduler state.
+pub fn record_cluster_state(
+    pending_jobs: usize,
+    running_jobs: usize,
+    nodes: &HashMap<String, Node>,
+) {
+    gauge!(PENDING_JOBS).set(pending_jobs as f64);
+    gauge!(RUNNING_JOBS).set(running_jobs as f64);
+    gauge!(NODES).set(nodes.len() as f64);
+
+    let offline = nodes
+        .values()
+        .filter(|node| node.status == NodeStatus::Offline)
+        .count();
+    gauge!(OFFLINE_NODES).set(offline as f64);
+
+    let (allocated, available) = nodes.values().fold((0u64, 0u64), |(used, free), node| {
+        (
+            used + node.used_resources.cpu_count as u64,
+            free + node.avail_resources.cpu_count as u64,
+        )
+    });
+    gauge!(CPUS_ALLOCATED).set(allocated as f64);
+    gauge!(CPUS_AVAILABLE).set(available as f64);
+}
+
+/// Counts a job that reached its final status.
+pub fn record_job_finished(status: &JobStatus) {
+    let status = String::from(status.clone()).to_lowercase();
+    counter!(FINISHED_JOBS, "status" => status).increment(1);
+}
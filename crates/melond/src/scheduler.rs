@@ -1,24 +1,47 @@
-use crate::db::DatabaseHandler;
+use crate::admission;
+use crate::auth::{request_admin, request_user};
+use crate::db::{DatabaseHandler, JobFilter};
 use crate::error::Result;
-use crate::settings::Settings;
+use crate::events::{JobEvent, JobEventSender, EVENT_CHANNEL_CAPACITY};
+use crate::monitoring;
+use crate::settings::{PartitionSettings, PlacementPolicy, SchedulerSettings, Settings};
+use melon_common::auth::{TokenInterceptor, WorkerClient};
 use melon_common::proto::melon_scheduler_server::MelonScheduler;
 use melon_common::proto::melon_worker_client::MelonWorkerClient;
-use melon_common::utils::get_current_timestamp;
-use melon_common::{log, proto, JobResult, JobStatus, RequestedResources};
-use melon_common::{Job, Node, NodeStatus};
+use melon_common::utils::{expand_output_path, get_current_timestamp};
+use melon_common::{
+    log, proto, JobResult, JobStatus, RequestedResources, DEFAULT_PARTITION, DEFAULT_PRIORITY,
+};
+use melon_common::{Job, Node, NodeResources, NodeStatus, Reservation, WarnSignal};
 use nanoid::nanoid;
 use std::time::Duration;
 use std::time::Instant;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     sync::{atomic::AtomicU64, Arc},
 };
 use tokio::sync::mpsc::Sender;
-use tokio::sync::{mpsc, Mutex, Notify};
-use tokio::task::JoinHandle;
+use tokio::sync::{broadcast, mpsc, Mutex, Notify};
+use tokio::task::{JoinHandle, JoinSet};
 use tokio::time::interval;
+use tonic::transport::Endpoint;
 use tonic::Status;
 
+/// Environment variable that lists the hosts of all nodes of a job, comma-separated
+pub const NODELIST_ENV: &str = "MELON_NODELIST";
+
+/// Window in which the submissions of a user count against `max_submissions_per_min`
+const SUBMISSION_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Largest script body that may be sent along with a submission
+const MAX_SCRIPT_BYTES: usize = 1024 * 1024;
+
+/// User the validation jobs of newly registered nodes run as
+const VALIDATION_USER: &str = "melond";
+
+/// How often the validation job is offered to a node whose server isn't up yet
+const VALIDATION_RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
 #[derive(Clone, Debug)]
 pub struct Scheduler {
     /// Atomic counter for generating unique job IDs
@@ -43,7 +66,7 @@ pub struct Scheduler {
 
     /// Queue of pending jobs waiting to be assigned to workers
     ///
-    /// Jobs are processed in FIFO order
+    /// Kept sorted by descending priority, ties are processed in FIFO order
     pending_jobs: Arc<Mutex<VecDeque<Job>>>,
 
     /// Handle to the job scheduling thread for lifecycle management
@@ -71,6 +94,51 @@ pub struct Scheduler {
 
     /// Channel sender for asynchronous database write operations
     db_tx: Arc<Sender<Job>>,
+
+    /// Configured partitions
+    ///
+    /// Key: Partition name
+    /// Value: Resource caps and member nodes
+    partitions: Arc<HashMap<String, PartitionSettings>>,
+
+    /// Put jobs of a lost node back into the queue instead of failing them
+    requeue_lost_jobs: bool,
+
+    /// Users that may kill or requeue the jobs of any user
+    admins: Arc<Vec<String>>,
+
+    /// Adds the token the workers expect to every request sent to them
+    worker_token: TokenInterceptor,
+
+    /// Nodes held back for some users during a time window, expired ones are removed lazily
+    reservations: Arc<Mutex<Vec<Reservation>>>,
+
+    /// Atomic counter for generating unique reservation IDs
+    reservation_ctr: Arc<AtomicU64>,
+
+    /// Times of the recent submissions of each user, for the submission rate limit
+    submissions: Arc<Mutex<HashMap<String, VecDeque<Instant>>>>,
+
+    /// Validation jobs of newly registered nodes
+    ///
+    /// Key: Job ID
+    /// Value: Node ID
+    validations: Arc<Mutex<HashMap<u64, String>>>,
+
+    /// Nodes whose validation failed, they stay offline until they register again
+    failed_nodes: Arc<Mutex<HashSet<String>>>,
+
+    /// Pending jobs that were sent to their nodes, but whose answer is still outstanding
+    ///
+    /// Key: Job ID
+    /// Value: Nodes of the job and the resources reserved on each of them
+    assigning: OpenAssignments,
+
+    /// Assignment and health polling timers and the maximum job time
+    timers: SchedulerSettings,
+
+    /// Broadcasts job state transitions, e.g. to the API
+    events: JobEventSender,
 }
 
 impl Drop for Scheduler {
@@ -110,9 +178,30 @@ impl Scheduler {
 
         let job_ctr = Arc::new(AtomicU64::new(highest_job_id + 1));
 
+        // known nodes stay offline until their next heartbeat confirms them
+        let nodes: HashMap<String, Node> = db_writer
+            .get_all_nodes()
+            .expect("Could not get nodes from database")
+            .into_iter()
+            .map(|node| (node.id.clone(), node))
+            .collect();
+        if !nodes.is_empty() {
+            log!(
+                info,
+                "Reloaded {} nodes, offline until they report back",
+                nodes.len()
+            );
+        }
+        // nodes that failed their validation stay offline until they register again
+        let failed_nodes: HashSet<String> = db_writer
+            .get_rejected_nodes()
+            .expect("Could not get rejected nodes from database")
+            .into_iter()
+            .collect();
+
         Self {
             job_ctr,
-            nodes: Arc::new(Mutex::new(HashMap::new())),
+            nodes: Arc::new(Mutex::new(nodes)),
             running_jobs: Arc::new(Mutex::new(HashMap::new())),
             pending_jobs: Arc::new(Mutex::new(VecDeque::new())),
             handle: None,
@@ -121,7 +210,61 @@ impl Scheduler {
             health_notifier: Arc::new(Notify::new()),
             db: db_writer,
             db_tx,
+            partitions: Arc::new(settings.partitions.clone()),
+            requeue_lost_jobs: settings.application.requeue_lost_jobs,
+            admins: Arc::new(settings.auth.admins.clone()),
+            worker_token: TokenInterceptor::new(settings.auth.worker_token.clone()),
+            reservations: Arc::new(Mutex::new(Vec::new())),
+            reservation_ctr: Arc::new(AtomicU64::new(1)),
+            submissions: Arc::new(Mutex::new(HashMap::new())),
+            validations: Arc::new(Mutex::new(HashMap::new())),
+            failed_nodes: Arc::new(Mutex::new(failed_nodes)),
+            assigning: Arc::new(Mutex::new(HashMap::new())),
+            timers: settings.scheduler.clone(),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    /// Stops the scheduling loops and stores the running jobs before the process exits.
+    ///
+    /// The scheduler doesn't keep track of running jobs across restarts, so they are
+    /// stored as failed instead of silently disappearing.
+    pub async fn shutdown(&self) {
+        self.health_notifier.notify_one();
+        self.notifier.notify_one();
+        // let the current assignment round finish, so no job is caught in between
+        if let Some(handle) = &self.handle {
+            let mut handle = handle.lock().await;
+            if let Err(e) = (&mut *handle).await {
+                log!(error, "Scheduling task failed: {}", e);
+            }
+        }
+
+        let running_jobs: Vec<Job> = {
+            let mut running_jobs = self.running_jobs.lock().await;
+            running_jobs.drain().map(|(_, job)| job).collect()
+        };
+        log!(
+            info,
+            "Shutting down, storing {} running jobs",
+            running_jobs.len()
+        );
+        for job in running_jobs {
+            self.archive_job(job, JobStatus::Failed).await;
         }
+
+        self.db.close().await;
+    }
+
+    /// Returns the sender of the job state transitions, used to subscribe to them.
+    pub fn job_events(&self) -> JobEventSender {
+        self.events.clone()
+    }
+
+    /// Announces a job event to all current subscribers.
+    fn publish_event(&self, event: JobEvent) {
+        // an error only means that nobody is listening
+        let _ = self.events.send(event);
     }
 
     /// Starts a dedicated task that periodically scans for pending jobs
@@ -136,54 +279,14 @@ impl Scheduler {
             let span = tracing::span!(tracing::Level::DEBUG, "Spawn pending jobs listener");
             let _guard = span.enter();
 
-            // FIXME: hardocded timer
-            let mut interval = interval(Duration::from_millis(250));
+            let mut interval = interval(Duration::from_millis(
+                scheduler.timers.assignment_interval_ms,
+            ));
 
             loop {
                 tokio::select! {
                     _ = interval.tick() => {
-                        let mut pending_jobs = scheduler.pending_jobs.lock().await;
-
-                        let mut to_remove = vec![];
-
-                        // assign jobs to nodes if they're available
-                        for (index, job) in pending_jobs.iter_mut().enumerate() {
-                            // log!(info, "Check job {}", index);
-                            if let Some(node_id) = scheduler.find_available_node(&job.req_res).await {
-                                let mut nodes = scheduler.nodes.lock().await;
-                                let node = nodes.get_mut(&node_id).unwrap();
-
-                                // submit the job to the node
-                                // FIXME: handle fails
-                                if let Ok(mut client) = MelonWorkerClient::connect(node.endpoint.clone()).await{
-                                    let req = tonic::Request::new(job.into());
-                                    // if it worked, reduce the available resources
-                                    if (client.assign_job(req).await).is_ok() {
-                                        // submission was successful => compute node started working
-                                        // reduce the available compute resources of the node
-                                        node.reduce_avail_resources(&job.req_res);
-
-                                        // set the node id of the job
-                                        job.assigned_node = Some(node_id);
-
-                                        // mark the job for removal
-                                        to_remove.push(index);
-
-                                    }
-                                }
-                            }
-                        }
-
-                        // move submitted jobs to running jobs list
-                        let mut running_jobs = scheduler.running_jobs.lock().await;
-                        for index in to_remove.iter().rev() {
-                            let mut job = pending_jobs.remove(*index).expect("Job should exist");
-                            job.start_time = Some(get_current_timestamp());
-                            job.status = JobStatus::Running;
-                            let job_id = job.id;
-
-                            running_jobs.insert(job_id, job);
-                        }
+                        scheduler.assign_pending_jobs().await;
                     }
 
                     _ = notifier.notified() => {
@@ -205,8 +308,9 @@ impl Scheduler {
         let notifier = self.health_notifier.clone();
 
         let handle = tokio::spawn(async move {
-            // FIXME: hardocded timer
-            let mut interval = interval(Duration::from_secs(30));
+            let mut interval = interval(Duration::from_secs(
+                scheduler.timers.health_poll_interval_secs,
+            ));
             loop {
                 tokio::select! {
                     _ = interval.tick() => {
@@ -228,282 +332,2156 @@ impl Scheduler {
     }
 
     /// Checks the health status of all registered compute nodes.
-    /// Marks nodes as offline if they haven't sent a heartbeat in the last 60 seconds.
+    /// Marks nodes as offline if they haven't sent a heartbeat within the configured node timeout.
+    ///
+    /// Draining nodes keep their status so they are still removed once their jobs are done.
+    /// Jobs running on a lost node are failed, or put back into the queue if they have
+    /// retries left or `requeue_lost_jobs` is enabled.
     #[tracing::instrument(level = "debug", name = "Poll node health", skip(self))]
     async fn poll_node_health(&self) -> Result<()> {
+        let mut pending_jobs = self.pending_jobs.lock().await;
+        let mut running_jobs = self.running_jobs.lock().await;
+        let mut nodes = self.nodes.lock().await;
+
         // regularly check which compute nodes have not called back in a while
         // mark those nodes as unavailable
-        let mut nodes = self.nodes.lock().await;
-        for (_, node) in nodes.iter_mut() {
-            let now = Instant::now();
-            if now.duration_since(node.last_heartbeat) > Duration::from_secs(60) {
+        let now = Instant::now();
+        let node_timeout = Duration::from_secs(self.timers.node_timeout_secs);
+        let mut lost_nodes = HashSet::new();
+        for (node_id, node) in nodes.iter_mut() {
+            if node.status == NodeStatus::Offline
+                || now.duration_since(node.last_heartbeat) <= node_timeout
+            {
+                continue;
+            }
+            if node.status == NodeStatus::Available {
+                log!(
+                    warn,
+                    "Node {} missed its heartbeats, mark as offline",
+                    node_id
+                );
                 node.status = NodeStatus::Offline;
             }
+            lost_nodes.insert(node_id.clone());
         }
-        Ok(())
-    }
-
-    /// Finds an available node for a given resource requirement.
-    #[tracing::instrument(
-        level = "debug",
-        name = "Find available node",
-        skip(self),
-        fields(
-            cpu_count = %res.cpu_count,
-            memory = %res.memory,
-            time = %res.time
-        )
-    )]
-    async fn find_available_node(&self, res: &RequestedResources) -> Option<String> {
-        let nodes = self.nodes.lock().await;
-
-        for (node_id, node) in nodes.iter() {
-            // log!(info, "Check node_id {}", node_id);
-            if node.status != NodeStatus::Available {
-                continue;
-            }
-
-            let available_cpu = node
-                .avail_resources
-                .cpu_count
-                .saturating_sub(node.used_resources.cpu_count);
-            let available_memory = node
-                .avail_resources
-                .memory
-                .saturating_sub(node.used_resources.memory);
 
-            if available_cpu >= res.cpu_count && available_memory >= res.memory {
-                return Some(node_id.clone());
-            }
+        // the jobs of a lost node will never report back
+        let lost_jobs: Vec<u64> = running_jobs
+            .values()
+            .filter(|job| job.nodes().any(|node_id| lost_nodes.contains(node_id)))
+            .map(|job| job.id)
+            .collect();
+        for job_id in lost_jobs {
+            let job = running_jobs.remove(&job_id).expect("Job should exist");
+            let node_id = job
+                .nodes()
+                .find(|node_id| lost_nodes.contains(*node_id))
+                .cloned()
+                .expect("Expect a lost node");
+            let reason = format!("Node {} is lost", node_id);
+            self.recover_lost_job(&mut pending_jobs, &mut nodes, job, &node_id, &reason)
+                .await;
         }
-        None
+        Ok(())
     }
-}
 
-#[tonic::async_trait]
-impl MelonScheduler for Scheduler {
-    #[tracing::instrument(level="debug", name = "Receive job submission", skip(self), fields(script_path = %request.get_ref().script_path))]
-    async fn submit_job(
+    /// Puts a job that will never report back into the queue if it has retries left
+    /// or `requeue_lost_jobs` is enabled, fails it otherwise.
+    ///
+    /// The job's resources are freed on all of its nodes, and the parts of a multi-node job
+    /// on the nodes other than `lost_node` are stopped.
+    async fn recover_lost_job(
         &self,
-        request: tonic::Request<proto::JobSubmission>,
-    ) -> core::result::Result<tonic::Response<proto::MasterJobResponse>, tonic::Status> {
-        log!(debug, "get job sub request");
-        let sub = request.get_ref();
+        pending_jobs: &mut VecDeque<Job>,
+        nodes: &mut HashMap<String, Node>,
+        mut job: Job,
+        lost_node: &str,
+        reason: &str,
+    ) {
+        let job_id = job.id;
+        free_job_resources(nodes, &job);
+        let others: Vec<String> = job
+            .nodes()
+            .filter(|node_id| *node_id != lost_node)
+            .cloned()
+            .collect();
+        stop_job_on_nodes(nodes, &job, &others, &self.worker_link());
+
+        // a step can't run without its parent
+        if !job.is_step() && (job.can_retry() || self.requeue_lost_jobs) {
+            log!(warn, "{}, requeue job {}", reason, job_id);
+            job.requeue(JobStatus::Failed);
+            self.publish_event(JobEvent::new(&job));
+            enqueue_pending_job(pending_jobs, job);
+        } else {
+            log!(warn, "{}, fail job {}", reason, job_id);
+            job.message = Some(reason.to_string());
+            self.archive_job(job, JobStatus::Failed).await;
+        }
+    }
 
-        // create new job
-        let job_id = self
-            .job_ctr
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        let res = sub.req_res.expect("No resources given");
-        let resources = res.into();
-        let new_job = Job::new(
-            job_id,
-            sub.user.clone(),
-            sub.script_path.clone(),
-            sub.script_args.clone(),
-            resources,
-        );
+    /// Runs one round of the scheduling loop.
+    ///
+    /// The assignments are planned with the queue locked, but sent to the nodes without any
+    /// lock held, so a slow node doesn't hold up submissions or heartbeats in the meantime.
+    /// Each node gets one job per pass, passes go on until no more jobs can be started.
+    async fn assign_pending_jobs(&self) {
+        // nodes that failed an assignment are not tried again until the next tick
+        let mut skipped_nodes: HashSet<String> = HashSet::new();
+        loop {
+            let planned = self.plan_assignments(&skipped_nodes).await;
+            if planned.is_empty() {
+                break;
+            }
+            self.send_assignments(planned, &mut skipped_nodes).await;
+        }
 
-        // push job to pending jobs queue
-        let pending_jobs = self.pending_jobs.clone();
-        let mut pending_jobs = pending_jobs.lock().await;
-        pending_jobs.push_back(new_job); // FIFO
+        let removed = {
+            let pending_jobs = self.pending_jobs.lock().await;
+            let running_jobs = self.running_jobs.lock().await;
 
-        // return created job id
-        let response = proto::MasterJobResponse { job_id };
-        log!(debug, "response. {:?}", response);
-        Ok(tonic::Response::new(response))
-    }
+            // retire draining nodes once their last job is gone
+            let removed = self.remove_drained_nodes(&running_jobs).await;
 
-    /// Register a new node in a master.
-    #[tracing::instrument(level="info", name = "Register new compute node", skip(self, request), fields(address = %request.get_ref().address))]
-    async fn register_node(
-        &self,
-        request: tonic::Request<proto::NodeInfo>,
-    ) -> core::result::Result<tonic::Response<proto::RegistrationResponse>, tonic::Status> {
-        let req = request.get_ref();
-        let resources = req.resources.unwrap();
-        let resources = melon_common::NodeResources::new(resources.cpu_count, resources.memory);
-
-        let id = nanoid!();
-        let node = Node::new(
-            id.clone(),
-            req.address.clone(),
-            resources,
-            NodeStatus::Available,
-        );
-        let res = proto::RegistrationResponse {
-            node_id: id.clone(),
+            let nodes = self.nodes.lock().await;
+            monitoring::record_cluster_state(pending_jobs.len(), running_jobs.len(), &nodes);
+            removed
         };
-        let response = tonic::Response::new(res);
-
-        let mut nodes = self.nodes.lock().await;
-        nodes.insert(id, node);
 
-        Ok(response)
+        // a retired node must not come back after a restart
+        for node_id in removed {
+            if let Err(e) = self.db.remove_node(&node_id) {
+                log!(
+                    error,
+                    "Could not remove node {} from database: {}",
+                    node_id,
+                    e
+                );
+            }
+        }
     }
 
-    #[tracing::instrument(level="debug", name = "Receive heartbeat", skip(self, request), fields(node_id = %request.get_ref().node_id))]
-    async fn send_heartbeat(
-        &self,
-        request: tonic::Request<proto::Heartbeat>,
-    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
-        let mut nodes = self.nodes.lock().await;
-        let node_id = &request.get_ref().node_id;
+    /// Picks the nodes of the pending jobs that can start now and reserves their resources.
+    ///
+    /// The jobs stay queued until their nodes accepted them. A job that only fits onto a
+    /// node that was already picked in this pass ends the pass, so it keeps its place in
+    /// the queue. Jobs whose dependencies can never be satisfied or that missed their
+    /// begin deadline are dropped along the way.
+    async fn plan_assignments(&self, skipped_nodes: &HashSet<String>) -> Vec<Assignment> {
+        let mut pending_jobs = self.pending_jobs.lock().await;
+
+        let mut to_cancel = vec![];
 
-        match nodes.get_mut(node_id) {
-            Some(node) => {
-                // compute node is registered
-                node.set_status(NodeStatus::Available);
-                node.update_heartbeat();
+        // jobs that are still queued or running can't satisfy a dependency yet
+        let mut active_jobs: HashSet<u64> = pending_jobs.iter().map(|j| j.id).collect();
+        let mut partition_usage: HashMap<String, PartitionUsage> = HashMap::new();
+        let mut allocations: Vec<Allocation> = vec![];
+        for job in self.running_jobs.lock().await.values() {
+            active_jobs.insert(job.id);
+            // steps use the resources of their parent
+            if job.is_step() {
+                continue;
             }
-            None => {
-                // compute node is not registered => reject
-                return Err(tonic::Status::unauthenticated("Node is not registered"));
+            partition_usage
+                .entry(job.partition.clone())
+                .or_default()
+                .add(&job.req_res);
+            if let Some(start_time) = job.start_time {
+                for node_id in job.nodes() {
+                    allocations.push(Allocation::new(node_id.clone(), start_time, &job.req_res));
+                }
             }
         }
 
-        let res = tonic::Response::new(());
-        Ok(res)
-    }
+        // earliest start of the first job that waits for free resources
+        let now = get_current_timestamp();
+        let mut reserved_start: Option<u64> = None;
 
-    #[tracing::instrument(level = "info", name = "Receive job results", skip(self, request))]
-    async fn submit_job_result(
-        &self,
-        request: tonic::Request<proto::JobResult>,
-    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
-        let req = request.get_ref();
-        let result: JobResult = req.into();
+        let mut planned = vec![];
+        let mut planned_nodes: HashSet<String> = HashSet::new();
+        for (index, job) in pending_jobs.iter().enumerate() {
+            if job.status == JobStatus::Held {
+                continue;
+            }
 
-        let job_id = result.id;
-        let mut jobs = self.running_jobs.lock().await;
-        if let Some(job) = jobs.get(&result.id) {
-            let res = &job.req_res;
-            let node_id = job.assigned_node.as_ref().expect("Expect assigned node id");
+            match self.dependency_state(job, &active_jobs) {
+                DependencyState::Satisfied => {}
+                DependencyState::Waiting => continue,
+                DependencyState::Failed => {
+                    to_cancel.push(index);
+                    continue;
+                }
+            }
 
-            // free up resources from the compute node
-            let mut nodes = self.nodes.lock().await;
-            let node = nodes.get_mut(node_id).expect("Expect node to exist");
-            node.free_avail_resource(res);
+            // stay within the partition caps, even if a node has room
+            let usage = partition_usage.entry(job.partition.clone()).or_default();
+            if !self.partition_has_room(&job.partition, usage, &job.req_res) {
+                continue;
+            }
 
-            // remove job from tracking map
-            let mut job = jobs.remove(&job_id).unwrap();
+            // later jobs may only skip ahead if they are done before the waiting job can start
+            if let Some(start) = reserved_start {
+                if !self.timers.backfill || end_time(now, &job.req_res) > start {
+                    continue;
+                }
+            }
+
+            // a multi-node job only starts once all of its nodes are free at the same time
+            let unavailable: HashSet<String> =
+                skipped_nodes.union(&planned_nodes).cloned().collect();
+            let found = self
+                .find_available_nodes(
+                    &job.req_res,
+                    &job.partition,
+                    &job.user,
+                    &job.constraints,
+                    &unavailable,
+                )
+                .await;
+            if found.is_none()
+                && !planned_nodes.is_empty()
+                && self
+                    .find_available_nodes(
+                        &job.req_res,
+                        &job.partition,
+                        &job.user,
+                        &job.constraints,
+                        skipped_nodes,
+                    )
+                    .await
+                    .is_some()
+            {
+                break;
+            }
+            match found {
+                Some(node_ids) => {
+                    usage.add(&job.req_res);
+                    for node_id in &node_ids {
+                        allocations.push(Allocation::new(node_id.clone(), now, &job.req_res));
+                    }
 
-            // send the finished job to the database writer for permanent storage
-            job.stop_time = Some(get_current_timestamp());
-            job.status = result.status;
+                    // the jobs after it must not be planned onto the same resources
+                    let mut nodes = self.nodes.lock().await;
+                    for node_id in &node_ids {
+                        if let Some(node) = nodes.get_mut(node_id) {
+                            node.reduce_avail_resources(&job.req_res);
+                        }
+                    }
+                    self.assigning
+                        .lock()
+                        .await
+                        .insert(job.id, (node_ids.clone(), job.req_res));
+
+                    planned_nodes.extend(node_ids.iter().cloned());
+                    planned.push(Assignment {
+                        job: job.clone(),
+                        node_ids,
+                    });
+                }
+                None if reserved_start.is_none() => {
+                    reserved_start = self
+                        .expected_start(
+                            &job.req_res,
+                            &job.partition,
+                            &job.user,
+                            &job.constraints,
+                            &allocations,
+                            now,
+                        )
+                        .await;
+                }
+                None => {}
+            }
+        }
 
-            let tx = self.db_tx.clone();
-            // FIXME: hardcoded timeout
-            if let Err(e) = tx.send(job).await {
+        // jobs that are still waiting past their begin deadline are of no use anymore
+        let planned_ids: HashSet<u64> = planned.iter().map(|a| a.job.id).collect();
+        let expired: Vec<usize> = pending_jobs
+            .iter()
+            .enumerate()
+            .filter(|(index, job)| {
+                !planned_ids.contains(&job.id)
+                    && !to_cancel.contains(index)
+                    && job.begin_deadline_passed(now)
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        // drop jobs whose dependencies can never be satisfied
+        let mut removals: Vec<usize> = to_cancel.iter().chain(expired.iter()).copied().collect();
+        removals.sort_unstable();
+        for index in removals.into_iter().rev() {
+            let mut job = pending_jobs.remove(index).expect("Job should exist");
+            if to_cancel.contains(&index) {
                 log!(
-                    error,
-                    "Could not send job {} to database writer: {}",
-                    job_id,
-                    e
+                    info,
+                    "Cancel job {} due to an unsatisfiable dependency",
+                    job.id
+                );
+            } else {
+                log!(
+                    info,
+                    "Cancel job {}, it did not start before its deadline",
+                    job.id
                 );
+                job.message = Some(format!(
+                    "Deadline exceeded, the job did not start within {} minutes",
+                    job.begin_deadline_mins.unwrap_or_default()
+                ));
             }
-
-            // ack
-            let res = tonic::Response::new(());
-            Ok(res)
-        } else {
-            Err(tonic::Status::not_found("Job not found"))
+            self.archive_job(job, JobStatus::Cancelled).await;
         }
+
+        planned
     }
 
-    #[tracing::instrument(level = "debug", name = "List all jobs", skip(self, _request))]
-    async fn list_jobs(
+    /// Sends planned jobs to their nodes, several at once, and applies each answer as soon
+    /// as it arrives.
+    async fn send_assignments(
         &self,
-        _request: tonic::Request<()>,
-    ) -> core::result::Result<tonic::Response<proto::JobListResponse>, tonic::Status> {
-        let pending_jobs = self.pending_jobs.lock().await;
-        let running_jobs = self.running_jobs.lock().await;
-
-        // Accumulate pending and running jobs
-        let mut jobs: Vec<proto::Job> = pending_jobs.iter().map(|j| j.into()).collect();
-        jobs.extend(running_jobs.values().map(|j| j.into()));
+        planned: Vec<Assignment>,
+        skipped_nodes: &mut HashSet<String>,
+    ) {
+        let max_in_flight = self.timers.max_concurrent_assignments.max(1);
+        let link = self.worker_link();
+        let mut waiting = planned.into_iter();
+        let mut in_flight = JoinSet::new();
+
+        loop {
+            while in_flight.len() < max_in_flight {
+                let Some(Assignment { mut job, node_ids }) = waiting.next() else {
+                    break;
+                };
 
-        // Fetch finished jobs from the database
-        match self.db.get_all_jobs() {
-            Ok(finished_jobs) => {
-                jobs.extend(finished_jobs.iter().map(|j| j.into()));
+                // a clone of the scheduler would shut it down once dropped
+                let (nodes, link) = (self.nodes.clone(), link.clone());
+                in_flight.spawn(async move {
+                    let result = assign_job_to_nodes(&nodes, &node_ids, &mut job, &link).await;
+                    AssignmentOutcome {
+                        job,
+                        node_ids,
+                        result,
+                    }
+                });
             }
-            Err(e) => {
-                log!(error, "Error fetching finished jobs from database: {}", e);
-                return Err(tonic::Status::internal("Failed to fetch finished jobs"));
+
+            match in_flight.join_next().await {
+                Some(Ok(outcome)) => self.apply_assignment(outcome, skipped_nodes).await,
+                Some(Err(e)) => {
+                    log!(error, "Job assignment task failed: {}", e);
+                }
+                None => break,
             }
         }
 
-        let response = proto::JobListResponse { jobs };
-        let response = tonic::Response::new(response);
-        Ok(response)
+        // a failed task leaves its reservation behind
+        let mut nodes = self.nodes.lock().await;
+        for (_, (node_ids, res)) in self.assigning.lock().await.drain() {
+            release_nodes(&mut nodes, &node_ids, &res);
+            skipped_nodes.extend(node_ids);
+        }
     }
 
-    #[tracing::instrument(
-        level = "info",
-        name = "Receive cancellation request",
-        skip(self, request),
-        fields(job_id = %request.get_ref().job_id, user=%request.get_ref().user)
-    )]
-    async fn cancel_job(
+    /// Moves a job that its nodes accepted to the running jobs, or releases its resources.
+    ///
+    /// A node that refused the job is skipped for the rest of the tick.
+    async fn apply_assignment(
         &self,
-        request: tonic::Request<proto::CancelJobRequest>,
-    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
-        let req = request.get_ref();
-        let id = req.job_id;
-        let user = req.user.clone();
-
-        // check in pending jobs
+        outcome: AssignmentOutcome,
+        skipped_nodes: &mut HashSet<String>,
+    ) {
         let mut pending_jobs = self.pending_jobs.lock().await;
-        if let Some(pos) = pending_jobs.iter().position(|job| job.id == id) {
-            if pending_jobs[pos].user != user {
-                return Err(Status::permission_denied(
-                    "Not authorized to cancel this job",
-                ));
+        let mut running_jobs = self.running_jobs.lock().await;
+        let mut nodes = self.nodes.lock().await;
+        self.assigning.lock().await.remove(&outcome.job.id);
+
+        let AssignmentOutcome {
+            job,
+            node_ids,
+            result,
+        } = outcome;
+        let position = pending_jobs.iter().position(|j| j.id == job.id);
+        match (result, position) {
+            (Ok(()), Some(position)) => {
+                let mut job = pending_jobs.remove(position).expect("Job should exist");
+                job.assigned_node = node_ids.first().cloned();
+                job.extra_nodes = node_ids[1..].to_vec();
+                job.start_time = Some(get_current_timestamp());
+                job.status = JobStatus::Running;
+                self.publish_event(JobEvent::new(&job));
+                running_jobs.insert(job.id, job);
+            }
+            (Ok(()), None) => {
+                log!(
+                    info,
+                    "Job {} was cancelled while it was assigned, stop it again",
+                    job.id
+                );
+                release_nodes(&mut nodes, &node_ids, &job.req_res);
+                stop_job_on_nodes(&nodes, &job, &node_ids, &self.worker_link());
+            }
+            (Err((node_id, status)), _) => {
+                release_nodes(&mut nodes, &node_ids, &job.req_res);
+                skipped_nodes.insert(node_id.clone());
+                if status.code() == tonic::Code::ResourceExhausted {
+                    // the node's view of its resources differs from ours, try another one
+                    log!(
+                        warn,
+                        "Node {} has no room for job {}: {}",
+                        node_id,
+                        job.id,
+                        status.message()
+                    );
+                } else {
+                    // most likely transient, the job is retried next tick
+                    log!(
+                        warn,
+                        "Could not assign job {} to node {}: {}",
+                        job.id,
+                        node_id,
+                        status.message()
+                    );
+                }
             }
-            pending_jobs.remove(pos);
-            return Ok(tonic::Response::new(()));
         }
+    }
 
-        // check in running jobs
-        let mut running_jobs = self.running_jobs.lock().await;
-        if let Some(job) = running_jobs.get(&id) {
-            if job.user != user {
-                return Err(Status::permission_denied(
-                    "Not authorized to cancel this job",
-                ));
+    /// Removes all draining nodes that have no running jobs left, returns their IDs.
+    async fn remove_drained_nodes(&self, running_jobs: &HashMap<u64, Job>) -> Vec<String> {
+        let mut nodes = self.nodes.lock().await;
+        let mut removed = vec![];
+        nodes.retain(|node_id, node| {
+            if node.status != NodeStatus::Draining {
+                return true;
             }
+            let busy = running_jobs.values().any(|job| job.runs_on(node_id));
+            if !busy {
+                log!(info, "Node {} is drained, removing it", node_id);
+                removed.push(node_id.clone());
+            }
+            busy
+        });
+        removed
+    }
 
-            // send cancellation request to the assigned node
-            let node = &job.assigned_node.clone().unwrap();
-            let mut nodes = self.nodes.lock().await;
-            if let Some(node) = nodes.get_mut(node) {
-                // send the cancellation request to the assigned node
-                let mut client = MelonWorkerClient::connect(node.endpoint.clone())
-                    .await
-                    .map_err(|e| Status::unknown(format!("Error connecting to node: {}", e)))?;
-                let worker_request = proto::CancelJobRequest {
-                    job_id: id,
-                    user: user.clone(),
-                };
-
-                client.cancel_job(worker_request).await?;
+    /// Marks a job as finished and hands it to the database writer for permanent storage.
+    async fn archive_job(&self, mut job: Job, status: JobStatus) {
+        let job_id = job.id;
+        job.stop_time = Some(get_current_timestamp());
+        job.status = status;
+        monitoring::record_job_finished(&job.status);
+        self.publish_event(JobEvent::new(&job));
+
+        if let Err(e) = self.db_tx.send(job).await {
+            log!(
+                error,
+                "Could not send job {} to database writer: {}",
+                job_id,
+                e
+            );
+        }
+    }
 
-                // free up the node resources to mark availability
-                let res = job.req_res;
-                node.free_avail_resource(&res);
+    /// Checks whether all dependencies of a job have completed successfully.
+    ///
+    /// Dependencies that are still pending or running (`active_jobs`) keep the job waiting.
+    /// Finished dependencies are looked up in the database. A dependency that ended in
+    /// any state other than [JobStatus::Completed] can never be satisfied.
+    fn dependency_state(&self, job: &Job, active_jobs: &HashSet<u64>) -> DependencyState {
+        for dep_id in &job.dependencies {
+            if active_jobs.contains(dep_id) {
+                return DependencyState::Waiting;
             }
 
-            running_jobs.remove(&id);
-            return Ok(tonic::Response::new(()));
+            match self.db.get_job_opt(*dep_id) {
+                Ok(Some(dep)) if dep.status == JobStatus::Completed => {}
+                Ok(Some(_)) => return DependencyState::Failed,
+                // the job result may still be on its way to the database writer
+                Ok(None) => return DependencyState::Waiting,
+                Err(e) => {
+                    log!(
+                        error,
+                        "Could not look up dependency {} of job {}: {}",
+                        dep_id,
+                        job.id,
+                        e
+                    );
+                    return DependencyState::Waiting;
+                }
+            }
         }
+        DependencyState::Satisfied
+    }
 
-        // no job found
-        Err(Status::not_found("Job not found"))
+    /// Checks whether a partition can take on a job without exceeding its caps.
+    ///
+    /// Partitions without a configuration, like `default`, are unlimited.
+    fn partition_has_room(
+        &self,
+        partition: &str,
+        usage: &PartitionUsage,
+        res: &RequestedResources,
+    ) -> bool {
+        let Some(settings) = self.partitions.get(partition) else {
+            return true;
+        };
+        let needed = PartitionUsage::of(res);
+        let cpus_ok = settings
+            .max_cpus
+            .is_none_or(|max| usage.cpu_count.saturating_add(needed.cpu_count) <= max);
+        let memory_ok = settings
+            .max_memory
+            .is_none_or(|max| usage.memory.saturating_add(needed.memory) <= max);
+        cpus_ok && memory_ok
     }
 
-    #[tracing::instrument(
+    /// Moves a pending job in or out of the held state on behalf of its owner.
+    async fn set_job_held(
+        &self,
+        job_id: u64,
+        user: &str,
+        held: bool,
+    ) -> core::result::Result<(), Status> {
+        let mut pending_jobs = self.pending_jobs.lock().await;
+        if let Some(job) = pending_jobs.iter_mut().find(|job| job.id == job_id) {
+            if job.user != user {
+                return Err(Status::permission_denied(
+                    "Not authorized to modify this job",
+                ));
+            }
+            if !held && job.status != JobStatus::Held {
+                return Err(Status::failed_precondition("Job is not held"));
+            }
+
+            // the job keeps its place in the queue
+            job.status = if held {
+                JobStatus::Held
+            } else {
+                JobStatus::Pending
+            };
+            self.publish_event(JobEvent::new(job));
+            return Ok(());
+        }
+
+        let running_jobs = self.running_jobs.lock().await;
+        if running_jobs.contains_key(&job_id) {
+            return Err(Status::failed_precondition("Job is already running"));
+        }
+
+        Err(Status::not_found("Job not found"))
+    }
+
+    /// Takes a job away from its owner on behalf of an admin, e.g. one left behind by a departed user.
+    ///
+    /// A running job is stopped on its nodes in the background and its resources are freed
+    /// right away, so an unreachable node can't keep it stuck. It is put back into the queue
+    /// if `requeue` is set, cancelled otherwise.
+    async fn admin_remove_job(
+        &self,
+        job_id: u64,
+        admin: &str,
+        requeue: bool,
+    ) -> core::result::Result<(), Status> {
+        let mut pending_jobs = self.pending_jobs.lock().await;
+        if let Some(pos) = pending_jobs.iter().position(|job| job.id == job_id) {
+            if requeue {
+                return Err(Status::failed_precondition("Job is not running"));
+            }
+            let mut job = pending_jobs.remove(pos).expect("exists for sure");
+            log!(
+                warn,
+                "Admin {} kills pending job {} of user {}",
+                admin,
+                job_id,
+                job.user
+            );
+            job.message = Some(format!("Killed by admin {}", admin));
+            self.archive_job(job, JobStatus::Cancelled).await;
+            return Ok(());
+        }
+
+        let mut running_jobs = self.running_jobs.lock().await;
+        if requeue && running_jobs.get(&job_id).is_some_and(Job::is_step) {
+            return Err(Status::failed_precondition("Steps can't be requeued"));
+        }
+        let Some(mut job) = running_jobs.remove(&job_id) else {
+            return Err(Status::not_found("Job not found"));
+        };
+        let mut nodes = self.nodes.lock().await;
+        free_job_resources(&mut nodes, &job);
+        let node_ids: Vec<String> = job.nodes().cloned().collect();
+        stop_job_on_nodes(&nodes, &job, &node_ids, &self.worker_link());
+
+        if requeue {
+            log!(
+                warn,
+                "Admin {} requeues job {} of user {}",
+                admin,
+                job_id,
+                job.user
+            );
+            job.requeue(JobStatus::Cancelled);
+            self.publish_event(JobEvent::new(&job));
+            enqueue_pending_job(&mut pending_jobs, job);
+        } else {
+            log!(
+                warn,
+                "Admin {} kills job {} of user {}",
+                admin,
+                job_id,
+                job.user
+            );
+            job.message = Some(format!("Killed by admin {}", admin));
+            self.archive_job(job, JobStatus::Cancelled).await;
+        }
+        Ok(())
+    }
+
+    /// How to reach the workers, with the timeout for connecting and for every request.
+    fn worker_link(&self) -> WorkerLink {
+        WorkerLink {
+            timeout: Duration::from_secs(self.timers.worker_timeout_secs),
+            token: self.worker_token.clone(),
+        }
+    }
+
+    /// Checks a job time limit in minutes against the configured maximum.
+    #[allow(clippy::result_large_err)] // handed to the client as is
+    fn check_max_job_time(&self, time: u32) -> core::result::Result<(), Status> {
+        match self.timers.max_job_time_mins {
+            Some(max) if time > max => Err(Status::invalid_argument(format!(
+                "Time limit of {} minutes exceeds the maximum of {} minutes",
+                time, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records a submission of `user`, unless they already reached the submission rate limit.
+    #[allow(clippy::result_large_err)] // handed to the client as is
+    async fn check_submission_rate(
+        &self,
+        user: &str,
+        count: usize,
+    ) -> core::result::Result<(), Status> {
+        let Some(max) = self.timers.max_submissions_per_min else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut submissions = self.submissions.lock().await;
+        let recent = submissions.entry(user.to_string()).or_default();
+        while recent
+            .front()
+            .is_some_and(|time| now.duration_since(*time) >= SUBMISSION_RATE_WINDOW)
+        {
+            recent.pop_front();
+        }
+        if recent.len() + count > max as usize {
+            return Err(Status::resource_exhausted(format!(
+                "User {} submitted {} jobs within the last minute, the limit is {}",
+                user,
+                recent.len(),
+                max
+            )));
+        }
+        recent.extend(std::iter::repeat_n(now, count));
+        Ok(())
+    }
+
+    /// Rejects submissions of `count` jobs that don't fit into the pending queue anymore.
+    #[allow(clippy::result_large_err)] // handed to the client as is
+    fn check_queue_space(&self, pending: usize, count: usize) -> core::result::Result<(), Status> {
+        match self.timers.max_pending_jobs {
+            Some(max) if pending + count > max => Err(Status::resource_exhausted(format!(
+                "Queue is full, {} jobs are pending already",
+                pending
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Passes a submission through the admission hook, `None` if there is no hook.
+    async fn run_admission_hook(
+        &self,
+        sub: &proto::JobSubmission,
+    ) -> core::result::Result<Option<proto::JobSubmission>, Status> {
+        let Some(hook) = &self.timers.admission_hook else {
+            return Ok(None);
+        };
+        let timeout = Duration::from_secs(self.timers.admission_timeout_secs);
+        admission::admit(hook, timeout, sub).await.map(Some)
+    }
+
+    /// Starts a step within the allocation of a running job, on the first node of the job.
+    ///
+    /// Steps skip the queue and hold no resources of their own: they get the resources of
+    /// their parent and at most the time it has left. Their parent only ends once all of its
+    /// steps have ended.
+    #[allow(clippy::result_large_err)] // handed to the client as is
+    async fn launch_step(
+        &self,
+        parent_id: u64,
+        sub: &proto::JobSubmission,
+        user: String,
+    ) -> core::result::Result<proto::MasterJobResponse, Status> {
+        if sub.dry_run || sub.interactive {
+            return Err(Status::invalid_argument(
+                "Steps can't be dry runs or interactive",
+            ));
+        }
+        let warn_signal = sub
+            .warn_signal
+            .as_deref()
+            .map(str::parse::<WarnSignal>)
+            .transpose()
+            .map_err(Status::invalid_argument)?;
+        for template in [&sub.output_path, &sub.error_path].into_iter().flatten() {
+            expand_output_path(template, 0, &sub.user).map_err(Status::invalid_argument)?;
+        }
+        check_script_size(sub)?;
+        self.check_submission_rate(&user, 1).await?;
+
+        let job_id = self
+            .job_ctr
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let (mut step, endpoint) = {
+            let mut running_jobs = self.running_jobs.lock().await;
+            let parent = running_jobs.get(&parent_id).ok_or_else(|| {
+                Status::failed_precondition(format!("Job {} is not running", parent_id))
+            })?;
+            if parent.user != user {
+                return Err(Status::permission_denied(
+                    "Not authorized to launch steps in this job",
+                ));
+            }
+            if parent.is_step() {
+                return Err(Status::invalid_argument(format!(
+                    "Job {} is a step itself",
+                    parent_id
+                )));
+            }
+            let node_id = parent
+                .assigned_node
+                .clone()
+                .ok_or_else(|| Status::failed_precondition("Job has no node"))?;
+            let endpoint = self
+                .nodes
+                .lock()
+                .await
+                .get(&node_id)
+                .map(|node| node.endpoint.clone())
+                .ok_or_else(|| Status::unavailable(format!("Node {} is not available", node_id)))?;
+
+            let now = get_current_timestamp();
+            let mut res = parent.req_res.with_node_count(1);
+            let left_secs =
+                end_time(parent.start_time.unwrap_or(now), &parent.req_res).saturating_sub(now);
+            res.time = (left_secs / 60).max(1) as u32;
+            let mut step = Job::new(
+                job_id,
+                user,
+                sub.script_path.clone(),
+                sub.script_args.clone(),
+                res,
+            );
+            step.parent_job_id = Some(parent_id);
+            step.partition = parent.partition.clone();
+            step.env = sub.env.clone();
+            step.work_dir = sub.work_dir.clone();
+            step.name = sub.name.clone();
+            step.grace_secs = sub.grace_secs;
+            step.warn_signal = warn_signal;
+            step.output_path = sub.output_path.clone();
+            step.error_path = sub.error_path.clone();
+            step.script_contents = sub.script_contents.clone();
+            step.assigned_node = Some(node_id);
+            step.start_time = Some(now);
+            step.status = JobStatus::Running;
+
+            // tracked before it is sent, a short step may report back right away
+            running_jobs.insert(job_id, step.clone());
+            (step, endpoint)
+        };
+
+        let assignment = proto::JobAssignment::from(&mut step);
+        let assigned = match connect_worker(endpoint, &self.worker_link()).await {
+            Ok(mut client) => client.assign_job(tonic::Request::new(assignment)).await,
+            Err(status) => Err(status),
+        };
+        if let Err(status) = assigned {
+            self.running_jobs.lock().await.remove(&job_id);
+            return Err(status);
+        }
+        log!(info, "Started step {} of job {}", job_id, parent_id);
+        self.publish_event(JobEvent::new(&step));
+
+        Ok(proto::MasterJobResponse {
+            job_id,
+            summary: String::new(),
+            attach_token: String::new(),
+        })
+    }
+
+    /// Checks a submission against the known jobs, the partitions and the registered nodes.
+    async fn check_submission(
+        &self,
+        sub: &proto::JobSubmission,
+        pending_jobs: &VecDeque<Job>,
+    ) -> core::result::Result<CheckedSubmission, Status> {
+        // an admission hook may have dropped them
+        let Some(res) = sub.req_res else {
+            return Err(Status::invalid_argument("No resources given"));
+        };
+
+        // every dependency has to refer to a known job
+        if !sub.dependencies.is_empty() {
+            let running_jobs = self.running_jobs.lock().await;
+            for dep_id in &sub.dependencies {
+                if pending_jobs.iter().any(|job| job.id == *dep_id)
+                    || running_jobs.contains_key(dep_id)
+                {
+                    continue;
+                }
+                match self.db.get_job_opt(*dep_id) {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        return Err(Status::invalid_argument(format!(
+                            "Unknown dependency job id {}",
+                            dep_id
+                        )));
+                    }
+                    Err(e) => {
+                        log!(error, "Could not look up dependency {}: {}", dep_id, e);
+                        return Err(Status::internal("Failed to look up job dependencies"));
+                    }
+                }
+            }
+        }
+
+        let partition = sub
+            .partition
+            .clone()
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| DEFAULT_PARTITION.to_string());
+        let total = PartitionUsage::of(&res.into());
+        match self.partitions.get(&partition) {
+            // a job that exceeds the caps on its own would never be scheduled
+            Some(limits)
+                if limits.max_cpus.is_some_and(|max| total.cpu_count > max)
+                    || limits.max_memory.is_some_and(|max| total.memory > max) =>
+            {
+                return Err(Status::invalid_argument(format!(
+                    "Job exceeds the resource limits of partition {}",
+                    partition
+                )));
+            }
+            None if partition != DEFAULT_PARTITION => {
+                return Err(Status::invalid_argument(format!(
+                    "Unknown partition {}",
+                    partition
+                )));
+            }
+            _ => {}
+        }
+
+        self.check_max_job_time(res.time)?;
+
+        if sub.interactive && res.node_count > 1 {
+            return Err(Status::invalid_argument(
+                "Interactive jobs run on a single node",
+            ));
+        }
+        let warn_signal = sub
+            .warn_signal
+            .as_deref()
+            .map(str::parse::<WarnSignal>)
+            .transpose()
+            .map_err(Status::invalid_argument)?;
+        // the paths are expanded by the worker, an invalid template would only show up there
+        for template in [&sub.output_path, &sub.error_path].into_iter().flatten() {
+            expand_output_path(template, 0, &sub.user).map_err(Status::invalid_argument)?;
+        }
+        check_script_size(sub)?;
+
+        // a job that fits on no registered node would stay pending forever
+        let capacity = if sub.force {
+            None
+        } else {
+            Some(
+                self.check_node_capacity(&res, &partition, &sub.constraints)
+                    .await?,
+            )
+        };
+
+        Ok(CheckedSubmission {
+            partition,
+            warn_signal,
+            capacity,
+        })
+    }
+
+    /// Sends the validation job to a node that just registered.
+    ///
+    /// The node gets jobs once the validation job completed, it is taken offline if the job
+    /// fails or can't be sent.
+    fn start_validation(&self, node_id: String, endpoint: String) {
+        let job_id = self
+            .job_ctr
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let assignment = proto::JobAssignment {
+            job_id,
+            script_path: self.timers.validation_script.clone(),
+            user: VALIDATION_USER.to_string(),
+            req_res: Some(RequestedResources::new(1, 64 * 1024 * 1024, 1, 0).into()),
+            ..Default::default()
+        };
+        let nodes = self.nodes.clone();
+        let validations = self.validations.clone();
+        let failed_nodes = self.failed_nodes.clone();
+        let db = self.db.clone();
+        let link = self.worker_link();
+        log!(info, "Validate node {} with job {}", node_id, job_id);
+        tokio::spawn(async move {
+            validations.lock().await.insert(job_id, node_id.clone());
+            if let Err(status) = send_validation_job(endpoint, assignment, &link).await {
+                validations.lock().await.remove(&job_id);
+                let reason = format!("Could not send the validation job: {}", status.message());
+                {
+                    let mut nodes = nodes.lock().await;
+                    let mut failed_nodes = failed_nodes.lock().await;
+                    reject_node(&mut nodes, &mut failed_nodes, &node_id, &reason);
+                }
+                store_rejection(&db, &node_id);
+            }
+        });
+    }
+
+    /// Checks that enough registered nodes of the partition could ever run the job.
+    ///
+    /// Draining nodes are not considered. Without any registered nodes every request is accepted,
+    /// since there is nothing to compare against yet.
+    /// Returns the number of nodes that are large enough and the number of nodes considered.
+    async fn check_node_capacity(
+        &self,
+        res: &proto::RequestedResources,
+        partition: &str,
+        constraints: &[String],
+    ) -> core::result::Result<(usize, usize), Status> {
+        let nodes = self.nodes.lock().await;
+        let partition = self.partitions.get(partition);
+        let candidates: Vec<&Node> = nodes
+            .values()
+            .filter(|node| node.status != NodeStatus::Draining)
+            .filter(|node| partition.is_none_or(|p| p.contains_node(&node.endpoint)))
+            .collect();
+        if candidates.is_empty() {
+            return Ok((0, 0));
+        }
+        let total = candidates.len();
+
+        let candidates: Vec<&Node> = candidates
+            .into_iter()
+            .filter(|node| node.has_labels(constraints))
+            .collect();
+        if candidates.is_empty() {
+            return Err(Status::invalid_argument(format!(
+                "No node has the labels {}",
+                constraints.join(", ")
+            )));
+        }
+
+        let fitting = candidates
+            .iter()
+            .filter(|node| {
+                let total = &node.avail_resources;
+                res.cpu_count <= total.cpu_count
+                    && res.memory <= total.memory
+                    && res.gpu_count <= total.gpu_count
+            })
+            .count();
+        let needed = res.node_count.max(1) as usize;
+        if fitting >= needed {
+            return Ok((fitting, total));
+        }
+        if fitting > 0 {
+            return Err(Status::invalid_argument(format!(
+                "The job needs {} nodes, but only {} nodes are large enough",
+                needed, fitting
+            )));
+        }
+
+        let max_cpus = candidates.iter().map(|n| n.avail_resources.cpu_count).max();
+        let max_memory = candidates.iter().map(|n| n.avail_resources.memory).max();
+        let max_gpus = candidates.iter().map(|n| n.avail_resources.gpu_count).max();
+        Err(Status::invalid_argument(format!(
+            "No node can run a job with {} CPUs, {} MB memory and {} GPUs \
+             (largest nodes: {} CPUs, {} MB memory, {} GPUs)",
+            res.cpu_count,
+            res.memory / (1024 * 1024),
+            res.gpu_count,
+            max_cpus.unwrap_or_default(),
+            max_memory.unwrap_or_default() / (1024 * 1024),
+            max_gpus.unwrap_or_default(),
+        )))
+    }
+
+    /// Resources of a node that jobs can be assigned, its CPUs and memory scaled by the overcommit factors.
+    ///
+    /// A single job still has to fit into the physical node, overcommitted jobs share its cores.
+    fn capacity(&self, node: &Node) -> NodeResources {
+        let scale = |value: u64, factor: f64| (value as f64 * factor.max(1.0)) as u64;
+        let total = &node.avail_resources;
+        NodeResources::new(
+            scale(total.cpu_count.into(), self.timers.cpu_overcommit_factor)
+                .try_into()
+                .unwrap_or(u32::MAX),
+            scale(total.memory, self.timers.memory_overcommit_factor),
+            total.gpu_count,
+        )
+    }
+
+    /// Estimates when a job that doesn't fit anywhere right now could start.
+    ///
+    /// Assumes that running jobs use up their full time limit. A multi-node job can start once
+    /// enough nodes are free, nodes reserved for other users only after their reservation.
+    /// Returns `None` if the partition doesn't have enough available nodes that are large
+    /// enough to ever run the job.
+    async fn expected_start(
+        &self,
+        res: &RequestedResources,
+        partition: &str,
+        user: &str,
+        constraints: &[String],
+        allocations: &[Allocation],
+        now: u64,
+    ) -> Option<u64> {
+        let reservations = self.reservations.lock().await;
+        let nodes = self.nodes.lock().await;
+        let partition = self.partitions.get(partition);
+
+        let mut starts: Vec<u64> = nodes
+            .iter()
+            .filter(|(_, node)| node.status == NodeStatus::Available)
+            .filter(|(_, node)| partition.is_none_or(|p| p.contains_node(&node.endpoint)))
+            .filter(|(_, node)| node.has_labels(constraints))
+            .filter(|(_, node)| {
+                res.cpu_count <= node.avail_resources.cpu_count
+                    && res.memory <= node.avail_resources.memory
+            })
+            .filter_map(|(node_id, node)| {
+                let capacity = self.capacity(node);
+                let mut free = NodeResources::new(
+                    capacity
+                        .cpu_count
+                        .saturating_sub(node.used_resources.cpu_count),
+                    capacity.memory.saturating_sub(node.used_resources.memory),
+                    capacity
+                        .gpu_count
+                        .saturating_sub(node.used_resources.gpu_count),
+                );
+                let fits = |free: &NodeResources| {
+                    free.cpu_count >= res.cpu_count
+                        && free.memory >= res.memory
+                        && free.gpu_count >= res.gpu_count
+                };
+
+                let mut ending: Vec<&Allocation> = allocations
+                    .iter()
+                    .filter(|a| &a.node_id == node_id)
+                    .collect();
+                ending.sort_by_key(|a| a.end_time);
+
+                let mut start = now;
+                for allocation in ending {
+                    if fits(&free) {
+                        break;
+                    }
+                    free.cpu_count += allocation.res.cpu_count;
+                    free.memory += allocation.res.memory;
+                    free.gpu_count += allocation.res.gpu_count;
+                    start = start.max(allocation.end_time);
+                }
+                while let Some(reservation) = reservations
+                    .iter()
+                    .find(|r| r.blocks(node_id, user, start, end_time(start, res)))
+                {
+                    start = reservation.end_time;
+                }
+                fits(&free).then_some(start)
+            })
+            .collect();
+        starts.sort_unstable();
+        starts.get(res.node_count.max(1) as usize - 1).copied()
+    }
+
+    /// Finds as many available nodes of a partition as a job asks for, each of them
+    /// with room for the given resource requirement and all labels in `constraints`.
+    ///
+    /// Nodes reserved for other users are skipped if the job would run during the reservation.
+    /// Returns `None` unless all of them are free right now. If more nodes fit, the placement
+    /// policy decides, room left is compared by CPUs first, then memory and GPUs.
+    #[tracing::instrument(
+        level = "debug",
+        name = "Find available nodes",
+        skip(self),
+        fields(
+            cpu_count = %res.cpu_count,
+            memory = %res.memory,
+            gpu_count = %res.gpu_count,
+            time = %res.time,
+            node_count = %res.node_count
+        )
+    )]
+    async fn find_available_nodes(
+        &self,
+        res: &RequestedResources,
+        partition: &str,
+        user: &str,
+        constraints: &[String],
+        skipped_nodes: &HashSet<String>,
+    ) -> Option<Vec<String>> {
+        let reservations = self.reservations.lock().await;
+        let nodes = self.nodes.lock().await;
+        let partition = self.partitions.get(partition);
+        let needed = res.node_count.max(1) as usize;
+
+        let placement = self.timers.placement;
+        // fitting nodes with the room they would have left
+        let mut found = Vec::with_capacity(needed);
+
+        for (node_id, node) in nodes.iter() {
+            // log!(info, "Check node_id {}", node_id);
+            if node.status != NodeStatus::Available || skipped_nodes.contains(node_id) {
+                continue;
+            }
+
+            if partition.is_some_and(|p| !p.contains_node(&node.endpoint)) {
+                continue;
+            }
+
+            if !node.has_labels(constraints) {
+                continue;
+            }
+
+            // the job must not run into a reservation of someone else
+            let start = get_current_timestamp();
+            let end = end_time(start, res);
+            if reservations
+                .iter()
+                .any(|r| r.blocks(node_id, user, start, end))
+            {
+                continue;
+            }
+
+            let capacity = self.capacity(node);
+            let available_cpu = capacity
+                .cpu_count
+                .saturating_sub(node.used_resources.cpu_count);
+            let available_memory = capacity.memory.saturating_sub(node.used_resources.memory);
+            let available_gpu = capacity
+                .gpu_count
+                .saturating_sub(node.used_resources.gpu_count);
+
+            // a single job can't use more than the physical node
+            if res.cpu_count <= node.avail_resources.cpu_count
+                && res.memory <= node.avail_resources.memory
+                && available_cpu >= res.cpu_count
+                && available_memory >= res.memory
+                && available_gpu >= res.gpu_count
+            {
+                let left = (
+                    available_cpu - res.cpu_count,
+                    available_memory - res.memory,
+                    available_gpu - res.gpu_count,
+                );
+                found.push((left, node_id));
+                if placement == PlacementPolicy::FirstFit && found.len() == needed {
+                    break;
+                }
+            }
+        }
+        if found.len() < needed {
+            return None;
+        }
+
+        // node ids break ties, so equal nodes are always picked in the same order
+        match placement {
+            PlacementPolicy::FirstFit => {}
+            PlacementPolicy::BestFit => found.sort(),
+            PlacementPolicy::WorstFit => {
+                found.sort_by(|(a, a_id), (b, b_id)| b.cmp(a).then_with(|| a_id.cmp(b_id)))
+            }
+        }
+        Some(
+            found
+                .into_iter()
+                .take(needed)
+                .map(|(_, node_id)| node_id.clone())
+                .collect(),
+        )
+    }
+}
+
+/// Resources held by a running job on a node until its time limit is reached.
+#[derive(Debug)]
+struct Allocation {
+    node_id: String,
+    end_time: u64,
+    res: RequestedResources,
+}
+
+impl Allocation {
+    fn new(node_id: String, start_time: u64, res: &RequestedResources) -> Self {
+        Self {
+            node_id,
+            end_time: end_time(start_time, res),
+            res: *res,
+        }
+    }
+}
+
+/// Returns when a job started at `start_time` reaches its time limit.
+fn end_time(start_time: u64, res: &RequestedResources) -> u64 {
+    start_time + res.time as u64 * 60
+}
+
+/// Resources held by the running jobs of a partition.
+#[derive(Debug, Default)]
+struct PartitionUsage {
+    cpu_count: u32,
+    memory: u64,
+}
+
+impl PartitionUsage {
+    /// Resources a job holds across all of its nodes.
+    fn of(res: &RequestedResources) -> Self {
+        let node_count = res.node_count.max(1);
+        Self {
+            cpu_count: res.cpu_count.saturating_mul(node_count),
+            memory: res.memory.saturating_mul(node_count as u64),
+        }
+    }
+
+    fn add(&mut self, res: &RequestedResources) {
+        let job = Self::of(res);
+        self.cpu_count = self.cpu_count.saturating_add(job.cpu_count);
+        self.memory = self.memory.saturating_add(job.memory);
+    }
+}
+
+/// Nodes and reserved resources of the jobs whose assignment is still outstanding, by job ID.
+type OpenAssignments = Arc<Mutex<HashMap<u64, (Vec<String>, RequestedResources)>>>;
+
+/// A job whose nodes were picked, their resources are reserved until the nodes answered.
+struct Assignment {
+    job: Job,
+    node_ids: Vec<String>,
+}
+
+/// A job that was sent to its nodes, along with their answer.
+struct AssignmentOutcome {
+    job: Job,
+    node_ids: Vec<String>,
+    result: core::result::Result<(), (String, Status)>,
+}
+
+/// Details of a single job, along with the script that was sent with it.
+///
+/// Job lists leave the script out, it can be up to [MAX_SCRIPT_BYTES] large.
+fn job_info(job: &Job) -> proto::Job {
+    let mut response: proto::Job = job.into();
+    response.script_contents = job.script_contents.clone();
+    response
+}
+
+/// Returns the host of a node address, e.g. `10.0.0.5` for `http://10.0.0.5:8082`.
+fn node_host(endpoint: &str) -> String {
+    endpoint
+        .parse::<tonic::transport::Uri>()
+        .ok()
+        .and_then(|uri| {
+            uri.host().map(|host| {
+                host.trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string()
+            })
+        })
+        .unwrap_or_else(|| endpoint.to_string())
+}
+
+/// Frees the resources a job holds on each of its nodes, steps hold none.
+fn free_job_resources(nodes: &mut HashMap<String, Node>, job: &Job) {
+    if job.is_step() {
+        return;
+    }
+    for node_id in job.nodes() {
+        if let Some(node) = nodes.get_mut(node_id) {
+            node.free_avail_resource(&job.req_res);
+        }
+    }
+}
+
+/// How the scheduler reaches its workers.
+#[derive(Clone, Debug)]
+struct WorkerLink {
+    /// The connection and every request on it give up after this
+    timeout: Duration,
+    /// Adds the worker token to every request
+    token: TokenInterceptor,
+}
+
+/// Connects to a worker, the connection and every request on it give up after the timeout.
+///
+/// Connection errors are reported as [tonic::Code::Unavailable].
+async fn connect_worker(
+    endpoint: String,
+    link: &WorkerLink,
+) -> core::result::Result<WorkerClient, Status> {
+    let endpoint = Endpoint::from_shared(endpoint)
+        .map_err(|e| Status::unavailable(format!("Invalid node address: {}", e)))?
+        .timeout(link.timeout);
+    // the HTTP/2 handshake isn't covered by the connect timeout of the endpoint
+    let channel = tokio::time::timeout(link.timeout, endpoint.connect())
+        .await
+        .map_err(|_| Status::unavailable("Could not connect: timed out"))?
+        .map_err(|e| Status::unavailable(format!("Could not connect: {}", e)))?;
+    Ok(MelonWorkerClient::with_interceptor(
+        channel,
+        link.token.clone(),
+    ))
+}
+
+/// Sends the validation job to a node.
+///
+/// Workers register before they start their server, so connecting is retried until the timeout.
+async fn send_validation_job(
+    endpoint: String,
+    assignment: proto::JobAssignment,
+    link: &WorkerLink,
+) -> core::result::Result<(), Status> {
+    let deadline = Instant::now() + link.timeout;
+    let mut client = loop {
+        match connect_worker(endpoint.clone(), link).await {
+            Ok(client) => break client,
+            Err(_) if Instant::now() < deadline => {
+                tokio::time::sleep(VALIDATION_RETRY_INTERVAL).await
+            }
+            Err(status) => return Err(status),
+        }
+    };
+    client.assign_job(tonic::Request::new(assignment)).await?;
+    Ok(())
+}
+
+/// Remembers that a node failed its validation, so it stays offline after a restart.
+fn store_rejection(db: &DatabaseHandler, node_id: &str) {
+    if let Err(e) = db.reject_node(node_id) {
+        log!(
+            error,
+            "Could not store rejection of node {}: {}",
+            node_id,
+            e
+        );
+    }
+}
+
+/// Takes a node whose validation failed offline, heartbeats don't bring it back.
+fn reject_node(
+    nodes: &mut HashMap<String, Node>,
+    failed_nodes: &mut HashSet<String>,
+    node_id: &str,
+    reason: &str,
+) {
+    log!(
+        warn,
+        "Validation of node {} failed, mark as offline: {}",
+        node_id,
+        reason
+    );
+    if let Some(node) = nodes.get_mut(node_id) {
+        node.set_status(NodeStatus::Offline);
+    }
+    failed_nodes.insert(node_id.to_string());
+}
+
+/// Sends a job to all of its nodes, together with the list of their hosts.
+///
+/// A multi-node job only starts if every node accepts it, the nodes that already
+/// accepted it are told to stop it again otherwise. Returns the node that refused
+/// the job along with its error. The resources of the job are reserved on its nodes
+/// beforehand, so they are only read here.
+async fn assign_job_to_nodes(
+    nodes: &Mutex<HashMap<String, Node>>,
+    node_ids: &[String],
+    job: &mut Job,
+    link: &WorkerLink,
+) -> core::result::Result<(), (String, Status)> {
+    let mut assignment = proto::JobAssignment::from(&mut *job);
+    let hosts = {
+        let nodes = nodes.lock().await;
+        node_ids
+            .iter()
+            .filter_map(|node_id| nodes.get(node_id))
+            .map(|node| node_host(&node.endpoint))
+            .collect::<Vec<_>>()
+    };
+    assignment
+        .env
+        .insert(NODELIST_ENV.to_string(), hosts.join(","));
+
+    for (index, node_id) in node_ids.iter().enumerate() {
+        if let Err(status) = assign_job_to_node(nodes, node_id, assignment.clone(), link).await {
+            let accepted = &node_ids[..index];
+            stop_job_on_nodes(&*nodes.lock().await, job, accepted, link);
+            return Err((node_id.clone(), status));
+        }
+    }
+    Ok(())
+}
+
+/// Sends a job to a node.
+///
+/// Connection errors are reported as [tonic::Code::Unavailable].
+async fn assign_job_to_node(
+    nodes: &Mutex<HashMap<String, Node>>,
+    node_id: &str,
+    assignment: proto::JobAssignment,
+    link: &WorkerLink,
+) -> core::result::Result<(), Status> {
+    // the nodes are only locked for the lookup, not while the node answers
+    let endpoint = {
+        let nodes = nodes.lock().await;
+        let node = nodes
+            .get(node_id)
+            .ok_or_else(|| Status::not_found("Node not found"))?;
+        node.endpoint.clone()
+    };
+
+    let mut client = connect_worker(endpoint, link).await?;
+    client.assign_job(tonic::Request::new(assignment)).await?;
+    Ok(())
+}
+
+/// Gives back the resources that were reserved for a job on its nodes.
+fn release_nodes(nodes: &mut HashMap<String, Node>, node_ids: &[String], res: &RequestedResources) {
+    for node_id in node_ids {
+        if let Some(node) = nodes.get_mut(node_id) {
+            node.free_avail_resource(res);
+        }
+    }
+}
+
+/// Tells nodes to stop their part of a job.
+///
+/// The requests are sent in the background, so an unreachable node doesn't hold up
+/// the caller. A node whose part has already ended just doesn't know the job anymore.
+fn stop_job_on_nodes(
+    nodes: &HashMap<String, Node>,
+    job: &Job,
+    node_ids: &[String],
+    link: &WorkerLink,
+) {
+    for node_id in node_ids {
+        let Some(node) = nodes.get(node_id) else {
+            continue;
+        };
+        let endpoint = node.endpoint.clone();
+        let node_id = node_id.clone();
+        let (request_job_id, link) = (job.id, link.clone());
+        let request = proto::CancelJobRequest {
+            job_id: job.id,
+            user: job.user.clone(),
+        };
+        tokio::spawn(async move {
+            let result = match connect_worker(endpoint, &link).await {
+                Ok(mut client) => client.cancel_job(request).await.map(|_| ()),
+                Err(status) => Err(status),
+            };
+            if let Err(status) = result {
+                log!(
+                    debug,
+                    "Could not stop job {} on node {}: {}",
+                    request_job_id,
+                    node_id,
+                    status.message()
+                );
+            }
+        });
+    }
+}
+
+/// Readiness of a pending job with respect to its dependencies.
+#[derive(Debug, PartialEq)]
+enum DependencyState {
+    /// All dependencies completed successfully
+    Satisfied,
+    /// At least one dependency has not finished yet
+    Waiting,
+    /// At least one dependency failed, timed out or was cancelled
+    Failed,
+}
+
+/// Inserts a job into the pending queue according to its priority.
+///
+/// Higher priorities are placed first. Jobs with equal priority are ordered by
+/// their submit time (and job id), which keeps the assignment order deterministic.
+fn enqueue_pending_job(pending_jobs: &mut VecDeque<Job>, job: Job) {
+    let pos = pending_jobs.partition_point(|other| {
+        (
+            other.priority,
+            std::cmp::Reverse(other.submit_time),
+            std::cmp::Reverse(other.id),
+        ) >= (
+            job.priority,
+            std::cmp::Reverse(job.submit_time),
+            std::cmp::Reverse(job.id),
+        )
+    });
+    pending_jobs.insert(pos, job);
+}
+
+/// What the checks of a submission found out about it.
+struct CheckedSubmission {
+    partition: String,
+    warn_signal: Option<WarnSignal>,
+    /// Nodes the job fits on and registered nodes, `None` if the check was skipped
+    capacity: Option<(usize, usize)>,
+}
+
+/// Rejects scripts sent along with a submission that are too large to be kept with the job.
+#[allow(clippy::result_large_err)] // handed to the client as is
+fn check_script_size(sub: &proto::JobSubmission) -> core::result::Result<(), Status> {
+    match &sub.script_contents {
+        Some(script) if script.len() > MAX_SCRIPT_BYTES => Err(Status::invalid_argument(format!(
+            "Script of {} bytes is too large to send along, the limit is {}",
+            script.len(),
+            MAX_SCRIPT_BYTES
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Turns a checked submission into a pending job.
+fn job_from_submission(
+    sub: &proto::JobSubmission,
+    job_id: u64,
+    user: String,
+    checked: CheckedSubmission,
+) -> Job {
+    let res = sub.req_res.expect("No resources given");
+    let mut new_job = Job::new(
+        job_id,
+        user,
+        sub.script_path.clone(),
+        sub.script_args.clone(),
+        res.into(),
+    );
+    new_job.priority = sub
+        .priority
+        .map(|p| p.min(u8::MAX as u32) as u8)
+        .unwrap_or(DEFAULT_PRIORITY);
+    new_job.dependencies = sub.dependencies.clone();
+    new_job.partition = checked.partition;
+    new_job.constraints = sub.constraints.clone();
+    new_job.env = sub.env.clone();
+    new_job.work_dir = sub.work_dir.clone();
+    new_job.name = sub.name.clone();
+    new_job.grace_secs = sub.grace_secs;
+    new_job.begin_deadline_mins = sub.begin_deadline_mins;
+    new_job.warn_signal = checked.warn_signal;
+    new_job.output_path = sub.output_path.clone();
+    new_job.error_path = sub.error_path.clone();
+    new_job.script_contents = sub.script_contents.clone();
+    new_job.max_retries = sub.max_retries;
+    if sub.interactive {
+        // nobody would be attached to a retry
+        new_job.max_retries = 0;
+        new_job.interactive = true;
+        new_job.attach_token = Some(nanoid!(32));
+    }
+    new_job
+}
+
+#[tonic::async_trait]
+impl MelonScheduler for Scheduler {
+    #[tracing::instrument(level="debug", name = "Receive job submission", skip(self), fields(script_path = %request.get_ref().script_path))]
+    async fn submit_job(
+        &self,
+        request: tonic::Request<proto::JobSubmission>,
+    ) -> core::result::Result<tonic::Response<proto::MasterJobResponse>, tonic::Status> {
+        log!(debug, "get job sub request");
+        // the hook runs before any lock is taken, it may take a while
+        let admitted = self.run_admission_hook(request.get_ref()).await?;
+        let sub = admitted.as_ref().unwrap_or(request.get_ref());
+        if let Some(parent_id) = sub.parent_job_id {
+            let user = request_user(&request, &sub.user);
+            let response = self.launch_step(parent_id, sub, user).await?;
+            return Ok(tonic::Response::new(response));
+        }
+
+        let pending_jobs = self.pending_jobs.clone();
+        let mut pending_jobs = pending_jobs.lock().await;
+        self.check_queue_space(pending_jobs.len(), 1)?;
+        let checked = self.check_submission(sub, &pending_jobs).await?;
+
+        if sub.dry_run {
+            let res = sub.req_res.expect("No resources given");
+            let nodes = match checked.capacity {
+                None => "node capacity not checked".to_string(),
+                Some((_, 0)) => "no nodes registered yet".to_string(),
+                Some((fitting, total)) => format!("fits on {} of {} nodes", fitting, total),
+            };
+            let node_count = if res.node_count > 1 {
+                format!(" on each of {} nodes", res.node_count)
+            } else {
+                String::new()
+            };
+            let summary = format!(
+                "Job would be accepted: {} CPUs, {} MB memory, {} GPUs{} for {} minutes in partition {}, {}",
+                res.cpu_count,
+                res.memory / (1024 * 1024),
+                res.gpu_count,
+                node_count,
+                res.time,
+                checked.partition,
+                nodes
+            );
+            return Ok(tonic::Response::new(proto::MasterJobResponse {
+                job_id: 0,
+                summary,
+                attach_token: String::new(),
+            }));
+        }
+
+        let user = request_user(&request, &sub.user);
+        self.check_submission_rate(&user, 1).await?;
+
+        // create new job
+        let job_id = self
+            .job_ctr
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let new_job = job_from_submission(sub, job_id, user, checked);
+        let attach_token = new_job.attach_token.clone().unwrap_or_default();
+
+        // push job to pending jobs queue
+        self.publish_event(JobEvent::new(&new_job));
+        enqueue_pending_job(&mut pending_jobs, new_job);
+
+        // return created job id
+        let response = proto::MasterJobResponse {
+            job_id,
+            summary: String::new(),
+            attach_token,
+        };
+        log!(debug, "Queued job {}", job_id);
+        Ok(tonic::Response::new(response))
+    }
+
+    #[tracing::instrument(level="debug", name = "Receive job batch", skip(self), fields(jobs = request.get_ref().submissions.len()))]
+    async fn submit_jobs(
+        &self,
+        request: tonic::Request<proto::JobBatch>,
+    ) -> core::result::Result<tonic::Response<proto::JobBatchResponse>, tonic::Status> {
+        let mut admitted = Vec::new();
+        for sub in &request.get_ref().submissions {
+            admitted.extend(self.run_admission_hook(sub).await?);
+        }
+        let subs = if self.timers.admission_hook.is_some() {
+            &admitted
+        } else {
+            &request.get_ref().submissions
+        };
+        if subs
+            .iter()
+            .any(|sub| sub.dry_run || sub.interactive || sub.parent_job_id.is_some())
+        {
+            return Err(Status::invalid_argument(
+                "Dry runs, interactive jobs and steps can't be submitted in a batch",
+            ));
+        }
+
+        let pending_jobs = self.pending_jobs.clone();
+        let mut pending_jobs = pending_jobs.lock().await;
+        self.check_queue_space(pending_jobs.len(), subs.len())?;
+        // nothing is queued unless every submission is accepted
+        let mut checked = Vec::with_capacity(subs.len());
+        for sub in subs {
+            checked.push(self.check_submission(sub, &pending_jobs).await?);
+        }
+        let users: Vec<String> = subs
+            .iter()
+            .map(|sub| request_user(&request, &sub.user))
+            .collect();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for user in &users {
+            *counts.entry(user).or_default() += 1;
+        }
+        for (user, count) in counts {
+            self.check_submission_rate(user, count).await?;
+        }
+
+        // the batch gets a contiguous range of ids
+        let first_id = self
+            .job_ctr
+            .fetch_add(subs.len() as u64, std::sync::atomic::Ordering::SeqCst);
+        let mut responses = Vec::with_capacity(subs.len());
+        for (((sub, checked), user), job_id) in subs.iter().zip(checked).zip(users).zip(first_id..)
+        {
+            let new_job = job_from_submission(sub, job_id, user, checked);
+            self.publish_event(JobEvent::new(&new_job));
+            enqueue_pending_job(&mut pending_jobs, new_job);
+            responses.push(proto::MasterJobResponse {
+                job_id,
+                summary: String::new(),
+                attach_token: String::new(),
+            });
+        }
+        log!(
+            debug,
+            "Queued {} jobs starting at {}",
+            responses.len(),
+            first_id
+        );
+        Ok(tonic::Response::new(proto::JobBatchResponse { responses }))
+    }
+
+    /// Register a new node in a master.
+    #[tracing::instrument(level="info", name = "Register new compute node", skip(self, request), fields(address = %request.get_ref().address))]
+    async fn register_node(
+        &self,
+        request: tonic::Request<proto::NodeInfo>,
+    ) -> core::result::Result<tonic::Response<proto::RegistrationResponse>, tonic::Status> {
+        let req = request.get_ref();
+
+        // the address is used to send jobs to the node, so it has to be reachable from here
+        tonic::transport::Endpoint::from_shared(req.address.clone()).map_err(|_| {
+            Status::invalid_argument(format!("Invalid node address {}", req.address))
+        })?;
+
+        let resources = req.resources.unwrap();
+        let resources = melon_common::NodeResources::new(
+            resources.cpu_count,
+            resources.memory,
+            resources.gpu_count,
+        );
+
+        let mut pending_jobs = self.pending_jobs.lock().await;
+        let mut running_jobs = self.running_jobs.lock().await;
+        let mut nodes = self.nodes.lock().await;
+
+        // a worker that registers again has restarted, replace its old entry
+        let existing = nodes
+            .values()
+            .find(|node| node.endpoint == req.address)
+            .map(|node| (node.id.clone(), node.status.clone()));
+        let (id, status) = match existing {
+            Some((id, status)) => {
+                log!(info, "Node {} registered again at {}", id, req.address);
+                let lost_jobs: Vec<u64> = running_jobs
+                    .values()
+                    .filter(|job| job.runs_on(&id))
+                    .map(|job| job.id)
+                    .collect();
+                for job_id in lost_jobs {
+                    let job = running_jobs.remove(&job_id).expect("Job should exist");
+                    let reason = format!("Node {} restarted", id);
+                    self.recover_lost_job(&mut pending_jobs, &mut nodes, job, &id, &reason)
+                        .await;
+                }
+
+                // a draining node stays draining
+                let status = match status {
+                    NodeStatus::Draining => NodeStatus::Draining,
+                    _ => NodeStatus::Available,
+                };
+                (id, status)
+            }
+            None => (nanoid!(), NodeStatus::Available),
+        };
+        // a draining node gets no jobs anyway
+        let validate = self.timers.validate_on_register && status == NodeStatus::Available;
+        let status = if validate {
+            NodeStatus::Validating
+        } else {
+            status
+        };
+        self.failed_nodes.lock().await.remove(&id);
+
+        let node = Node::new(id.clone(), req.address.clone(), resources, status)
+            .with_labels(req.labels.clone());
+        nodes.insert(id.clone(), node.clone());
+        drop(nodes);
+        drop(running_jobs);
+        drop(pending_jobs);
+
+        // the node still works without, it only has to register again after a restart
+        if let Err(e) = self.db.store_node(&node) {
+            log!(error, "Could not store node {}: {}", id, e);
+        }
+        if validate {
+            self.start_validation(id.clone(), req.address.clone());
+        }
+
+        let res = proto::RegistrationResponse { node_id: id };
+        Ok(tonic::Response::new(res))
+    }
+
+    #[tracing::instrument(level="debug", name = "Receive heartbeat", skip(self, request), fields(node_id = %request.get_ref().node_id))]
+    async fn send_heartbeat(
+        &self,
+        request: tonic::Request<proto::Heartbeat>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let mut pending_jobs = self.pending_jobs.lock().await;
+        let mut running_jobs = self.running_jobs.lock().await;
+        let mut nodes = self.nodes.lock().await;
+        let req = request.get_ref();
+        let node_id = &req.node_id;
+
+        let Some(node) = nodes.get_mut(node_id) else {
+            // compute node is not registered => reject
+            return Err(tonic::Status::unauthenticated("Node is not registered"));
+        };
+
+        // compute node is registered, a draining node stays draining
+        if node.status == NodeStatus::Offline && !self.failed_nodes.lock().await.contains(node_id) {
+            node.set_status(NodeStatus::Available);
+        }
+        node.update_heartbeat();
+
+        if let Some(usage) = &req.usage {
+            node.max_jobs = usage.max_jobs;
+
+            // jobs that were just assigned may be missing from a heartbeat sent in the meantime,
+            // the other nodes of a multi-node job may have finished their part already
+            let reported: HashSet<u64> = usage.running_job_ids.iter().copied().collect();
+            let now = get_current_timestamp();
+            let lost_jobs: Vec<u64> = running_jobs
+                .values()
+                .filter(|job| {
+                    job.assigned_node.as_ref() == Some(node_id)
+                        && !reported.contains(&job.id)
+                        && job.start_time.is_some_and(|start| {
+                            now.saturating_sub(start) >= self.timers.lost_job_grace_secs
+                        })
+                })
+                .map(|job| job.id)
+                .collect();
+            for job_id in lost_jobs {
+                let job = running_jobs.remove(&job_id).expect("Job should exist");
+                let reason = format!("Node {} no longer runs the job", node_id);
+                self.recover_lost_job(&mut pending_jobs, &mut nodes, job, node_id, &reason)
+                    .await;
+            }
+
+            // rebuild the node's usage from the jobs that are still running on it
+            let node = nodes.get_mut(node_id).expect("Node should exist");
+            node.used_resources = NodeResources::empty();
+            for job in running_jobs
+                .values()
+                .filter(|job| job.runs_on(node_id) && !job.is_step())
+            {
+                node.reduce_avail_resources(&job.req_res);
+            }
+            // the node may not know about the jobs that are sent to it right now
+            for (node_ids, res) in self.assigning.lock().await.values() {
+                if node_ids.contains(node_id) {
+                    node.reduce_avail_resources(res);
+                }
+            }
+            if usage.allocated_cpus != node.used_resources.cpu_count {
+                log!(
+                    debug,
+                    "Node {} has {} cores allocated, expected {}",
+                    node_id,
+                    usage.allocated_cpus,
+                    node.used_resources.cpu_count
+                );
+            }
+        }
+
+        let res = tonic::Response::new(());
+        Ok(res)
+    }
+
+    #[tracing::instrument(level = "info", name = "Receive job results", skip(self, request))]
+    async fn submit_job_result(
+        &self,
+        request: tonic::Request<proto::JobResult>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let req = request.get_ref();
+        let result: JobResult = req.into();
+
+        let job_id = result.id;
+        let validated_node = self.validations.lock().await.remove(&job_id);
+        if let Some(node_id) = validated_node {
+            let mut nodes = self.nodes.lock().await;
+            if result.status == JobStatus::Completed {
+                if let Some(node) = nodes
+                    .get_mut(&node_id)
+                    .filter(|node| node.status == NodeStatus::Validating)
+                {
+                    log!(info, "Node {} passed its validation", node_id);
+                    node.set_status(NodeStatus::Available);
+                }
+            } else {
+                let status: String = result.status.into();
+                let reason = result
+                    .message
+                    .unwrap_or_else(|| format!("Validation job ended as {}", status));
+                let mut failed_nodes = self.failed_nodes.lock().await;
+                reject_node(&mut nodes, &mut failed_nodes, &node_id, &reason);
+                drop(failed_nodes);
+                drop(nodes);
+                store_rejection(&self.db, &node_id);
+            }
+            return Ok(tonic::Response::new(()));
+        }
+
+        let mut pending_jobs = self.pending_jobs.lock().await;
+        let mut jobs = self.running_jobs.lock().await;
+        if let Some(job) = jobs.get(&result.id) {
+            // e.g. a job that an admin requeued in the meantime
+            if !req.node_id.is_empty() && !job.runs_on(&req.node_id) {
+                return Err(tonic::Status::not_found("Job is not running on this node"));
+            }
+
+            // the first node decides how a multi-node job ends, the others only if they fail,
+            // until then a node keeps the resources of its finished part
+            if result.status == JobStatus::Completed && job.extra_nodes.contains(&req.node_id) {
+                log!(
+                    debug,
+                    "Node {} finished its part of job {}",
+                    req.node_id,
+                    job_id
+                );
+                return Ok(tonic::Response::new(()));
+            }
+
+            // free up resources from the compute nodes and stop the rest of the job
+            let mut nodes = self.nodes.lock().await;
+            free_job_resources(&mut nodes, job);
+            if !job.extra_nodes.is_empty() {
+                let others: Vec<String> = job
+                    .nodes()
+                    .filter(|node_id| **node_id != req.node_id)
+                    .cloned()
+                    .collect();
+                stop_job_on_nodes(&nodes, job, &others, &self.worker_link());
+            }
+
+            // remove job from tracking map
+            let mut job = jobs.remove(&job_id).unwrap();
+            job.max_rss_bytes = result.max_rss_bytes;
+            job.cpu_seconds = result.cpu_seconds;
+            job.exit_code = result.exit_code;
+            job.message = result.message;
+
+            if result.status == JobStatus::Failed && job.can_retry() {
+                log!(
+                    info,
+                    "Job {} failed, requeue attempt {}/{}",
+                    job_id,
+                    job.retry_count + 2,
+                    job.max_retries + 1
+                );
+                job.requeue(JobStatus::Failed);
+                self.publish_event(JobEvent::new(&job));
+                enqueue_pending_job(&mut pending_jobs, job);
+            } else {
+                // send the finished job to the database writer for permanent storage
+                self.archive_job(job, result.status).await;
+            }
+
+            // ack
+            let res = tonic::Response::new(());
+            Ok(res)
+        } else if self.assigning.lock().await.contains_key(&job_id) {
+            // the job ended before the scheduler learned that it was accepted, the worker retries
+            Err(tonic::Status::unavailable("Job is still being assigned"))
+        } else if pending_jobs.iter().any(|job| job.id == job_id) {
+            // a failed job that was requeued when its result first arrived
+            log!(debug, "Ignore repeated result of requeued job {}", job_id);
+            Ok(tonic::Response::new(()))
+        } else {
+            // workers retry results whose ack got lost, the job is already stored then
+            match self.db.get_job_opt(job_id) {
+                Ok(Some(job)) if job.status.is_finished() => {
+                    log!(debug, "Ignore repeated result of finished job {}", job_id);
+                    Ok(tonic::Response::new(()))
+                }
+                Ok(_) => Err(tonic::Status::not_found("Job not found")),
+                Err(e) => {
+                    log!(error, "Could not look up job {} in database: {}", job_id, e);
+                    Err(tonic::Status::internal("Failed to look up finished job"))
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "debug", name = "List all jobs", skip(self, request))]
+    async fn list_jobs(
+        &self,
+        request: tonic::Request<proto::ListJobsRequest>,
+    ) -> core::result::Result<tonic::Response<proto::JobListResponse>, tonic::Status> {
+        let req = request.get_ref();
+        let filter = JobFilter {
+            user: None,
+            submitted_since: req.submitted_since,
+            submitted_until: req.submitted_until,
+        };
+        let pending_jobs = self.pending_jobs.lock().await;
+        let running_jobs = self.running_jobs.lock().await;
+
+        // Accumulate pending and running jobs
+        let mut jobs: Vec<proto::Job> = pending_jobs
+            .iter()
+            .chain(running_jobs.values())
+            .filter(|job| filter.matches(job))
+            .map(|j| j.into())
+            .collect();
+
+        // Fetch finished jobs from the database
+        match self.db.get_jobs(&filter) {
+            Ok(finished_jobs) => {
+                if finished_jobs.skipped > 0 {
+                    log!(
+                        warn,
+                        "Skipped {} unreadable jobs in the database",
+                        finished_jobs.skipped
+                    );
+                }
+                jobs.extend(finished_jobs.jobs.iter().map(|j| j.into()));
+            }
+            Err(e) => {
+                log!(error, "Error fetching finished jobs from database: {}", e);
+                return Err(tonic::Status::internal("Failed to fetch finished jobs"));
+            }
+        }
+
+        let response = proto::JobListResponse { jobs };
+        let response = tonic::Response::new(response);
+        Ok(response)
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "List jobs of user",
+        skip(self, request),
+        fields(user = %request.get_ref().user)
+    )]
+    async fn list_user_jobs(
+        &self,
+        request: tonic::Request<proto::ListUserJobsRequest>,
+    ) -> core::result::Result<tonic::Response<proto::JobListResponse>, tonic::Status> {
+        let req = request.get_ref();
+        let filter = JobFilter {
+            user: Some(request_user(&request, &req.user)),
+            submitted_since: req.submitted_since,
+            submitted_until: req.submitted_until,
+        };
+        let pending_jobs = self.pending_jobs.lock().await;
+        let running_jobs = self.running_jobs.lock().await;
+
+        let mut jobs: Vec<proto::Job> = pending_jobs
+            .iter()
+            .chain(running_jobs.values())
+            .filter(|job| filter.matches(job))
+            .map(|j| j.into())
+            .collect();
+
+        match self.db.get_jobs(&filter) {
+            Ok(finished_jobs) => {
+                if finished_jobs.skipped > 0 {
+                    log!(
+                        warn,
+                        "Skipped {} unreadable jobs in the database",
+                        finished_jobs.skipped
+                    );
+                }
+                jobs.extend(finished_jobs.jobs.iter().map(|j| j.into()));
+            }
+            Err(e) => {
+                log!(error, "Error fetching finished jobs from database: {}", e);
+                return Err(tonic::Status::internal("Failed to fetch finished jobs"));
+            }
+        }
+
+        Ok(tonic::Response::new(proto::JobListResponse { jobs }))
+    }
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Receive cancellation request",
+        skip(self, request),
+        fields(job_id = %request.get_ref().job_id, user=%request.get_ref().user)
+    )]
+    async fn cancel_job(
+        &self,
+        request: tonic::Request<proto::CancelJobRequest>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let req = request.get_ref();
+        let id = req.job_id;
+        let user = request_user(&request, &req.user);
+
+        // check in pending jobs
+        let mut pending_jobs = self.pending_jobs.lock().await;
+        if let Some(pos) = pending_jobs.iter().position(|job| job.id == id) {
+            if pending_jobs[pos].user != user {
+                return Err(Status::permission_denied(
+                    "Not authorized to cancel this job",
+                ));
+            }
+            let job = pending_jobs.remove(pos).expect("exists for sure");
+            self.archive_job(job, JobStatus::Cancelled).await;
+            return Ok(tonic::Response::new(()));
+        }
+
+        // check in running jobs
+        let endpoint = {
+            let running_jobs = self.running_jobs.lock().await;
+            let Some(job) = running_jobs.get(&id) else {
+                // no job found
+                return Err(Status::not_found("Job not found"));
+            };
+            if job.user != user {
+                return Err(Status::permission_denied(
+                    "Not authorized to cancel this job",
+                ));
+            }
+            let nodes = self.nodes.lock().await;
+            job.assigned_node
+                .as_ref()
+                .and_then(|node_id| nodes.get(node_id))
+                .map(|node| node.endpoint.clone())
+        };
+        // a slow node must not hold up the rest of the scheduler
+        drop(pending_jobs);
+
+        // send the cancellation request to the assigned node
+        if let Some(endpoint) = endpoint {
+            let mut client = connect_worker(endpoint, &self.worker_link()).await?;
+            let worker_request = proto::CancelJobRequest {
+                job_id: id,
+                user: user.clone(),
+            };
+            client.cancel_job(worker_request).await?;
+        }
+
+        // the job may have ended in the meantime
+        let mut running_jobs = self.running_jobs.lock().await;
+        if let Some(job) = running_jobs.remove(&id) {
+            // free up the node resources to mark availability
+            let mut nodes = self.nodes.lock().await;
+            free_job_resources(&mut nodes, &job);
+            stop_job_on_nodes(&nodes, &job, &job.extra_nodes, &self.worker_link());
+            drop(nodes);
+
+            self.archive_job(job, JobStatus::Cancelled).await;
+        }
+        Ok(tonic::Response::new(()))
+    }
+
+    #[tracing::instrument(
         level = "info",
         name = "Receive time extension request",
         skip(self, request),
@@ -515,7 +2493,7 @@ impl MelonScheduler for Scheduler {
     ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
         let req = request.get_ref();
         let id = req.job_id;
-        let user = req.user.clone();
+        let user = request_user(&request, &req.user);
         let time_in_mins = req.extension_mins;
 
         // first check the pending jobs
@@ -529,41 +2507,91 @@ impl MelonScheduler for Scheduler {
 
             // adjust the deadline
             let job = pending_jobs.get_mut(pos).expect("exists for sure");
+            self.check_max_job_time(job.req_res.time.saturating_add(time_in_mins))?;
             job.req_res.time += time_in_mins;
+            self.publish_event(JobEvent::extension(job, time_in_mins));
 
             return Ok(tonic::Response::new(()));
         }
 
         // check running jobs
-        let mut running_jobs = self.running_jobs.lock().await;
-        if let Some(job) = running_jobs.get_mut(&id) {
+        let endpoints: Vec<String> = {
+            let running_jobs = self.running_jobs.lock().await;
+            let Some(job) = running_jobs.get(&id) else {
+                return Err(tonic::Status::not_found("Couldn't find job id"));
+            };
             if job.user != user {
                 return Err(Status::permission_denied(
                     "Not authorized to cancel this job",
                 ));
             }
 
-            let node = &job.assigned_node.clone().unwrap();
-            let mut nodes = self.nodes.lock().await;
-            if let Some(node) = nodes.get_mut(node) {
-                let mut client = MelonWorkerClient::connect(node.endpoint.clone())
-                    .await
-                    .map_err(|e| Status::unknown(format!("Error connecting to node: {}", e)))?;
-                let worker_request = proto::ExtendJobRequest {
-                    job_id: req.job_id,
-                    user: user.clone(),
-                    extension_mins: req.extension_mins,
-                };
-                client.extend_job(worker_request).await?;
+            self.check_max_job_time(job.req_res.time.saturating_add(time_in_mins))?;
 
-                // adjust the job resources
-                job.extend_time(time_in_mins);
+            // every node of a multi-node job enforces the time limit on its own
+            let nodes = self.nodes.lock().await;
+            job.nodes()
+                .filter_map(|node_id| nodes.get(node_id))
+                .map(|node| node.endpoint.clone())
+                .collect()
+        };
+        // a slow node must not hold up the rest of the scheduler
+        drop(pending_jobs);
+        if endpoints.is_empty() {
+            return Err(tonic::Status::not_found("Couldn't find job id"));
+        }
 
-                return Ok(tonic::Response::new(()));
-            }
+        for endpoint in endpoints {
+            let mut client = connect_worker(endpoint, &self.worker_link()).await?;
+            let worker_request = proto::ExtendJobRequest {
+                job_id: req.job_id,
+                user: user.clone(),
+                extension_mins: req.extension_mins,
+            };
+            client.extend_job(worker_request).await?;
         }
 
-        Err(tonic::Status::not_found("Couldn't find job id"))
+        // adjust the job resources, unless the job ended in the meantime
+        let mut running_jobs = self.running_jobs.lock().await;
+        let job = running_jobs
+            .get_mut(&id)
+            .ok_or_else(|| tonic::Status::not_found("Couldn't find job id"))?;
+        job.extend_time(time_in_mins);
+        self.publish_event(JobEvent::extension(job, time_in_mins));
+
+        Ok(tonic::Response::new(()))
+    }
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Receive hold request",
+        skip(self, request),
+        fields(job_id = %request.get_ref().job_id, user=%request.get_ref().user)
+    )]
+    async fn hold_job(
+        &self,
+        request: tonic::Request<proto::HoldJobRequest>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let req = request.get_ref();
+        let user = request_user(&request, &req.user);
+        self.set_job_held(req.job_id, &user, true).await?;
+        Ok(tonic::Response::new(()))
+    }
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Receive release request",
+        skip(self, request),
+        fields(job_id = %request.get_ref().job_id, user=%request.get_ref().user)
+    )]
+    async fn release_job(
+        &self,
+        request: tonic::Request<proto::ReleaseJobRequest>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let req = request.get_ref();
+        let user = request_user(&request, &req.user);
+        self.set_job_held(req.job_id, &user, false).await?;
+        Ok(tonic::Response::new(()))
     }
 
     #[tracing::instrument(
@@ -579,26 +2607,36 @@ impl MelonScheduler for Scheduler {
         let req = request.get_ref();
         let id = req.job_id;
 
-        // check in running jobs => O(1)
+        // same lock order as the scheduling loop, which holds the pending jobs the longest
+        let pending_jobs = self.pending_jobs.lock().await;
         let running_jobs = self.running_jobs.lock().await;
+
+        // check in running jobs => O(1)
         if let Some(job) = running_jobs.get(&id) {
             log!(debug, "Found job with id {} in running jobs", id);
-            return Ok(tonic::Response::new(job.into()));
+            return Ok(tonic::Response::new(job_info(job)));
         }
 
         // check in pending jobs
-        let pending_jobs = self.pending_jobs.lock().await;
         if let Some(pos) = pending_jobs.iter().position(|job| job.id == id) {
             log!(debug, "Found job with id {} in pending jobs", id);
             let job = pending_jobs.get(pos).expect("exists for sure");
-            return Ok(tonic::Response::new(job.into()));
+            let mut response = job_info(job);
+            // the queue is kept in scheduling order, held jobs wait outside of it
+            if job.status == JobStatus::Pending {
+                let waiting = |job: &&Job| job.status == JobStatus::Pending;
+                let ahead = pending_jobs.iter().take(pos).filter(waiting).count();
+                response.queue_position = Some(ahead as u32 + 1);
+                response.queue_length = Some(pending_jobs.iter().filter(waiting).count() as u32);
+            }
+            return Ok(tonic::Response::new(response));
         }
 
         // check finished jobs in database
         match self.db.get_job_opt(id) {
             Ok(Some(job)) => {
                 log!(debug, "Found job with id {} in database", id);
-                Ok(tonic::Response::new((&job).into()))
+                Ok(tonic::Response::new(job_info(&job)))
             }
             Ok(None) => {
                 log!(debug, "Could not find job with id {} anywhere", id);
@@ -615,4 +2653,343 @@ impl MelonScheduler for Scheduler {
             }
         }
     }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "Get job status",
+        skip(self, request),
+        fields(job_id = %request.get_ref().job_id)
+    )]
+    async fn get_job_status(
+        &self,
+        request: tonic::Request<proto::JobStatusRequest>,
+    ) -> core::result::Result<tonic::Response<proto::JobStatusResponse>, tonic::Status> {
+        let id = request.get_ref().job_id;
+
+        let status = {
+            let pending_jobs = self.pending_jobs.lock().await;
+            let running_jobs = self.running_jobs.lock().await;
+            running_jobs
+                .get(&id)
+                .or_else(|| pending_jobs.iter().find(|job| job.id == id))
+                .map(|job| (job.status.clone(), None))
+        };
+        // only finished jobs are in the database, it is read without holding the queues
+        let status = match status {
+            Some(status) => Some(status),
+            None => self.db.get_job_status(id).map_err(|e| {
+                log!(error, "Could not read the status of job {}: {}", id, e);
+                tonic::Status::unknown(format!("Unexpected Error {}", e))
+            })?,
+        };
+
+        match status {
+            Some((status, exit_code)) => Ok(tonic::Response::new(proto::JobStatusResponse {
+                status: status.into(),
+                exit_code,
+            })),
+            None => Err(tonic::Status::not_found(format!("Job ID not found {}", id))),
+        }
+    }
+
+    type GetJobOutputStream = tonic::Streaming<proto::JobOutput>;
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Get job output",
+        skip(self, request),
+        fields(job_id = %request.get_ref().job_id)
+    )]
+    async fn get_job_output(
+        &self,
+        request: tonic::Request<proto::JobOutputRequest>,
+    ) -> core::result::Result<tonic::Response<Self::GetJobOutputStream>, tonic::Status> {
+        let req = request.into_inner();
+        let id = req.job_id;
+
+        // find the node that is working or has worked on the job
+        let assigned_node = {
+            let running_jobs = self.running_jobs.lock().await;
+            running_jobs.get(&id).map(|job| job.assigned_node.clone())
+        };
+        let assigned_node = match assigned_node {
+            Some(node) => node,
+            None => {
+                let pending_jobs = self.pending_jobs.lock().await;
+                if pending_jobs.iter().any(|job| job.id == id) {
+                    return Err(Status::failed_precondition("Job has not started yet"));
+                }
+                drop(pending_jobs);
+
+                match self.db.get_job_opt(id) {
+                    Ok(Some(job)) => job.assigned_node,
+                    Ok(None) => {
+                        return Err(Status::not_found(format!("Job ID not found {}", id)));
+                    }
+                    Err(e) => {
+                        log!(error, "Could not look up job {} in database: {}", id, e);
+                        return Err(Status::unknown(format!("Unexpected Error {}", e)));
+                    }
+                }
+            }
+        };
+        let node_id =
+            assigned_node.ok_or_else(|| Status::failed_precondition("Job was never started"))?;
+
+        let endpoint = {
+            let nodes = self.nodes.lock().await;
+            nodes.get(&node_id).map(|node| node.endpoint.clone())
+        }
+        .ok_or_else(|| Status::unavailable(format!("Node {} is not available", node_id)))?;
+
+        // the worker's frames are passed on as they arrive
+        let mut client = connect_worker(endpoint, &self.worker_link()).await?;
+        client.get_job_output(req).await
+    }
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Drain node",
+        skip(self, request),
+        fields(node_id = %request.get_ref().node_id)
+    )]
+    async fn drain_node(
+        &self,
+        request: tonic::Request<proto::DrainNodeRequest>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let node_id = &request.get_ref().node_id;
+
+        let node = {
+            let mut nodes = self.nodes.lock().await;
+            let Some(node) = nodes.get_mut(node_id) else {
+                return Err(Status::not_found(format!("Node {} not found", node_id)));
+            };
+            // the scheduling loop removes the node once its running jobs are done
+            log!(info, "Draining node {}", node_id);
+            node.set_status(NodeStatus::Draining);
+            node.clone()
+        };
+
+        // a restart must not bring the node back into service
+        if let Err(e) = self.db.store_node(&node) {
+            log!(error, "Could not store node {}: {}", node_id, e);
+        }
+        Ok(tonic::Response::new(()))
+    }
+
+    #[tracing::instrument(level = "debug", name = "List all nodes", skip(self, _request))]
+    async fn list_nodes(
+        &self,
+        _request: tonic::Request<()>,
+    ) -> core::result::Result<tonic::Response<proto::NodeListResponse>, tonic::Status> {
+        let running_jobs = self.running_jobs.lock().await;
+        let nodes = self.nodes.lock().await;
+
+        let nodes = nodes
+            .values()
+            .map(|node| {
+                let running = running_jobs
+                    .values()
+                    .filter(|job| job.runs_on(&node.id))
+                    .count();
+                proto::NodeDetails {
+                    id: node.id.clone(),
+                    address: node.endpoint.clone(),
+                    status: proto::NodeStatus::from(node.status.clone()).into(),
+                    total_resources: Some((&node.avail_resources).into()),
+                    used_resources: Some((&node.used_resources).into()),
+                    running_jobs: running as u32,
+                    heartbeat_age_secs: node.last_heartbeat.elapsed().as_secs(),
+                    max_jobs: node.max_jobs,
+                    labels: node.labels.clone(),
+                }
+            })
+            .collect();
+
+        let response = proto::NodeListResponse { nodes };
+        Ok(tonic::Response::new(response))
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        name = "List jobs of node",
+        skip(self, request),
+        fields(node_id = %request.get_ref().node_id)
+    )]
+    async fn get_node_jobs(
+        &self,
+        request: tonic::Request<proto::GetNodeJobsRequest>,
+    ) -> core::result::Result<tonic::Response<proto::JobListResponse>, tonic::Status> {
+        let node_id = &request.get_ref().node_id;
+        let mut jobs: Vec<proto::Job> = {
+            let running_jobs = self.running_jobs.lock().await;
+            running_jobs
+                .values()
+                .filter(|job| job.assigned_node.as_ref() == Some(node_id))
+                .map(|j| j.into())
+                .collect()
+        };
+
+        // nodes that went away are no longer registered, their history is still kept
+        match self.db.get_jobs_by_node(node_id) {
+            Ok(finished_jobs) => {
+                if finished_jobs.skipped > 0 {
+                    log!(
+                        warn,
+                        "Skipped {} unreadable jobs in the database",
+                        finished_jobs.skipped
+                    );
+                }
+                jobs.extend(finished_jobs.jobs.iter().map(|j| j.into()));
+            }
+            Err(e) => {
+                log!(error, "Error fetching finished jobs from database: {}", e);
+                return Err(tonic::Status::internal("Failed to fetch finished jobs"));
+            }
+        }
+        jobs.sort_by_key(|job| job.id);
+
+        Ok(tonic::Response::new(proto::JobListResponse { jobs }))
+    }
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Receive admin kill request",
+        skip(self, request),
+        fields(job_id = %request.get_ref().job_id)
+    )]
+    async fn admin_kill_job(
+        &self,
+        request: tonic::Request<proto::AdminJobRequest>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let admin = request_admin(&request, &self.admins)?;
+        self.admin_remove_job(request.get_ref().job_id, &admin, false)
+            .await?;
+        Ok(tonic::Response::new(()))
+    }
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Receive admin requeue request",
+        skip(self, request),
+        fields(job_id = %request.get_ref().job_id)
+    )]
+    async fn admin_requeue_job(
+        &self,
+        request: tonic::Request<proto::AdminJobRequest>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let admin = request_admin(&request, &self.admins)?;
+        self.admin_remove_job(request.get_ref().job_id, &admin, true)
+            .await?;
+        Ok(tonic::Response::new(()))
+    }
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Create reservation",
+        skip(self, request),
+        fields(name = %request.get_ref().name)
+    )]
+    async fn create_reservation(
+        &self,
+        request: tonic::Request<proto::CreateReservationRequest>,
+    ) -> core::result::Result<tonic::Response<proto::Reservation>, tonic::Status> {
+        let admin = request_admin(&request, &self.admins)?;
+        let req = request.into_inner();
+
+        let now = get_current_timestamp();
+        if req.node_ids.is_empty() || req.users.is_empty() {
+            return Err(Status::invalid_argument(
+                "A reservation needs at least one node and one user",
+            ));
+        }
+        if req.end_time <= req.start_time || req.end_time <= now {
+            return Err(Status::invalid_argument(
+                "A reservation has to end after it starts and in the future",
+            ));
+        }
+        {
+            let nodes = self.nodes.lock().await;
+            if let Some(node_id) = req.node_ids.iter().find(|id| !nodes.contains_key(*id)) {
+                return Err(Status::invalid_argument(format!(
+                    "Unknown node {}",
+                    node_id
+                )));
+            }
+        }
+
+        let mut reservations = self.reservations.lock().await;
+        reservations.retain(|r| r.end_time > now);
+        // a node can't be promised to two groups of users at once
+        if let Some(other) = reservations.iter().find(|r| {
+            r.overlaps(req.start_time, req.end_time)
+                && r.node_ids.iter().any(|id| req.node_ids.contains(id))
+        }) {
+            return Err(Status::failed_precondition(format!(
+                "Overlaps reservation {} ({})",
+                other.id, other.name
+            )));
+        }
+
+        let reservation = Reservation {
+            id: self
+                .reservation_ctr
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst),
+            name: req.name,
+            node_ids: req.node_ids,
+            users: req.users,
+            start_time: req.start_time,
+            end_time: req.end_time,
+            created_by: admin,
+        };
+        log!(
+            info,
+            "Admin {} reserves nodes {} for {} from {} to {}",
+            reservation.created_by,
+            reservation.node_ids.join(","),
+            reservation.users.join(","),
+            reservation.start_time,
+            reservation.end_time
+        );
+        let response = proto::Reservation::from(&reservation);
+        reservations.push(reservation);
+        Ok(tonic::Response::new(response))
+    }
+
+    #[tracing::instrument(level = "debug", name = "List reservations", skip(self, _request))]
+    async fn list_reservations(
+        &self,
+        _request: tonic::Request<()>,
+    ) -> core::result::Result<tonic::Response<proto::ReservationListResponse>, tonic::Status> {
+        let now = get_current_timestamp();
+        let mut reservations = self.reservations.lock().await;
+        reservations.retain(|r| r.end_time > now);
+        let reservations = reservations.iter().map(Into::into).collect();
+        Ok(tonic::Response::new(proto::ReservationListResponse {
+            reservations,
+        }))
+    }
+
+    #[tracing::instrument(
+        level = "info",
+        name = "Delete reservation",
+        skip(self, request),
+        fields(reservation_id = %request.get_ref().reservation_id)
+    )]
+    async fn delete_reservation(
+        &self,
+        request: tonic::Request<proto::DeleteReservationRequest>,
+    ) -> core::result::Result<tonic::Response<()>, tonic::Status> {
+        let admin = request_admin(&request, &self.admins)?;
+        let id = request.get_ref().reservation_id;
+
+        let mut reservations = self.reservations.lock().await;
+        let count = reservations.len();
+        reservations.retain(|r| r.id != id);
+        if reservations.len() == count {
+            return Err(Status::not_found(format!("Reservation {} not found", id)));
+        }
+        log!(info, "Admin {} deletes reservation {}", admin, id);
+        Ok(tonic::Response::new(()))
+    }
 }
@@ -1,4 +1,5 @@
 use serde_aux::field_attributes::deserialize_number_from_string;
+use std::collections::HashMap;
 use std::fmt;
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -6,6 +7,15 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub database: DatabaseSettings,
     pub api: ApiSettings,
+    #[serde(default)]
+    pub scheduler: SchedulerSettings,
+    /// Partitions by name, jobs without a partition use `default`
+    #[serde(default)]
+    pub partitions: HashMap<String, PartitionSettings>,
+    #[serde(default)]
+    pub auth: AuthSettings,
+    #[serde(default)]
+    pub audit: AuditSettings,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -13,6 +23,9 @@ pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub host: String,
+    /// Requeue the jobs of a node that went offline instead of failing them
+    #[serde(default)]
+    pub requeue_lost_jobs: bool,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -22,30 +35,186 @@ pub struct ApiSettings {
     pub host: String,
 }
 
+/// Timers of the scheduler loops.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SchedulerSettings {
+    /// How often pending jobs are assigned to nodes
+    pub assignment_interval_ms: u64,
+    /// Most jobs that are sent to nodes at the same time, each to other nodes, at least 1
+    pub max_concurrent_assignments: usize,
+    /// How often the node heartbeats are checked
+    pub health_poll_interval_secs: u64,
+    /// How long a node may miss heartbeats before it is considered offline
+    pub node_timeout_secs: u64,
+    /// How long a job may be missing from its node's heartbeats after it started before it is considered lost
+    pub lost_job_grace_secs: u64,
+    /// Let jobs skip ahead of a job that waits for resources, as long as they don't delay it
+    pub backfill: bool,
+    /// Longest time limit in minutes a job may have, also after extensions, unlimited if unset
+    pub max_job_time_mins: Option<u32>,
+    /// How long a worker may take to accept a connection and to answer a request
+    pub worker_timeout_secs: u64,
+    /// Most jobs that may wait in the queue at once, unlimited if unset
+    pub max_pending_jobs: Option<usize>,
+    /// Most jobs a single user may submit within a minute, unlimited if unset
+    pub max_submissions_per_min: Option<u32>,
+    /// Which of the nodes a job fits on it is assigned to
+    pub placement: PlacementPolicy,
+    /// Run `validation_script` on every node that registers before it gets any jobs
+    pub validate_on_register: bool,
+    /// Script that checks a node, it has to exist there and exit with 0 within a minute
+    pub validation_script: String,
+    /// Command that gets every submission as JSON on stdin and accepts it by exiting with 0, no hook if unset
+    pub admission_hook: Option<String>,
+    /// How long the admission hook may take before the submission is rejected
+    pub admission_timeout_secs: u64,
+    /// How many CPUs are handed out per physical core of a node, values below 1 count as 1
+    pub cpu_overcommit_factor: f64,
+    /// How much memory is handed out per byte of a node's memory, values below 1 count as 1
+    pub memory_overcommit_factor: f64,
+}
+
+/// How a node is picked among those that have room for a job.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementPolicy {
+    /// The first node found, cheapest to compute
+    #[default]
+    FirstFit,
+    /// The node with the least room left, keeps large nodes free for large jobs
+    BestFit,
+    /// The node with the most room left, spreads the load
+    WorstFit,
+}
+
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        Self {
+            assignment_interval_ms: 250,
+            max_concurrent_assignments: 16,
+            health_poll_interval_secs: 30,
+            node_timeout_secs: 60,
+            lost_job_grace_secs: 30,
+            backfill: true,
+            max_job_time_mins: None,
+            worker_timeout_secs: 10,
+            max_pending_jobs: None,
+            max_submissions_per_min: None,
+            placement: PlacementPolicy::FirstFit,
+            validate_on_register: false,
+            validation_script: "/bin/true".to_string(),
+            admission_hook: None,
+            admission_timeout_secs: 10,
+            cpu_overcommit_factor: 1.0,
+            memory_overcommit_factor: 1.0,
+        }
+    }
+}
+
+/// Bearer tokens that clients have to send with every RPC.
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AuthSettings {
+    /// Reject requests without a known token, trust the user sent by the client otherwise
+    pub enabled: bool,
+    /// Users by token, requests act as the user of their token
+    pub tokens: HashMap<String, String>,
+    /// Users that may kill or requeue the jobs of any user, only if authentication is enabled
+    pub admins: Vec<String>,
+    /// Sent with every request to the workers, which expect it in `MELON_WORKER_TOKEN`
+    pub worker_token: Option<String>,
+}
+
+/// Append-only log of all job events as JSON lines.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AuditSettings {
+    /// File the events are appended to, no audit log if unset
+    pub path: Option<String>,
+    /// Size in bytes after which the file is rotated
+    pub max_bytes: u64,
+    /// How many rotated files are kept, as `<path>.1` (newest) to `<path>.<max_files>`
+    pub max_files: u32,
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct DatabaseSettings {
     pub path: String,
+    /// How long a connection waits for another one to release its lock before it fails
+    #[serde(default = "default_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+}
+
+fn default_busy_timeout_ms() -> u64 {
+    5000
+}
+
+/// Resource caps and members of a partition.
+///
+/// Limits apply to the sum of all running jobs in the partition, not to a single node.
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+pub struct PartitionSettings {
+    /// Maximum number of CPUs used by all running jobs, unlimited if unset
+    pub max_cpus: Option<u32>,
+    /// Maximum memory in bytes used by all running jobs, unlimited if unset
+    pub max_memory: Option<u64>,
+    /// Addresses of the nodes that belong to the partition, any node if empty
+    #[serde(default)]
+    pub nodes: Vec<String>,
+}
+
+impl PartitionSettings {
+    /// Returns whether the node with the given address may run jobs of this partition.
+    pub fn contains_node(&self, address: &str) -> bool {
+        self.nodes.is_empty() || self.nodes.iter().any(|node| node == address)
+    }
 }
 
 impl fmt::Display for Settings {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Settings:\n  Application:\n{} \n Database:\n{} \n API:\n{}",
-            self.application, self.database, self.api
-        )
+            "Settings:\n  Application:\n{} \n Database:\n{} \n API:\n{} \n Scheduler:\n{} \n Auth:\n{} \n Audit:\n{}",
+            self.application, self.database, self.api, self.scheduler, self.auth, self.audit
+        )?;
+        if !self.partitions.is_empty() {
+            write!(f, " \n Partitions:")?;
+            for (name, partition) in &self.partitions {
+                write!(f, "\n    {}: {}", name, partition)?;
+            }
+        }
+        Ok(())
     }
 }
 
 impl fmt::Display for ApplicationSettings {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "    Host: {}\n    Port: {}", self.host, self.port)
+        write!(
+            f,
+            "    Host: {}\n    Port: {}\n    Requeue lost jobs: {}",
+            self.host, self.port, self.requeue_lost_jobs
+        )
     }
 }
 
 impl fmt::Display for DatabaseSettings {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "    Path: {}", self.path)
+        writeln!(
+            f,
+            "    Path: {}\n    Busy timeout: {}ms",
+            self.path, self.busy_timeout_ms
+        )
     }
 }
 
@@ -54,3 +223,94 @@ impl fmt::Display for ApiSettings {
         write!(f, "    Host: {}\n    Port: {}", self.host, self.port)
     }
 }
+
+impl fmt::Display for SchedulerSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "    Assignment interval: {}ms ({} concurrent)\n    Health poll interval: {}s\n    Node timeout: {}s\n    Lost job grace: {}s\n    Backfill: {}\n    Max job time: {}\n    Worker timeout: {}s\n    Max pending jobs: {}\n    Max submissions per minute: {}\n    Placement: {}\n    Validate on register: {} ({})\n    Admission hook: {} ({}s)\n    Overcommit: {}x CPUs, {}x memory",
+            self.assignment_interval_ms,
+            self.max_concurrent_assignments,
+            self.health_poll_interval_secs,
+            self.node_timeout_secs,
+            self.lost_job_grace_secs,
+            self.backfill,
+            self.max_job_time_mins
+                .map(|mins| format!("{}min", mins))
+                .unwrap_or_else(|| "unlimited".to_string()),
+            self.worker_timeout_secs,
+            self.max_pending_jobs
+                .map(|max| max.to_string())
+                .unwrap_or_else(|| "unlimited".to_string()),
+            self.max_submissions_per_min
+                .map(|max| max.to_string())
+                .unwrap_or_else(|| "unlimited".to_string()),
+            self.placement,
+            self.validate_on_register,
+            self.validation_script,
+            self.admission_hook.as_deref().unwrap_or("none"),
+            self.admission_timeout_secs,
+            self.cpu_overcommit_factor,
+            self.memory_overcommit_factor
+        )
+    }
+}
+
+impl fmt::Display for PlacementPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PlacementPolicy::FirstFit => "first_fit",
+            PlacementPolicy::BestFit => "best_fit",
+            PlacementPolicy::WorstFit => "worst_fit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for AuthSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // never print the tokens themselves
+        write!(
+            f,
+            "    Enabled: {}\n    Tokens: {}\n    Admins: {}\n    Worker token: {}",
+            self.enabled,
+            self.tokens.len(),
+            self.admins.join(", "),
+            if self.worker_token.is_some() {
+                "set"
+            } else {
+                "not set"
+            }
+        )
+    }
+}
+
+impl fmt::Display for AuditSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(
+                f,
+                "    Path: {}\n    Max size: {} bytes\n    Rotated files: {}",
+                path, self.max_bytes, self.max_files
+            ),
+            None => write!(f, "    Disabled"),
+        }
+    }
+}
+
+impl fmt::Display for PartitionSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let limit = |value: Option<String>| value.unwrap_or_else(|| "unlimited".to_string());
+        write!(
+            f,
+            "CPUs: {}, Memory: {}, Nodes: {}",
+            limit(self.max_cpus.map(|c| c.to_string())),
+            limit(self.max_memory.map(|m| m.to_string())),
+            if self.nodes.is_empty() {
+                "any".to_string()
+            } else {
+                self.nodes.join(", ")
+            }
+        )
+    }
+}
@@ -1,14 +1,19 @@
 use crate::constants::*;
 use anyhow::Result;
 use melon_common::{
+    auth::{self, SchedulerClient, TokenInterceptor},
     configuration::get_configuration,
-    proto::{
-        self, melon_scheduler_client::MelonSchedulerClient, Heartbeat, NodeInfo, NodeResources,
-        RegistrationResponse,
-    },
+    proto::{self, Heartbeat, NodeInfo, NodeResources, RegistrationResponse},
 };
-use melond::{api::Api, application::Application, settings::Settings};
+use melond::{
+    api::Api,
+    application::Application,
+    settings::{AuthSettings, PartitionSettings, SchedulerSettings, Settings},
+};
+use std::collections::HashMap;
+use std::future::Future;
 use tempdir::TempDir;
+use tokio::task::JoinHandle;
 use tonic::Response;
 use uuid::Uuid;
 
@@ -20,14 +25,24 @@ pub struct TestApp {
     #[allow(dead_code)]
     pub api_host: String,
     pub api_port: u16,
+    /// Token sent with every request
+    pub token: Option<String>,
 }
 
 impl TestApp {
+    async fn client(&self) -> Result<SchedulerClient, tonic::transport::Error> {
+        auth::connect_with_token(
+            self.address.clone(),
+            TokenInterceptor::new(self.token.clone()),
+        )
+        .await
+    }
+
     pub async fn register_node(
         &self,
         info: NodeInfo,
     ) -> Result<Response<RegistrationResponse>, Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.address.clone().to_string()).await?;
+        let mut client = self.client().await?;
         let request = tonic::Request::new(info);
         let response = client.register_node(request).await?;
         Ok(response)
@@ -37,8 +52,27 @@ impl TestApp {
         &self,
         node_id: String,
     ) -> Result<Response<()>, Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.address.clone().to_string()).await?;
-        let req = Heartbeat { node_id };
+        let mut client = self.client().await?;
+        let req = Heartbeat {
+            node_id,
+            usage: None,
+        };
+
+        let request = tonic::Request::new(req);
+        let response = client.send_heartbeat(request).await?;
+        Ok(response)
+    }
+
+    pub async fn send_heartbeat_with_usage(
+        &self,
+        node_id: String,
+        usage: proto::NodeUsage,
+    ) -> Result<Response<()>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let req = Heartbeat {
+            node_id,
+            usage: Some(usage),
+        };
 
         let request = tonic::Request::new(req);
         let response = client.send_heartbeat(request).await?;
@@ -49,26 +83,60 @@ impl TestApp {
         &self,
         submission: proto::JobSubmission,
     ) -> Result<tonic::Response<proto::MasterJobResponse>, Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.address.clone().to_string()).await?;
+        let mut client = self.client().await?;
         let request = tonic::Request::new(submission);
         let response = client.submit_job(request).await?;
         Ok(response)
     }
 
+    pub async fn submit_jobs(
+        &self,
+        submissions: Vec<proto::JobSubmission>,
+    ) -> Result<tonic::Response<proto::JobBatchResponse>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::JobBatch { submissions });
+        let response = client.submit_jobs(request).await?;
+        Ok(response)
+    }
+
     pub async fn list_jobs(
         &self,
     ) -> Result<tonic::Response<proto::JobListResponse>, Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.address.clone().to_string()).await?;
-        let request = tonic::Request::new(());
+        self.list_jobs_submitted(None, None).await
+    }
+
+    pub async fn list_jobs_submitted(
+        &self,
+        since: Option<u64>,
+        until: Option<u64>,
+    ) -> Result<tonic::Response<proto::JobListResponse>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::ListJobsRequest {
+            submitted_since: since,
+            submitted_until: until,
+        });
         let response = client.list_jobs(request).await?;
         Ok(response)
     }
 
+    pub async fn list_user_jobs(
+        &self,
+        user: &str,
+    ) -> Result<tonic::Response<proto::JobListResponse>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::ListUserJobsRequest {
+            user: user.to_string(),
+            ..Default::default()
+        });
+        let response = client.list_user_jobs(request).await?;
+        Ok(response)
+    }
+
     pub async fn submit_job_result(
         &self,
         result: proto::JobResult,
     ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.address.clone().to_string()).await?;
+        let mut client = self.client().await?;
         let request = tonic::Request::new(result);
         let response = client.submit_job_result(request).await?;
         Ok(response)
@@ -78,31 +146,153 @@ impl TestApp {
         &self,
         request: proto::CancelJobRequest,
     ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.address.clone().to_string()).await?;
+        let mut client = self.client().await?;
         let request = tonic::Request::new(request);
         let response = client.cancel_job(request).await?;
         Ok(response)
     }
 
+    pub async fn hold_job(
+        &self,
+        request: proto::HoldJobRequest,
+    ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(request);
+        let response = client.hold_job(request).await?;
+        Ok(response)
+    }
+
+    pub async fn release_job(
+        &self,
+        request: proto::ReleaseJobRequest,
+    ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(request);
+        let response = client.release_job(request).await?;
+        Ok(response)
+    }
+
     pub async fn extend_job(
         &self,
         request: proto::ExtendJobRequest,
     ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.address.clone().to_string()).await?;
+        let mut client = self.client().await?;
         let request = tonic::Request::new(request);
         let response = client.extend_job(request).await?;
         Ok(response)
     }
 
+    pub async fn get_job_output(
+        &self,
+        request: proto::JobOutputRequest,
+    ) -> Result<Vec<proto::JobOutput>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(request);
+        let mut frames = client.get_job_output(request).await?.into_inner();
+        let mut output = vec![];
+        while let Some(frame) = frames.message().await? {
+            output.push(frame);
+        }
+        Ok(output)
+    }
+
     pub async fn get_job_info(
         &self,
         request: proto::GetJobInfoRequest,
     ) -> Result<tonic::Response<proto::Job>, Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.address.clone().to_string()).await?;
+        let mut client = self.client().await?;
         let request = tonic::Request::new(request);
         let response = client.get_job_info(request).await?;
         Ok(response)
     }
+
+    pub async fn get_job_status(
+        &self,
+        job_id: u64,
+    ) -> Result<tonic::Response<proto::JobStatusResponse>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::JobStatusRequest { job_id });
+        let response = client.get_job_status(request).await?;
+        Ok(response)
+    }
+
+    pub async fn admin_kill_job(
+        &self,
+        job_id: u64,
+    ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::AdminJobRequest { job_id });
+        let response = client.admin_kill_job(request).await?;
+        Ok(response)
+    }
+
+    pub async fn admin_requeue_job(
+        &self,
+        job_id: u64,
+    ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::AdminJobRequest { job_id });
+        let response = client.admin_requeue_job(request).await?;
+        Ok(response)
+    }
+
+    pub async fn drain_node(
+        &self,
+        node_id: String,
+    ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::DrainNodeRequest { node_id });
+        let response = client.drain_node(request).await?;
+        Ok(response)
+    }
+
+    pub async fn list_nodes(
+        &self,
+    ) -> Result<tonic::Response<proto::NodeListResponse>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(());
+        let response = client.list_nodes(request).await?;
+        Ok(response)
+    }
+
+    pub async fn get_node_jobs(
+        &self,
+        node_id: String,
+    ) -> Result<tonic::Response<proto::JobListResponse>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::GetNodeJobsRequest { node_id });
+        let response = client.get_node_jobs(request).await?;
+        Ok(response)
+    }
+
+    pub async fn create_reservation(
+        &self,
+        request: proto::CreateReservationRequest,
+    ) -> Result<tonic::Response<proto::Reservation>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(request);
+        let response = client.create_reservation(request).await?;
+        Ok(response)
+    }
+
+    pub async fn list_reservations(
+        &self,
+    ) -> Result<tonic::Response<proto::ReservationListResponse>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(());
+        let response = client.list_reservations(request).await?;
+        Ok(response)
+    }
+
+    pub async fn delete_reservation(
+        &self,
+        reservation_id: u64,
+    ) -> Result<tonic::Response<()>, Box<dyn std::error::Error>> {
+        let mut client = self.client().await?;
+        let request = tonic::Request::new(proto::DeleteReservationRequest { reservation_id });
+        let response = client.delete_reservation(request).await?;
+        Ok(response)
+    }
 }
 
 fn configure_common_settings(c: &mut Settings) {
@@ -123,6 +313,83 @@ pub async fn spawn_app() -> TestApp {
     .await
 }
 
+pub async fn spawn_app_with_partitions(partitions: HashMap<String, PartitionSettings>) -> TestApp {
+    configure_and_spawn_app(|c: &mut Settings| {
+        configure_common_settings(c);
+        c.partitions = partitions;
+    })
+    .await
+}
+
+pub async fn spawn_app_with_scheduler(scheduler: SchedulerSettings) -> TestApp {
+    configure_and_spawn_app(|c: &mut Settings| {
+        configure_common_settings(c);
+        c.scheduler = scheduler;
+    })
+    .await
+}
+
+/// Spawns the scheduler with authentication, the returned app doesn't send a token yet.
+pub async fn spawn_app_with_auth(tokens: HashMap<String, String>) -> TestApp {
+    spawn_app_with_admins(tokens, vec![]).await
+}
+
+/// Spawns the scheduler with authentication and the given admin users.
+pub async fn spawn_app_with_admins(
+    tokens: HashMap<String, String>,
+    admins: Vec<String>,
+) -> TestApp {
+    configure_and_spawn_app(|c: &mut Settings| {
+        configure_common_settings(c);
+        c.auth = AuthSettings {
+            enabled: true,
+            tokens,
+            admins,
+            ..Default::default()
+        };
+    })
+    .await
+}
+
+/// Spawns the scheduler that sends `worker_token` with every request to its workers.
+pub async fn spawn_app_with_worker_token(worker_token: &str) -> TestApp {
+    configure_and_spawn_app(|c: &mut Settings| {
+        configure_common_settings(c);
+        c.auth.worker_token = Some(worker_token.to_string());
+    })
+    .await
+}
+
+/// Spawns the scheduler on a given database, it shuts down once `shutdown` resolves.
+pub async fn spawn_app_on_database<F>(db_path: &str, shutdown: F) -> (TestApp, JoinHandle<()>)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let mut settings: Settings = get_configuration().expect("Failed to read config");
+    settings.application.port = 0;
+    settings.database.path = db_path.to_string();
+
+    let application = Application::build(settings.clone())
+        .await
+        .expect("Failed to build application");
+    let port = application.port();
+
+    let handle = tokio::spawn(async move {
+        if let Err(e) = application.run_until_shutdown(shutdown).await {
+            println!("App shut down: {}", e);
+        }
+    });
+
+    let app = TestApp {
+        address: format!("http://{}:{}", settings.application.host, port),
+        port,
+        api_host: settings.api.host,
+        api_port: 0,
+        token: None,
+    };
+    (app, handle)
+}
+
 // only run API to test unavailable scheduler deamon
 pub async fn spawn_app_api_only() -> TestApp {
     configure_and_spawn_api(|c: &mut Settings| {
@@ -147,7 +414,7 @@ where
     let port = application.port();
     settings.application.port = port;
 
-    let api = Api::new(settings.clone());
+    let api = Api::new(settings.clone()).with_events(application.job_events());
     let api_addr = format!("{}:0", settings.api.host);
     let api_listener = tokio::net::TcpListener::bind(&api_addr).await.unwrap();
     let api_port = api_listener.local_addr().unwrap().port();
@@ -168,6 +435,7 @@ where
         port,
         api_host: settings.api.host,
         api_port,
+        token: None,
     }
 }
 
@@ -197,6 +465,7 @@ where
         port: 0,
         api_host: settings.api.host,
         api_port,
+        token: None,
     }
 }
 
@@ -204,10 +473,12 @@ pub fn get_node_info(port: u16) -> NodeInfo {
     let resources = NodeResources {
         cpu_count: 8,
         memory: 4 * 1024 * 1024,
+        gpu_count: 0,
     };
     NodeInfo {
         address: format!("http://[::1]:{}", port),
         resources: Some(resources),
+        labels: vec![],
     }
 }
 
@@ -219,7 +490,27 @@ pub fn get_job_submission() -> proto::JobSubmission {
             cpu_count: TEST_COU_COUNT,
             memory: TEST_MEMORY_SIZE,
             time: TEST_TIME_MINS,
+            gpu_count: 0,
+            node_count: 1,
         }),
         script_args: [].to_vec(),
+        priority: None,
+        dependencies: vec![],
+        partition: None,
+        force: false,
+        env: HashMap::new(),
+        work_dir: None,
+        name: None,
+        grace_secs: None,
+        max_retries: 0,
+        dry_run: false,
+        interactive: false,
+        constraints: vec![],
+        begin_deadline_mins: None,
+        warn_signal: None,
+        output_path: None,
+        error_path: None,
+        parent_job_id: None,
+        script_contents: None,
     }
 }
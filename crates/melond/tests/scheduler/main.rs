@@ -2,4 +2,7 @@ mod constants;
 mod helpers;
 mod mock_worker;
 mod test_api;
+mod test_auth;
+mod test_client;
+mod test_reservation;
 mod test_scheduler;
@@ -1,6 +1,10 @@
 use anyhow::Result;
+use melon_common::auth::bearer_token;
 use melon_common::proto;
 use melon_common::proto::melon_worker_server::{MelonWorker, MelonWorkerServer};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc::{self, Sender};
 use tokio::sync::watch;
@@ -15,6 +19,18 @@ pub struct MockWorker {
 
     // Used when the worker receives an extension request for running jobs
     job_extension_sender: Sender<proto::ExtendJobRequest>,
+
+    // Number of assignments that are still rejected as if the worker had no free cores
+    rejections: Arc<AtomicUsize>,
+
+    // Number of received assignments, including rejected ones
+    assign_attempts: Arc<AtomicUsize>,
+
+    // How long the worker takes to answer an assignment
+    assign_delay: Duration,
+
+    // Token that assignments have to come with, any request is taken if unset
+    token: Option<String>,
 }
 
 impl MockWorker {
@@ -22,11 +38,18 @@ impl MockWorker {
         job_assignment_sender: Sender<proto::JobAssignment>,
         job_cancellation_sender: Sender<proto::CancelJobRequest>,
         job_extension_sender: Sender<proto::ExtendJobRequest>,
+        rejections: usize,
+        assign_delay: Duration,
+        token: Option<String>,
     ) -> Result<Self, anyhow::Error> {
         Ok(Self {
             job_assignment_sender,
             job_cancellation_sender,
             job_extension_sender,
+            rejections: Arc::new(AtomicUsize::new(rejections)),
+            assign_attempts: Arc::new(AtomicUsize::new(0)),
+            assign_delay,
+            token,
         })
     }
 }
@@ -37,6 +60,23 @@ impl MelonWorker for MockWorker {
         &self,
         request: tonic::Request<proto::JobAssignment>,
     ) -> Result<tonic::Response<()>, tonic::Status> {
+        self.assign_attempts.fetch_add(1, Ordering::SeqCst);
+        if let Some(token) = &self.token {
+            if bearer_token(&request) != Some(token.as_str()) {
+                return Err(tonic::Status::unauthenticated(
+                    "Missing or invalid worker token",
+                ));
+            }
+        }
+        tokio::time::sleep(self.assign_delay).await;
+        let rejected = self
+            .rejections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if rejected {
+            return Err(tonic::Status::resource_exhausted("No free cores"));
+        }
+
         let job_assignment = request.into_inner();
         self.job_assignment_sender
             .send(job_assignment)
@@ -69,6 +109,42 @@ impl MelonWorker for MockWorker {
             .map_err(|e| tonic::Status::internal(e.to_string()))?;
         Ok(tonic::Response::new(()))
     }
+
+    type AttachStream =
+        tokio_stream::wrappers::ReceiverStream<Result<proto::AttachOutput, tonic::Status>>;
+
+    async fn attach(
+        &self,
+        _request: tonic::Request<tonic::Streaming<proto::AttachInput>>,
+    ) -> Result<tonic::Response<Self::AttachStream>, tonic::Status> {
+        Err(tonic::Status::unimplemented("Mock worker runs no jobs"))
+    }
+
+    type GetJobOutputStream =
+        tokio_stream::wrappers::ReceiverStream<Result<proto::JobOutput, tonic::Status>>;
+
+    /// Streams the name of the output stream and the job id, in one frame per word
+    async fn get_job_output(
+        &self,
+        request: tonic::Request<proto::JobOutputRequest>,
+    ) -> Result<tonic::Response<Self::GetJobOutputStream>, tonic::Status> {
+        let req = request.get_ref();
+        let content = format!("{:?} of job {}", req.stream(), req.job_id);
+        let (tx, rx) = mpsc::channel(content.len());
+        let mut offset = 0;
+        for word in content.split_inclusive(' ') {
+            let frame = proto::JobOutput {
+                content: word.as_bytes().to_vec(),
+                offset,
+                truncated_bytes: 0,
+            };
+            offset += word.len() as u64;
+            tx.try_send(Ok(frame)).unwrap();
+        }
+        Ok(tonic::Response::new(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        ))
+    }
 }
 
 pub struct MockWorkerSetup {
@@ -77,10 +153,34 @@ pub struct MockWorkerSetup {
     pub server_notifier: watch::Sender<()>,
     pub server_handle: tokio::task::JoinHandle<()>,
     pub job_extension_receiver: mpsc::Receiver<proto::ExtendJobRequest>,
+    pub assign_attempts: Arc<AtomicUsize>,
     pub port: u16,
 }
 
 pub async fn setup_mock_worker() -> MockWorkerSetup {
+    setup_rejecting_mock_worker(0).await
+}
+
+/// Sets up a mock worker that rejects the first `rejections` job assignments.
+pub async fn setup_rejecting_mock_worker(rejections: usize) -> MockWorkerSetup {
+    setup_mock_worker_with(rejections, Duration::ZERO, None).await
+}
+
+/// Sets up a mock worker that takes `assign_delay` to answer every job assignment.
+pub async fn setup_slow_mock_worker(assign_delay: Duration) -> MockWorkerSetup {
+    setup_mock_worker_with(0, assign_delay, None).await
+}
+
+/// Sets up a mock worker that only takes job assignments that come with `token`.
+pub async fn setup_mock_worker_with_token(token: &str) -> MockWorkerSetup {
+    setup_mock_worker_with(0, Duration::ZERO, Some(token.to_string())).await
+}
+
+async fn setup_mock_worker_with(
+    rejections: usize,
+    assign_delay: Duration,
+    token: Option<String>,
+) -> MockWorkerSetup {
     let (job_assignment_sender, job_assignment_receiver) = mpsc::channel(1);
     let (job_cancellation_sender, job_cancellation_receiver) = mpsc::channel(1);
     let (server_notifier, server_notifier_rx) = watch::channel(());
@@ -90,9 +190,13 @@ pub async fn setup_mock_worker() -> MockWorkerSetup {
         job_assignment_sender.clone(),
         job_cancellation_sender.clone(),
         job_extension_sender.clone(),
+        rejections,
+        assign_delay,
+        token,
     )
     .await
     .unwrap();
+    let assign_attempts = worker.assign_attempts.clone();
 
     let addr = String::from("[::1]:0");
     let listener = TcpListener::bind(&addr).await.unwrap();
@@ -119,6 +223,7 @@ pub async fn setup_mock_worker() -> MockWorkerSetup {
         server_notifier,
         server_handle,
         job_extension_receiver,
+        assign_attempts,
         port,
     }
 }
@@ -44,6 +44,7 @@ async fn test_api_list_jobs() {
 #[tokio::test]
 async fn test_api_health_check() {
     let app = spawn_app().await;
+    app.register_node(get_node_info(42)).await.unwrap();
 
     let client = reqwest::Client::new();
     let response = client
@@ -56,8 +57,35 @@ async fn test_api_health_check() {
         .unwrap();
 
     assert_eq!(response.status(), StatusCode::OK);
-    let body = response.text().await.unwrap();
-    assert_eq!(body, "Ok");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["scheduler_reachable"], true);
+    assert_eq!(body["node_count"], 1);
+    assert_eq!(body["pending_jobs"], 0);
+}
+
+#[tokio::test]
+async fn test_api_health_check_with_unavailable_scheduler() {
+    let app = spawn_app_api_only().await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!(
+            "http://{}:{}/api/health",
+            app.api_host, app.api_port
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 503);
+
+    // the API itself is still alive
+    let response = client
+        .get(format!("http://{}:{}/api/live", app.api_host, app.api_port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
 }
 
 #[tokio::test]
@@ -84,3 +112,62 @@ async fn submit_multiple_jobs(app: &TestApp, count: usize) -> Vec<u64> {
     }
     job_ids
 }
+
+#[tokio::test]
+async fn test_api_metrics() {
+    let app = spawn_app().await;
+    app.submit_job(get_job_submission()).await.unwrap();
+    // wait for the scheduler to record its state
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("http://{}:{}/metrics", app.api_host, app.api_port))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("melon_pending_jobs"));
+    assert!(body.contains("melon_cpus_available"));
+}
+
+#[tokio::test]
+async fn test_api_job_events() {
+    let app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let mut response = client
+        .get(format!(
+            "http://{}:{}/api/events",
+            app.api_host, app.api_port
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), response.chunk())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let chunk = String::from_utf8_lossy(&chunk);
+    let data = chunk
+        .lines()
+        .find_map(|line| line.strip_prefix("data: "))
+        .unwrap();
+    let event: Value = serde_json::from_str(data).unwrap();
+
+    assert!(chunk.contains("event: job"));
+    assert_eq!(event["job_id"].as_u64().unwrap(), job_id);
+    assert_eq!(event["status"].as_str().unwrap(), "Pending");
+    assert!(event["timestamp"].as_u64().is_some());
+}
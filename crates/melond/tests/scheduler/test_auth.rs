@@ -0,0 +1,246 @@
+use crate::constants::TEST_USER;
+use crate::helpers::*;
+use crate::mock_worker::{setup_mock_worker, setup_mock_worker_with_token};
+use melon_common::{proto, JobStatus};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tonic::Status;
+
+const USER_TOKEN: &str = "user-token";
+const OTHER_TOKEN: &str = "other-token";
+const ADMIN_TOKEN: &str = "admin-token";
+const ADMIN: &str = "root";
+const WORKER_TOKEN: &str = "worker-token";
+
+fn get_tokens() -> HashMap<String, String> {
+    HashMap::from([
+        (USER_TOKEN.to_string(), TEST_USER.to_string()),
+        (OTHER_TOKEN.to_string(), "someone-else".to_string()),
+        (ADMIN_TOKEN.to_string(), ADMIN.to_string()),
+    ])
+}
+
+async fn spawn_app_with_admin() -> TestApp {
+    spawn_app_with_admins(get_tokens(), vec![ADMIN.to_string()]).await
+}
+
+#[tokio::test]
+async fn test_request_without_token_is_rejected() {
+    let app = spawn_app_with_auth(get_tokens()).await;
+
+    let res = app.submit_job(get_job_submission()).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_request_with_unknown_token_is_rejected() {
+    let mut app = spawn_app_with_auth(get_tokens()).await;
+    app.token = Some("guessed".to_string());
+
+    let res = app.list_jobs().await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_job_belongs_to_user_of_token() {
+    let mut app = spawn_app_with_auth(get_tokens()).await;
+    app.token = Some(USER_TOKEN.to_string());
+
+    // the claimed user is ignored
+    let mut submission = get_job_submission();
+    submission.user = "someone-else".to_string();
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(job.get_ref().user, TEST_USER);
+}
+
+#[tokio::test]
+async fn test_cancel_job_of_other_user_with_forged_name() {
+    let mut app = spawn_app_with_auth(get_tokens()).await;
+    app.token = Some(USER_TOKEN.to_string());
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    // claiming to be the owner doesn't help without their token
+    app.token = Some(OTHER_TOKEN.to_string());
+    let res = app
+        .cancel_job(proto::CancelJobRequest {
+            job_id,
+            user: TEST_USER.to_string(),
+        })
+        .await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn test_admin_kills_job_of_other_user() {
+    let mut app = spawn_app_with_admin().await;
+    app.token = Some(USER_TOKEN.to_string());
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    app.token = Some(ADMIN_TOKEN.to_string());
+    app.admin_kill_job(job_id).await.unwrap();
+
+    let cancel_request = mock_setup.job_cancellation_receiver.recv().await.unwrap();
+    assert_eq!(cancel_request.job_id, job_id);
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Cancelled
+    );
+    assert_eq!(
+        job.get_ref().message,
+        Some(format!("Killed by admin {}", ADMIN))
+    );
+    let nodes = app.list_nodes().await.unwrap();
+    assert_eq!(
+        nodes.get_ref().nodes[0].used_resources.unwrap().cpu_count,
+        0
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_admin_requeues_running_job() {
+    let mut app = spawn_app_with_admin().await;
+    app.token = Some(USER_TOKEN.to_string());
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    app.token = Some(ADMIN_TOKEN.to_string());
+    app.admin_requeue_job(job_id).await.unwrap();
+
+    // stopped on its node and started again
+    let cancel_request = mock_setup.job_cancellation_receiver.recv().await.unwrap();
+    assert_eq!(cancel_request.job_id, job_id);
+    let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, job_id);
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(job.get_ref().user, TEST_USER);
+    assert_eq!(job.get_ref().attempts.len(), 1);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_admin_request_from_regular_user_is_rejected() {
+    let mut app = spawn_app_with_admin().await;
+    app.token = Some(USER_TOKEN.to_string());
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    // not even for their own job
+    let res = app.admin_kill_job(job_id).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn test_admin_request_needs_authentication() {
+    let app = spawn_app().await;
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    let res = app.admin_kill_job(job_id).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn test_scheduler_sends_worker_token() {
+    let app = spawn_app_with_worker_token(WORKER_TOKEN).await;
+    let mut mock_setup = setup_mock_worker_with_token(WORKER_TOKEN).await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, job_id);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_worker_rejects_scheduler_without_token() {
+    let app = spawn_app().await;
+    let mock_setup = setup_mock_worker_with_token(WORKER_TOKEN).await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    app.submit_job(get_job_submission()).await.unwrap();
+
+    while mock_setup.assign_attempts.load(Ordering::SeqCst) == 0 {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let jobs = app.list_jobs().await.unwrap().into_inner().jobs;
+    assert_eq!(jobs[0].status, proto::JobStatus::Pending as i32);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
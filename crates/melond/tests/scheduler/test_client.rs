@@ -0,0 +1,62 @@
+use crate::constants::TEST_USER;
+use crate::helpers::*;
+use melon_client::{Client, Code};
+use melon_common::JobStatus;
+use std::collections::HashMap;
+
+const TOKEN: &str = "client-token";
+
+#[tokio::test]
+async fn test_client_manages_a_job() {
+    let app =
+        spawn_app_with_auth(HashMap::from([(TOKEN.to_string(), TEST_USER.to_string())])).await;
+    // the client adds the scheme to bare addresses
+    let endpoint = app.address.trim_start_matches("http://").to_string();
+    let mut client = Client::builder(endpoint)
+        .with_token(Some(TOKEN.to_string()))
+        .connect()
+        .await
+        .unwrap();
+
+    let submission = get_job_submission();
+    let time = submission.req_res.unwrap().time;
+    let job_id = client.submit(submission).await.unwrap().job_id;
+
+    let jobs = client.list(Some(TEST_USER)).await.unwrap();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].id, job_id);
+
+    client.extend(job_id, TEST_USER, 30).await.unwrap();
+    let job = client.info(job_id).await.unwrap();
+    assert_eq!(job.status, JobStatus::Pending);
+    assert_eq!(job.req_res.time, time + 30);
+
+    assert_eq!(
+        client.status(job_id).await.unwrap(),
+        (JobStatus::Pending, None)
+    );
+
+    client.cancel(job_id, TEST_USER).await.unwrap();
+    let job = client.info(job_id).await.unwrap();
+    assert_eq!(job.status, JobStatus::Cancelled);
+
+    let err = client.info(job_id + 1).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    let err = client.status(job_id + 1).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    assert!(client.nodes().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_client_without_token_is_rejected() {
+    let app =
+        spawn_app_with_auth(HashMap::from([(TOKEN.to_string(), TEST_USER.to_string())])).await;
+    let mut client = Client::builder(app.address.clone())
+        .with_token(None)
+        .connect()
+        .await
+        .unwrap();
+
+    let err = client.list(None).await.unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+}
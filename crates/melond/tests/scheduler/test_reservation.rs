@@ -0,0 +1,209 @@
+use crate::constants::TEST_USER;
+use crate::helpers::*;
+use crate::mock_worker::setup_mock_worker;
+use melon_common::{proto, utils::get_current_timestamp};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tonic::Status;
+
+const USER_TOKEN: &str = "user-token";
+const OTHER_TOKEN: &str = "other-token";
+const ADMIN_TOKEN: &str = "admin-token";
+const ADMIN: &str = "root";
+
+async fn spawn_app_with_admin() -> TestApp {
+    let tokens = HashMap::from([
+        (USER_TOKEN.to_string(), TEST_USER.to_string()),
+        (OTHER_TOKEN.to_string(), "someone-else".to_string()),
+        (ADMIN_TOKEN.to_string(), ADMIN.to_string()),
+    ]);
+    spawn_app_with_admins(tokens, vec![ADMIN.to_string()]).await
+}
+
+/// Reserves the node for the test user from `start` to `end` seconds from now.
+fn reservation(node_id: &str, start: i64, end: i64) -> proto::CreateReservationRequest {
+    let now = get_current_timestamp() as i64;
+    proto::CreateReservationRequest {
+        name: "maintenance".to_string(),
+        node_ids: vec![node_id.to_string()],
+        users: vec![TEST_USER.to_string()],
+        start_time: (now + start) as u64,
+        end_time: (now + end) as u64,
+    }
+}
+
+#[tokio::test]
+async fn test_create_reservation_needs_admin() {
+    let mut app = spawn_app_with_admin().await;
+    app.token = Some(USER_TOKEN.to_string());
+    let node_id = app
+        .register_node(get_node_info(42))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+
+    let res = app.create_reservation(reservation(&node_id, 0, 3600)).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn test_create_reservation_rejects_overlap_and_unknown_nodes() {
+    let mut app = spawn_app_with_admin().await;
+    app.token = Some(ADMIN_TOKEN.to_string());
+    let node_id = app
+        .register_node(get_node_info(42))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    app.create_reservation(reservation(&node_id, 0, 3600))
+        .await
+        .unwrap();
+
+    let err = app
+        .create_reservation(reservation(&node_id, 1800, 7200))
+        .await
+        .unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    let err = app
+        .create_reservation(reservation("unknown", 0, 3600))
+        .await
+        .unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    // back to back is fine
+    app.create_reservation(reservation(&node_id, 3600, 7200))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_reserved_node_only_runs_jobs_of_its_users() {
+    let mut app = spawn_app_with_admin().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.token = Some(ADMIN_TOKEN.to_string());
+    let node_id = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    app.create_reservation(reservation(&node_id, 0, 7 * 24 * 3600))
+        .await
+        .unwrap();
+
+    app.token = Some(OTHER_TOKEN.to_string());
+    app.submit_job(get_job_submission()).await.unwrap();
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    app.token = Some(USER_TOKEN.to_string());
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, job_id);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_job_does_not_run_into_reservation() {
+    let mut app = spawn_app_with_admin().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.token = Some(ADMIN_TOKEN.to_string());
+    let node_id = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    // starts in an hour
+    app.create_reservation(reservation(&node_id, 3600, 7200))
+        .await
+        .unwrap();
+
+    // would still be running when it starts
+    app.token = Some(OTHER_TOKEN.to_string());
+    let long_job = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    // ends in time
+    app.cancel_job(proto::CancelJobRequest {
+        job_id: long_job,
+        user: "someone-else".to_string(),
+    })
+    .await
+    .unwrap();
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().time = 30;
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+    let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, job_id);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_list_and_delete_reservations() {
+    let mut app = spawn_app_with_admin().await;
+    app.token = Some(ADMIN_TOKEN.to_string());
+    let node_id = app
+        .register_node(get_node_info(42))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    let created = app
+        .create_reservation(reservation(&node_id, 0, 3600))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(created.created_by, ADMIN);
+
+    // anyone may look at them
+    app.token = Some(USER_TOKEN.to_string());
+    let list = app.list_reservations().await.unwrap().into_inner();
+    assert_eq!(list.reservations, vec![created.clone()]);
+
+    app.token = Some(ADMIN_TOKEN.to_string());
+    app.delete_reservation(created.id).await.unwrap();
+    let list = app.list_reservations().await.unwrap().into_inner();
+    assert!(list.reservations.is_empty());
+
+    let err = app.delete_reservation(created.id).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
@@ -1,9 +1,17 @@
 use crate::{
     constants::*,
-    helpers::{get_job_submission, get_node_info, spawn_app},
-    mock_worker::setup_mock_worker,
+    helpers::{
+        get_job_submission, get_node_info, spawn_app, spawn_app_on_database,
+        spawn_app_with_partitions, spawn_app_with_scheduler, TestApp,
+    },
+    mock_worker::{setup_mock_worker, setup_rejecting_mock_worker, setup_slow_mock_worker},
 };
 use melon_common::{proto, JobStatus};
+use melond::settings::{PartitionSettings, PlacementPolicy, SchedulerSettings};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::time::timeout;
 use tonic::Status;
 
 #[tokio::test]
@@ -13,6 +21,77 @@ async fn worker_registration_works() {
     assert!(res.is_ok())
 }
 
+#[tokio::test]
+async fn test_register_node_with_invalid_address() {
+    let app = spawn_app().await;
+    let mut info = get_node_info(42);
+    info.address = "not a uri".to_string();
+
+    let res = app.register_node(info).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_register_same_node_twice() {
+    let app = spawn_app().await;
+    let first = app.register_node(get_node_info(42)).await.unwrap();
+    let mut info = get_node_info(42);
+    info.resources.as_mut().unwrap().cpu_count = 16;
+
+    let second = app.register_node(info).await.unwrap();
+
+    assert_eq!(first.get_ref().node_id, second.get_ref().node_id);
+    let nodes = app.list_nodes().await.unwrap();
+    let nodes = &nodes.get_ref().nodes;
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].total_resources.unwrap().cpu_count, 16);
+}
+
+#[tokio::test]
+async fn test_jobs_of_restarted_node_are_failed() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    let res = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let node_id = res.get_ref().node_id.clone();
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let _ = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    // the worker lost its jobs when it restarted
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Failed
+    );
+    assert_eq!(
+        job.get_ref().message,
+        Some(format!("Node {} restarted", node_id))
+    );
+    let nodes = app.list_nodes().await.unwrap();
+    let used = nodes.get_ref().nodes[0].used_resources.unwrap();
+    assert_eq!(used.cpu_count, 0);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
 #[tokio::test]
 async fn worker_heartbeat_works() {
     let app = spawn_app().await;
@@ -55,7 +134,10 @@ async fn test_list_pending_job() {
 
     assert_eq!(first_job.id, job_id);
     assert_eq!(first_job.user, submission.user);
-    assert_eq!(JobStatus::from(first_job.status), JobStatus::Pending);
+    assert_eq!(
+        JobStatus::try_from(first_job.status).unwrap(),
+        JobStatus::Pending
+    );
 }
 
 #[tokio::test]
@@ -76,7 +158,10 @@ async fn test_list_running_job() {
 
     assert_eq!(first_job.id, job_id);
     assert_eq!(first_job.user, submission.user);
-    assert_eq!(JobStatus::from(first_job.status), JobStatus::Running);
+    assert_eq!(
+        JobStatus::try_from(first_job.status).unwrap(),
+        JobStatus::Running
+    );
 
     mock_setup.server_notifier.send(()).unwrap();
     mock_setup.server_handle.await.unwrap();
@@ -101,6 +186,43 @@ async fn test_successful_job_assignment() {
     mock_setup.server_handle.await.unwrap();
 }
 
+#[tokio::test]
+async fn test_interactive_job_gets_attach_token() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let mut submission = get_job_submission();
+    submission.interactive = true;
+    submission.max_retries = 3;
+    let res = app.submit_job(submission.clone()).await.unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    assert!(!res.get_ref().attach_token.is_empty());
+    assert!(job_assignment.interactive);
+    assert_eq!(job_assignment.attach_token, res.get_ref().attach_token);
+    // the client is gone once the job ends, so it is never retried
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest {
+            job_id: job_assignment.job_id,
+        })
+        .await
+        .unwrap();
+    assert!(job.get_ref().interactive);
+    assert_eq!(job.get_ref().max_retries, 0);
+
+    // multi-node jobs can't be attached to
+    submission.req_res.as_mut().unwrap().node_count = 2;
+    let err = app.submit_job(submission).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
 #[tokio::test]
 async fn test_submit_job_results() {
     let app = spawn_app().await;
@@ -114,6 +236,7 @@ async fn test_submit_job_results() {
     let job_result = proto::JobResult {
         job_id: job_assignment.job_id,
         status: 1,
+        ..Default::default()
     };
     let res = app.submit_job_result(job_result).await;
     assert!(res.is_ok());
@@ -135,6 +258,7 @@ async fn test_submit_job_fails_for_unknown_id() {
     let job_result = proto::JobResult {
         job_id: 99999999,
         status: 1,
+        ..Default::default()
     };
     let res = app.submit_job_result(job_result).await;
     assert!(res.is_err());
@@ -143,6 +267,51 @@ async fn test_submit_job_fails_for_unknown_id() {
     mock_setup.server_handle.await.unwrap();
 }
 
+#[tokio::test]
+async fn test_repeated_job_result_is_acked() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    let info = get_node_info(mock_setup.port);
+    app.register_node(info).await.unwrap();
+    app.submit_job(get_job_submission()).await.unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    let job_id = job_assignment.job_id;
+
+    let job_result = proto::JobResult {
+        job_id,
+        status: proto::JobStatus::Completed.into(),
+        ..Default::default()
+    };
+    app.submit_job_result(job_result.clone()).await.unwrap();
+
+    // the worker retries once the job is stored, e.g. because the first ack got lost
+    let mut status = JobStatus::Running;
+    for _ in 0..20 {
+        let request = proto::GetJobInfoRequest { job_id };
+        if let Ok(res) = app.get_job_info(request).await {
+            status = JobStatus::try_from(res.get_ref().status).unwrap();
+            if status == JobStatus::Completed {
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(status, JobStatus::Completed);
+
+    app.submit_job_result(job_result).await.unwrap();
+    let res = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(res.get_ref().status).unwrap(),
+        JobStatus::Completed
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
 #[tokio::test]
 async fn test_cancel_pending_job_successfully() {
     let app = spawn_app().await;
@@ -448,6 +617,7 @@ async fn test_mshow_failed() {
     let job_result = proto::JobResult {
         job_id: job_assignment.job_id,
         status: proto::JobStatus::Failed.into(),
+        ..Default::default()
     };
     let _ = app.submit_job_result(job_result).await.unwrap();
 
@@ -476,6 +646,7 @@ async fn test_mshow_completed() {
     let job_result = proto::JobResult {
         job_id: job_assignment.job_id,
         status: proto::JobStatus::Completed.into(),
+        ..Default::default()
     };
     let _ = app.submit_job_result(job_result).await.unwrap();
 
@@ -491,6 +662,77 @@ async fn test_mshow_completed() {
     mock_setup.server_handle.await.unwrap();
 }
 
+#[tokio::test]
+async fn test_mshow_resource_usage() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    let info = get_node_info(mock_setup.port);
+    app.register_node(info).await.unwrap();
+    let submission = get_job_submission();
+    let _ = app.submit_job(submission.clone()).await.unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    let job_id = job_assignment.job_id;
+    let job_result = proto::JobResult {
+        job_id,
+        status: proto::JobStatus::Completed.into(),
+        max_rss_bytes: Some(512 * 1024 * 1024),
+        cpu_seconds: Some(12.5),
+        ..Default::default()
+    };
+    let _ = app.submit_job_result(job_result).await.unwrap();
+
+    // the usage reported by the worker is stored with the finished job
+    let request = proto::GetJobInfoRequest { job_id };
+    let res = app.get_job_info(request).await.unwrap();
+    let job: melon_common::Job = res.get_ref().into();
+
+    assert_eq!(job.max_rss_bytes, Some(512 * 1024 * 1024));
+    assert_eq!(job.cpu_seconds, Some(12.5));
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_mshow_failure_reason() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let _ = app.submit_job(get_job_submission()).await.unwrap();
+    let job_id = mock_setup
+        .job_assignment_receiver
+        .recv()
+        .await
+        .unwrap()
+        .job_id;
+    let job_result = proto::JobResult {
+        job_id,
+        status: proto::JobStatus::Failed.into(),
+        exit_code: Some(2),
+        message: Some("Process ended with exit status: 2, stderr:\nNo such file".to_string()),
+        ..Default::default()
+    };
+    app.submit_job_result(job_result).await.unwrap();
+
+    // the reason reported by the worker is stored with the finished job
+    let res = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    let job: melon_common::Job = res.get_ref().into();
+    assert_eq!(job.status, JobStatus::Failed);
+    assert_eq!(job.exit_code, Some(2));
+    assert_eq!(
+        job.message.as_deref(),
+        Some("Process ended with exit status: 2, stderr:\nNo such file")
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
 #[tokio::test]
 async fn test_mshow_unknown_id() {
     let app = spawn_app().await;
@@ -509,3 +751,2354 @@ async fn test_mshow_unknown_id() {
         }
     }
 }
+
+#[tokio::test]
+async fn test_higher_priority_job_is_assigned_first() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+
+    let mut low = get_job_submission();
+    low.priority = Some(10);
+    let low_id = app.submit_job(low).await.unwrap().get_ref().job_id;
+
+    let mut high = get_job_submission();
+    high.priority = Some(90);
+    let high_id = app.submit_job(high).await.unwrap().get_ref().job_id;
+
+    // register the node after both jobs are queued
+    let info = get_node_info(mock_setup.port);
+    app.register_node(info).await.unwrap();
+
+    let first = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    let second = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(first.job_id, high_id);
+    assert_eq!(second.job_id, low_id);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_list_pending_jobs_by_priority() {
+    let app = spawn_app().await;
+
+    let first_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let mut urgent = get_job_submission();
+    urgent.priority = Some(100);
+    let urgent_id = app.submit_job(urgent).await.unwrap().get_ref().job_id;
+    let last_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    let res = app.list_jobs().await.unwrap();
+    let ids: Vec<u64> = res.get_ref().jobs.iter().map(|j| j.id).collect();
+    assert_eq!(ids, vec![urgent_id, first_id, last_id]);
+    assert_eq!(res.get_ref().jobs[0].priority, 100);
+    assert_eq!(res.get_ref().jobs[1].priority, 50);
+}
+
+#[tokio::test]
+async fn test_gpu_job_is_assigned_to_gpu_node() {
+    let app = spawn_app().await;
+    let mut cpu_setup = setup_mock_worker().await;
+    let mut gpu_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(cpu_setup.port))
+        .await
+        .unwrap();
+
+    // no registered node has GPUs yet
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().gpu_count = 2;
+    submission.force = true;
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    // the node without GPUs must not receive the job
+    let res = timeout(
+        Duration::from_millis(750),
+        cpu_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    let mut info = get_node_info(gpu_setup.port);
+    info.resources.as_mut().unwrap().gpu_count = 2;
+    app.register_node(info).await.unwrap();
+
+    let assignment = gpu_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, job_id);
+    assert_eq!(assignment.req_res.unwrap().gpu_count, 2);
+
+    cpu_setup.server_notifier.send(()).unwrap();
+    cpu_setup.server_handle.await.unwrap();
+    gpu_setup.server_notifier.send(()).unwrap();
+    gpu_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_constrained_job_is_assigned_to_labeled_node() {
+    let app = spawn_app().await;
+    let mut plain_setup = setup_mock_worker().await;
+    let mut ssd_setup = setup_mock_worker().await;
+    let mut info = get_node_info(plain_setup.port);
+    info.labels = vec!["ssd".to_string()];
+    app.register_node(info).await.unwrap();
+    let mut info = get_node_info(ssd_setup.port);
+    info.labels = vec!["avx512".to_string(), "ssd".to_string()];
+    app.register_node(info).await.unwrap();
+
+    let mut submission = get_job_submission();
+    submission.constraints = vec!["ssd".to_string(), "avx512".to_string()];
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    // only the node with all labels may receive the job
+    let assignment = ssd_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, job_id);
+    let res = timeout(
+        Duration::from_millis(750),
+        plain_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(job.get_ref().constraints, vec!["ssd", "avx512"]);
+
+    plain_setup.server_notifier.send(()).unwrap();
+    plain_setup.server_handle.await.unwrap();
+    ssd_setup.server_notifier.send(()).unwrap();
+    ssd_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reject_job_with_unknown_constraint() {
+    let app = spawn_app().await;
+    let mut info = get_node_info(42);
+    info.labels = vec!["ssd".to_string()];
+    app.register_node(info).await.unwrap();
+
+    let mut submission = get_job_submission();
+    submission.constraints = vec!["gpu-a100".to_string()];
+    let err = app.submit_job(submission.clone()).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert!(status.message().contains("gpu-a100"));
+
+    // e.g. for nodes that join later
+    submission.force = true;
+    assert!(app.submit_job(submission).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_dependent_job_waits_for_parent() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    let info = get_node_info(mock_setup.port);
+    app.register_node(info).await.unwrap();
+
+    let parent_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let parent = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(parent.job_id, parent_id);
+
+    let mut child = get_job_submission();
+    child.dependencies = vec![parent_id];
+    let child_id = app.submit_job(child).await.unwrap().get_ref().job_id;
+
+    // the child must not be assigned while the parent is running
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    let job_result = proto::JobResult {
+        job_id: parent_id,
+        status: proto::JobStatus::Completed.into(),
+        ..Default::default()
+    };
+    app.submit_job_result(job_result).await.unwrap();
+
+    let child = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(child.job_id, child_id);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dependent_job_is_cancelled_when_parent_fails() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    let info = get_node_info(mock_setup.port);
+    app.register_node(info).await.unwrap();
+
+    let parent_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let _ = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    let mut child = get_job_submission();
+    child.dependencies = vec![parent_id];
+    let child_id = app.submit_job(child).await.unwrap().get_ref().job_id;
+
+    let job_result = proto::JobResult {
+        job_id: parent_id,
+        status: proto::JobStatus::Failed.into(),
+        ..Default::default()
+    };
+    app.submit_job_result(job_result).await.unwrap();
+
+    let mut status = JobStatus::Pending;
+    for _ in 0..20 {
+        let request = proto::GetJobInfoRequest { job_id: child_id };
+        let res = app.get_job_info(request).await.unwrap();
+        status = JobStatus::try_from(res.get_ref().status).unwrap();
+        if status == JobStatus::Cancelled {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(status, JobStatus::Cancelled);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reject_unknown_dependency() {
+    let app = spawn_app().await;
+    let mut submission = get_job_submission();
+    submission.dependencies = vec![424242];
+
+    let res = app.submit_job(submission).await;
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn test_get_job_output_from_worker() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    let info = get_node_info(mock_setup.port);
+    app.register_node(info).await.unwrap();
+    let _ = app.submit_job(get_job_submission()).await.unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    let job_id = job_assignment.job_id;
+
+    let request = proto::JobOutputRequest {
+        job_id,
+        stream: proto::OutputStream::Stderr.into(),
+        offset: None,
+        tail_bytes: None,
+    };
+    let frames = app.get_job_output(request).await.unwrap();
+    // the frames of the worker are passed on one by one
+    let offsets: Vec<u64> = frames.iter().map(|frame| frame.offset).collect();
+    assert_eq!(offsets, vec![0, 7, 10, 14]);
+    let content: Vec<u8> = frames.into_iter().flat_map(|frame| frame.content).collect();
+    assert_eq!(
+        String::from_utf8(content).unwrap(),
+        format!("Stderr of job {}", job_id)
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reject_job_output_for_pending_job() {
+    let app = spawn_app().await;
+    let res = app.submit_job(get_job_submission()).await.unwrap();
+    let job_id = res.get_ref().job_id;
+
+    let request = proto::JobOutputRequest {
+        job_id,
+        stream: proto::OutputStream::Stdout.into(),
+        offset: None,
+        tail_bytes: None,
+    };
+    let res = app.get_job_output(request).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_draining_node_receives_no_new_jobs() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    let info = get_node_info(mock_setup.port);
+    let node_id = app
+        .register_node(info)
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+
+    let running_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let running = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(running.job_id, running_id);
+
+    app.drain_node(node_id.clone()).await.unwrap();
+    app.submit_job(get_job_submission()).await.unwrap();
+
+    // the queued job must not land on the draining node
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    // the running job keeps the node alive
+    let res = app.list_nodes().await.unwrap();
+    let node = &res.get_ref().nodes[0];
+    assert_eq!(node.id, node_id);
+    assert_eq!(node.status, proto::NodeStatus::Draining as i32);
+    assert_eq!(node.running_jobs, 1);
+
+    let job_result = proto::JobResult {
+        job_id: running_id,
+        status: proto::JobStatus::Completed.into(),
+        ..Default::default()
+    };
+    app.submit_job_result(job_result).await.unwrap();
+
+    // the node is removed once its last job has finished
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let res = app.list_nodes().await.unwrap();
+    assert!(res.get_ref().nodes.is_empty());
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_list_nodes_reports_resources() {
+    let app = spawn_app().await;
+    let info = get_node_info(42);
+    let res = app.register_node(info.clone()).await.unwrap();
+    let node_id = res.get_ref().node_id.clone();
+
+    let res = app.list_nodes().await.unwrap();
+    let node = &res.get_ref().nodes[0];
+    assert_eq!(node.id, node_id);
+    assert_eq!(node.address, info.address);
+    assert_eq!(node.status, proto::NodeStatus::Available as i32);
+    assert_eq!(node.total_resources, info.resources);
+    assert_eq!(node.used_resources.unwrap().cpu_count, 0);
+    assert_eq!(node.running_jobs, 0);
+    assert!(node.heartbeat_age_secs < 5);
+}
+
+#[tokio::test]
+async fn test_drain_idle_node_removes_it() {
+    let app = spawn_app().await;
+    let res = app.register_node(get_node_info(42)).await.unwrap();
+    let node_id = res.get_ref().node_id.clone();
+
+    app.drain_node(node_id).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let res = app.list_nodes().await.unwrap();
+    assert!(res.get_ref().nodes.is_empty());
+}
+
+#[tokio::test]
+async fn test_reject_drain_unknown_node() {
+    let app = spawn_app().await;
+
+    let res = app.drain_node("UNKNOWN".to_string()).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+fn short_partition(max_cpus: u32, nodes: Vec<String>) -> HashMap<String, PartitionSettings> {
+    let partition = PartitionSettings {
+        max_cpus: Some(max_cpus),
+        max_memory: None,
+        nodes,
+    };
+    HashMap::from([("short".to_string(), partition)])
+}
+
+#[tokio::test]
+async fn test_partition_cap_limits_running_jobs() {
+    let app = spawn_app_with_partitions(short_partition(TEST_COU_COUNT, vec![])).await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let mut submission = get_job_submission();
+    submission.partition = Some("short".to_string());
+    let first_id = app
+        .submit_job(submission.clone())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let second_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    let first = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(first.job_id, first_id);
+
+    // the node has room, but the partition is at its cap
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    // jobs of the default partition are not affected
+    let default_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let default = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(default.job_id, default_id);
+
+    let job_result = proto::JobResult {
+        job_id: first_id,
+        status: proto::JobStatus::Completed.into(),
+        ..Default::default()
+    };
+    app.submit_job_result(job_result).await.unwrap();
+
+    let second = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(second.job_id, second_id);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_partition_jobs_only_run_on_member_nodes() {
+    let mut other_setup = setup_mock_worker().await;
+    let mut member_setup = setup_mock_worker().await;
+    let member_info = get_node_info(member_setup.port);
+    let app =
+        spawn_app_with_partitions(short_partition(8, vec![member_info.address.clone()])).await;
+    app.register_node(get_node_info(other_setup.port))
+        .await
+        .unwrap();
+
+    let mut submission = get_job_submission();
+    submission.partition = Some("short".to_string());
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    let res = timeout(
+        Duration::from_millis(750),
+        other_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    app.register_node(member_info).await.unwrap();
+    let assignment = member_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, job_id);
+
+    let res = app.get_job_info(proto::GetJobInfoRequest { job_id }).await;
+    assert_eq!(res.unwrap().get_ref().partition, "short");
+
+    other_setup.server_notifier.send(()).unwrap();
+    other_setup.server_handle.await.unwrap();
+    member_setup.server_notifier.send(()).unwrap();
+    member_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reject_unknown_partition() {
+    let app = spawn_app().await;
+    let mut submission = get_job_submission();
+    submission.partition = Some("unknown".to_string());
+
+    let res = app.submit_job(submission).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_reject_job_exceeding_partition_cap() {
+    let app = spawn_app_with_partitions(short_partition(TEST_COU_COUNT, vec![])).await;
+    let mut submission = get_job_submission();
+    submission.partition = Some("short".to_string());
+    submission.req_res.as_mut().unwrap().cpu_count = TEST_COU_COUNT + 1;
+
+    let res = app.submit_job(submission).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_reject_job_larger_than_any_node() {
+    let app = spawn_app().await;
+    app.register_node(get_node_info(42)).await.unwrap();
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().cpu_count = 1000;
+
+    let res = app.submit_job(submission).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert!(status.message().contains("1000 CPUs"));
+}
+
+#[tokio::test]
+async fn test_force_submit_job_larger_than_any_node() {
+    let app = spawn_app().await;
+    app.register_node(get_node_info(42)).await.unwrap();
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().cpu_count = 1000;
+    submission.force = true;
+
+    let res = app.submit_job(submission).await;
+
+    assert!(res.is_ok());
+}
+
+#[tokio::test]
+async fn test_job_is_assigned_after_worker_rejected_it() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_rejecting_mock_worker(1).await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    // the first assignment is rejected, the next tick tries again
+    let job_assignment = timeout(
+        Duration::from_secs(2),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(job_assignment.job_id, job_id);
+    assert_eq!(mock_setup.assign_attempts.load(Ordering::SeqCst), 2);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rejected_job_moves_to_other_node() {
+    let app = spawn_app().await;
+    let rejecting_setup = setup_rejecting_mock_worker(usize::MAX).await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(rejecting_setup.port))
+        .await
+        .unwrap();
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    let job_assignment = timeout(
+        Duration::from_secs(2),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(job_assignment.job_id, job_id);
+    // the rejecting node is asked at most once per tick
+    assert!(rejecting_setup.assign_attempts.load(Ordering::SeqCst) <= 1);
+
+    rejecting_setup.server_notifier.send(()).unwrap();
+    rejecting_setup.server_handle.await.unwrap();
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_jobs_are_assigned_to_several_nodes_at_once() {
+    const NODES: usize = 4;
+    let assign_delay = Duration::from_secs(1);
+    let app = spawn_app().await;
+    let mut setups = vec![];
+    for _ in 0..NODES {
+        let setup = setup_slow_mock_worker(assign_delay).await;
+        app.register_node(get_node_info(setup.port)).await.unwrap();
+        setups.push(setup);
+    }
+
+    let started = std::time::Instant::now();
+    let first_job_id = app
+        .submit_jobs(vec![get_job_submission(); NODES])
+        .await
+        .unwrap()
+        .get_ref()
+        .responses[0]
+        .job_id;
+
+    // the queue takes new jobs while the nodes are still answering,
+    // this one waits for another job so it doesn't take a node itself
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let mut submission = get_job_submission();
+    submission.dependencies = vec![first_job_id];
+    timeout(Duration::from_millis(200), app.submit_job(submission))
+        .await
+        .expect("Submitting should not wait for the nodes")
+        .unwrap();
+
+    // one after another, the nodes would take NODES seconds to answer
+    for setup in &mut setups {
+        timeout(Duration::from_secs(3), setup.job_assignment_receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed < assign_delay + Duration::from_millis(2 * 250 + 300),
+        "Assigning took {:?}",
+        elapsed
+    );
+    for setup in &setups {
+        assert_eq!(setup.assign_attempts.load(Ordering::SeqCst), 1);
+    }
+
+    for setup in setups {
+        setup.server_notifier.send(()).unwrap();
+        setup.server_handle.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_dry_run_does_not_queue_job() {
+    let app = spawn_app().await;
+    app.register_node(get_node_info(42)).await.unwrap();
+    let mut submission = get_job_submission();
+    submission.dry_run = true;
+
+    let res = app.submit_job(submission).await.unwrap();
+
+    assert_eq!(res.get_ref().job_id, 0);
+    assert!(res.get_ref().summary.contains("fits on 1 of 1 nodes"));
+    let jobs = app.list_jobs().await.unwrap();
+    assert!(jobs.get_ref().jobs.is_empty());
+}
+
+#[tokio::test]
+async fn test_dry_run_rejects_job_larger_than_any_node() {
+    let app = spawn_app().await;
+    app.register_node(get_node_info(42)).await.unwrap();
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().cpu_count = 1000;
+    submission.dry_run = true;
+
+    let res = app.submit_job(submission).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_held_job_is_not_assigned_until_released() {
+    let app = spawn_app().await;
+    let submission = get_job_submission();
+    let job_id = app
+        .submit_job(submission.clone())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    app.hold_job(proto::HoldJobRequest {
+        job_id,
+        user: submission.user.clone(),
+    })
+    .await
+    .unwrap();
+
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    let jobs = app.list_jobs().await.unwrap();
+    let job = jobs.get_ref().jobs.first().unwrap();
+    assert_eq!(JobStatus::try_from(job.status).unwrap(), JobStatus::Held);
+
+    app.release_job(proto::ReleaseJobRequest {
+        job_id,
+        user: submission.user,
+    })
+    .await
+    .unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(job_assignment.job_id, job_id);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_hold_running_job() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let submission = get_job_submission();
+    let job_id = app
+        .submit_job(submission.clone())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let _ = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    let res = app
+        .hold_job(proto::HoldJobRequest {
+            job_id,
+            user: submission.user,
+        })
+        .await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_hold_job_of_other_user() {
+    let app = spawn_app().await;
+    let submission = get_job_submission();
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    let res = app
+        .hold_job(proto::HoldJobRequest {
+            job_id,
+            user: "someone-else".to_string(),
+        })
+        .await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn test_release_job_that_is_not_held() {
+    let app = spawn_app().await;
+    let submission = get_job_submission();
+    let job_id = app
+        .submit_job(submission.clone())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    let res = app
+        .release_job(proto::ReleaseJobRequest {
+            job_id,
+            user: submission.user,
+        })
+        .await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_job_of_lost_node_is_failed() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        health_poll_interval_secs: 1,
+        node_timeout_secs: 1,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    let res = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let node_id = res.get_ref().node_id.clone();
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let _ = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    // the mock worker never sends heartbeats
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let nodes = app.list_nodes().await.unwrap();
+    let node = nodes
+        .get_ref()
+        .nodes
+        .iter()
+        .find(|node| node.id == node_id)
+        .unwrap();
+    assert_eq!(node.status, proto::NodeStatus::Offline as i32);
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Failed
+    );
+    assert_eq!(
+        job.get_ref().message,
+        Some(format!("Node {} is lost", node_id))
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_job_of_lost_node_is_requeued_with_retries_left() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        health_poll_interval_secs: 1,
+        node_timeout_secs: 1,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let mut submission = get_job_submission();
+    submission.max_retries = 1;
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+    let _ = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    // the mock worker never sends heartbeats
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    let job: melon_common::Job = job.get_ref().into();
+    assert_eq!(job.status, JobStatus::Pending);
+    assert_eq!(job.retry_count, 1);
+    assert_eq!(job.attempts.len(), 1);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_job_missing_from_heartbeat_is_failed() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        lost_job_grace_secs: 0,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    let res = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let node_id = res.get_ref().node_id.clone();
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let _ = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    // the worker no longer knows the job, e.g. after a restart
+    app.send_heartbeat_with_usage(node_id.clone(), proto::NodeUsage::default())
+        .await
+        .unwrap();
+
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Failed
+    );
+    let nodes = app.list_nodes().await.unwrap();
+    let node = nodes
+        .get_ref()
+        .nodes
+        .iter()
+        .find(|node| node.id == node_id)
+        .unwrap();
+    let used = node.used_resources.as_ref().unwrap();
+    assert_eq!(used.cpu_count, 0);
+    assert_eq!(used.memory, 0);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_job_reported_in_heartbeat_keeps_running() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        lost_job_grace_secs: 0,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    let res = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let node_id = res.get_ref().node_id.clone();
+    let submission = get_job_submission();
+    let req_cpus = submission.req_res.as_ref().unwrap().cpu_count;
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+    let _ = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    let usage = proto::NodeUsage {
+        running_job_ids: vec![job_id],
+        allocated_cpus: req_cpus,
+        free_memory: 0,
+        max_jobs: Some(4),
+        active_jobs: 1,
+    };
+    app.send_heartbeat_with_usage(node_id.clone(), usage)
+        .await
+        .unwrap();
+
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Running
+    );
+    let nodes = app.list_nodes().await.unwrap();
+    let node = nodes
+        .get_ref()
+        .nodes
+        .iter()
+        .find(|node| node.id == node_id)
+        .unwrap();
+    assert_eq!(node.used_resources.as_ref().unwrap().cpu_count, req_cpus);
+    assert_eq!(node.max_jobs, Some(4));
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_failed_job_is_requeued_until_retries_run_out() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let mut submission = get_job_submission();
+    submission.max_retries = 1;
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    for _ in 0..2 {
+        let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+        assert_eq!(job_assignment.job_id, job_id);
+        let job_result = proto::JobResult {
+            job_id,
+            status: proto::JobStatus::Failed.into(),
+            ..Default::default()
+        };
+        app.submit_job_result(job_result).await.unwrap();
+    }
+
+    // the second failure exhausts the retries
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    let job: melon_common::Job = job.get_ref().into();
+    assert_eq!(job.status, JobStatus::Failed);
+    assert_eq!(job.retry_count, 1);
+    assert_eq!(job.attempts.len(), 1);
+    assert_eq!(job.attempts[0].status, JobStatus::Failed);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_job_env_is_passed_to_worker() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let mut submission = get_job_submission();
+    submission
+        .env
+        .insert("MODE".to_string(), "fast".to_string());
+
+    app.submit_job(submission.clone()).await.unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    // the scheduler adds the hosts the job runs on
+    let mut expected = submission.env.clone();
+    expected.insert("MELON_NODELIST".to_string(), "::1".to_string());
+    assert_eq!(job_assignment.env, expected);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_list_user_jobs() {
+    let app = spawn_app().await;
+    let submission = get_job_submission();
+    let own_id = app
+        .submit_job(submission.clone())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let mut other = get_job_submission();
+    other.user = "someone-else".to_string();
+    app.submit_job(other).await.unwrap();
+
+    let res = app.list_user_jobs(&submission.user).await.unwrap();
+    let jobs = &res.get_ref().jobs;
+
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].id, own_id);
+}
+
+fn get_sized_submission(cpu_count: u32, time: u32) -> proto::JobSubmission {
+    let mut submission = get_job_submission();
+    let req_res = submission.req_res.as_mut().unwrap();
+    req_res.cpu_count = cpu_count;
+    req_res.memory = 1024 * 1024;
+    req_res.time = time;
+    submission
+}
+
+#[tokio::test]
+async fn test_backfill_jobs_that_finish_before_waiting_job() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    // occupy half of the node for an hour
+    let running_id = app
+        .submit_job(get_sized_submission(4, 60))
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let running = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(running.job_id, running_id);
+
+    // the big job has to wait for the running one, the long job would delay it
+    let mut big = get_sized_submission(8, 60);
+    big.priority = Some(90);
+    app.submit_job(big).await.unwrap();
+    app.submit_job(get_sized_submission(2, 120)).await.unwrap();
+    let short_id = app
+        .submit_job(get_sized_submission(2, 10))
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+
+    let backfilled = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(backfilled.job_id, short_id);
+
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unschedulable_job_does_not_block_queue() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        backfill: false,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    // no node will ever be large enough
+    let mut big = get_sized_submission(16, 60);
+    big.force = true;
+    app.submit_job(big).await.unwrap();
+
+    for _ in 0..3 {
+        let job_id = app
+            .submit_job(get_sized_submission(2, 120))
+            .await
+            .unwrap()
+            .get_ref()
+            .job_id;
+        let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+        assert_eq!(assignment.job_id, job_id);
+    }
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_no_backfill_keeps_queue_order() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        backfill: false,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    app.submit_job(get_sized_submission(4, 60)).await.unwrap();
+    mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    let mut big = get_sized_submission(8, 60);
+    big.priority = Some(90);
+    app.submit_job(big).await.unwrap();
+    app.submit_job(get_sized_submission(2, 10)).await.unwrap();
+
+    // the short job would fit, but must not skip ahead
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_stores_running_jobs() {
+    let tmp_dir = tempdir::TempDir::new("melon-shutdown").unwrap();
+    let db_path = tmp_dir.path().join("melon.db");
+    let db_path = db_path.to_str().unwrap();
+
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let (app, handle) = spawn_app_on_database(db_path, async {
+        stop_rx.await.ok();
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    stop_tx.send(()).unwrap();
+    handle.await.unwrap();
+
+    // a restarted scheduler knows what happened to the job
+    let (app, _handle) = spawn_app_on_database(db_path, std::future::pending()).await;
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Failed
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_multi_node_job_rejected_without_enough_nodes() {
+    let app = spawn_app().await;
+    app.register_node(get_node_info(42)).await.unwrap();
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().node_count = 2;
+
+    let res = app.submit_job(submission).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_multi_node_job_waits_for_all_nodes() {
+    let app = spawn_app().await;
+    let mut first = setup_mock_worker().await;
+    let mut second = setup_mock_worker().await;
+    app.register_node(get_node_info(first.port)).await.unwrap();
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().node_count = 2;
+    submission.force = true;
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    // a single free node is not enough
+    let res = timeout(
+        Duration::from_millis(750),
+        first.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    app.register_node(get_node_info(second.port)).await.unwrap();
+
+    let first_assignment = first.job_assignment_receiver.recv().await.unwrap();
+    let second_assignment = second.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(first_assignment.job_id, job_id);
+    assert_eq!(second_assignment.job_id, job_id);
+    assert_eq!(
+        first_assignment.env.get("MELON_NODELIST"),
+        Some(&"::1,::1".to_string())
+    );
+    assert_eq!(first_assignment.env, second_assignment.env);
+
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    let job = job.get_ref();
+    assert_eq!(JobStatus::try_from(job.status).unwrap(), JobStatus::Running);
+    assert!(!job.assigned_node.is_empty());
+    assert_eq!(job.extra_nodes.len(), 1);
+    let nodes = app.list_nodes().await.unwrap();
+    for node in &nodes.get_ref().nodes {
+        assert_eq!(node.running_jobs, 1);
+        assert_eq!(node.used_resources.unwrap().cpu_count, TEST_COU_COUNT);
+    }
+
+    first.server_notifier.send(()).unwrap();
+    first.server_handle.await.unwrap();
+    second.server_notifier.send(()).unwrap();
+    second.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_multi_node_job_ends_with_first_node() {
+    let app = spawn_app().await;
+    let mut first = setup_mock_worker().await;
+    let mut second = setup_mock_worker().await;
+    app.register_node(get_node_info(first.port)).await.unwrap();
+    app.register_node(get_node_info(second.port)).await.unwrap();
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().node_count = 2;
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+    first.job_assignment_receiver.recv().await.unwrap();
+    second.job_assignment_receiver.recv().await.unwrap();
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    let head = job.get_ref().assigned_node.clone();
+    let peer = job.get_ref().extra_nodes[0].clone();
+
+    // the other node finishing its part doesn't end the job
+    app.submit_job_result(proto::JobResult {
+        job_id,
+        status: proto::JobStatus::Completed.into(),
+        node_id: peer,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Running
+    );
+
+    app.submit_job_result(proto::JobResult {
+        job_id,
+        status: proto::JobStatus::Completed.into(),
+        node_id: head,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Completed
+    );
+    let nodes = app.list_nodes().await.unwrap();
+    for node in &nodes.get_ref().nodes {
+        assert_eq!(node.running_jobs, 0);
+        assert_eq!(node.used_resources.unwrap().cpu_count, 0);
+    }
+
+    first.server_notifier.send(()).unwrap();
+    first.server_handle.await.unwrap();
+    second.server_notifier.send(()).unwrap();
+    second.server_handle.await.unwrap();
+}
+
+fn capped_scheduler() -> SchedulerSettings {
+    SchedulerSettings {
+        max_job_time_mins: Some(TEST_TIME_MINS),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_reject_job_over_max_time() {
+    let app = spawn_app_with_scheduler(capped_scheduler()).await;
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().time = TEST_TIME_MINS + 1;
+
+    let res = app.submit_job(submission).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert!(status
+        .message()
+        .contains(&format!("maximum of {} minutes", TEST_TIME_MINS)));
+}
+
+#[tokio::test]
+async fn test_reject_extension_over_max_time() {
+    let app = spawn_app_with_scheduler(capped_scheduler()).await;
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().time = TEST_TIME_MINS - 10;
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    // extensions add up, the second one would pass the cap
+    let request = proto::ExtendJobRequest {
+        job_id,
+        user: TEST_USER.to_string(),
+        extension_mins: 10,
+    };
+    app.extend_job(request.clone()).await.unwrap();
+    let res = app.extend_job(request).await;
+
+    let err = res.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(job.get_ref().req_res.unwrap().time, TEST_TIME_MINS);
+}
+
+#[tokio::test]
+async fn test_hung_worker_does_not_block_scheduler() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        worker_timeout_secs: 1,
+        ..Default::default()
+    })
+    .await;
+    // accepts connections, but never answers
+    let listener = tokio::net::TcpListener::bind("[::1]:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let hung_worker = tokio::spawn(async move {
+        let mut connections = vec![];
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    });
+    let node_id = app
+        .register_node(get_node_info(port))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // submissions, heartbeats and lookups go on while the assignment waits for the worker
+    let nodes = timeout(Duration::from_millis(500), async {
+        app.submit_job(get_job_submission()).await.unwrap();
+        app.send_heartbeat_with_usage(node_id.clone(), proto::NodeUsage::default())
+            .await
+            .unwrap();
+        app.list_nodes().await.unwrap()
+    })
+    .await
+    .expect("The scheduler should not wait for the worker");
+    assert_eq!(nodes.get_ref().nodes.len(), 1);
+    // the heartbeat keeps the resources of the job that is sent to the node
+    assert_eq!(
+        nodes.get_ref().nodes[0].used_resources.unwrap().cpu_count,
+        TEST_COU_COUNT
+    );
+
+    // the assignment gives up and the job stays queued
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Pending
+    );
+
+    hung_worker.abort();
+}
+
+#[tokio::test]
+async fn test_slow_cancellation_does_not_block_scheduler() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        worker_timeout_secs: 5,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    let mut job_ids = vec![];
+    for _ in 0..2 {
+        let res = app.submit_job(get_job_submission()).await.unwrap();
+        job_ids.push(res.get_ref().job_id);
+        mock_setup.job_assignment_receiver.recv().await.unwrap();
+    }
+
+    // the mock worker answers the second cancellation only once the first one is received
+    let cancel = |job_id| proto::CancelJobRequest {
+        job_id,
+        user: TEST_USER.to_string(),
+    };
+    app.cancel_job(cancel(job_ids[0])).await.unwrap();
+    let slow_cancel = {
+        let app = app.clone();
+        let request = cancel(job_ids[1]);
+        tokio::spawn(async move { app.cancel_job(request).await.unwrap() })
+    };
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    timeout(Duration::from_millis(500), app.list_nodes())
+        .await
+        .expect("Listing nodes should not wait for the worker")
+        .unwrap();
+    timeout(
+        Duration::from_millis(500),
+        app.submit_job(get_job_submission()),
+    )
+    .await
+    .expect("Submitting should not wait for the worker")
+    .unwrap();
+
+    mock_setup.job_cancellation_receiver.recv().await.unwrap();
+    slow_cancel.await.unwrap();
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id: job_ids[1] })
+        .await
+        .unwrap();
+    assert_eq!(
+        JobStatus::try_from(job.get_ref().status).unwrap(),
+        JobStatus::Cancelled
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_get_node_jobs() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    let node_id = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    let mut job_ids = vec![];
+    for _ in 0..2 {
+        let res = app.submit_job(get_job_submission()).await.unwrap();
+        job_ids.push(res.get_ref().job_id);
+        mock_setup.job_assignment_receiver.recv().await.unwrap();
+    }
+    let job_result = proto::JobResult {
+        job_id: job_ids[0],
+        status: proto::JobStatus::Completed.into(),
+        ..Default::default()
+    };
+    app.submit_job_result(job_result).await.unwrap();
+
+    // the finished job shows up once it is written to the database
+    let mut jobs = vec![];
+    for _ in 0..20 {
+        jobs = app
+            .get_node_jobs(node_id.clone())
+            .await
+            .unwrap()
+            .into_inner()
+            .jobs;
+        if jobs.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let ids: Vec<u64> = jobs.iter().map(|job| job.id).collect();
+    assert_eq!(ids, job_ids);
+    assert_eq!(
+        JobStatus::try_from(jobs[0].status).unwrap(),
+        JobStatus::Completed
+    );
+    assert_eq!(
+        JobStatus::try_from(jobs[1].status).unwrap(),
+        JobStatus::Running
+    );
+
+    let res = app.get_node_jobs("unknown".to_string()).await.unwrap();
+    assert!(res.get_ref().jobs.is_empty());
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_job_past_begin_deadline_is_cancelled() {
+    let app = spawn_app().await;
+    let mut submission = get_job_submission();
+    submission.begin_deadline_mins = Some(0);
+    let job_id = app.submit_job(submission).await.unwrap().get_ref().job_id;
+
+    let mut job = None;
+    for _ in 0..20 {
+        let request = proto::GetJobInfoRequest { job_id };
+        let res = app.get_job_info(request).await.unwrap().into_inner();
+        if JobStatus::try_from(res.status).unwrap() == JobStatus::Cancelled {
+            job = Some(res);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let job = job.expect("Job should have been cancelled");
+    assert!(job.message.unwrap().contains("Deadline exceeded"));
+}
+
+#[tokio::test]
+async fn test_warn_signal_is_passed_to_the_worker() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let mut submission = get_job_submission();
+    submission.warn_signal = Some("KILL@90".to_string());
+    let err = app.submit_job(submission.clone()).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    submission.warn_signal = Some("usr1@90".to_string());
+    app.submit_job(submission).await.unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(job_assignment.warn_signal.as_deref(), Some("USR1@90"));
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_output_paths_are_passed_to_the_worker() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let mut submission = get_job_submission();
+    submission.output_path = Some("/tmp/out.%x".to_string());
+    let err = app.submit_job(submission.clone()).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    submission.output_path = Some("/tmp/out.%j".to_string());
+    submission.error_path = Some("/tmp/%u/err.%j".to_string());
+    app.submit_job(submission).await.unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(job_assignment.output_path.as_deref(), Some("/tmp/out.%j"));
+    assert_eq!(job_assignment.error_path.as_deref(), Some("/tmp/%u/err.%j"));
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_script_body_is_passed_to_the_worker() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let mut submission = get_job_submission();
+    submission.script_contents = Some(vec![b'#'; 2 * 1024 * 1024]);
+    let err = app.submit_job(submission.clone()).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    submission.script_path = "wrap".to_string();
+    submission.script_contents = Some(b"#!/bin/sh\necho hi\n".to_vec());
+    app.submit_job(submission).await.unwrap();
+    let job_assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(job_assignment.script_path, "wrap");
+    assert_eq!(
+        job_assignment.script_contents.as_deref(),
+        Some(&b"#!/bin/sh\necho hi\n"[..])
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_full_queue_rejects_submissions() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        max_pending_jobs: Some(2),
+        ..Default::default()
+    })
+    .await;
+
+    // without nodes every job stays pending
+    for _ in 0..2 {
+        app.submit_job(get_job_submission()).await.unwrap();
+    }
+    let err = app.submit_job(get_job_submission()).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+}
+
+#[tokio::test]
+async fn test_submission_rate_is_limited_per_user() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        max_submissions_per_min: Some(2),
+        ..Default::default()
+    })
+    .await;
+
+    let mut submission = get_job_submission();
+    // dry runs don't count
+    submission.dry_run = true;
+    app.submit_job(submission.clone()).await.unwrap();
+    submission.dry_run = false;
+    for _ in 0..2 {
+        app.submit_job(submission.clone()).await.unwrap();
+    }
+    let err = app.submit_job(submission.clone()).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+    submission.user = "someone-else".to_string();
+    app.submit_job(submission).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_batch_submission_gets_sequential_ids() {
+    let app = spawn_app().await;
+
+    let batch = vec![get_job_submission(); 100];
+    let responses = app.submit_jobs(batch).await.unwrap().into_inner().responses;
+    assert_eq!(responses.len(), 100);
+    let first_id = responses[0].job_id;
+    for (i, response) in responses.iter().enumerate() {
+        assert_eq!(response.job_id, first_id + i as u64);
+    }
+    let jobs = app.list_jobs().await.unwrap().into_inner().jobs;
+    assert_eq!(jobs.len(), 100);
+
+    // one bad submission rejects the whole batch
+    let mut bad = get_job_submission();
+    bad.partition = Some("unknown".to_string());
+    let err = app
+        .submit_jobs(vec![get_job_submission(), bad])
+        .await
+        .unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    let jobs = app.list_jobs().await.unwrap().into_inner().jobs;
+    assert_eq!(jobs.len(), 100);
+
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .into_inner()
+        .job_id;
+    assert_eq!(job_id, first_id + 100);
+}
+
+/// Places a single job with `policy` on a small and a large node, returns whether it went to the small one.
+async fn is_placed_on_small_node(policy: PlacementPolicy) -> bool {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        placement: policy,
+        ..Default::default()
+    })
+    .await;
+    let mut small_setup = setup_mock_worker().await;
+    let mut large_setup = setup_mock_worker().await;
+    let mut small_info = get_node_info(small_setup.port);
+    small_info.resources.as_mut().unwrap().cpu_count = 2;
+    app.register_node(small_info).await.unwrap();
+    app.register_node(get_node_info(large_setup.port))
+        .await
+        .unwrap();
+
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let on_small = tokio::select! {
+        Some(assignment) = small_setup.job_assignment_receiver.recv() => {
+            assert_eq!(assignment.job_id, job_id);
+            true
+        }
+        Some(assignment) = large_setup.job_assignment_receiver.recv() => {
+            assert_eq!(assignment.job_id, job_id);
+            false
+        }
+    };
+
+    small_setup.server_notifier.send(()).unwrap();
+    small_setup.server_handle.await.unwrap();
+    large_setup.server_notifier.send(()).unwrap();
+    large_setup.server_handle.await.unwrap();
+    on_small
+}
+
+#[tokio::test]
+async fn test_placement_policies() {
+    assert!(is_placed_on_small_node(PlacementPolicy::BestFit).await);
+    assert!(!is_placed_on_small_node(PlacementPolicy::WorstFit).await);
+    // first fit takes whichever node comes first
+    is_placed_on_small_node(PlacementPolicy::FirstFit).await;
+}
+
+#[tokio::test]
+async fn test_cpu_overcommit_fits_more_jobs() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        cpu_overcommit_factor: 2.0,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    let mut node_info = get_node_info(mock_setup.port);
+    node_info.resources.as_mut().unwrap().cpu_count = 2;
+    node_info.resources.as_mut().unwrap().memory = 8 * TEST_MEMORY_SIZE;
+    app.register_node(node_info).await.unwrap();
+
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().cpu_count = 2;
+    let mut job_ids = vec![];
+    for _ in 0..3 {
+        let job_id = app
+            .submit_job(submission.clone())
+            .await
+            .unwrap()
+            .get_ref()
+            .job_id;
+        job_ids.push(job_id);
+    }
+
+    // two jobs share the node's cores, the third one waits
+    for job_id in &job_ids[..2] {
+        let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+        assert_eq!(assignment.job_id, *job_id);
+    }
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_overcommit_does_not_enlarge_single_jobs() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        cpu_overcommit_factor: 2.0,
+        memory_overcommit_factor: 2.0,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    let mut node_info = get_node_info(mock_setup.port);
+    node_info.resources.as_mut().unwrap().cpu_count = 2;
+    app.register_node(node_info).await.unwrap();
+
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().cpu_count = 3;
+    let err = app.submit_job(submission).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    // but two jobs with all of the node's memory fit at once
+    let mut submission = get_job_submission();
+    submission.req_res.as_mut().unwrap().memory = 4 * 1024 * 1024;
+    for _ in 0..2 {
+        let job_id = app
+            .submit_job(submission.clone())
+            .await
+            .unwrap()
+            .get_ref()
+            .job_id;
+        let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+        assert_eq!(assignment.job_id, job_id);
+    }
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_pending_jobs_report_their_queue_position() {
+    let app = spawn_app().await;
+
+    // without nodes every job stays pending
+    let first_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let second_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let mut urgent = get_job_submission();
+    urgent.priority = Some(90);
+    let urgent_id = app.submit_job(urgent).await.unwrap().get_ref().job_id;
+
+    let position = |job: proto::Job| (job.queue_position, job.queue_length);
+    let info = |job_id| app.get_job_info(proto::GetJobInfoRequest { job_id });
+    assert_eq!(
+        position(info(urgent_id).await.unwrap().into_inner()),
+        (Some(1), Some(3))
+    );
+    assert_eq!(
+        position(info(first_id).await.unwrap().into_inner()),
+        (Some(2), Some(3))
+    );
+    assert_eq!(
+        position(info(second_id).await.unwrap().into_inner()),
+        (Some(3), Some(3))
+    );
+}
+
+async fn node_status(app: &TestApp, node_id: &str) -> i32 {
+    let nodes = app.list_nodes().await.unwrap();
+    nodes
+        .get_ref()
+        .nodes
+        .iter()
+        .find(|node| node.id == node_id)
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+async fn test_nodes_get_jobs_once_validated() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        validate_on_register: true,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    let node_id = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    let validation = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(validation.script_path, "/bin/true");
+    assert_eq!(
+        node_status(&app, &node_id).await,
+        proto::NodeStatus::Validating as i32
+    );
+
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let res = timeout(
+        Duration::from_millis(750),
+        mock_setup.job_assignment_receiver.recv(),
+    )
+    .await;
+    assert!(res.is_err());
+
+    app.submit_job_result(proto::JobResult {
+        job_id: validation.job_id,
+        status: proto::JobStatus::Completed.into(),
+        node_id: node_id.clone(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, job_id);
+    assert_eq!(
+        node_status(&app, &node_id).await,
+        proto::NodeStatus::Available as i32
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_failed_validation_keeps_node_offline() {
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        validate_on_register: true,
+        ..Default::default()
+    })
+    .await;
+    let mut mock_setup = setup_mock_worker().await;
+    let node_id = app
+        .register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    let validation = mock_setup.job_assignment_receiver.recv().await.unwrap();
+
+    app.submit_job_result(proto::JobResult {
+        job_id: validation.job_id,
+        status: proto::JobStatus::Failed.into(),
+        node_id: node_id.clone(),
+        message: Some("python3: not found".to_string()),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    app.send_heartbeat(node_id.clone()).await.unwrap();
+    assert_eq!(
+        node_status(&app, &node_id).await,
+        proto::NodeStatus::Offline as i32
+    );
+
+    // registering again starts another validation
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(
+        node_status(&app, &node_id).await,
+        proto::NodeStatus::Validating as i32
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+/// Writes an executable admission hook with the given shell script body.
+fn write_hook(dir: &tempdir::TempDir, body: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.path().join("hook.sh");
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[tokio::test]
+async fn test_admission_hook_rejects_submissions() {
+    let dir = tempdir::TempDir::new("melon-hook").unwrap();
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        admission_hook: Some(write_hook(
+            &dir,
+            "cat > /dev/null\necho 'over quota' >&2\nexit 1",
+        )),
+        ..Default::default()
+    })
+    .await;
+
+    let err = app.submit_job(get_job_submission()).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert_eq!(status.message(), "over quota");
+    let jobs = app.list_jobs().await.unwrap().into_inner().jobs;
+    assert!(jobs.is_empty());
+}
+
+#[tokio::test]
+async fn test_admission_hook_rewrites_submissions() {
+    let dir = tempdir::TempDir::new("melon-hook").unwrap();
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        admission_hook: Some(write_hook(
+            &dir,
+            r#"sed 's/"name":null/"name":"admitted"/'"#,
+        )),
+        ..Default::default()
+    })
+    .await;
+
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .into_inner()
+        .job_id;
+    let job = app
+        .get_job_info(proto::GetJobInfoRequest { job_id })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(job.name.as_deref(), Some("admitted"));
+}
+
+#[tokio::test]
+async fn test_admission_hook_without_resources_is_rejected() {
+    let dir = tempdir::TempDir::new("melon-hook").unwrap();
+    let app = spawn_app_with_scheduler(SchedulerSettings {
+        admission_hook: Some(write_hook(
+            &dir,
+            r#"cat > /dev/null
+echo '{"user":"chris","script_path":"test.sh"}'"#,
+        )),
+        ..Default::default()
+    })
+    .await;
+
+    let mut dry_run = get_job_submission();
+    dry_run.dry_run = true;
+    for sub in [get_job_submission(), dry_run] {
+        let err = app.submit_job(sub).await.unwrap_err();
+        let status = err.downcast_ref::<Status>().unwrap();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.message(), "No resources given");
+    }
+    let err = app
+        .submit_jobs(vec![get_job_submission(); 2])
+        .await
+        .unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    // the scheduler is still up
+    let jobs = app.list_jobs().await.unwrap().into_inner().jobs;
+    assert!(jobs.is_empty());
+}
+
+#[tokio::test]
+async fn test_steps_run_in_the_parent_allocation() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let parent_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let parent = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(parent.job_id, parent_id);
+
+    let mut step = get_job_submission();
+    step.req_res = None;
+    step.parent_job_id = Some(parent_id);
+    let step_id = app.submit_job(step).await.unwrap().get_ref().job_id;
+    let assignment = mock_setup.job_assignment_receiver.recv().await.unwrap();
+    assert_eq!(assignment.job_id, step_id);
+    assert_eq!(assignment.parent_job_id, Some(parent_id));
+    let res = assignment.req_res.unwrap();
+    assert_eq!(res.cpu_count, TEST_COU_COUNT);
+    assert_eq!(res.memory, TEST_MEMORY_SIZE);
+
+    let info = app
+        .get_job_info(proto::GetJobInfoRequest { job_id: step_id })
+        .await
+        .unwrap();
+    assert_eq!(info.get_ref().parent_job_id, Some(parent_id));
+    assert_eq!(
+        JobStatus::try_from(info.get_ref().status).unwrap(),
+        JobStatus::Running
+    );
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_steps_need_a_running_parent_of_the_same_user() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+
+    let mut step = get_job_submission();
+    step.parent_job_id = Some(42);
+    let err = app.submit_job(step.clone()).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    let parent_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    mock_setup.job_assignment_receiver.recv().await.unwrap();
+    step.parent_job_id = Some(parent_id);
+    step.user = "mallory".to_string();
+    let err = app.submit_job(step).await.unwrap_err();
+    let status = err.downcast_ref::<Status>().unwrap();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_nodes_survive_a_restart() {
+    let tmp_dir = tempdir::TempDir::new("melon-nodes").unwrap();
+    let db_path = tmp_dir.path().join("melon.db");
+    let db_path = db_path.to_str().unwrap();
+
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let (app, handle) = spawn_app_on_database(db_path, async {
+        stop_rx.await.ok();
+    })
+    .await;
+    let mut info = get_node_info(42);
+    info.labels = vec!["ssd".to_string()];
+    let node_id = app
+        .register_node(info)
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+
+    stop_tx.send(()).unwrap();
+    handle.await.unwrap();
+
+    // the restarted scheduler knows the node, but waits for it to report back
+    let (app, _handle) = spawn_app_on_database(db_path, std::future::pending()).await;
+    let nodes = app.list_nodes().await.unwrap();
+    let node = &nodes.get_ref().nodes[0];
+    assert_eq!(node.id, node_id);
+    assert_eq!(node.address, "http://[::1]:42");
+    assert_eq!(node.labels, vec!["ssd".to_string()]);
+    assert_eq!(node.total_resources.unwrap().cpu_count, 8);
+    assert_eq!(node.status, proto::NodeStatus::Offline as i32);
+
+    app.send_heartbeat(node_id).await.unwrap();
+    let nodes = app.list_nodes().await.unwrap();
+    assert_eq!(
+        nodes.get_ref().nodes[0].status,
+        proto::NodeStatus::Available as i32
+    );
+}
+
+#[tokio::test]
+async fn test_drained_node_stays_removed_after_a_restart() {
+    let tmp_dir = tempdir::TempDir::new("melon-nodes").unwrap();
+    let db_path = tmp_dir.path().join("melon.db");
+    let db_path = db_path.to_str().unwrap();
+
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let (app, handle) = spawn_app_on_database(db_path, async {
+        stop_rx.await.ok();
+    })
+    .await;
+    let node_id = app
+        .register_node(get_node_info(42))
+        .await
+        .unwrap()
+        .get_ref()
+        .node_id
+        .clone();
+    app.drain_node(node_id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(app.list_nodes().await.unwrap().get_ref().nodes.is_empty());
+
+    stop_tx.send(()).unwrap();
+    handle.await.unwrap();
+
+    let (app, _handle) = spawn_app_on_database(db_path, std::future::pending()).await;
+    assert!(app.list_nodes().await.unwrap().get_ref().nodes.is_empty());
+}
+
+#[tokio::test]
+async fn test_list_jobs_by_submit_time() {
+    let app = spawn_app().await;
+    let job_id = app
+        .submit_job(get_job_submission())
+        .await
+        .unwrap()
+        .get_ref()
+        .job_id;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let jobs = app
+        .list_jobs_submitted(Some(now - 60), None)
+        .await
+        .unwrap()
+        .into_inner()
+        .jobs;
+    assert_eq!(
+        jobs.iter().map(|job| job.id).collect::<Vec<_>>(),
+        vec![job_id]
+    );
+
+    let jobs = app
+        .list_jobs_submitted(Some(now + 60), None)
+        .await
+        .unwrap()
+        .into_inner()
+        .jobs;
+    assert!(jobs.is_empty());
+    let jobs = app
+        .list_jobs_submitted(None, Some(now - 60))
+        .await
+        .unwrap()
+        .into_inner()
+        .jobs;
+    assert!(jobs.is_empty());
+}
+
+#[tokio::test]
+async fn test_job_status_of_running_and_finished_jobs() {
+    let app = spawn_app().await;
+    let mut mock_setup = setup_mock_worker().await;
+    app.register_node(get_node_info(mock_setup.port))
+        .await
+        .unwrap();
+    app.submit_job(get_job_submission()).await.unwrap();
+    let job_id = mock_setup
+        .job_assignment_receiver
+        .recv()
+        .await
+        .unwrap()
+        .job_id;
+
+    let res = app.get_job_status(job_id).await.unwrap().into_inner();
+    assert_eq!(res.status, proto::JobStatus::Running as i32);
+    assert_eq!(res.exit_code, None);
+
+    let job_result = proto::JobResult {
+        job_id,
+        status: proto::JobStatus::Failed.into(),
+        exit_code: Some(3),
+        ..Default::default()
+    };
+    app.submit_job_result(job_result).await.unwrap();
+
+    // finished jobs are read from the database once they are stored
+    let mut res = None;
+    for _ in 0..20 {
+        if let Ok(status) = app.get_job_status(job_id).await {
+            res = Some(status.into_inner());
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let res = res.unwrap();
+    assert_eq!(res.status, proto::JobStatus::Failed as i32);
+    assert_eq!(res.exit_code, Some(3));
+
+    mock_setup.server_notifier.send(()).unwrap();
+    mock_setup.server_handle.await.unwrap();
+}
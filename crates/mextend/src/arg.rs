@@ -12,24 +12,44 @@ pub struct Args {
     )]
     pub api_endpoint: String,
 
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
     /// The job id
     #[arg()]
     pub job: u64,
 
-    /// Time extension in D-HH-MM format
+    /// Time extension in minutes, HH:MM, D-HH:MM or D-HH-MM format
     #[arg(short = 't', long = "time", value_parser = parse_time_extension)]
     pub extension: Duration,
 }
 
-fn parse_time_extension(arg: &str) -> Result<Duration, String> {
-    let parts: Vec<&str> = arg.split('-').collect();
-    if parts.len() != 3 {
-        return Err("Time extension must be in D-HH-MM format".to_string());
-    }
+const FORMAT_ERROR: &str = "Time extension must be in MM, HH:MM, D-HH:MM or D-HH-MM format";
 
-    let days = parts[0].parse::<u64>().map_err(|_| "Invalid day format")?;
-    let hours = parts[1].parse::<u64>().map_err(|_| "Invalid hour format")?;
-    let minutes = parts[2]
+fn parse_time_extension(arg: &str) -> Result<Duration, String> {
+    let (days, hours, minutes) = match arg.split_once('-') {
+        // D-HH:MM, or D-HH-MM for compatibility
+        Some((days, clock)) => {
+            let (hours, minutes) = clock
+                .split_once(':')
+                .or_else(|| clock.split_once('-'))
+                .ok_or(FORMAT_ERROR)?;
+            (days, hours, minutes)
+        }
+        None => match arg.split_once(':') {
+            Some((hours, minutes)) => ("0", hours, minutes),
+            // a bare number of minutes may exceed an hour
+            None => {
+                let minutes = arg.parse::<u64>().map_err(|_| "Invalid minute format")?;
+                return Ok(Duration::from_secs(minutes * 60));
+            }
+        },
+    };
+
+    let days = days.parse::<u64>().map_err(|_| "Invalid day format")?;
+    let hours = hours.parse::<u64>().map_err(|_| "Invalid hour format")?;
+    let minutes = minutes
         .parse::<u64>()
         .map_err(|_| "Invalid minute format")?;
 
@@ -64,12 +84,47 @@ mod tests {
     fn test_invalid_format() {
         let result = parse_time_extension("2-12");
         assert!(result.is_err());
+        assert_eq!(result.err().unwrap(), FORMAT_ERROR.to_string());
+    }
+
+    #[test]
+    fn test_bare_minutes() {
         assert_eq!(
-            result.err().unwrap(),
-            "Time extension must be in D-HH-MM format".to_string()
+            parse_time_extension("30").unwrap(),
+            Duration::from_secs(30 * 60)
+        );
+        assert_eq!(
+            parse_time_extension("90").unwrap(),
+            Duration::from_secs(90 * 60)
         );
     }
 
+    #[test]
+    fn test_colon_forms() {
+        assert_eq!(
+            parse_time_extension("1:30").unwrap(),
+            Duration::from_secs(90 * 60)
+        );
+        assert_eq!(
+            parse_time_extension("2-12:30").unwrap(),
+            Duration::from_secs(2 * 24 * 60 * 60 + 12 * 60 * 60 + 30 * 60)
+        );
+    }
+
+    #[test]
+    fn test_dash_form_is_still_accepted() {
+        assert_eq!(
+            parse_time_extension("0-00-30").unwrap(),
+            Duration::from_secs(30 * 60)
+        );
+    }
+
+    #[test]
+    fn test_invalid_bare_minutes() {
+        let result = parse_time_extension("30m");
+        assert_eq!(result.err().unwrap(), "Invalid minute format".to_string());
+    }
+
     #[test]
     fn test_invalid_day_format() {
         let result = parse_time_extension("x-12-30");
@@ -1,8 +1,9 @@
 use arg::Args;
 use clap::Parser;
+use std::time::Duration;
 mod arg;
 use anyhow::Result;
-use melon_common::proto::{self, melon_scheduler_client::MelonSchedulerClient};
+use melon_client::{Client, Code};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -12,20 +13,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let time_in_mins = args.extension;
     let time_in_mins = (time_in_mins.as_secs() / 60) as u32;
 
-    let mut client = MelonSchedulerClient::connect(args.api_endpoint).await?;
-    let request = tonic::Request::new(proto::ExtendJobRequest {
-        job_id,
-        user,
-        extension_mins: time_in_mins,
-    });
-    match client.extend_job(request).await {
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    match client.extend(job_id, &user, time_in_mins).await {
         Ok(_) => println!(
             "Successfully extended the job runtime by {} minutes",
             time_in_mins
         ),
         Err(e) => match e.code() {
-            tonic::Code::NotFound => println!("Unknown job id {}", job_id),
-            tonic::Code::PermissionDenied => {
+            Code::NotFound => println!("Unknown job id {}", job_id),
+            Code::PermissionDenied => {
                 println!("Not authorized to cancel job id {}", job_id)
             }
             _ => println!("Unknown error!"),
@@ -0,0 +1,41 @@
+mod arg;
+use arg::Args;
+use clap::Parser;
+use melon_client::{proto, Client, Code};
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let job_id = args.job;
+    let user = whoami::username();
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let request = tonic::Request::new(proto::HoldJobRequest { job_id, user });
+    match client.scheduler().hold_job(request).await {
+        Ok(_) => println!("Successfully held job {}", job_id),
+        Err(e) => match e.code() {
+            Code::NotFound => println!("Unknown job id {}", job_id),
+            Code::PermissionDenied => {
+                println!("Not authorized to hold job id {}", job_id)
+            }
+            Code::FailedPrecondition => {
+                println!("Cannot hold job {}: {}", job_id, e.message())
+            }
+            _ => println!("Unknown error!"),
+        },
+    }
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use clap::Parser;
+use std::net::SocketAddr;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(short = 'a', long = "api_endpoint", default_value = "[::1]:8080")]
+    pub api_endpoint: SocketAddr,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// Print the nodes as json
+    #[arg(short = 'p', long = "parseable")]
+    pub parseable: bool,
+
+    /// Only show this node
+    pub node_id: Option<String>,
+
+    /// List the running and finished jobs that were started on the node
+    #[arg(long = "history", requires = "node_id")]
+    pub history: bool,
+}
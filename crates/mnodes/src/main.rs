@@ -0,0 +1,188 @@
+mod arg;
+use arg::Args;
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use melon_client::Client;
+use melon_common::{proto, NodeStatus};
+use serde::Serialize;
+use std::time::Duration;
+
+const GIB: f64 = (1024 * 1024 * 1024) as f64;
+
+/// Resource report of a single compute node.
+#[derive(Serialize)]
+struct NodeReport {
+    id: String,
+    address: String,
+    status: String,
+    cpus_total: u32,
+    cpus_used: u32,
+    memory_total: u64,
+    memory_used: u64,
+    gpus_total: u32,
+    gpus_used: u32,
+    running_jobs: u32,
+    max_jobs: Option<u32>,
+    heartbeat_age_secs: u64,
+    labels: Vec<String>,
+}
+
+impl From<&proto::NodeDetails> for NodeReport {
+    fn from(node: &proto::NodeDetails) -> Self {
+        let status = match proto::NodeStatus::try_from(node.status).map(NodeStatus::from) {
+            Ok(NodeStatus::Available) => "Available",
+            Ok(NodeStatus::Offline) => "Offline",
+            Ok(NodeStatus::Draining) => "Draining",
+            Ok(NodeStatus::Validating) => "Validating",
+            Err(_) => "Unknown",
+        };
+        let total = node.total_resources.unwrap_or_default();
+        let used = node.used_resources.unwrap_or_default();
+
+        NodeReport {
+            id: node.id.clone(),
+            address: node.address.clone(),
+            status: status.to_string(),
+            cpus_total: total.cpu_count,
+            cpus_used: used.cpu_count,
+            memory_total: total.memory,
+            memory_used: used.memory,
+            gpus_total: total.gpu_count,
+            gpus_used: used.gpu_count,
+            running_jobs: node.running_jobs,
+            max_jobs: node.max_jobs,
+            heartbeat_age_secs: node.heartbeat_age_secs,
+            labels: node.labels.clone(),
+        }
+    }
+}
+
+/// A job that was started on the node.
+#[derive(Serialize)]
+struct NodeJobReport {
+    id: u64,
+    user: String,
+    name: String,
+    status: String,
+    start_time: Option<u64>,
+    stop_time: Option<u64>,
+}
+
+impl From<&proto::Job> for NodeJobReport {
+    fn from(job: &proto::Job) -> Self {
+        let job: melon_common::Job = job.into();
+        let status: String = job.status.clone().into();
+
+        NodeJobReport {
+            id: job.id,
+            user: job.user.clone(),
+            name: job.display_name().to_string(),
+            status,
+            start_time: job.start_time,
+            stop_time: job.stop_time,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint.to_string())
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if args.history {
+        let node_id = args.node_id.expect("required by --history");
+        let request = tonic::Request::new(proto::GetNodeJobsRequest { node_id });
+        let res = client.scheduler().get_node_jobs(request).await?;
+        let jobs: Vec<NodeJobReport> = res.get_ref().jobs.iter().map(|j| j.into()).collect();
+
+        if args.parseable {
+            println!("{}", serde_json::to_string_pretty(&jobs)?);
+        } else {
+            print_jobs(&jobs);
+        }
+        return Ok(());
+    }
+
+    let nodes: Vec<NodeReport> = client
+        .nodes()
+        .await?
+        .iter()
+        .filter(|n| args.node_id.as_ref().is_none_or(|id| &n.id == id))
+        .map(|n| n.into())
+        .collect();
+    if let (Some(node_id), true) = (&args.node_id, nodes.is_empty()) {
+        eprintln!("Node {} not found", node_id);
+        std::process::exit(1);
+    }
+
+    if args.parseable {
+        println!("{}", serde_json::to_string_pretty(&nodes)?);
+    } else {
+        print_nodes(&nodes);
+    }
+
+    Ok(())
+}
+
+fn print_nodes(nodes: &[NodeReport]) {
+    println!(
+        "{:<21} {:>9} {:>7} {:>13} {:>7} {:>5} {:>6}  {:<20}  LABELS",
+        "NODEID", "STATUS", "CPUS", "MEMORY", "GPUS", "JOBS", "SEEN", "ADDRESS"
+    );
+    for node in nodes {
+        println!(
+            "{:<21} {:>9} {:>7} {:>13} {:>7} {:>5} {:>6}  {:<20}  {}",
+            node.id,
+            node.status,
+            format!("{}/{}", node.cpus_used, node.cpus_total),
+            format!(
+                "{:.1}/{:.1}G",
+                node.memory_used as f64 / GIB,
+                node.memory_total as f64 / GIB
+            ),
+            format!("{}/{}", node.gpus_used, node.gpus_total),
+            match node.max_jobs {
+                Some(max_jobs) => format!("{}/{}", node.running_jobs, max_jobs),
+                None => node.running_jobs.to_string(),
+            },
+            format!("{}s", node.heartbeat_age_secs),
+            node.address,
+            node.labels.join(",")
+        );
+    }
+}
+
+fn print_jobs(jobs: &[NodeJobReport]) {
+    println!(
+        "{:>8} {:<12} {:<20} {:<11} {:<19} {:<19}",
+        "JOBID", "USER", "NAME", "STATUS", "START", "END"
+    );
+    for job in jobs {
+        println!(
+            "{:>8} {:<12} {:<20} {:<11} {:<19} {:<19}",
+            job.id,
+            job.user,
+            job.name,
+            job.status,
+            format_time(job.start_time),
+            format_time(job.stop_time)
+        );
+    }
+}
+
+fn format_time(timestamp: Option<u64>) -> String {
+    timestamp
+        .and_then(|t| Utc.timestamp_opt(t as i64, 0).single())
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
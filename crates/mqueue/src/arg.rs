@@ -1,3 +1,5 @@
+use crate::format::DEFAULT_FORMAT;
+use crate::time_filter::parse_time_bound;
 use clap::Parser;
 use std::net::SocketAddr;
 
@@ -7,4 +9,33 @@ pub struct Args {
     /// API Endpoint
     #[arg(short = 'a', long = "api_endpoint", default_value = "[::1]:8080")]
     pub api_endpoint: SocketAddr,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// Show the compute nodes instead of the job queue
+    #[arg(short = 'n', long = "nodes")]
+    pub nodes: bool,
+
+    /// Only show the jobs of a user, the current user if no name is given
+    #[arg(short = 'm', long = "mine", value_name = "USER", num_args = 0..=1)]
+    pub mine: Option<Option<String>>,
+
+    /// Only show jobs submitted since then, an RFC3339 timestamp or a span back from now, e.g. 1h or 2d
+    #[arg(long = "since", value_name = "TIME", value_parser = parse_time_bound, conflicts_with = "nodes")]
+    pub since: Option<u64>,
+
+    /// Only show jobs submitted before then, in the same forms as --since
+    #[arg(long = "until", value_name = "TIME", value_parser = parse_time_bound, conflicts_with = "nodes")]
+    pub until: Option<u64>,
+
+    /// Comma-separated list of columns, out of
+    /// jobid,name,user,status,time,limit,cpus,memory,gpus,priority,partition,nodes
+    #[arg(short = 'o', long = "format", default_value = DEFAULT_FORMAT)]
+    pub format: String,
+
+    /// Print the jobs as json instead of a table
+    #[arg(long = "json", conflicts_with_all = ["nodes", "format"])]
+    pub json: bool,
 }
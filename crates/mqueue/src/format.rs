@@ -0,0 +1,340 @@
+use melon_common::{utils::format_memory, Job, JobStatus};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The columns shown if no `--format` is given.
+pub const DEFAULT_FORMAT: &str = "jobid,name,user,status,time,nodes";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+/// A column of the job list.
+#[derive(Debug)]
+pub struct Column {
+    /// Name used in the format string
+    name: &'static str,
+    header: &'static str,
+    /// Minimum width, the column grows with its longest value
+    width: usize,
+    align: Align,
+    value: fn(&Job) -> String,
+}
+
+/// All columns that can be selected, in the order they are listed in the help.
+const COLUMNS: &[Column] = &[
+    Column {
+        name: "jobid",
+        header: "JOBID",
+        width: 10,
+        align: Align::Right,
+        value: |job| job.id.to_string(),
+    },
+    Column {
+        name: "name",
+        header: "NAME",
+        width: 11,
+        align: Align::Right,
+        value: |job| job.display_name().to_string(),
+    },
+    Column {
+        name: "user",
+        header: "USER",
+        width: 7,
+        align: Align::Right,
+        value: |job| job.user.clone(),
+    },
+    Column {
+        name: "status",
+        header: "ST",
+        width: 3,
+        align: Align::Right,
+        value: |job| status_code(&job.status).to_string(),
+    },
+    Column {
+        name: "time",
+        header: "TIME",
+        width: 8,
+        align: Align::Right,
+        value: calculate_job_time,
+    },
+    Column {
+        name: "limit",
+        header: "LIMIT",
+        width: 8,
+        align: Align::Right,
+        value: |job| format_duration(Duration::from_secs(job.req_res.time as u64 * 60)),
+    },
+    Column {
+        name: "cpus",
+        header: "CPUS",
+        width: 4,
+        align: Align::Right,
+        value: |job| job.req_res.cpu_count.to_string(),
+    },
+    Column {
+        name: "memory",
+        header: "MEMORY",
+        width: 6,
+        align: Align::Right,
+        value: |job| format_memory(job.req_res.memory),
+    },
+    Column {
+        name: "gpus",
+        header: "GPUS",
+        width: 4,
+        align: Align::Right,
+        value: |job| job.req_res.gpu_count.to_string(),
+    },
+    Column {
+        name: "priority",
+        header: "PRIO",
+        width: 4,
+        align: Align::Right,
+        value: |job| job.priority.to_string(),
+    },
+    Column {
+        name: "partition",
+        header: "PARTITION",
+        width: 9,
+        align: Align::Left,
+        value: |job| job.partition.clone(),
+    },
+    Column {
+        name: "nodes",
+        header: "NODES",
+        width: 20,
+        align: Align::Left,
+        value: node_name,
+    },
+];
+
+/// Parses a comma-separated list of column names, e.g. `jobid,name,status`.
+pub fn parse_format(format: &str) -> Result<Vec<&'static Column>, String> {
+    format
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            COLUMNS
+                .iter()
+                .find(|column| column.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| {
+                    let known: Vec<&str> = COLUMNS.iter().map(|column| column.name).collect();
+                    format!(
+                        "Unknown field '{}', expected one of: {}",
+                        name,
+                        known.join(",")
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|columns| {
+            if columns.is_empty() {
+                Err("The format needs at least one field".to_string())
+            } else {
+                Ok(columns)
+            }
+        })
+}
+
+/// Renders the jobs as a table with a header line.
+///
+/// Values are never cut off, each column is as wide as its longest value.
+pub fn render_table(columns: &[&Column], jobs: &[Job]) -> Vec<String> {
+    let rows: Vec<Vec<String>> = jobs
+        .iter()
+        .map(|job| columns.iter().map(|column| (column.value)(job)).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.width, column.header.len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let header: Vec<String> = columns
+        .iter()
+        .map(|column| column.header.to_string())
+        .collect();
+    std::iter::once(header)
+        .chain(rows)
+        .map(|row| render_row(columns, &widths, &row))
+        .collect()
+}
+
+fn render_row(columns: &[&Column], widths: &[usize], values: &[String]) -> String {
+    let mut line = String::new();
+    for (i, (column, value)) in columns.iter().zip(values).enumerate() {
+        // left aligned columns get some extra room to set them apart
+        if i > 0 {
+            line.push_str(if column.align == Align::Left {
+                "  "
+            } else {
+                " "
+            });
+        }
+        match column.align {
+            Align::Left => line.push_str(&format!("{:<width$}", value, width = widths[i])),
+            Align::Right => line.push_str(&format!("{:>width$}", value, width = widths[i])),
+        }
+    }
+    line.trim_end().to_string()
+}
+
+fn status_code(status: &JobStatus) -> &'static str {
+    match status {
+        JobStatus::Completed => "C",
+        JobStatus::Failed => "F",
+        JobStatus::Pending => "PD",
+        JobStatus::Running => "R",
+        JobStatus::Timeout => "TO",
+        JobStatus::Cancelled => "CA",
+        JobStatus::Held => "H",
+        JobStatus::OutOfMemory => "OOM",
+    }
+}
+
+fn node_name(job: &Job) -> String {
+    match job.status {
+        JobStatus::Pending | JobStatus::Held => "pending".to_string(),
+        _ if job.assigned_node.is_none() => "unknown".to_string(),
+        _ => job.nodes().cloned().collect::<Vec<_>>().join(","),
+    }
+}
+
+fn calculate_job_time(job: &Job) -> String {
+    match job.status {
+        JobStatus::Pending | JobStatus::Held => "00:00:00".to_string(),
+        JobStatus::Running => {
+            if let Some(start_time) = job.start_time {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                // the node's clock may be ahead of ours
+                let duration = Duration::from_secs(now.saturating_sub(start_time));
+                format_duration(duration)
+            } else {
+                "00:00:00".to_string()
+            }
+        }
+        JobStatus::Completed
+        | JobStatus::Failed
+        | JobStatus::Timeout
+        | JobStatus::Cancelled
+        | JobStatus::OutOfMemory => {
+            if let (Some(start_time), Some(stop_time)) = (job.start_time, job.stop_time) {
+                // start and stop come from different clocks if the job was stopped by the scheduler
+                let duration = Duration::from_secs(stop_time.saturating_sub(start_time));
+                format_duration(duration)
+            } else {
+                "00:00:00".to_string()
+            }
+        }
+    }
+}
+
+/// Formats as `HH:MM:SS`, or `D-HH:MM:SS` from one day on.
+fn format_duration(duration: Duration) -> String {
+    let total_seconds = duration.as_secs();
+    let days = total_seconds / 86400;
+    let hours = (total_seconds % 86400) / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+    if days > 0 {
+        format!("{}-{:02}:{:02}:{:02}", days, hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use melon_common::RequestedResources;
+
+    fn job(name: &str) -> Job {
+        let mut job = Job::new(
+            7,
+            "alice".to_string(),
+            "/home/alice/job.sh".to_string(),
+            vec![],
+            RequestedResources::new(4, 2 * 1024 * 1024 * 1024, 60, 0),
+        );
+        job.name = Some(name.to_string());
+        job
+    }
+
+    #[test]
+    fn test_default_format_keeps_layout() {
+        let columns = parse_format(DEFAULT_FORMAT).unwrap();
+        let lines = render_table(&columns, &[job("train")]);
+        assert_eq!(
+            lines,
+            vec![
+                "     JOBID        NAME    USER  ST     TIME  NODES",
+                "         7       train   alice  PD 00:00:00  pending",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_values_are_not_truncated() {
+        let columns = parse_format("jobid,name").unwrap();
+        let lines = render_table(&columns, &[job("a-rather-long-job-name")]);
+        assert_eq!(lines[1], "         7 a-rather-long-job-name");
+    }
+
+    #[test]
+    fn test_resource_columns() {
+        let columns = parse_format("cpus, memory").unwrap();
+        let lines = render_table(&columns, &[job("train")]);
+        assert_eq!(lines, vec!["CPUS MEMORY", "   4     2G"]);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        let hours = |h: u64| Duration::from_secs(h * 3600);
+        assert_eq!(format_duration(Duration::from_secs(3725)), "01:02:05");
+        assert_eq!(
+            format_duration(hours(23) + Duration::from_secs(59)),
+            "23:00:59"
+        );
+        assert_eq!(format_duration(hours(25)), "1-01:00:00");
+        assert_eq!(
+            format_duration(hours(49) + Duration::from_secs(61)),
+            "2-01:01:01"
+        );
+    }
+
+    #[test]
+    fn test_start_time_in_the_future() {
+        let mut job = job("train");
+        job.status = JobStatus::Running;
+        job.start_time = Some(u64::MAX);
+        assert_eq!(calculate_job_time(&job), "00:00:00");
+    }
+
+    #[test]
+    fn test_stop_time_before_start_time() {
+        let mut job = job("train");
+        job.status = JobStatus::Failed;
+        job.start_time = Some(1_000);
+        job.stop_time = Some(900);
+        assert_eq!(calculate_job_time(&job), "00:00:00");
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        let err = parse_format("jobid,owner").unwrap_err();
+        assert!(err.contains("'owner'"));
+        assert!(parse_format(",").is_err());
+    }
+}
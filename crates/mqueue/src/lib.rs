@@ -1 +1,3 @@
 mod arg;
+pub mod format;
+pub mod time_filter;
@@ -1,94 +1,83 @@
 mod arg;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
 use arg::Args;
 use clap::Parser;
-use melon_common::{proto::melon_scheduler_client::MelonSchedulerClient, Job, JobStatus};
+use melon_client::Client;
+use melon_common::{proto, NodeStatus};
+use mqueue::{format, time_filter};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let endpoint = format!("http://{}", args.api_endpoint);
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint.to_string())
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if args.nodes {
+        return print_nodes(&mut client).await;
+    }
 
-    let mut client = MelonSchedulerClient::connect(endpoint.clone()).await?;
-    let request = tonic::Request::new(());
-    let res = client.list_jobs(request).await?;
-    let jobs = res.get_ref();
+    let user = args.mine.map(|user| user.unwrap_or_else(whoami::username));
+    let jobs = client
+        .list_submitted(user.as_deref(), args.since, args.until)
+        .await?;
 
-    println!(
-        "{:>10} {:>11} {:>7} {:>3} {:>8}  {:<20}",
-        "JOBID", "NAME", "USER", "ST", "TIME", "NODES"
-    );
-    for job in &jobs.jobs {
-        let job: Job = job.into();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&jobs)?);
+        return Ok(());
+    }
 
-        let name = if job.script_path.len() > 10 {
-            job.script_path[..10].to_string()
-        } else {
-            job.script_path.clone()
-        };
-        let user = if job.user.len() > 8 {
-            job.user[..8].to_string()
-        } else {
-            job.user.clone()
-        };
+    let columns = match format::parse_format(&args.format) {
+        Ok(columns) => columns,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    for line in format::render_table(&columns, &jobs) {
+        println!("{}", line);
+    }
 
-        let node = match job.status {
-            JobStatus::Pending => "pending".to_string(),
-            _ => job
-                .assigned_node
-                .clone()
-                .unwrap_or_else(|| "unknown".to_string()),
-        };
-        let time = calculate_job_time(&job);
+    Ok(())
+}
+
+async fn print_nodes(client: &mut Client) -> Result<(), Box<dyn std::error::Error>> {
+    let nodes = client.nodes().await?;
 
-        let status = match job.status {
-            JobStatus::Completed => "C".to_string(),
-            JobStatus::Failed => "F".to_string(),
-            JobStatus::Pending => "PD".to_string(),
-            JobStatus::Running => "R".to_string(),
-            JobStatus::Timeout => "TO".to_string(),
+    println!(
+        "{:<21} {:>3} {:>7} {:>7} {:>5}  {:<20}",
+        "NODEID", "ST", "CPUS", "GPUS", "JOBS", "ADDRESS"
+    );
+    for node in &nodes {
+        let status = match proto::NodeStatus::try_from(node.status).map(NodeStatus::from) {
+            Ok(NodeStatus::Available) => "A",
+            Ok(NodeStatus::Offline) => "OFF",
+            Ok(NodeStatus::Draining) => "DR",
+            Ok(NodeStatus::Validating) => "VAL",
+            Err(_) => "?",
         };
+        let total = node.total_resources.unwrap_or_default();
+        let used = node.used_resources.unwrap_or_default();
 
         println!(
-            "{:>10} {:>11} {:>7} {:>3} {:>8}  {:<20}",
-            job.id, name, user, status, time, node
+            "{:<21} {:>3} {:>7} {:>7} {:>5}  {:<20}",
+            node.id,
+            status,
+            format!("{}/{}", used.cpu_count, total.cpu_count),
+            format!("{}/{}", used.gpu_count, total.gpu_count),
+            node.running_jobs,
+            node.address
         );
     }
 
     Ok(())
 }
-
-fn calculate_job_time(job: &Job) -> String {
-    match job.status {
-        JobStatus::Pending => "00:00:00".to_string(),
-        JobStatus::Running => {
-            if let Some(start_time) = job.start_time {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                let duration = Duration::from_secs(now - start_time);
-                format_duration(duration)
-            } else {
-                "00:00:00".to_string()
-            }
-        }
-        JobStatus::Completed | JobStatus::Failed | JobStatus::Timeout => {
-            if let (Some(start_time), Some(stop_time)) = (job.start_time, job.stop_time) {
-                let duration = Duration::from_secs(stop_time - start_time);
-                format_duration(duration)
-            } else {
-                "00:00:00".to_string()
-            }
-        }
-    }
-}
-
-fn format_duration(duration: Duration) -> String {
-    let total_seconds = duration.as_secs();
-    let hours = total_seconds / 3600;
-    let minutes = (total_seconds % 3600) / 60;
-    let seconds = total_seconds % 60;
-    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
-}
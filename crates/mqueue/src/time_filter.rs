@@ -0,0 +1,92 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+/// Parses a point in time for `--since` and `--until` into a unix time.
+///
+/// Takes an RFC3339 timestamp, e.g. `2024-05-01T08:00:00Z`, or a span back from now
+/// like `30m`, `1h`, `2d` or `1w`.
+pub fn parse_time_bound(value: &str) -> Result<u64> {
+    time_bound_at(value, Utc::now())
+}
+
+fn time_bound_at(value: &str, now: DateTime<Utc>) -> Result<u64> {
+    let value = value.trim();
+    let time = match DateTime::parse_from_rfc3339(value) {
+        Ok(time) => time.with_timezone(&Utc),
+        Err(_) => now - relative_span(value)?,
+    };
+    u64::try_from(time.timestamp()).map_err(|_| anyhow!("{} lies before 1970", value))
+}
+
+fn relative_span(value: &str) -> Result<chrono::Duration> {
+    let invalid = || {
+        anyhow!(
+            "Invalid time {}, expected an RFC3339 timestamp or a span like 1h or 2d",
+            value
+        )
+    };
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let span = match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        _ => return Err(invalid()),
+    };
+    span.ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-10T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_relative_times() {
+        let now_secs = now().timestamp() as u64;
+        assert_eq!(time_bound_at("90s", now()).unwrap(), now_secs - 90);
+        assert_eq!(time_bound_at("30m", now()).unwrap(), now_secs - 30 * 60);
+        assert_eq!(time_bound_at("1h", now()).unwrap(), now_secs - 3600);
+        assert_eq!(time_bound_at("2d", now()).unwrap(), now_secs - 2 * 86400);
+        assert_eq!(time_bound_at("1w", now()).unwrap(), now_secs - 7 * 86400);
+        assert_eq!(time_bound_at("0h", now()).unwrap(), now_secs);
+    }
+
+    #[test]
+    fn test_rfc3339_times() {
+        assert_eq!(
+            time_bound_at("2024-05-01T08:00:00Z", now()).unwrap(),
+            1714550400
+        );
+        assert_eq!(
+            time_bound_at("2024-05-01T10:00:00+02:00", now()).unwrap(),
+            1714550400
+        );
+    }
+
+    #[test]
+    fn test_invalid_times() {
+        for value in [
+            "",
+            "h",
+            "1",
+            "1y",
+            "-1h",
+            "1.5h",
+            "yesterday",
+            "1969-01-01T00:00:00Z",
+        ] {
+            assert!(time_bound_at(value, now()).is_err(), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(
+        short = 'a',
+        long = "api_endpoint",
+        default_value = "http://[::1]:8080"
+    )]
+    pub api_endpoint: String,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// The job id
+    #[arg()]
+    pub job: u64,
+}
@@ -0,0 +1,29 @@
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(
+        short = 'a',
+        long = "api_endpoint",
+        default_value = "http://[::1]:8080"
+    )]
+    pub api_endpoint: String,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// The id of the finished job to run again
+    #[arg()]
+    pub job: u64,
+
+    /// New time limit, e.g. 0-06:00 or minutes
+    #[arg(short = 't', long = "time", value_parser = mbatch::parse_time_limit)]
+    pub time: Option<u32>,
+
+    /// New memory per node, e.g. 4G
+    #[arg(short = 'm', long = "mem", value_parser = mbatch::parse_memory)]
+    pub mem: Option<u64>,
+}
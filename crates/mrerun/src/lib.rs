@@ -0,0 +1,67 @@
+use melon_common::{proto::JobSubmission, Job};
+
+/// Builds a submission that runs a job again with the settings it had.
+///
+/// The new job belongs to `user`. Dependencies are left out, they refer to jobs of the
+/// original run. `time` and `mem` replace the time limit and the memory per node if given.
+pub fn resubmission(job: &Job, user: &str, time: Option<u32>, mem: Option<u64>) -> JobSubmission {
+    let mut job = job.clone();
+    if let Some(time) = time {
+        job.req_res.time = time;
+    }
+    if let Some(mem) = mem {
+        job.req_res.memory = mem;
+    }
+    let mut submission = JobSubmission::from(&mut job);
+    submission.user = user.to_string();
+    submission.dependencies.clear();
+    submission
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use melon_common::RequestedResources;
+
+    fn job() -> Job {
+        let mut job = Job::new(
+            7,
+            "alice".to_string(),
+            "/home/alice/train.sh".to_string(),
+            vec!["--epochs".to_string(), "10".to_string()],
+            RequestedResources::new(4, 8 * 1024 * 1024 * 1024, 90, 0),
+        );
+        job.dependencies = vec![5, 6];
+        job.env.insert("MODE".to_string(), "fast".to_string());
+        job.work_dir = Some("/home/alice/runs".to_string());
+        job
+    }
+
+    #[test]
+    fn test_resubmission_keeps_settings() {
+        let submission = resubmission(&job(), "alice", None, None);
+        assert_eq!(submission.script_path, "/home/alice/train.sh");
+        assert_eq!(submission.script_args, vec!["--epochs", "10"]);
+        assert_eq!(submission.env.get("MODE").map(String::as_str), Some("fast"));
+        assert_eq!(submission.work_dir.as_deref(), Some("/home/alice/runs"));
+        let res = submission.req_res.unwrap();
+        assert_eq!((res.cpu_count, res.time), (4, 90));
+        assert!(submission.dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_resubmission_keeps_shipped_script() {
+        let mut job = job();
+        job.script_contents = Some(b"#!/bin/sh\necho hi\n".to_vec());
+        let submission = resubmission(&job, "alice", None, None);
+        assert_eq!(submission.script_contents, job.script_contents);
+    }
+
+    #[test]
+    fn test_resubmission_overrides() {
+        let submission = resubmission(&job(), "bob", Some(120), Some(1024));
+        assert_eq!(submission.user, "bob");
+        let res = submission.req_res.unwrap();
+        assert_eq!((res.time, res.memory), (120, 1024));
+    }
+}
@@ -0,0 +1,56 @@
+mod arg;
+use arg::Args;
+use clap::Parser;
+use melon_client::{Client, Code};
+use mrerun::resubmission;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let job_id = args.job;
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let job = match client.info(job_id).await {
+        Ok(job) => job,
+        Err(e) if e.code() == Code::NotFound => {
+            eprintln!("Unknown job id {}", job_id);
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !job.status.is_finished() {
+        let status: String = job.status.into();
+        eprintln!(
+            "Job {} is still {}, only finished jobs can be run again",
+            job_id, status
+        );
+        std::process::exit(1);
+    }
+    if job.interactive {
+        eprintln!("Job {} was interactive, start it again with mrun", job_id);
+        std::process::exit(1);
+    }
+
+    let submission = resubmission(&job, &whoami::username(), args.time, args.mem);
+    match client.submit(submission).await {
+        Ok(response) => println!("Started job with id: {:?}", response.job_id),
+        Err(e) if matches!(e.code(), Code::InvalidArgument | Code::ResourceExhausted) => {
+            eprintln!("Job rejected: {}", e.message());
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
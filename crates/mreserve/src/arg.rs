@@ -0,0 +1,56 @@
+use clap::{Parser, Subcommand};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(
+        short = 'a',
+        long = "api_endpoint",
+        default_value = "http://[::1]:8080"
+    )]
+    pub api_endpoint: String,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Creating and deleting reservations needs an admin token, listing them doesn't.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Hold nodes for some users during a time window
+    Create {
+        /// Name of the reservation
+        #[arg(long = "name", default_value = "")]
+        name: String,
+
+        /// Comma-separated node ids
+        #[arg(short = 'n', long = "nodes", value_delimiter = ',', required = true)]
+        nodes: Vec<String>,
+
+        /// Comma-separated users that may still run jobs on the nodes
+        #[arg(short = 'u', long = "users", value_delimiter = ',', required = true)]
+        users: Vec<String>,
+
+        /// Start as "YYYY-MM-DD HH:MM" in UTC, defaults to now
+        #[arg(short = 's', long = "start")]
+        start: Option<String>,
+
+        /// End as "YYYY-MM-DD HH:MM" in UTC
+        #[arg(short = 'e', long = "end")]
+        end: String,
+    },
+
+    /// List the current and upcoming reservations
+    List,
+
+    /// Delete a reservation
+    Delete {
+        /// The reservation id
+        id: u64,
+    },
+}
@@ -0,0 +1,135 @@
+mod arg;
+use arg::{Args, Command};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use clap::Parser;
+use melon_client::{proto, Client, Code};
+use melon_common::utils::get_current_timestamp;
+use prettytable::{Cell, Row, Table};
+use std::time::Duration;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let result = match args.command {
+        Command::Create {
+            name,
+            nodes,
+            users,
+            start,
+            end,
+        } => {
+            let start_time = match start {
+                Some(start) => parse_time(&start),
+                None => Ok(get_current_timestamp()),
+            };
+            let (start_time, end_time) = match (start_time, parse_time(&end)) {
+                (Ok(start_time), Ok(end_time)) => (start_time, end_time),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let request = tonic::Request::new(proto::CreateReservationRequest {
+                name,
+                node_ids: nodes,
+                users,
+                start_time,
+                end_time,
+            });
+            client
+                .scheduler()
+                .create_reservation(request)
+                .await
+                .map(|response| {
+                    let reservation = response.into_inner();
+                    println!(
+                        "Created reservation {} from {} to {}",
+                        reservation.id,
+                        format_time(reservation.start_time),
+                        format_time(reservation.end_time)
+                    );
+                })
+        }
+        Command::List => client
+            .scheduler()
+            .list_reservations(tonic::Request::new(()))
+            .await
+            .map(|response| print_reservations(&response.into_inner().reservations)),
+        Command::Delete { id } => {
+            let request =
+                tonic::Request::new(proto::DeleteReservationRequest { reservation_id: id });
+            client
+                .scheduler()
+                .delete_reservation(request)
+                .await
+                .map(|_| println!("Deleted reservation {}", id))
+        }
+    };
+    if let Err(e) = result {
+        match e.code() {
+            Code::NotFound
+            | Code::InvalidArgument
+            | Code::PermissionDenied
+            | Code::FailedPrecondition => eprintln!("{}", e.message()),
+            _ => eprintln!("Unknown error: {}", e.message()),
+        }
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn parse_time(time: &str) -> Result<u64, String> {
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+        .map(|dt| dt.and_utc().timestamp() as u64)
+        .map_err(|_| format!("Invalid time {}, expected YYYY-MM-DD HH:MM", time))
+}
+
+fn format_time(timestamp: u64) -> String {
+    Utc.timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|dt| dt.format(TIME_FORMAT).to_string())
+        .unwrap_or_else(|| "N/A".to_string())
+}
+
+fn print_reservations(reservations: &[proto::Reservation]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("ID"),
+        Cell::new("NAME"),
+        Cell::new("NODES"),
+        Cell::new("USERS"),
+        Cell::new("START"),
+        Cell::new("END"),
+        Cell::new("BY"),
+    ]));
+    for reservation in reservations {
+        table.add_row(Row::new(vec![
+            Cell::new(&reservation.id.to_string()),
+            Cell::new(&reservation.name),
+            Cell::new(&reservation.node_ids.join(",")),
+            Cell::new(&reservation.users.join(",")),
+            Cell::new(&format_time(reservation.start_time)),
+            Cell::new(&format_time(reservation.end_time)),
+            Cell::new(&reservation.created_by),
+        ]));
+    }
+    table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+    table.printstd();
+}
@@ -0,0 +1,53 @@
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(
+        short = 'a',
+        long = "api_endpoint",
+        default_value = "http://[::1]:8080"
+    )]
+    pub api_endpoint: String,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// CPUs for the command
+    #[arg(short = 'c', long = "cpus")]
+    pub cpus: Option<u32>,
+
+    /// Memory for the command, e.g. 4G
+    #[arg(short = 'm', long = "mem", value_parser = mbatch::parse_memory)]
+    pub mem: Option<u64>,
+
+    /// Time limit, e.g. 0-06:00 or minutes
+    #[arg(short = 't', long = "time", value_parser = mbatch::parse_time_limit)]
+    pub time: Option<u32>,
+
+    /// GPUs for the command
+    #[arg(short = 'g', long = "gpus")]
+    pub gpus: Option<u32>,
+
+    /// Partition to schedule the command in
+    #[arg(short = 'P', long = "partition")]
+    pub partition: Option<String>,
+
+    /// Comma-separated labels the node has to have, e.g. ssd,avx512
+    #[arg(short = 'C', long = "constraint", value_delimiter = ',')]
+    pub constraints: Vec<String>,
+
+    /// Job name shown by mqueue and mshow
+    #[arg(short = 'J', long = "name")]
+    pub name: Option<String>,
+
+    /// Require CPUs, memory and time on the command line, ignore ~/.melon/defaults
+    #[arg(long = "strict")]
+    pub strict: bool,
+
+    /// Command and its arguments, the login shell if not given
+    #[arg(trailing_var_arg = true)]
+    pub command: Vec<String>,
+}
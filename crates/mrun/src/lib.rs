@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+use mbatch::{ResourceDefaults, ResourceOverrides};
+use melon_common::script::is_executable;
+use melon_common::RequestedResources;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Returns the command that runs if none is given, the user's shell.
+pub fn default_command() -> String {
+    std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+}
+
+/// Resolves the program of a command to an absolute path, like a shell would.
+///
+/// Programs with a `/` are taken relative to `cwd`, the others are looked up in `path_var`.
+/// The node runs the same path, so it has to exist there as well.
+pub fn resolve_command(program: &str, cwd: &Path, path_var: Option<&OsStr>) -> Result<PathBuf> {
+    if program.contains('/') {
+        let path = cwd.join(program);
+        return match is_executable(&path) {
+            Ok(true) => Ok(path),
+            Ok(false) => Err(anyhow!("{} is not executable", path.display())),
+            Err(e) => Err(anyhow!("Could not run {}: {}", path.display(), e)),
+        };
+    }
+
+    let dirs = path_var.map(std::env::split_paths).into_iter().flatten();
+    for dir in dirs {
+        let path = cwd.join(dir).join(program);
+        if path.is_file() && is_executable(&path).unwrap_or(false) {
+            return Ok(path);
+        }
+    }
+    Err(anyhow!("Command not found: {}", program))
+}
+
+/// Combines the resources from the command line with the user's defaults.
+pub fn requested_resources(
+    overrides: &ResourceOverrides,
+    defaults: &ResourceDefaults,
+) -> Result<RequestedResources> {
+    let cpu_count = overrides.cpu_count.or(defaults.cpu_count);
+    let memory = overrides.memory.or(defaults.memory);
+    let time = overrides.time.or(defaults.time);
+    match (cpu_count, memory, time) {
+        (Some(cpu_count), Some(memory), Some(time)) => Ok(RequestedResources::new(
+            cpu_count,
+            memory,
+            time,
+            overrides.gpu_count.unwrap_or(0),
+        )),
+        _ => Err(anyhow!(
+            "Missing resources, pass -c, -m and -t or set them in ~/.melon/defaults"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn write_file(dir: &Path, name: &str, mode: u32) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_resolve_command_in_path() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        write_file(first.path(), "tool", 0o644);
+        let tool = write_file(second.path(), "tool", 0o755);
+        let path_var = std::env::join_paths([first.path(), second.path()]).unwrap();
+
+        // the first match that can be run wins
+        let resolved = resolve_command("tool", Path::new("/"), Some(&path_var)).unwrap();
+        assert_eq!(resolved, tool);
+
+        assert!(resolve_command("missing", Path::new("/"), Some(&path_var)).is_err());
+        assert!(resolve_command("tool", Path::new("/"), None).is_err());
+    }
+
+    #[test]
+    fn test_resolve_relative_command() {
+        let dir = TempDir::new().unwrap();
+        let tool = write_file(dir.path(), "tool", 0o755);
+        write_file(dir.path(), "data", 0o644);
+
+        let resolved = resolve_command("./tool", dir.path(), None).unwrap();
+        assert_eq!(resolved, dir.path().join("./tool"));
+        assert!(resolved.ends_with(tool.file_name().unwrap()));
+        assert!(resolve_command("./data", dir.path(), None).is_err());
+        assert!(resolve_command("./missing", dir.path(), None).is_err());
+    }
+
+    #[test]
+    fn test_requested_resources_fall_back_to_defaults() {
+        let overrides = ResourceOverrides {
+            cpu_count: Some(4),
+            ..Default::default()
+        };
+        let defaults = ResourceDefaults {
+            cpu_count: Some(1),
+            memory: Some(1024),
+            time: Some(60),
+        };
+
+        let res = requested_resources(&overrides, &defaults).unwrap();
+        assert_eq!((res.cpu_count, res.memory, res.time), (4, 1024, 60));
+
+        assert!(requested_resources(&overrides, &ResourceDefaults::default()).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+mod arg;
+use anyhow::anyhow;
+use arg::Args;
+use clap::Parser;
+use mbatch::{default_resources_path, read_resource_defaults, ResourceDefaults, ResourceOverrides};
+use melon_client::{proto, Client, Code};
+use melon_common::proto::melon_worker_client::MelonWorkerClient;
+use melon_common::JobStatus;
+use mrun::{default_command, requested_resources, resolve_command};
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Endpoint;
+
+/// How often the job is polled while it waits for resources
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Exit code of a command that was interrupted, like a shell reports it
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let mut command = args.command;
+    if command.is_empty() {
+        command.push(default_command());
+    }
+    let cwd = std::env::current_dir()?;
+    let program = match resolve_command(&command[0], &cwd, std::env::var_os("PATH").as_deref()) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let overrides = ResourceOverrides {
+        cpu_count: args.cpus,
+        memory: args.mem,
+        time: args.time,
+        gpu_count: args.gpus,
+        node_count: None,
+    };
+    let defaults = if args.strict {
+        ResourceDefaults::default()
+    } else {
+        read_resource_defaults(&default_resources_path())?
+    };
+    let req_res = match requested_resources(&overrides, &defaults) {
+        Ok(req_res) => req_res,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let user = whoami::username();
+    let req = proto::JobSubmission {
+        user: user.clone(),
+        script_path: program.to_string_lossy().into_owned(),
+        req_res: Some(req_res.into()),
+        script_args: command[1..].to_vec(),
+        priority: None,
+        dependencies: vec![],
+        partition: args.partition,
+        force: false,
+        env: HashMap::new(),
+        work_dir: Some(cwd.to_string_lossy().into_owned()),
+        name: args.name,
+        grace_secs: None,
+        max_retries: 0,
+        dry_run: false,
+        interactive: true,
+        constraints: args.constraints,
+        begin_deadline_mins: None,
+        warn_signal: None,
+        output_path: None,
+        error_path: None,
+        parent_job_id: None,
+        script_contents: None,
+    };
+    let response = match client.submit(req).await {
+        Ok(response) => response,
+        Err(e) if matches!(e.code(), Code::InvalidArgument | Code::ResourceExhausted) => {
+            eprintln!("Job rejected: {}", e.message());
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let job_id = response.job_id;
+    eprintln!("Job {} is waiting for resources", job_id);
+
+    let address = tokio::select! {
+        address = wait_for_node(client.clone(), job_id) => address,
+        _ = tokio::signal::ctrl_c() => {
+            if let Err(e) = client.cancel(job_id, &user).await {
+                eprintln!("Could not cancel job {}: {}", job_id, e.message());
+            } else {
+                eprintln!("Cancelled job {}", job_id);
+            }
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    };
+    let address = match address {
+        Ok(address) => address,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let code = match attach(address, job_id, response.attach_token, timeout).await {
+        Ok(Some(code)) => code,
+        Ok(None) => {
+            eprintln!("Lost the connection to job {}", job_id);
+            1
+        }
+        Err(e) => {
+            eprintln!("Could not attach to job {}: {}", job_id, e);
+            1
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Waits until the job runs and returns the address of its node.
+async fn wait_for_node(mut client: Client, job_id: u64) -> anyhow::Result<String> {
+    let node_id = loop {
+        let job = client.info(job_id).await?;
+        if job.status == JobStatus::Running {
+            break job.assigned_node.unwrap_or_default();
+        }
+        if job.status.is_finished() {
+            let name: String = job.status.into();
+            return Err(anyhow!(
+                "Job {} ended as {} before it started",
+                job_id,
+                name
+            ));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    };
+
+    let nodes = client.nodes().await?;
+    nodes
+        .into_iter()
+        .find(|node| node.id == node_id)
+        .map(|node| node.address)
+        .ok_or_else(|| anyhow!("Node {} of job {} is gone", node_id, job_id))
+}
+
+/// Relays the terminal to the job until it ends and returns its exit code.
+///
+/// Returns `None` if the job ended without one, e.g. because it was killed by a signal or
+/// the connection broke.
+async fn attach(
+    address: String,
+    job_id: u64,
+    attach_token: String,
+    timeout: Duration,
+) -> anyhow::Result<Option<i32>> {
+    let endpoint = Endpoint::from_shared(address)?;
+    let channel = tokio::time::timeout(timeout, endpoint.connect())
+        .await
+        .map_err(|_| anyhow!("No connection to the node after {}s", timeout.as_secs()))??;
+    let mut worker = MelonWorkerClient::new(channel);
+
+    let tty = std::io::stdin().is_terminal();
+    let (cols, rows) = if tty {
+        crossterm::terminal::size().unwrap_or((80, 24))
+    } else {
+        (0, 0)
+    };
+    let (tx, rx) = mpsc::channel(16);
+    tx.send(proto::AttachInput {
+        job_id,
+        attach_token,
+        tty,
+        rows: rows.into(),
+        cols: cols.into(),
+        ..Default::default()
+    })
+    .await?;
+    let mut output = worker.attach(ReceiverStream::new(rx)).await?.into_inner();
+
+    let _raw_mode = if tty { Some(RawMode::enable()?) } else { None };
+    tokio::spawn(forward_stdin(tx));
+
+    let mut exit_code = None;
+    while let Some(message) = output.message().await? {
+        match message.stream() {
+            proto::OutputStream::Stdout => {
+                let mut stdout = std::io::stdout();
+                stdout.write_all(&message.content)?;
+                stdout.flush()?;
+            }
+            proto::OutputStream::Stderr => {
+                let mut stderr = std::io::stderr();
+                stderr.write_all(&message.content)?;
+                stderr.flush()?;
+            }
+        }
+        if message.exited {
+            exit_code = message.exit_code;
+        }
+    }
+    Ok(exit_code)
+}
+
+/// Sends everything read from stdin to the job, and tells it once stdin is closed.
+async fn forward_stdin(tx: mpsc::Sender<proto::AttachInput>) {
+    let mut stdin = tokio::io::stdin();
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let input = match stdin.read(&mut buf).await {
+            Ok(0) | Err(_) => proto::AttachInput {
+                stdin_closed: true,
+                ..Default::default()
+            },
+            Ok(n) => proto::AttachInput {
+                stdin: buf[..n].to_vec(),
+                ..Default::default()
+            },
+        };
+        let closed = input.stdin_closed;
+        if tx.send(input).await.is_err() || closed {
+            break;
+        }
+    }
+}
+
+/// Keeps the terminal in raw mode until dropped, so that keys like ctrl-c reach the job.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> std::io::Result<Self> {
+        crossterm::terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
@@ -1,4 +1,5 @@
 use clap::Parser;
+use melon_common::utils::parse_memory;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -11,10 +12,39 @@ pub struct Args {
     )]
     pub api_endpoint: String,
 
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
     /// The job id
     #[arg()]
     pub job: u64,
 
     #[arg(short = 'p', long = "parseable")]
     pub parseable: bool,
+
+    /// Print the captured output of the job instead of its details
+    #[arg(short = 'l', long = "logs", group = "output")]
+    pub logs: bool,
+
+    /// Print an mbatch command that submits the job again with the same settings
+    #[arg(short = 'r', long = "reproduce", conflicts_with_all = ["parseable", "logs"])]
+    pub reproduce: bool,
+
+    /// Stream the output of the job until it ends, then print its final status
+    #[arg(
+        short = 'f',
+        long = "follow",
+        group = "output",
+        conflicts_with_all = ["parseable", "reproduce"]
+    )]
+    pub follow: bool,
+
+    /// Only print the last part of the output, e.g. `64K` or `1M` (requires --logs)
+    #[arg(long = "tail", requires = "logs", value_parser = parse_memory)]
+    pub tail: Option<u64>,
+
+    /// Print the captured stderr instead of stdout (requires --logs or --follow)
+    #[arg(short = 'e', long = "stderr", requires = "output")]
+    pub stderr: bool,
 }
@@ -1 +1,2 @@
 mod arg;
+pub mod reproduce;
@@ -3,32 +3,117 @@ use arg::Args;
 use chrono::{TimeZone, Utc};
 use clap::Parser;
 use colored::*;
-use melon_common::{
-    proto::{self, melon_scheduler_client::MelonSchedulerClient},
-    JobStatus,
-};
+use melon_client::{proto, Client, Code};
+use melon_common::{utils::get_current_timestamp, Job, JobStatus};
+use mshow::reproduce;
 use prettytable::{Cell, Row, Table};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::io::Write;
+use std::time::Duration;
+
+/// Share of the time limit below which the remaining time of a job is highlighted
+const LOW_TIME_LEFT_RATIO: f64 = 0.1;
+
+/// How often `--follow` polls the job
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
     let job_id = args.job;
 
-    let mut client = MelonSchedulerClient::connect(args.api_endpoint).await?;
-    let request = tonic::Request::new(proto::GetJobInfoRequest { job_id });
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    match client.get_job_info(request).await {
-        Ok(response) => {
-            let job = response.get_ref();
+    let stream = if args.stderr {
+        proto::OutputStream::Stderr
+    } else {
+        proto::OutputStream::Stdout
+    };
+    if args.logs {
+        return print_job_output(&mut client, job_id, stream, args.tail).await;
+    }
+    if args.follow {
+        return follow_job(&mut client, job_id, stream).await;
+    }
+
+    match client.info(job_id).await {
+        Ok(job) => {
             if args.parseable {
-                print_job_json(job)?;
+                print_job_json(&job)?;
+            } else if args.reproduce {
+                match reproduce::mbatch_command(&job) {
+                    Some(command) => println!("{}", command),
+                    None => {
+                        eprintln!(
+                            "Job {} ran a script that was sent along and is not text, use mrerun instead",
+                            job_id
+                        );
+                        std::process::exit(1);
+                    }
+                }
             } else {
-                print_job_info(job);
+                print_job_info(&job);
+            }
+        }
+        Err(e) => match e.code() {
+            Code::NotFound => println!("Unknown job id {}", job_id),
+            _ => println!("Unknown error: {}", e),
+        },
+    }
+
+    Ok(())
+}
+
+/// Writes the frames of a job's output to stdout as they arrive.
+///
+/// Moves `offset` past every frame that was written, also if the stream breaks off.
+async fn write_output(
+    mut frames: tonic::Streaming<proto::JobOutput>,
+    offset: &mut u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    while let Some(frame) = frames.message().await? {
+        std::io::stdout().write_all(&frame.content)?;
+        std::io::stdout().flush()?;
+        *offset = frame.offset + frame.content.len() as u64;
+    }
+    Ok(())
+}
+
+async fn print_job_output(
+    client: &mut Client,
+    job_id: u64,
+    stream: proto::OutputStream,
+    tail: Option<u64>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = tonic::Request::new(proto::JobOutputRequest {
+        job_id,
+        stream: stream.into(),
+        offset: None,
+        tail_bytes: tail,
+    });
+
+    match client.scheduler().get_job_output(request).await {
+        Ok(response) => {
+            if let Err(e) = write_output(response.into_inner(), &mut 0).await {
+                eprintln!("Could not read the output of job {}: {}", job_id, e);
             }
         }
         Err(e) => match e.code() {
-            tonic::Code::NotFound => println!("Unknown job id {}", job_id),
+            Code::NotFound => println!("No output found for job id {}", job_id),
+            Code::FailedPrecondition => println!("Job {} has not started yet", job_id),
+            Code::Unavailable => {
+                println!("Node of job {} is not reachable: {}", job_id, e.message())
+            }
             _ => println!("Unknown error: {}", e),
         },
     }
@@ -36,14 +121,85 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn print_job_json(job: &proto::Job) -> Result<(), Box<dyn std::error::Error>> {
-    let job: melon_common::Job = job.into();
-    let json = serde_json::to_string_pretty(&job)?;
+/// Prints new output of a job as it appears, until the job has ended.
+///
+/// Status changes go to stderr, so the job output itself can be piped.
+async fn follow_job(
+    client: &mut Client,
+    job_id: u64,
+    stream: proto::OutputStream,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut last_status = None;
+    let mut offset = 0;
+    loop {
+        let job = match client.info(job_id).await {
+            Ok(job) => job,
+            Err(e) => {
+                match e.code() {
+                    Code::NotFound => println!("Unknown job id {}", job_id),
+                    _ => println!("Unknown error: {}", e),
+                }
+                return Ok(());
+            }
+        };
+
+        let status = job.status.clone();
+        if last_status.as_ref() != Some(&status) {
+            // a requeued job starts over with fresh output
+            if matches!(status, JobStatus::Pending | JobStatus::Held) {
+                offset = 0;
+            }
+            if !status.is_finished() {
+                let name: String = status.clone().into();
+                eprintln!("Job {} is {}", job_id, name);
+            }
+            last_status = Some(status.clone());
+        }
+
+        // the output is read once more after the job has ended, to catch its last lines
+        if job.start_time.is_some() {
+            let request = tonic::Request::new(proto::JobOutputRequest {
+                job_id,
+                stream: stream.into(),
+                offset: Some(offset),
+                tail_bytes: None,
+            });
+            match client.scheduler().get_job_output(request).await {
+                Ok(response) => {
+                    if let Err(e) = write_output(response.into_inner(), &mut offset).await {
+                        eprintln!("Could not read the output of job {}: {}", job_id, e);
+                    }
+                }
+                // the node may not have written any output yet
+                Err(e) if matches!(e.code(), Code::NotFound | Code::FailedPrecondition) => {}
+                Err(e) => eprintln!("Could not read the output of job {}: {}", job_id, e),
+            }
+        }
+
+        if status.is_finished() {
+            let exit_code = job
+                .exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "N/A".to_string());
+            let name: String = status.into();
+            eprintln!("Job {} is {}, exit code {}", job_id, name, exit_code);
+            if let Some(message) = &job.message {
+                eprintln!("{}", message);
+            }
+            return Ok(());
+        }
+
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+    }
+}
+
+fn print_job_json(job: &Job) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(job)?;
     println!("{}", json);
     Ok(())
 }
 
-fn print_job_info(job: &proto::Job) {
+fn print_job_info(job: &Job) {
     let mut table = Table::new();
 
     // Add headers
@@ -52,37 +208,52 @@ fn print_job_info(job: &proto::Job) {
         Cell::new("NAME"),
         Cell::new("USER"),
         Cell::new("STATUS"),
+        Cell::new("PARTITION"),
         Cell::new("SUBMIT DATE"),
         Cell::new("START DATE"),
         Cell::new("STOP DATE"),
+        Cell::new("TIME LEFT"),
         Cell::new("NODES"),
+        Cell::new("MAX RSS"),
+        Cell::new("CPU TIME"),
+        Cell::new("ATTEMPT"),
+        Cell::new("EXIT CODE"),
     ]));
 
-    let job_status = JobStatus::from(job.status);
-    let status: String = job_status.clone().into();
+    let status: String = job.status.clone().into();
 
-    let node = if job_status == JobStatus::Pending {
+    let node = if matches!(job.status, JobStatus::Pending | JobStatus::Held) {
         "(PD)".to_string()
     } else {
-        job.assigned_node.clone()
+        job.nodes().cloned().collect::<Vec<_>>().join(",")
     };
 
-    let script_name = job
-        .script_path
-        .split('/')
-        .last()
-        .unwrap_or(&job.script_path);
+    let name = job.display_name().to_string();
 
     // Add job data
     table.add_row(Row::new(vec![
         Cell::new(&job.id.to_string()),
-        Cell::new(truncate_str(script_name, 15).as_str()),
+        Cell::new(truncate_str(&name, 15).as_str()),
         Cell::new(&job.user),
         Cell::new(&status),
+        Cell::new(&job.partition),
         Cell::new(&format_timestamp(Some(job.submit_time))),
         Cell::new(&format_timestamp(job.start_time)),
         Cell::new(&format_timestamp(job.stop_time)),
+        Cell::new(&format_time_left(job, get_current_timestamp())),
         Cell::new(&node),
+        Cell::new(&format_bytes(job.max_rss_bytes)),
+        Cell::new(&format_cpu_seconds(job.cpu_seconds)),
+        Cell::new(&format!(
+            "{}/{}",
+            job.retry_count + 1,
+            job.max_retries.max(job.retry_count) + 1
+        )),
+        Cell::new(
+            &job.exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "N/A".to_string()),
+        ),
     ]));
 
     // Set table formatting
@@ -90,6 +261,44 @@ fn print_job_info(job: &proto::Job) {
 
     // Print the table
     table.printstd();
+
+    if let Some(queue) = job.queue_position {
+        println!();
+        println!("QUEUE");
+        println!("position {} of {}", queue.position, queue.length);
+    }
+
+    // why the job did not complete, e.g. the end of its error output
+    if let Some(message) = &job.message {
+        println!();
+        println!("REASON");
+        println!("{}", message);
+    }
+
+    // earlier runs of a requeued job
+    if !job.attempts.is_empty() {
+        let mut attempts = Table::new();
+        attempts.add_row(Row::new(vec![
+            Cell::new("ATTEMPT"),
+            Cell::new("STATUS"),
+            Cell::new("START DATE"),
+            Cell::new("STOP DATE"),
+            Cell::new("NODE"),
+        ]));
+        for (i, attempt) in job.attempts.iter().enumerate() {
+            let status: String = attempt.status.clone().into();
+            attempts.add_row(Row::new(vec![
+                Cell::new(&(i + 1).to_string()),
+                Cell::new(&status),
+                Cell::new(&format_timestamp(attempt.start_time)),
+                Cell::new(&format_timestamp(Some(attempt.stop_time))),
+                Cell::new(&attempt.node_id),
+            ]));
+        }
+        attempts.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+        println!();
+        attempts.printstd();
+    }
 }
 
 fn truncate_str(s: &str, max_chars: usize) -> String {
@@ -110,6 +319,62 @@ fn format_timestamp(timestamp: Option<u64>) -> String {
         .unwrap_or_else(|| "N/A".to_string())
 }
 
+/// Returns how long a running job has left until it reaches its time limit.
+fn time_left(job: &Job, now: u64) -> Option<Duration> {
+    if job.status != JobStatus::Running {
+        return None;
+    }
+    let start = job.start_time?;
+    let limit = job.req_res.time as u64 * 60;
+    let elapsed = now.saturating_sub(start);
+    Some(Duration::from_secs(limit.saturating_sub(elapsed)))
+}
+
+fn format_time_left(job: &Job, now: u64) -> String {
+    let Some(left) = time_left(job, now) else {
+        return "N/A".to_string();
+    };
+    let secs = left.as_secs();
+    let formatted = format!(
+        "{}-{:02}:{:02}:{:02}",
+        secs / 86400,
+        (secs % 86400) / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    );
+
+    let limit = job.req_res.time as u64 * 60;
+    if (secs as f64) < limit as f64 * LOW_TIME_LEFT_RATIO {
+        formatted.red().bold().to_string()
+    } else {
+        formatted
+    }
+}
+
+fn format_bytes(bytes: Option<u64>) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let Some(bytes) = bytes else {
+        return "N/A".to_string();
+    };
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", bytes, UNITS[0])
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+fn format_cpu_seconds(seconds: Option<f64>) -> String {
+    seconds
+        .map(|s| format!("{:.1}s", s))
+        .unwrap_or_else(|| "N/A".to_string())
+}
+
 #[allow(dead_code)]
 fn color_status(status: JobStatus) -> ColoredString {
     match status {
@@ -118,39 +383,25 @@ fn color_status(status: JobStatus) -> ColoredString {
         JobStatus::Pending => "Pending".yellow(),
         JobStatus::Running => "Running".blue(),
         JobStatus::Timeout => "Timeout".purple(),
+        JobStatus::Cancelled => "Cancelled".dimmed(),
+        JobStatus::Held => "Held".yellow(),
+        JobStatus::OutOfMemory => "OutOfMemory".red(),
     }
 }
 
 #[allow(dead_code)]
-fn calculate_elapsed_time(job: &proto::Job) -> String {
-    let start = job.start_time.map(|t| UNIX_EPOCH + Duration::from_secs(t));
-    let stop = job.stop_time.map(|t| UNIX_EPOCH + Duration::from_secs(t));
-    let now = SystemTime::now();
-
-    let duration = match job.status {
-        2 => {
-            // Completed
-            match (start, stop) {
-                (Some(s), Some(e)) => e.duration_since(s).unwrap_or_default(),
-                _ => Duration::default(),
-            }
-        }
-        1 => {
-            // Running
-            match start {
-                Some(s) => now.duration_since(s).unwrap_or_default(),
-                None => Duration::default(),
-            }
-        }
-        _ => {
-            // Pending or any other status
-            Duration::default()
-        }
+fn calculate_elapsed_time(job: &Job) -> String {
+    // timestamps of different clocks may be inverted, that counts as no time at all
+    let elapsed = match (&job.status, job.start_time, job.stop_time) {
+        (JobStatus::Completed, Some(start), Some(stop)) => stop.saturating_sub(start),
+        (JobStatus::Running, Some(start), _) => get_current_timestamp().saturating_sub(start),
+        // Pending or any other status
+        _ => 0,
     };
 
-    let days = duration.as_secs() / 86400;
-    let hours = (duration.as_secs() % 86400) / 3600;
-    let minutes = (duration.as_secs() % 3600) / 60;
+    let days = elapsed / 86400;
+    let hours = (elapsed % 86400) / 3600;
+    let minutes = (elapsed % 3600) / 60;
 
     format!("{}-{:02}-{:02}", days, hours, minutes)
 }
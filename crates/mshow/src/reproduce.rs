@@ -0,0 +1,175 @@
+use melon_common::{utils::format_memory, Job, DEFAULT_PARTITION, DEFAULT_PRIORITY};
+
+/// Reconstructs an `mbatch` command that submits a job again with the same settings.
+///
+/// Resources are passed as options, so later edits to the `#MBATCH` directives of the script
+/// don't change them. Dependencies are left out, they refer to jobs of the original run.
+/// A script that was sent along is passed on stdin, `None` if it is not text.
+pub fn mbatch_command(job: &Job) -> Option<String> {
+    let script = match &job.script_contents {
+        Some(contents) => Some(std::str::from_utf8(contents).ok()?),
+        None => None,
+    };
+    let res = &job.req_res;
+    let mut parts = vec![
+        "mbatch".to_string(),
+        format!("-c {}", res.cpu_count),
+        format!("-m {}", format_memory(res.memory)),
+        format!("-t {}", res.time),
+    ];
+    if res.gpu_count > 0 {
+        parts.push(format!("-g {}", res.gpu_count));
+    }
+    if res.node_count > 1 {
+        parts.push(format!("-N {}", res.node_count));
+    }
+    if job.partition != DEFAULT_PARTITION {
+        parts.push(format!("-P {}", quote(&job.partition)));
+    }
+    if !job.constraints.is_empty() {
+        parts.push(format!("-C {}", quote(&job.constraints.join(","))));
+    }
+    if job.priority != DEFAULT_PRIORITY {
+        parts.push(format!("-p {}", job.priority));
+    }
+    if let Some(name) = &job.name {
+        parts.push(format!("-J {}", quote(name)));
+    }
+    if let Some(dir) = &job.work_dir {
+        parts.push(format!("-D {}", quote(dir)));
+    }
+    if let Some(path) = &job.output_path {
+        parts.push(format!("-o {}", quote(path)));
+    }
+    if let Some(path) = &job.error_path {
+        parts.push(format!("--error {}", quote(path)));
+    }
+    let mut env: Vec<_> = job.env.iter().collect();
+    env.sort();
+    for (key, value) in env {
+        parts.push(format!("-e {}", quote(&format!("{}={}", key, value))));
+    }
+    if let Some(grace_secs) = job.grace_secs {
+        parts.push(format!("--grace {}", grace_secs));
+    }
+    if job.max_retries > 0 {
+        parts.push(format!("--requeue {}", job.max_retries));
+    }
+    if let Some(warn_signal) = &job.warn_signal {
+        parts.push(format!("--signal {}", warn_signal));
+    }
+    if let Some(deadline) = job.begin_deadline_mins {
+        parts.push(format!("--deadline {}", deadline));
+    }
+
+    // arguments that look like options would be taken as options of mbatch
+    if job.script_args.iter().any(|arg| arg.starts_with('-')) {
+        parts.push("--".to_string());
+    }
+    match script {
+        Some(_) => parts.push("-".to_string()),
+        None => parts.push(quote(&job.script_path)),
+    }
+    parts.extend(job.script_args.iter().map(|arg| quote(arg)));
+    let mut command = parts.join(" ");
+    if let Some(script) = script {
+        command.push_str(&heredoc(script));
+    }
+    Some(command)
+}
+
+/// Writes a script as a heredoc, its delimiter doesn't occur as a line of the script.
+fn heredoc(script: &str) -> String {
+    let mut delimiter = "MBATCH_SCRIPT".to_string();
+    while script.lines().any(|line| line == delimiter) {
+        delimiter.push('_');
+    }
+    let newline = if script.ends_with('\n') { "" } else { "\n" };
+    format!(" <<'{0}'\n{1}{2}{0}", delimiter, script, newline)
+}
+
+/// Quotes a value for a POSIX shell, unless it only contains characters that are safe as they are.
+fn quote(value: &str) -> String {
+    let safe = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if safe {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use melon_common::RequestedResources;
+
+    fn job() -> Job {
+        Job::new(
+            7,
+            "alice".to_string(),
+            "/home/alice/train.sh".to_string(),
+            vec!["--epochs".to_string(), "10".to_string()],
+            RequestedResources::new(4, 8 * 1024 * 1024 * 1024, 90, 0),
+        )
+    }
+
+    #[test]
+    fn test_minimal_command() {
+        let mut job = job();
+        job.script_args.clear();
+        assert_eq!(
+            mbatch_command(&job).unwrap(),
+            "mbatch -c 4 -m 8G -t 90 /home/alice/train.sh"
+        );
+    }
+
+    #[test]
+    fn test_shipped_script_is_passed_on_stdin() {
+        let mut job = job();
+        job.script_args.clear();
+        job.script_contents = Some(b"#!/bin/sh\necho MBATCH_SCRIPT\nMBATCH_SCRIPT\n".to_vec());
+        assert_eq!(
+            mbatch_command(&job).unwrap(),
+            "mbatch -c 4 -m 8G -t 90 - <<'MBATCH_SCRIPT_'\n#!/bin/sh\necho MBATCH_SCRIPT\nMBATCH_SCRIPT\nMBATCH_SCRIPT_"
+        );
+
+        job.script_contents = Some(vec![0xff, 0xfe]);
+        assert!(mbatch_command(&job).is_none());
+    }
+
+    #[test]
+    fn test_full_command() {
+        let mut job = job();
+        job.req_res = RequestedResources::new(2, 1536 * 1024 * 1024, 30, 1).with_node_count(2);
+        job.partition = "gpu".to_string();
+        job.constraints = vec!["ssd".to_string(), "avx512".to_string()];
+        job.priority = 80;
+        job.name = Some("first try".to_string());
+        job.work_dir = Some("/home/alice/runs".to_string());
+        job.env.insert("MODE".to_string(), "fast".to_string());
+        job.env
+            .insert("GREETING".to_string(), "it's me".to_string());
+        job.grace_secs = Some(60);
+        job.max_retries = 2;
+        job.begin_deadline_mins = Some(120);
+        job.warn_signal = "USR1@90".parse().ok();
+        job.output_path = Some("/home/alice/runs/out.%j".to_string());
+
+        assert_eq!(
+            mbatch_command(&job).unwrap(),
+            "mbatch -c 2 -m 1536M -t 30 -g 1 -N 2 -P gpu -C ssd,avx512 -p 80 -J 'first try' -D /home/alice/runs \
+             -o /home/alice/runs/out.%j -e 'GREETING=it'\\''s me' -e MODE=fast --grace 60 --requeue 2 --signal USR1@90 --deadline 120 \
+             -- /home/alice/train.sh --epochs 10"
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain-value_1.0"), "plain-value_1.0");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+    }
+}
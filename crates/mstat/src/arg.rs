@@ -0,0 +1,18 @@
+use clap::Parser;
+use std::net::SocketAddr;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(short = 'a', long = "api_endpoint", default_value = "[::1]:8080")]
+    pub api_endpoint: SocketAddr,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// Print the summary as json
+    #[arg(long = "json")]
+    pub json: bool,
+}
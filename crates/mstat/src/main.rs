@@ -0,0 +1,76 @@
+mod arg;
+mod summary;
+use arg::Args;
+use clap::Parser;
+use melon_client::Client;
+use melon_common::utils::get_current_timestamp;
+use std::time::Duration;
+use summary::ClusterSummary;
+
+const GIB: f64 = (1024 * 1024 * 1024) as f64;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint.to_string())
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let nodes = client.nodes().await?;
+    let jobs = client.list(None).await?;
+
+    let summary = ClusterSummary::new(&nodes, &jobs, get_current_timestamp());
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        print_summary(&summary);
+    }
+
+    Ok(())
+}
+
+fn print_summary(summary: &ClusterSummary) {
+    println!(
+        "Nodes:   {} available, {} offline or draining",
+        summary.nodes_available, summary.nodes_unavailable
+    );
+    println!(
+        "CPUs:    {}/{} used, {} free",
+        summary.cpus_used, summary.cpus_total, summary.cpus_free
+    );
+    println!(
+        "Memory:  {:.1}/{:.1}G used, {:.1}G free",
+        summary.memory_used as f64 / GIB,
+        summary.memory_total as f64 / GIB,
+        summary.memory_free as f64 / GIB
+    );
+    let jobs: Vec<String> = summary
+        .jobs
+        .iter()
+        .map(|(status, count)| format!("{} {}", count, status))
+        .collect();
+    if jobs.is_empty() {
+        println!("Jobs:    none");
+    } else {
+        println!("Jobs:    {}", jobs.join(", "));
+    }
+    if let (Some(job_id), Some(wait)) =
+        (summary.oldest_pending_job, summary.oldest_pending_wait_secs)
+    {
+        println!(
+            "Oldest pending job {} waits for {:02}:{:02}:{:02}",
+            job_id,
+            wait / 3600,
+            (wait % 3600) / 60,
+            wait % 60
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use melon_common::{proto, Job, JobStatus, NodeStatus};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Resources and jobs of the whole cluster at one point in time.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ClusterSummary {
+    /// Nodes that take new jobs
+    pub nodes_available: usize,
+
+    /// Nodes that are offline or draining
+    pub nodes_unavailable: usize,
+
+    /// CPUs of the available nodes
+    pub cpus_total: u32,
+    pub cpus_used: u32,
+    pub cpus_free: u32,
+
+    /// Memory of the available nodes in bytes
+    pub memory_total: u64,
+    pub memory_used: u64,
+    pub memory_free: u64,
+
+    /// Number of jobs by status, finished ones included
+    pub jobs: BTreeMap<String, usize>,
+
+    /// The pending job that was submitted first
+    pub oldest_pending_job: Option<u64>,
+
+    /// Seconds the oldest pending job has been waiting for
+    pub oldest_pending_wait_secs: Option<u64>,
+}
+
+impl ClusterSummary {
+    pub fn new(nodes: &[proto::NodeDetails], jobs: &[Job], now: u64) -> Self {
+        let mut summary = ClusterSummary::default();
+
+        for node in nodes {
+            let status = proto::NodeStatus::try_from(node.status).map(NodeStatus::from);
+            if !matches!(status, Ok(NodeStatus::Available)) {
+                summary.nodes_unavailable += 1;
+                continue;
+            }
+            let total = node.total_resources.unwrap_or_default();
+            let used = node.used_resources.unwrap_or_default();
+            summary.nodes_available += 1;
+            summary.cpus_total += total.cpu_count;
+            summary.cpus_used += used.cpu_count;
+            summary.memory_total += total.memory;
+            summary.memory_used += used.memory;
+        }
+        summary.cpus_free = summary.cpus_total.saturating_sub(summary.cpus_used);
+        summary.memory_free = summary.memory_total.saturating_sub(summary.memory_used);
+
+        for job in jobs {
+            *summary
+                .jobs
+                .entry(String::from(job.status.clone()))
+                .or_default() += 1;
+        }
+        if let Some(oldest) = jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Pending)
+            .min_by_key(|job| job.submit_time)
+        {
+            summary.oldest_pending_job = Some(oldest.id);
+            summary.oldest_pending_wait_secs = Some(now.saturating_sub(oldest.submit_time));
+        }
+
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use melon_common::RequestedResources;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn node(status: proto::NodeStatus, cpus: (u32, u32), memory: (u64, u64)) -> proto::NodeDetails {
+        proto::NodeDetails {
+            status: status.into(),
+            total_resources: Some(proto::NodeResources {
+                cpu_count: cpus.1,
+                memory: memory.1,
+                gpu_count: 0,
+            }),
+            used_resources: Some(proto::NodeResources {
+                cpu_count: cpus.0,
+                memory: memory.0,
+                gpu_count: 0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn job(id: u64, status: JobStatus, submit_time: u64) -> Job {
+        let mut job = Job::new(
+            id,
+            "alice".to_string(),
+            "/home/alice/job.sh".to_string(),
+            vec![],
+            RequestedResources::new(1, GIB, 10, 0),
+        );
+        job.status = status;
+        job.submit_time = submit_time;
+        job
+    }
+
+    #[test]
+    fn test_summary_counts_available_nodes_only() {
+        let nodes = vec![
+            node(proto::NodeStatus::Available, (2, 8), (GIB, 16 * GIB)),
+            node(proto::NodeStatus::Available, (0, 4), (0, 8 * GIB)),
+            node(proto::NodeStatus::Offline, (4, 4), (GIB, 8 * GIB)),
+        ];
+
+        let summary = ClusterSummary::new(&nodes, &[], 0);
+
+        assert_eq!(summary.nodes_available, 2);
+        assert_eq!(summary.nodes_unavailable, 1);
+        assert_eq!(
+            (summary.cpus_used, summary.cpus_free, summary.cpus_total),
+            (2, 10, 12)
+        );
+        assert_eq!(summary.memory_free, 23 * GIB);
+        assert_eq!(summary.oldest_pending_job, None);
+    }
+
+    #[test]
+    fn test_summary_counts_jobs_by_status() {
+        let jobs = vec![
+            job(1, JobStatus::Completed, 100),
+            job(2, JobStatus::Running, 200),
+            job(3, JobStatus::Pending, 400),
+            job(4, JobStatus::Pending, 300),
+            job(5, JobStatus::Held, 50),
+        ];
+
+        let summary = ClusterSummary::new(&[], &jobs, 1000);
+
+        assert_eq!(summary.jobs["Pending"], 2);
+        assert_eq!(summary.jobs["Held"], 1);
+        assert_eq!(summary.jobs.get("Failed"), None);
+        // held jobs don't wait for resources
+        assert_eq!(summary.oldest_pending_job, Some(4));
+        assert_eq!(summary.oldest_pending_wait_secs, Some(700));
+    }
+}
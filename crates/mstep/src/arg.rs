@@ -0,0 +1,32 @@
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(
+        short = 'a',
+        long = "api_endpoint",
+        default_value = "http://[::1]:8080"
+    )]
+    pub api_endpoint: String,
+
+    /// Seconds to wait for a connection to the scheduler
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// Step name shown by mqueue and mshow
+    #[arg(short = 'J', long = "name")]
+    pub name: Option<String>,
+
+    /// The id of the running job to start the step in
+    #[arg()]
+    pub job: u64,
+
+    /// Script path
+    pub script: String,
+
+    /// Script arguments
+    #[arg(trailing_var_arg = true)]
+    pub script_args: Vec<String>,
+}
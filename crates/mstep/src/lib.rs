@@ -0,0 +1,52 @@
+use melon_common::proto::JobSubmission;
+use std::path::Path;
+
+/// Builds the submission of a step that runs `script` within the allocation of job `parent`.
+///
+/// The step takes its node, cores and memory from the parent, so no resources are requested.
+/// A relative script path is resolved against `cwd`, which also becomes the working directory.
+pub fn step_submission(
+    parent: u64,
+    user: &str,
+    script: &str,
+    args: Vec<String>,
+    cwd: &Path,
+    name: Option<String>,
+) -> JobSubmission {
+    JobSubmission {
+        user: user.to_string(),
+        script_path: cwd.join(script).to_string_lossy().into_owned(),
+        script_args: args,
+        work_dir: Some(cwd.to_string_lossy().into_owned()),
+        name,
+        parent_job_id: Some(parent),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_submission() {
+        let cwd = Path::new("/home/alice/runs");
+        let sub = step_submission(7, "alice", "prep.sh", vec!["-v".to_string()], cwd, None);
+        assert_eq!(sub.parent_job_id, Some(7));
+        assert_eq!(sub.script_path, "/home/alice/runs/prep.sh");
+        assert_eq!(sub.script_args, vec!["-v"]);
+        assert_eq!(sub.work_dir.as_deref(), Some("/home/alice/runs"));
+        assert!(sub.req_res.is_none());
+
+        let sub = step_submission(
+            7,
+            "alice",
+            "/opt/train.sh",
+            vec![],
+            cwd,
+            Some("train".into()),
+        );
+        assert_eq!(sub.script_path, "/opt/train.sh");
+        assert_eq!(sub.name.as_deref(), Some("train"));
+    }
+}
@@ -0,0 +1,57 @@
+mod arg;
+use arg::Args;
+use clap::Parser;
+use mbatch::check_script;
+use melon_client::{Client, Code};
+use mstep::step_submission;
+use std::path::Path;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let cwd = std::env::current_dir()?;
+    let submission = step_submission(
+        args.job,
+        &whoami::username(),
+        &args.script,
+        args.script_args,
+        &cwd,
+        args.name,
+    );
+    if let Err(e) = check_script(Path::new(&submission.script_path)) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut client = match Client::builder(args.api_endpoint)
+        .with_timeout(timeout)
+        .connect()
+        .await
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    match client.submit(submission).await {
+        Ok(response) => println!("Started step with id: {:?}", response.job_id),
+        Err(e)
+            if matches!(
+                e.code(),
+                Code::InvalidArgument
+                    | Code::ResourceExhausted
+                    | Code::FailedPrecondition
+                    | Code::PermissionDenied
+            ) =>
+        {
+            eprintln!("Step rejected: {}", e.message());
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use clap::Parser;
+use std::net::SocketAddr;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// API Endpoint
+    #[arg(short = 'a', long = "api_endpoint", default_value = "[::1]:8080")]
+    pub api_endpoint: SocketAddr,
+
+    /// Seconds to wait for the scheduler on every refresh
+    #[arg(long = "timeout", default_value_t = 5)]
+    pub timeout: u64,
+
+    /// Seconds between two refreshes
+    #[arg(short = 'i', long = "interval", default_value_t = 1)]
+    pub interval: u64,
+}
@@ -0,0 +1,112 @@
+mod arg;
+use arg::Args;
+use clap::Parser;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use melon_client::{proto, Client, Status};
+use melon_common::Job;
+use mtop::view::{self, Dashboard};
+use ratatui::DefaultTerminal;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::interval;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let events = spawn_event_reader();
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &args, events).await;
+    ratatui::restore();
+    result
+}
+
+/// Refreshes the dashboard until the user quits.
+async fn run(
+    terminal: &mut DefaultTerminal,
+    args: &Args,
+    mut events: mpsc::Receiver<Event>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let endpoint = args.api_endpoint.to_string();
+    let timeout = Duration::from_secs(args.timeout);
+    let mut dashboard = Dashboard::new(args.api_endpoint.to_string());
+    let mut client = None;
+
+    let mut tick = interval(Duration::from_secs(args.interval.max(1)));
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                refresh(&mut client, &endpoint, timeout, &mut dashboard).await;
+            }
+            event = events.recv() => match event {
+                Some(Event::Key(key)) if is_quit(&key) => return Ok(()),
+                Some(_) => {}
+                None => return Ok(()),
+            }
+        }
+        terminal.draw(|frame| view::render(frame, &dashboard))?;
+    }
+}
+
+/// Fetches the nodes and jobs, connecting to the scheduler first if needed.
+///
+/// On failure the connection is dropped, so the next refresh connects again.
+async fn refresh(
+    client: &mut Option<Client>,
+    endpoint: &str,
+    timeout: Duration,
+    dashboard: &mut Dashboard,
+) {
+    let connected = match client {
+        Some(client) => client,
+        None => match Client::builder(endpoint)
+            .with_timeout(timeout)
+            .connect()
+            .await
+        {
+            Ok(connected) => client.insert(connected),
+            Err(e) => {
+                dashboard.disconnect(e.to_string());
+                return;
+            }
+        },
+    };
+
+    match tokio::time::timeout(timeout, fetch(connected)).await {
+        Ok(Ok((nodes, jobs))) => dashboard.update(nodes, jobs),
+        Ok(Err(status)) => {
+            dashboard.disconnect(status.message().to_string());
+            *client = None;
+        }
+        Err(_) => {
+            dashboard.disconnect("request timed out".to_string());
+            *client = None;
+        }
+    }
+}
+
+async fn fetch(client: &mut Client) -> Result<(Vec<proto::NodeDetails>, Vec<Job>), Status> {
+    let nodes = client.nodes().await?;
+    let jobs = client.list(None).await?;
+    Ok((nodes, jobs))
+}
+
+/// Reads terminal events on a separate thread, reading them blocks.
+fn spawn_event_reader() -> mpsc::Receiver<Event> {
+    let (tx, rx) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if tx.blocking_send(event).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// The terminal is in raw mode, so Ctrl-C arrives as a key press as well.
+fn is_quit(key: &KeyEvent) -> bool {
+    key.kind == KeyEventKind::Press
+        && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)))
+}
@@ -0,0 +1,312 @@
+use melon_common::{proto, Job, JobStatus, NodeStatus};
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Row, Table},
+    Frame,
+};
+
+/// Number of characters of a usage bar between the brackets.
+const BAR_WIDTH: usize = 20;
+
+/// Everything the dashboard shows, updated on every refresh.
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    /// Address of the scheduler
+    pub endpoint: String,
+
+    /// Compute nodes as of the last successful refresh
+    pub nodes: Vec<proto::NodeDetails>,
+
+    /// Running and pending jobs as of the last successful refresh
+    pub jobs: Vec<Job>,
+
+    /// Why the last refresh failed, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+impl Dashboard {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the shown state with a successful refresh.
+    pub fn update(&mut self, nodes: Vec<proto::NodeDetails>, jobs: Vec<Job>) {
+        self.nodes = nodes;
+        self.jobs = jobs
+            .into_iter()
+            .filter(|job| {
+                matches!(
+                    job.status,
+                    JobStatus::Running | JobStatus::Pending | JobStatus::Held
+                )
+            })
+            .collect();
+        // running jobs first, the queue below
+        self.jobs
+            .sort_by_key(|job| (job.status != JobStatus::Running, job.id));
+        self.error = None;
+    }
+
+    /// Marks the scheduler as unreachable, the last known state stays visible.
+    pub fn disconnect(&mut self, error: String) {
+        self.error = Some(error);
+    }
+}
+
+/// Draws the header, the nodes pane and the jobs pane.
+pub fn render(frame: &mut Frame, dashboard: &Dashboard) {
+    let [header, nodes, jobs, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(dashboard.nodes.len().max(1) as u16 + 3),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(header_line(dashboard), header);
+    frame.render_widget(nodes_table(dashboard), nodes);
+    frame.render_widget(jobs_table(dashboard), jobs);
+    frame.render_widget(
+        Line::from("q: quit").style(Style::default().add_modifier(Modifier::DIM)),
+        footer,
+    );
+}
+
+fn header_line(dashboard: &Dashboard) -> Line<'_> {
+    let running = dashboard
+        .jobs
+        .iter()
+        .filter(|job| job.status == JobStatus::Running)
+        .count();
+    let queued = dashboard.jobs.len() - running;
+    let status = match &dashboard.error {
+        None => Span::styled("connected", Style::default().fg(Color::Green)),
+        Some(e) => Span::styled(
+            format!("disconnected, retrying ({})", e),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+    };
+    Line::from(vec![
+        Span::raw(format!("melon @ {} | ", dashboard.endpoint)),
+        status,
+        Span::raw(format!(
+            " | {} nodes, {} running, {} pending",
+            dashboard.nodes.len(),
+            running,
+            queued
+        )),
+    ])
+}
+
+fn nodes_table(dashboard: &Dashboard) -> Table<'_> {
+    let rows = dashboard.nodes.iter().map(|node| {
+        let total = node.total_resources.unwrap_or_default();
+        let used = node.used_resources.unwrap_or_default();
+        let status = match proto::NodeStatus::try_from(node.status).map(NodeStatus::from) {
+            Ok(NodeStatus::Available) => "A",
+            Ok(NodeStatus::Offline) => "OFF",
+            Ok(NodeStatus::Draining) => "DR",
+            Ok(NodeStatus::Validating) => "VAL",
+            Err(_) => "?",
+        };
+        Row::new(vec![
+            node.id.clone(),
+            status.to_string(),
+            format!(
+                "{} {}/{}",
+                usage_bar(used.cpu_count as u64, total.cpu_count as u64),
+                used.cpu_count,
+                total.cpu_count
+            ),
+            format!(
+                "{} {}",
+                usage_bar(used.memory, total.memory),
+                format_gib(used.memory, total.memory)
+            ),
+            node.running_jobs.to_string(),
+        ])
+    });
+
+    Table::new(
+        rows,
+        [
+            Constraint::Length(21),
+            Constraint::Length(3),
+            Constraint::Length(BAR_WIDTH as u16 + 10),
+            Constraint::Length(BAR_WIDTH as u16 + 16),
+            Constraint::Length(4),
+        ],
+    )
+    .header(header_row(["NODEID", "ST", "CPUS", "MEMORY", "JOBS"]))
+    .block(Block::default().borders(Borders::ALL).title(" Nodes "))
+}
+
+fn jobs_table(dashboard: &Dashboard) -> Table<'_> {
+    let rows = dashboard.jobs.iter().map(|job| {
+        let (status, node) = match job.status {
+            JobStatus::Running => ("R", job.nodes().cloned().collect::<Vec<_>>().join(",")),
+            JobStatus::Held => ("H", "held".to_string()),
+            _ => ("PD", "pending".to_string()),
+        };
+        Row::new(vec![
+            job.id.to_string(),
+            job.display_name().to_string(),
+            job.user.clone(),
+            status.to_string(),
+            job.req_res.cpu_count.to_string(),
+            format_gib(job.req_res.memory, 0),
+            node,
+        ])
+    });
+
+    Table::new(
+        rows,
+        [
+            Constraint::Length(8),
+            Constraint::Length(20),
+            Constraint::Length(10),
+            Constraint::Length(3),
+            Constraint::Length(5),
+            Constraint::Length(9),
+            Constraint::Min(10),
+        ],
+    )
+    .header(header_row([
+        "JOBID", "NAME", "USER", "ST", "CPUS", "MEMORY", "NODE",
+    ]))
+    .block(Block::default().borders(Borders::ALL).title(" Jobs "))
+}
+
+fn header_row<const N: usize>(titles: [&'static str; N]) -> Row<'static> {
+    Row::new(titles).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+/// Renders a usage bar like `[#####     ]`, empty if the total is unknown.
+fn usage_bar(used: u64, total: u64) -> String {
+    let filled = if total == 0 {
+        0
+    } else {
+        ((used.min(total) as f64 / total as f64) * BAR_WIDTH as f64).round() as usize
+    };
+    format!("[{}{}]", "#".repeat(filled), " ".repeat(BAR_WIDTH - filled))
+}
+
+/// Formats bytes as GiB, as `used/total` if a total is given.
+fn format_gib(used: u64, total: u64) -> String {
+    const GIB: f64 = (1024 * 1024 * 1024) as f64;
+    if total == 0 {
+        format!("{:.1}G", used as f64 / GIB)
+    } else {
+        format!("{:.1}/{:.1}G", used as f64 / GIB, total as f64 / GIB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use melon_common::RequestedResources;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    fn node() -> proto::NodeDetails {
+        proto::NodeDetails {
+            id: "node-1".to_string(),
+            address: "http://10.0.0.5:8082".to_string(),
+            status: proto::NodeStatus::Available.into(),
+            total_resources: Some(proto::NodeResources {
+                cpu_count: 8,
+                memory: 16 * 1024 * 1024 * 1024,
+                gpu_count: 0,
+            }),
+            used_resources: Some(proto::NodeResources {
+                cpu_count: 4,
+                memory: 4 * 1024 * 1024 * 1024,
+                gpu_count: 0,
+            }),
+            running_jobs: 1,
+            heartbeat_age_secs: 2,
+            max_jobs: None,
+            labels: vec![],
+        }
+    }
+
+    fn job(id: u64, status: JobStatus) -> Job {
+        let mut job = Job::new(
+            id,
+            "alice".to_string(),
+            "/home/alice/train.sh".to_string(),
+            vec![],
+            RequestedResources::new(4, 1024 * 1024 * 1024, 60, 0),
+        );
+        job.status = status;
+        job
+    }
+
+    fn draw(dashboard: &Dashboard) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 14)).unwrap();
+        terminal.draw(|frame| render(frame, dashboard)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_usage_bar() {
+        assert_eq!(usage_bar(0, 8), format!("[{}]", " ".repeat(20)));
+        assert_eq!(
+            usage_bar(4, 8),
+            format!("[{}{}]", "#".repeat(10), " ".repeat(10))
+        );
+        assert_eq!(usage_bar(9, 8), format!("[{}]", "#".repeat(20)));
+        assert_eq!(usage_bar(1, 0), format!("[{}]", " ".repeat(20)));
+    }
+
+    #[test]
+    fn test_update_keeps_active_jobs_running_first() {
+        let mut dashboard = Dashboard::new("[::1]:8080".to_string());
+        dashboard.update(
+            vec![node()],
+            vec![
+                job(1, JobStatus::Pending),
+                job(2, JobStatus::Completed),
+                job(3, JobStatus::Running),
+            ],
+        );
+
+        let ids: Vec<u64> = dashboard.jobs.iter().map(|job| job.id).collect();
+        assert_eq!(ids, vec![3, 1]);
+    }
+
+    #[test]
+    fn test_render_nodes_and_jobs() {
+        let mut dashboard = Dashboard::new("[::1]:8080".to_string());
+        dashboard.update(vec![node()], vec![job(3, JobStatus::Running)]);
+
+        let screen = draw(&dashboard);
+
+        assert!(screen.contains("connected | 1 nodes, 1 running, 0 pending"));
+        assert!(screen.contains(&format!("[{}{}] 4/8", "#".repeat(10), " ".repeat(10))));
+        assert!(screen.contains("4.0/16.0G"));
+        assert!(screen.contains("train.sh"));
+    }
+
+    #[test]
+    fn test_render_disconnected_keeps_last_state() {
+        let mut dashboard = Dashboard::new("[::1]:8080".to_string());
+        dashboard.update(vec![node()], vec![]);
+        dashboard.disconnect("connection refused".to_string());
+
+        let screen = draw(&dashboard);
+
+        assert!(screen.contains("disconnected, retrying (connection refused)"));
+        assert!(screen.contains("node-1"));
+    }
+}
@@ -0,0 +1,66 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+/// Finds the local IP address that the scheduler can reach this worker at.
+///
+/// Asks the OS which interface it would route traffic to the scheduler through.
+/// No packets are sent. For a scheduler on the same machine, this is a loopback address.
+pub fn discover_local_ip(scheduler: SocketAddr) -> io::Result<IpAddr> {
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified(scheduler.ip()), 0))?;
+    socket.connect(scheduler)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Returns the address to listen on for the given advertised address.
+///
+/// The server only listens on the loopback address of the same IP version,
+/// other interfaces have to be chosen explicitly.
+pub fn listen_addr(advertised: SocketAddr) -> SocketAddr {
+    let loopback = match advertised.ip() {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+    };
+    SocketAddr::new(loopback, advertised.port())
+}
+
+/// Returns the endpoint URL the scheduler uses to reach the worker.
+pub fn endpoint_url(advertised: SocketAddr) -> String {
+    format!("http://{}", advertised)
+}
+
+fn unspecified(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_loopback_for_local_scheduler() {
+        let scheduler: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let ip = discover_local_ip(scheduler).unwrap();
+        assert!(ip.is_loopback());
+    }
+
+    #[test]
+    fn test_listen_addr_matches_ip_version() {
+        let v4: SocketAddr = "10.0.0.5:8082".parse().unwrap();
+        assert_eq!(listen_addr(v4), "127.0.0.1:8082".parse().unwrap());
+
+        let v6: SocketAddr = "[fd00::5]:8082".parse().unwrap();
+        assert_eq!(listen_addr(v6), "[::1]:8082".parse().unwrap());
+    }
+
+    #[test]
+    fn test_endpoint_url() {
+        let v4: SocketAddr = "10.0.0.5:8082".parse().unwrap();
+        assert_eq!(endpoint_url(v4), "http://10.0.0.5:8082");
+
+        let v6: SocketAddr = "[fd00::5]:8082".parse().unwrap();
+        assert_eq!(endpoint_url(v6), "http://[fd00::5]:8082");
+    }
+}
@@ -0,0 +1,67 @@
+use crate::core_mask::CpuSet;
+use tokio::process::Command;
+
+/// Pins the process started by `command` to the allocated cores, for builds without cgroups.
+///
+/// Core `i` of the allocation is the `i`-th core the worker itself may run on, so the
+/// allocation stays valid if the worker is restricted to a subset of the machine's cores.
+/// The affinity is set before the script is executed, processes it forks inherit it.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cores(command: &mut Command, cores: &CpuSet) -> std::io::Result<()> {
+    use nix::sched::{sched_getaffinity, sched_setaffinity, CpuSet as AffinitySet};
+    use nix::unistd::Pid;
+
+    let allowed = sched_getaffinity(Pid::from_raw(0)).map_err(std::io::Error::from)?;
+    let allowed: Vec<usize> = (0..AffinitySet::count())
+        .filter(|&cpu| allowed.is_set(cpu).unwrap_or(false))
+        .collect();
+
+    let mut set = AffinitySet::new();
+    for core in cores.cores() {
+        if let Some(&cpu) = allowed.get(core as usize) {
+            set.set(cpu).map_err(std::io::Error::from)?;
+        }
+    }
+
+    // SAFETY: the closure only makes a single syscall on data prepared before the fork
+    unsafe {
+        command.pre_exec(move || {
+            sched_setaffinity(Pid::from_raw(0), &set).map_err(std::io::Error::from)
+        });
+    }
+    Ok(())
+}
+
+/// Pinning processes is only supported on Linux, elsewhere jobs may run on any core.
+///
+/// The core allocation still limits how many cores the jobs of a node request in total.
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cores(_command: &mut Command, _cores: &CpuSet) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use nix::sched::sched_getaffinity;
+    use nix::unistd::Pid;
+
+    #[tokio::test]
+    async fn test_pinned_process_runs_on_allocated_core() {
+        let allowed = sched_getaffinity(Pid::from_raw(0)).unwrap();
+        let first_cpu = (0..nix::sched::CpuSet::count())
+            .find(|&cpu| allowed.is_set(cpu).unwrap())
+            .unwrap();
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "grep Cpus_allowed_list /proc/self/status"]);
+        pin_to_cores(&mut command, &CpuSet::from(0b1)).unwrap();
+        let output = command.output().await.unwrap();
+
+        let status = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(
+            status.split_whitespace().last(),
+            Some(first_cpu.to_string().as_str())
+        );
+    }
+}
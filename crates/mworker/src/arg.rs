@@ -1,5 +1,10 @@
 use clap::Parser;
-use std::net::SocketAddr;
+use melon_common::telemetry::LogFormat;
+use melon_common::utils::parse_memory;
+use melon_common::WarnSignal;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -11,4 +16,76 @@ pub struct Args {
     /// API Endpoint
     #[arg(short = 'a', long = "api_endpoint", default_value = "[::1]:8080")]
     pub api_endpoint: SocketAddr,
+
+    /// IP address the scheduler reaches this worker at, detected from the route to the scheduler if unset
+    #[arg(long = "advertise_addr", alias = "advertise-addr")]
+    pub advertise_addr: Option<IpAddr>,
+
+    /// IP address the worker server listens on, e.g. 0.0.0.0 for all interfaces, only safe with a worker token (loopback if unset)
+    #[arg(short = 'b', long = "bind")]
+    pub bind: Option<IpAddr>,
+
+    /// Directory for captured job output (defaults to ~/.melon/logs)
+    #[arg(short = 'l', long = "log_dir")]
+    pub log_dir: Option<PathBuf>,
+
+    /// Base directory for the per-job scratch directories (defaults to <tmp>/melon/scratch)
+    #[arg(short = 's', long = "scratch_dir")]
+    pub scratch_dir: Option<PathBuf>,
+
+    /// Most output kept of a job's stdout and of its stderr, e.g. 1G, the middle of longer output is cut
+    #[arg(long = "max_output_bytes", alias = "max-output-bytes", default_value = "100M", value_parser = parse_memory)]
+    pub max_output_bytes: u64,
+
+    /// Attempts to deliver a job result before it is kept for the next poll
+    #[arg(long = "submit_attempts", default_value_t = 5)]
+    pub submit_attempts: u32,
+
+    /// Wait time in milliseconds before the first retry, doubled on every retry
+    #[arg(long = "submit_backoff_ms", default_value_t = 500)]
+    pub submit_backoff_ms: u64,
+
+    /// Seconds jobs get to exit after SIGTERM on timeout, cancellation or shutdown, 0 kills them right away
+    #[arg(long = "grace_secs", default_value_t = 10)]
+    pub grace_secs: u64,
+
+    /// Signal jobs get once they used a share of their time limit, e.g. USR1@90, unless they set their own
+    #[arg(long = "warn_signal", value_parser = WarnSignal::from_str)]
+    pub warn_signal: Option<WarnSignal>,
+
+    /// Most jobs that run at once, however many cores are free (unlimited if unset)
+    #[arg(long = "max_jobs", alias = "max-jobs")]
+    pub max_jobs: Option<u32>,
+
+    /// Directory the job cgroups are created in, only used with cgroups (default: /sys/fs/cgroup/melon)
+    #[arg(long = "cgroup_path", alias = "cgroup-path")]
+    pub cgroup_path: Option<PathBuf>,
+
+    /// Most swap a job may use, e.g. 0 or 2G, only enforced with cgroups (unlimited if unset)
+    #[arg(long = "swap_limit", alias = "swap-limit", value_parser = parse_memory)]
+    pub swap_limit: Option<u64>,
+
+    /// Share of CPU time jobs get under contention, 1 to 10000, only applied with cgroups (kernel default if unset)
+    #[arg(long = "cpu_weight", alias = "cpu-weight", value_parser = clap::value_parser!(u64).range(1..=10000))]
+    pub cpu_weight: Option<u64>,
+
+    /// Run every job as the user who submitted it instead of the worker's user, needs root and Linux
+    #[arg(long = "run_as_user", alias = "run-as-user")]
+    pub run_as_user: bool,
+
+    /// Lowest uid that jobs may run as with --run_as_user, jobs never run as root
+    #[arg(long = "min_uid", alias = "min-uid", default_value_t = 1000)]
+    pub min_uid: u32,
+
+    /// Feature of the node that jobs can ask for with #MBATCH -C, e.g. ssd, can be repeated
+    #[arg(long = "label", value_delimiter = ',')]
+    pub labels: Vec<String>,
+
+    /// Log level or RUST_LOG-style directives, e.g. debug (RUST_LOG or info if unset)
+    #[arg(long = "log_level", alias = "log-level")]
+    pub log_level: Option<String>,
+
+    /// Format of the log lines: text, json or bunyan
+    #[arg(long = "log_format", alias = "log-format", default_value_t = LogFormat::Text, value_parser = LogFormat::from_str)]
+    pub log_format: LogFormat,
 }
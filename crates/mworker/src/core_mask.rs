@@ -4,9 +4,9 @@
 /// It allows for efficient allocation and deallocation of CPU cores, typically used in conjunction with
 /// cgroups and cpusets in system resource management.
 ///
-/// The `CoreMask` uses a 64-bit unsigned integer to represent up to 64 CPU cores, where each bit
+/// The `CoreMask` stores its cores in a [CpuSet], a bitmap made of 64-bit words where each bit
 /// corresponds to a core. A set bit (1) indicates an allocated core, while an unset bit (0) represents
-/// an available core.
+/// an available core. There is no upper limit on the number of cores.
 ///
 /// Why is this so complicated? I just felt like it...
 ///
@@ -73,77 +73,209 @@
 /// assert_eq!(available, 0b0011_1000);
 /// ```
 ///
+/// ### Systems with more than 64 Cores
+///
+/// ```
+/// use mworker::core_mask::CoreMask;
+///
+/// let mut mask = CoreMask::new(128);
+/// let allocation = mask.allocate(2).unwrap();
+/// assert_eq!(CoreMask::mask_to_string(allocation), "126,127");
+/// ```
+///
 /// ## Implementation Details
 ///
 /// The `CoreMask` struct uses a greedy allocation strategy, always trying to allocate cores from
 /// the highest available core ID. This can lead to fragmentation over time, but ensures that
 /// lower-numbered cores are kept free for as long as possible, which can be beneficial in some
 /// system configurations.
+use std::fmt;
+
+/// A set of CPU cores.
 ///
-/// Note that this implementation is limited to systems with up to 64 cores due to the use of a
-/// `u64` for the internal representation. For systems with more cores, the implementation would
-/// need to be adapted, possibly using a vector of `u64` or a different data structure.
+/// Core `i` is bit `i % 64` of word `i / 64`. Masks of up to 64 cores can be built from
+/// and compared with a plain `u64`.
+#[derive(Debug, Clone, Default)]
+pub struct CpuSet {
+    words: Vec<u64>,
+}
+
+impl CpuSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the core is part of the set.
+    pub fn contains(&self, core: u32) -> bool {
+        let (word, bit) = Self::position(core);
+        self.words.get(word).is_some_and(|w| w & (1u64 << bit) != 0)
+    }
+
+    /// Adds a core to the set.
+    pub fn insert(&mut self, core: u32) {
+        let (word, bit) = Self::position(core);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1u64 << bit;
+    }
+
+    /// Adds all cores of another set.
+    pub fn union_with(&mut self, other: &CpuSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word |= other_word;
+        }
+    }
+
+    /// Removes all cores of another set.
+    pub fn difference_with(&mut self, other: &CpuSet) {
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word &= !other_word;
+        }
+    }
+
+    /// Returns whether no core is part of the set.
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    /// Returns the number of cores in the set.
+    pub fn len(&self) -> u32 {
+        self.words.iter().map(|w| w.count_ones()).sum()
+    }
+
+    /// Iterates over the cores in the set in ascending order.
+    pub fn cores(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words.iter().enumerate().flat_map(|(index, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1u64 << bit) != 0)
+                .map(move |bit| index as u32 * 64 + bit)
+        })
+    }
+
+    fn position(core: u32) -> (usize, u32) {
+        ((core / 64) as usize, core % 64)
+    }
+}
+
+impl PartialEq for CpuSet {
+    fn eq(&self, other: &Self) -> bool {
+        // trailing empty words don't change the set
+        let len = self.words.len().max(other.words.len());
+        (0..len).all(|i| {
+            self.words.get(i).copied().unwrap_or(0) == other.words.get(i).copied().unwrap_or(0)
+        })
+    }
+}
+
+impl Eq for CpuSet {}
+
+impl PartialEq<u64> for CpuSet {
+    fn eq(&self, other: &u64) -> bool {
+        self.words.first().copied().unwrap_or(0) == *other
+            && self.words.iter().skip(1).all(|&w| w == 0)
+    }
+}
+
+impl From<u64> for CpuSet {
+    fn from(mask: u64) -> Self {
+        Self { words: vec![mask] }
+    }
+}
+
+/// Formats the set in the list format of `cpuset.cpus`, e.g. `0,1,5`.
+impl fmt::Display for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cores: Vec<String> = self.cores().map(|core| core.to_string()).collect();
+        write!(f, "{}", cores.join(","))
+    }
+}
+
+/// Formats the set as one binary number, the highest core first.
+impl fmt::Binary for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self
+            .words
+            .iter()
+            .rposition(|&w| w != 0)
+            .map_or(1, |i| i + 1);
+        let mut words = self.words.iter().take(len).rev();
+        write!(f, "{:b}", words.next().copied().unwrap_or(0))?;
+        for word in words {
+            write!(f, "{:064b}", word)?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct CoreMask {
-    mask: u64,
+    mask: CpuSet,
     total_cores: u32,
 }
 
 impl CoreMask {
     pub fn new(total_cores: u32) -> Self {
         Self {
-            mask: 0,
+            mask: CpuSet::new(),
             total_cores,
         }
     }
 
-    pub fn allocate(&mut self, cores_needed: u32) -> Option<u64> {
+    pub fn allocate(&mut self, cores_needed: u32) -> Option<CpuSet> {
         println!("Allocate {} cores", cores_needed);
+        let allocated_mask = self.get_available_core_ids(cores_needed)?;
+        self.mask.union_with(&allocated_mask);
+        Some(allocated_mask)
+    }
+
+    /// Picks cores for a job that the scheduler placed on a node whose cores are all taken,
+    /// which happens when it overcommits CPUs.
+    ///
+    /// Free cores come first, the rest is shared with running jobs. Nothing is marked as
+    /// allocated, so the cores must not be freed again.
+    pub fn share(&self, cores_needed: u32) -> Option<CpuSet> {
         if cores_needed == 0 || cores_needed > self.total_cores {
             return None;
         }
 
-        let mut allocated_mask = 0u64;
-        let mut count = 0;
-
-        // start from the leftmost bit (most significant bit)
-        for i in (0..self.total_cores).rev() {
-            if self.mask & (1u64 << i) == 0 {
-                allocated_mask |= 1u64 << i; // set bit in the allocated mask
-                self.mask |= 1u64 << i; // set bit in the overall mask
-                count += 1;
-
-                if count == cores_needed {
-                    return Some(allocated_mask);
-                }
-            }
+        let (free, allocated): (Vec<u32>, Vec<u32>) = (0..self.total_cores)
+            .rev()
+            .partition(|&i| !self.mask.contains(i));
+        let mut shared_mask = CpuSet::new();
+        for i in free
+            .into_iter()
+            .chain(allocated)
+            .take(cores_needed as usize)
+        {
+            shared_mask.insert(i);
         }
-
-        // roll back the allocation if not enough were found
-        self.mask &= !allocated_mask;
-        None
+        Some(shared_mask)
     }
 
-    pub fn free(&mut self, mask_to_free: u64) {
-        self.mask &= !mask_to_free;
+    pub fn free(&mut self, mask_to_free: CpuSet) {
+        self.mask.difference_with(&mask_to_free);
     }
 
-    pub fn get_allocated_cores(&self) -> u64 {
-        self.mask
+    pub fn get_allocated_cores(&self) -> CpuSet {
+        self.mask.clone()
     }
 
-    pub fn get_available_core_ids(&self, cores_needed: u32) -> Option<u64> {
+    pub fn get_available_core_ids(&self, cores_needed: u32) -> Option<CpuSet> {
         if cores_needed == 0 || cores_needed > self.total_cores {
             return None;
         }
 
-        let mut available_mask = 0u64;
+        let mut available_mask = CpuSet::new();
         let mut count = 0;
 
+        // start from the highest core id
         for i in (0..self.total_cores).rev() {
-            if self.mask & (1u64 << i) == 0 {
-                available_mask |= 1u64 << i;
+            if !self.mask.contains(i) {
+                available_mask.insert(i);
                 count += 1;
                 if count == cores_needed {
                     return Some(available_mask);
@@ -154,12 +286,8 @@ impl CoreMask {
         None
     }
 
-    pub fn mask_to_string(mask: u64) -> String {
-        (0..64)
-            .filter(|&i| mask & (1 << i) != 0)
-            .map(|i| i.to_string())
-            .collect::<Vec<String>>()
-            .join(",")
+    pub fn mask_to_string(mask: impl Into<CpuSet>) -> String {
+        mask.into().to_string()
     }
 }
 
@@ -239,6 +367,17 @@ mod tests {
         assert_eq!(result.unwrap(), 0b0000_1100);
     }
 
+    #[test]
+    fn test_share_prefers_free_cores() {
+        let mut core_mask = CoreMask::new(8);
+        core_mask.allocate(6).unwrap();
+        assert!(core_mask.allocate(4).is_none());
+        assert_eq!(core_mask.share(4).unwrap(), 0b1100_0011);
+        // shared cores stay with their jobs
+        assert_eq!(core_mask.get_allocated_cores(), 0b1111_1100);
+        assert!(core_mask.share(9).is_none());
+    }
+
     #[test]
     fn test_mask_to_string_empty() {
         assert_eq!(CoreMask::mask_to_string(0), "");
@@ -391,6 +530,58 @@ mod tests {
         assert_eq!(core_mask.get_allocated_cores(), 0b1000_0000);
     }
 
+    #[test]
+    fn test_allocate_beyond_64_cores() {
+        let mut core_mask = CoreMask::new(128);
+        let allocation = core_mask.allocate(66).unwrap();
+        assert_eq!(allocation.len(), 66);
+        assert!(allocation.contains(127));
+        assert!(allocation.contains(62));
+        assert!(!allocation.contains(61));
+
+        let allocation2 = core_mask.allocate(62).unwrap();
+        assert_eq!(
+            CoreMask::mask_to_string(allocation2.clone())
+                .split(',')
+                .count(),
+            62
+        );
+        assert!(core_mask.allocate(1).is_none());
+
+        core_mask.free(allocation);
+        assert_eq!(core_mask.get_allocated_cores(), allocation2);
+    }
+
+    #[test]
+    fn test_mask_to_string_beyond_64_cores() {
+        let mut set = CpuSet::new();
+        set.insert(3);
+        set.insert(64);
+        set.insert(127);
+        assert_eq!(CoreMask::mask_to_string(set), "3,64,127");
+    }
+
+    #[test]
+    fn test_cpu_set_binary_format() {
+        let mut set = CpuSet::from(0b101);
+        assert_eq!(format!("{:b}", set), "101");
+        set.insert(64);
+        assert_eq!(format!("{:b}", set), format!("1{:064b}", 0b101));
+    }
+
+    #[test]
+    fn test_cpu_set_ignores_trailing_empty_words() {
+        let mut other = CpuSet::new();
+        other.insert(100);
+        let mut set = CpuSet::from(0b11);
+        set.union_with(&other);
+        set.difference_with(&other);
+        assert_eq!(set, 0b11);
+        assert_eq!(set, CpuSet::from(0b11));
+        set.difference_with(&CpuSet::from(0b11));
+        assert!(set.is_empty());
+    }
+
     #[test]
     fn test_get_allocated_cores_allocate_free_allocate() {
         let mut core_mask = CoreMask::new(8);
@@ -0,0 +1,82 @@
+use derive_more::From;
+
+pub type Result<T> = std::result::Result<T, WorkerError>;
+
+#[derive(Debug, From)]
+pub enum WorkerError {
+    // Externals
+    #[from]
+    IoError(std::io::Error),
+
+    /// No connection to the scheduler
+    #[from]
+    ConnectError(tonic::transport::Error),
+
+    /// The scheduler answered a request with an error
+    #[from]
+    RpcError(tonic::Status),
+
+    #[cfg(feature = "cgroups")]
+    #[from]
+    CGroupsError(cgroups::error::CGroupsError),
+
+    // Internals
+    /// A job can't be started as it was assigned
+    SpawnFailed(String),
+
+    /// Not enough free cores or job slots for a job
+    ResourcesExhausted(String),
+}
+
+impl WorkerError {
+    /// Whether the error may go away on its own, so that the request is worth retrying.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::ConnectError(_) => true,
+            Self::RpcError(status) => crate::retry::is_transient(status),
+            _ => false,
+        }
+    }
+}
+
+impl core::fmt::Display for WorkerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for WorkerError {}
+
+/// The scheduler decides by the status code whether to try another node.
+impl From<WorkerError> for tonic::Status {
+    fn from(error: WorkerError) -> Self {
+        match error {
+            WorkerError::RpcError(status) => status,
+            WorkerError::SpawnFailed(message) => tonic::Status::invalid_argument(message),
+            WorkerError::ResourcesExhausted(message) => tonic::Status::resource_exhausted(message),
+            e => tonic::Status::internal(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_keeps_the_error_kind() {
+        let status: tonic::Status = WorkerError::ResourcesExhausted("No cores".into()).into();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        let status: tonic::Status = WorkerError::SpawnFailed("No resources".into()).into();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status: tonic::Status = WorkerError::RpcError(tonic::Status::not_found("gone")).into();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(WorkerError::RpcError(tonic::Status::unavailable("down")).is_transient());
+        assert!(!WorkerError::RpcError(tonic::Status::invalid_argument("bad")).is_transient());
+        assert!(!WorkerError::SpawnFailed("No resources".into()).is_transient());
+    }
+}
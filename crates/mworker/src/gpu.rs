@@ -0,0 +1,68 @@
+#[cfg(feature = "nvidia")]
+use melon_common::log;
+
+/// Returns the number of GPUs on this node.
+///
+/// With the `nvidia` feature the GPUs reported by `nvidia-smi -L` are counted.
+/// Falls back to 0 if the feature is disabled or `nvidia-smi` is missing or fails.
+#[cfg(feature = "nvidia")]
+pub fn detect_gpu_count() -> u32 {
+    match std::process::Command::new("nvidia-smi").arg("-L").output() {
+        Ok(output) if output.status.success() => {
+            parse_gpu_list(&String::from_utf8_lossy(&output.stdout))
+        }
+        Ok(output) => {
+            log!(
+                warn,
+                "nvidia-smi exited with {}, assuming no GPUs",
+                output.status
+            );
+            0
+        }
+        Err(e) => {
+            log!(warn, "Could not run nvidia-smi, assuming no GPUs: {}", e);
+            0
+        }
+    }
+}
+
+/// Returns the number of GPUs on this node.
+///
+/// GPU detection requires the `nvidia` feature, so this always returns 0.
+#[cfg(not(feature = "nvidia"))]
+pub fn detect_gpu_count() -> u32 {
+    0
+}
+
+/// Counts the `GPU <n>: ...` lines in the output of `nvidia-smi -L`.
+pub fn parse_gpu_list(list: &str) -> u32 {
+    list.lines()
+        .filter(|line| line.trim_start().starts_with("GPU "))
+        .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gpu_list() {
+        let list = "GPU 0: NVIDIA A100-SXM4-40GB (UUID: GPU-5d5ba0d6-d33d-2b2c-524d-e5aeb0d0d0b5)\n\
+                    GPU 1: NVIDIA A100-SXM4-40GB (UUID: GPU-1c1ff8d6-c5a0-7a3b-4a3a-8d1e4a0b9e2f)\n";
+        assert_eq!(parse_gpu_list(list), 2);
+    }
+
+    #[test]
+    fn test_parse_gpu_list_ignores_mig_devices() {
+        let list =
+            "GPU 0: NVIDIA A100-SXM4-40GB (UUID: GPU-5d5ba0d6-d33d-2b2c-524d-e5aeb0d0d0b5)\n  \
+                    MIG 3g.20gb     Device  0: (UUID: MIG-0ea7d8ad-ef86-5b2f-a5b8-f4a6ad2f2a44)\n";
+        assert_eq!(parse_gpu_list(list), 1);
+    }
+
+    #[test]
+    fn test_parse_empty_gpu_list() {
+        assert_eq!(parse_gpu_list(""), 0);
+        assert_eq!(parse_gpu_list("No devices found.\n"), 0);
+    }
+}
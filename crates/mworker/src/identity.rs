@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Account of the submitting user, as found in the passwd database of the node.
+#[derive(Debug, Clone)]
+pub struct JobUser {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    /// Supplementary groups, resolved before the fork since that reads the group database
+    pub groups: Vec<u32>,
+    pub home: PathBuf,
+}
+
+/// Looks up the account a job runs as, the error is the reason to fail the job with.
+///
+/// Root and system accounts with a uid below `min_uid` are refused.
+#[cfg(target_os = "linux")]
+pub fn lookup_user(name: &str, min_uid: u32) -> Result<JobUser, String> {
+    use nix::unistd::{getgrouplist, User};
+    use std::ffi::CString;
+
+    let user = User::from_name(name)
+        .map_err(|e| format!("Could not look up user {}: {}", name, e))?
+        .ok_or_else(|| format!("User {} does not exist on this node", name))?;
+    if user.uid.is_root() {
+        return Err(format!("Jobs don't run as root, refuse user {}", name));
+    }
+    if user.uid.as_raw() < min_uid {
+        return Err(format!(
+            "User {} has uid {}, jobs only run as users from uid {}",
+            name, user.uid, min_uid
+        ));
+    }
+    let c_name = CString::new(name).map_err(|_| format!("Invalid user name {}", name))?;
+    let groups = getgrouplist(&c_name, user.gid)
+        .map_err(|e| format!("Could not look up the groups of user {}: {}", name, e))?;
+
+    Ok(JobUser {
+        name: user.name,
+        uid: user.uid.as_raw(),
+        gid: user.gid.as_raw(),
+        groups: groups.into_iter().map(|gid| gid.as_raw()).collect(),
+        home: user.dir,
+    })
+}
+
+/// Switching users is only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn lookup_user(_name: &str, _min_uid: u32) -> Result<JobUser, String> {
+    Err("Running jobs as their user is only supported on Linux".to_string())
+}
+
+/// Runs the process started by `command` as `user` instead of the worker's own user.
+///
+/// The privileges are dropped in the forked process right before the script is executed,
+/// so the worker has to run as root. `USER`, `LOGNAME` and `HOME` are set to the user's.
+#[cfg(target_os = "linux")]
+pub fn run_as(command: &mut Command, user: &JobUser) {
+    use nix::unistd::{setgid, setgroups, setuid, Gid, Uid};
+
+    command
+        .env("USER", &user.name)
+        .env("LOGNAME", &user.name)
+        .env("HOME", &user.home);
+    let groups: Vec<Gid> = user.groups.iter().map(|&gid| Gid::from_raw(gid)).collect();
+    let (uid, gid) = (Uid::from_raw(user.uid), Gid::from_raw(user.gid));
+
+    // SAFETY: the closure only makes syscalls on data prepared before the fork
+    unsafe {
+        command.pre_exec(move || {
+            // the groups can't be changed anymore once the uid is dropped
+            setgroups(&groups)?;
+            setgid(gid)?;
+            setuid(uid)?;
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn run_as(_command: &mut Command, _user: &JobUser) {}
+
+/// Hands a directory the worker created for a job, e.g. its scratch directory, to the user.
+pub fn hand_over(path: &Path, user: &JobUser) -> std::io::Result<()> {
+    std::os::unix::fs::chown(path, Some(user.uid), Some(user.gid))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_user() {
+        let nobody = lookup_user("nobody", 1).unwrap();
+        assert_eq!(nobody.name, "nobody");
+        assert!(nobody.groups.contains(&nobody.gid));
+
+        let err = lookup_user("nobody", nobody.uid + 1).unwrap_err();
+        assert_eq!(
+            err,
+            format!(
+                "User nobody has uid {}, jobs only run as users from uid {}",
+                nobody.uid,
+                nobody.uid + 1
+            )
+        );
+
+        let err = lookup_user("melon-no-such-user", 1).unwrap_err();
+        assert_eq!(err, "User melon-no-such-user does not exist on this node");
+    }
+
+    #[test]
+    fn test_root_is_refused() {
+        // whatever the lowest uid is set to
+        let err = lookup_user("root", 0).unwrap_err();
+        assert_eq!(err, "Jobs don't run as root, refuse user root");
+    }
+
+    #[tokio::test]
+    async fn test_run_as_drops_privileges() {
+        // only root may switch users
+        if !nix::unistd::geteuid().is_root() {
+            return;
+        }
+        let nobody = lookup_user("nobody", 1).unwrap();
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "id -u; id -g; echo $USER"]);
+        run_as(&mut command, &nobody);
+        let output = command.output().await.unwrap();
+
+        let output = String::from_utf8(output.stdout).unwrap();
+        let expected = format!("{}\n{}\nnobody\n", nobody.uid, nobody.gid);
+        assert_eq!(output, expected);
+    }
+}
@@ -0,0 +1,170 @@
+use melon_common::proto::{AttachInput, AttachOutput, OutputStream};
+use std::fs::File;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tonic::{Status, Streaming};
+
+/// How long an interactive job waits for its client to attach before it fails.
+pub const ATTACH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Output messages that are buffered for a slow client.
+pub const OUTPUT_BUFFER: usize = 64;
+
+/// Sends the output of an interactive job to its client.
+pub type OutputSender = mpsc::Sender<Result<AttachOutput, Status>>;
+
+/// A client that attached to an interactive job.
+#[derive(Debug)]
+pub struct AttachSession {
+    /// Input of the client, after the message that named the job
+    pub input: Streaming<AttachInput>,
+
+    /// Output to the client
+    pub output: OutputSender,
+
+    /// Rows and columns of the client's terminal, if it has one
+    pub terminal: Option<(u16, u16)>,
+}
+
+/// An interactive job that waits for its client.
+#[derive(Debug)]
+pub struct PendingAttach {
+    /// Secret the client has to present
+    pub token: String,
+
+    /// Hands the client over to the job's task
+    pub session: oneshot::Sender<AttachSession>,
+}
+
+/// Runs the process in a new terminal of the given size.
+///
+/// Returns the master side of the terminal twice, to read the output from and to write the input to.
+/// The process gets a session of its own with the terminal as its controlling one, so that
+/// e.g. ctrl-c reaches it.
+#[cfg(target_os = "linux")]
+pub fn attach_terminal(
+    command: &mut Command,
+    rows: u16,
+    cols: u16,
+) -> std::io::Result<(File, File)> {
+    use nix::pty::{openpty, Winsize};
+
+    let size = Winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let pty = openpty(Some(&size), None).map_err(std::io::Error::from)?;
+    let master = File::from(pty.master);
+    let slave = File::from(pty.slave);
+    let writer = master.try_clone()?;
+    command
+        .stdin(slave.try_clone()?)
+        .stdout(slave.try_clone()?)
+        .stderr(slave);
+
+    // SAFETY: the closure only makes syscalls, no allocations or locks after the fork
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok((master, writer))
+}
+
+/// Terminals are only supported on Linux, elsewhere interactive jobs use pipes.
+#[cfg(not(target_os = "linux"))]
+pub fn attach_terminal(
+    _command: &mut Command,
+    _rows: u16,
+    _cols: u16,
+) -> std::io::Result<(File, File)> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Terminals are only supported on Linux",
+    ))
+}
+
+/// Spawns a task that sends everything from `reader` to the client of an interactive job.
+///
+/// The task finishes at EOF or once the client is gone.
+pub fn spawn_output_relay<R>(
+    mut reader: R,
+    stream: OutputStream,
+    output: OutputSender,
+) -> JoinHandle<std::io::Result<u64>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 8 * 1024];
+        let mut sent = 0u64;
+        loop {
+            let n = match reader.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => n,
+                // the master side of a terminal fails instead of EOF once the process is gone
+                Err(e) if e.raw_os_error() == Some(libc::EIO) => break,
+                Err(e) => return Err(e),
+            };
+            let message = AttachOutput {
+                stream: stream.into(),
+                content: buf[..n].to_vec(),
+                ..Default::default()
+            };
+            if output.send(Ok(message)).await.is_err() {
+                break;
+            }
+            sent += n as u64;
+        }
+        Ok(sent)
+    })
+}
+
+/// Spawns a task that writes the input of the client to the process of an interactive job.
+///
+/// Input that arrives after the process closed its stdin is dropped. `disconnected` is
+/// notified once the client is gone. A client that only ends its input closes the process's
+/// stdin and stays attached until it stops reading the output.
+pub fn spawn_input_relay<W>(
+    mut input: Streaming<AttachInput>,
+    mut stdin: Option<W>,
+    output: OutputSender,
+    disconnected: mpsc::Sender<()>,
+) -> JoinHandle<()>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let ended = loop {
+            let message = match input.message().await {
+                Ok(Some(message)) => message,
+                Ok(None) => break true,
+                Err(_) => break false,
+            };
+            if let Some(writer) = stdin.as_mut() {
+                if !message.stdin.is_empty()
+                    && (writer.write_all(&message.stdin).await.is_err()
+                        || writer.flush().await.is_err())
+                {
+                    stdin = None;
+                }
+            }
+            if message.stdin_closed {
+                stdin = None;
+            }
+        };
+        drop(stdin);
+        if ended {
+            output.closed().await;
+        }
+        let _ = disconnected.send(()).await;
+    })
+}
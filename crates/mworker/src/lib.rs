@@ -1,4 +1,14 @@
+pub mod address;
+pub mod affinity;
 pub mod arg;
 pub mod worker;
 pub use arg::Args;
 pub mod core_mask;
+pub mod error;
+pub mod gpu;
+pub mod identity;
+pub mod interactive;
+pub mod output;
+pub mod retry;
+pub mod scratch;
+pub mod usage;
@@ -1,13 +1,22 @@
 use clap::Parser;
-use melon_common::telemetry::{get_subscriber, init_subscriber};
+use melon_common::{
+    log,
+    telemetry::{get_subscriber, init_subscriber},
+    utils::shutdown_signal,
+};
 use mworker::{worker::Worker, Args};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let subscriber = get_subscriber("mworker".into(), "info".into(), std::io::stdout);
+    let args = Args::parse();
+    let subscriber = get_subscriber(
+        "mworker".into(),
+        args.log_level.clone(),
+        args.log_format,
+        std::io::stdout,
+    );
     init_subscriber(subscriber);
 
-    let args = Args::parse();
     let mut worker = Worker::new(&args)?;
 
     // connect worker
@@ -19,6 +28,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // start polling
     worker.start_polling().await?;
 
+    // terminate the running jobs on Ctrl-C or SIGTERM
+    let stopper = worker.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        log!(info, "Received shutdown signal");
+        stopper.shutdown().await;
+    });
+
     // start the server
     worker.start_server().await?;
 
@@ -0,0 +1,475 @@
+use dashmap::DashMap;
+use melon_common::{log, proto::OutputStream};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::task::JoinHandle;
+
+/// How long to wait for the remaining output once a job's process has ended.
+///
+/// Processes forked by the job may keep the pipes open after the job itself exited.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How much of the error output of a failed job is logged.
+pub const ERROR_TAIL_BYTES: u64 = 4 * 1024;
+
+/// Size of the frames that output is streamed in.
+pub const OUTPUT_CHUNK_BYTES: u64 = 64 * 1024;
+
+/// Bytes cut from the middle of output files that grew past their cap, by path.
+pub type TruncatedOutputs = Arc<DashMap<PathBuf, u64>>;
+
+/// How much output is kept of a stream, see [capture_output].
+#[derive(Debug, Clone)]
+pub struct OutputCap {
+    pub max_bytes: u64,
+    pub truncated: TruncatedOutputs,
+}
+
+/// Returns the default log directory `~/.melon/logs`.
+pub fn default_log_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join(".melon").join("logs")
+}
+
+/// Returns the path of the captured output file for a job.
+///
+/// Standard output is written to `job-<id>.out`, standard error to `job-<id>.err`.
+pub fn output_path(log_dir: &Path, job_id: u64, stream: OutputStream) -> PathBuf {
+    let extension = match stream {
+        OutputStream::Stdout => "out",
+        OutputStream::Stderr => "err",
+    };
+    log_dir.join(format!("job-{}.{}", job_id, extension))
+}
+
+/// Turns the output file at `log_path` into a link to `target`, where the job asked its output to go.
+///
+/// Creates the directories of `target`; the output stays readable through the log directory.
+pub async fn link_output(log_path: &Path, target: &Path) -> std::io::Result<()> {
+    // a retried job may have left a file or link behind
+    match tokio::fs::remove_file(log_path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::symlink(target, log_path).await
+}
+
+/// Spawns a task that copies everything from `reader` into the file at `path`.
+///
+/// Every chunk is written and flushed as soon as it is read, so the file always
+/// reflects what the process has produced so far, even if the job is aborted.
+/// The task finishes once the reader reaches EOF, i.e. when the process exits or is killed.
+/// Output beyond the cap is cut from the middle, the number of bytes cut is recorded in the cap.
+pub fn spawn_output_capture<R>(
+    reader: R,
+    path: PathBuf,
+    cap: OutputCap,
+) -> JoinHandle<std::io::Result<u64>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        // a requeued job starts over
+        cap.truncated.remove(&path);
+        let mut file = create_output_file(&path).await?;
+        let (written, cut) = capture_output(reader, &mut file, cap.max_bytes).await?;
+        if cut > 0 {
+            cap.truncated.insert(path, cut);
+        }
+        Ok(written)
+    })
+}
+
+async fn create_output_file(path: &Path) -> std::io::Result<File> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .await
+}
+
+/// Copies `reader` into `file` and keeps at most `max_bytes` of it, returns the bytes read and cut.
+///
+/// Output past the cap keeps its first and last half, with a `[... N bytes truncated ...]`
+/// line in between. Until the reader ends, the file may hold up to half as much again:
+/// the middle is cut whenever the part after the head has grown to twice the tail.
+async fn capture_output<R>(
+    mut reader: R,
+    file: &mut File,
+    max_bytes: u64,
+) -> std::io::Result<(u64, u64)>
+where
+    R: AsyncRead + Unpin,
+{
+    let head = max_bytes / 2;
+    let tail = max_bytes - head;
+
+    let mut buf = vec![0u8; 8 * 1024];
+    let mut written = 0u64;
+    let mut len = 0u64;
+    let mut cut = 0u64;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n]).await?;
+        file.flush().await?;
+        written += n as u64;
+        len += n as u64;
+        if len > head + 2 * tail {
+            cut += drop_middle(file, head, len, tail).await?;
+            len = head + tail;
+        }
+    }
+
+    if len > max_bytes {
+        cut += drop_middle(file, head, len, tail).await?;
+        len = head + tail;
+    }
+    if cut > 0 {
+        let marker = format!("\n[... {} bytes truncated ...]\n", cut);
+        let marker_len = marker.len() as u64;
+        move_within(file, head, head + marker_len, len - head).await?;
+        file.seek(SeekFrom::Start(head)).await?;
+        file.write_all(marker.as_bytes()).await?;
+        file.seek(SeekFrom::End(0)).await?;
+        file.flush().await?;
+    }
+    Ok((written, cut))
+}
+
+/// Keeps the first `head` and the last `tail` bytes of the `len` bytes in `file`, returns the bytes dropped.
+async fn drop_middle(file: &mut File, head: u64, len: u64, tail: u64) -> std::io::Result<u64> {
+    move_within(file, len - tail, head, tail).await?;
+    file.set_len(head + tail).await?;
+    file.seek(SeekFrom::End(0)).await?;
+    Ok(len - head - tail)
+}
+
+/// Moves `len` bytes at `from` to `to` in chunks, the ranges may overlap.
+async fn move_within(file: &mut File, from: u64, to: u64, len: u64) -> std::io::Result<()> {
+    let mut buf = vec![0u8; OUTPUT_CHUNK_BYTES as usize];
+    let mut moved = 0;
+    while moved < len {
+        let n = (len - moved).min(OUTPUT_CHUNK_BYTES);
+        // moving back starts at the front, moving forward at the end, so nothing is overwritten before it is read
+        let offset = if to < from { moved } else { len - moved - n };
+        let chunk = &mut buf[..n as usize];
+        file.seek(SeekFrom::Start(from + offset)).await?;
+        file.read_exact(chunk).await?;
+        file.seek(SeekFrom::Start(to + offset)).await?;
+        file.write_all(chunk).await?;
+        moved += n;
+    }
+    Ok(())
+}
+
+/// Writes a message to the output file at `path`, replacing previous output.
+///
+/// Used to leave an explanation for jobs that fail before their process is started.
+pub async fn write_output_message(path: &Path, message: &str) -> std::io::Result<()> {
+    let mut file = create_output_file(path).await?;
+    file.write_all(message.as_bytes()).await?;
+    file.flush().await
+}
+
+/// Reads at most the last `max_bytes` of the output file at `path`.
+///
+/// Output files can grow far beyond what fits into memory, so they are never read in full.
+pub async fn read_output_tail(path: &Path, max_bytes: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path).await?;
+    let len = file.metadata().await?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(max_bytes)))
+        .await?;
+
+    // the job may still be writing, so don't read past the limit
+    let mut content = Vec::with_capacity(len.min(max_bytes) as usize);
+    file.take(max_bytes).read_to_end(&mut content).await?;
+    Ok(content)
+}
+
+/// Reads an output file in chunks, without ever holding more than one of them in memory.
+///
+/// Ends at the length the file had when it was opened, so a job that keeps writing
+/// doesn't keep the reader going.
+pub struct OutputReader {
+    file: File,
+    offset: u64,
+    end: u64,
+}
+
+impl OutputReader {
+    /// Opens the output file at `path`, to read from byte `offset` on or only its last `tail_bytes`.
+    ///
+    /// Lets clients fetch output they haven't seen yet, an offset past the end reads nothing.
+    /// Reads the whole file if neither is given, the later start wins if both are.
+    pub async fn open(
+        path: &Path,
+        offset: Option<u64>,
+        tail_bytes: Option<u64>,
+    ) -> std::io::Result<Self> {
+        let mut file = File::open(path).await?;
+        let end = file.metadata().await?.len();
+        let start = offset
+            .unwrap_or(0)
+            .max(tail_bytes.map_or(0, |tail| end.saturating_sub(tail)))
+            .min(end);
+        file.seek(SeekFrom::Start(start)).await?;
+        Ok(Self {
+            file,
+            offset: start,
+            end,
+        })
+    }
+
+    /// Reads the next chunk of at most [OUTPUT_CHUNK_BYTES] along with its offset in the file.
+    ///
+    /// Returns `None` once the end is reached.
+    pub async fn next_chunk(&mut self) -> std::io::Result<Option<(u64, Vec<u8>)>> {
+        let len = (self.end - self.offset).min(OUTPUT_CHUNK_BYTES);
+        let mut content = Vec::with_capacity(len as usize);
+        (&mut self.file).take(len).read_to_end(&mut content).await?;
+        // the file may have been truncated, e.g. by a requeued job
+        if content.is_empty() {
+            return Ok(None);
+        }
+        let offset = self.offset;
+        self.offset += content.len() as u64;
+        Ok(Some((offset, content)))
+    }
+}
+
+/// Waits for an output capture task and logs any error.
+///
+/// Gives up after [OUTPUT_DRAIN_TIMEOUT]; the capture task keeps writing in the background.
+pub async fn finish_output_capture(handle: JoinHandle<std::io::Result<u64>>, job_id: u64) {
+    match tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, handle).await {
+        Ok(Ok(Ok(bytes))) => {
+            log!(
+                debug,
+                "Captured {} bytes of output for job {}",
+                bytes,
+                job_id
+            );
+        }
+        Ok(Ok(Err(e))) => {
+            log!(error, "Failed to capture output for job {}: {}", job_id, e);
+        }
+        Ok(Err(e)) => {
+            log!(
+                error,
+                "Output capture task for job {} failed: {}",
+                job_id,
+                e
+            );
+        }
+        Err(_) => {
+            log!(
+                warn,
+                "Output of job {} is still open, continue in background",
+                job_id
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn cap(max_bytes: u64) -> OutputCap {
+        OutputCap {
+            max_bytes,
+            truncated: TruncatedOutputs::default(),
+        }
+    }
+
+    #[test]
+    fn test_output_path() {
+        let dir = PathBuf::from("/tmp/logs");
+        assert_eq!(
+            output_path(&dir, 42, OutputStream::Stdout),
+            PathBuf::from("/tmp/logs/job-42.out")
+        );
+        assert_eq!(
+            output_path(&dir, 42, OutputStream::Stderr),
+            PathBuf::from("/tmp/logs/job-42.err")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capture_writes_to_file() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(&dir.path().join("nested"), 1, OutputStream::Stdout);
+        let content: &[u8] = b"hello\nworld\n";
+
+        let handle = spawn_output_capture(content, path.clone(), cap(1024));
+        let written = handle.await.unwrap().unwrap();
+
+        assert_eq!(written, content.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_capture_follows_linked_output() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(dir.path(), 1, OutputStream::Stdout);
+        let target = dir.path().join("results").join("out.1");
+        std::fs::write(&path, b"previous attempt").unwrap();
+
+        link_output(&path, &target).await.unwrap();
+        let handle = spawn_output_capture(&b"hello\n"[..], path.clone(), cap(1024));
+        handle.await.unwrap().unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), b"hello\n");
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello\n");
+    }
+
+    #[tokio::test]
+    async fn test_write_output_message() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(&dir.path().join("nested"), 1, OutputStream::Stderr);
+
+        write_output_message(&path, "no such directory\n")
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"no such directory\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_output_tail() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(dir.path(), 1, OutputStream::Stderr);
+        std::fs::write(&path, b"first line\nlast line\n").unwrap();
+
+        assert_eq!(read_output_tail(&path, 10).await.unwrap(), b"last line\n");
+        assert_eq!(
+            read_output_tail(&path, 1024).await.unwrap(),
+            b"first line\nlast line\n"
+        );
+    }
+
+    async fn read_all(mut reader: OutputReader) -> Vec<(u64, Vec<u8>)> {
+        let mut chunks = vec![];
+        while let Some(chunk) = reader.next_chunk().await.unwrap() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_ranges() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(dir.path(), 1, OutputStream::Stdout);
+        std::fs::write(&path, b"first line\nlast line\n").unwrap();
+
+        let reader = OutputReader::open(&path, Some(11), None).await.unwrap();
+        assert_eq!(read_all(reader).await, vec![(11, b"last line\n".to_vec())]);
+        let reader = OutputReader::open(&path, None, Some(5)).await.unwrap();
+        assert_eq!(read_all(reader).await, vec![(16, b"line\n".to_vec())]);
+        let reader = OutputReader::open(&path, Some(100), None).await.unwrap();
+        assert!(read_all(reader).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_output_reader_streams_in_chunks() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(dir.path(), 1, OutputStream::Stdout);
+        let content = vec![b'x'; 2 * OUTPUT_CHUNK_BYTES as usize + 10];
+        std::fs::write(&path, &content).unwrap();
+
+        let reader = OutputReader::open(&path, None, None).await.unwrap();
+        // output written after the reader was opened is left for the next request
+        std::fs::write(&path, [content.as_slice(), b"more"].concat()).unwrap();
+        let chunks = read_all(reader).await;
+        let offsets: Vec<u64> = chunks.iter().map(|(offset, _)| *offset).collect();
+        assert_eq!(offsets, vec![0, OUTPUT_CHUNK_BYTES, 2 * OUTPUT_CHUNK_BYTES]);
+        let read: Vec<u8> = chunks.into_iter().flat_map(|(_, chunk)| chunk).collect();
+        assert_eq!(read, content);
+    }
+
+    #[tokio::test]
+    async fn test_read_output_tail_of_missing_file() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(dir.path(), 1, OutputStream::Stdout);
+
+        let err = read_output_tail(&path, 10).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_capture_truncates_previous_output() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(dir.path(), 1, OutputStream::Stderr);
+        std::fs::write(&path, b"stale output from an earlier run").unwrap();
+
+        let content: &[u8] = b"fresh";
+        spawn_output_capture(content, path.clone(), cap(1024))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_capture_cuts_the_middle_of_long_output() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(dir.path(), 1, OutputStream::Stdout);
+        let cap = cap(100);
+        // long enough to be cut several times while it is written
+        let content: Vec<u8> = (0..100_000u32).map(|i| b'a' + (i % 26) as u8).collect();
+
+        let written = spawn_output_capture(
+            std::io::Cursor::new(content.clone()),
+            path.clone(),
+            cap.clone(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(written, 100_000);
+        let expected = [
+            &content[..50],
+            b"\n[... 99900 bytes truncated ...]\n",
+            &content[content.len() - 50..],
+        ]
+        .concat();
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        assert_eq!(cap.truncated.get(&path).map(|cut| *cut), Some(99_900));
+    }
+
+    #[tokio::test]
+    async fn test_capture_keeps_output_within_the_cap() {
+        let dir = TempDir::new().unwrap();
+        let path = output_path(dir.path(), 1, OutputStream::Stdout);
+        let cap = cap(5);
+        // left over from an earlier run that was cut
+        cap.truncated.insert(path.clone(), 10);
+
+        spawn_output_capture(&b"fresh"[..], path.clone(), cap.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"fresh");
+        assert!(cap.truncated.is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use std::future::Future;
+use std::time::Duration;
+
+/// Upper bound for the wait time between two attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Bounded retries with exponential backoff.
+///
+/// The first retry waits `initial_backoff`, every further retry waits twice as long as the one before,
+/// but never longer than [MAX_BACKOFF].
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+
+    /// Wait time before the first retry
+    pub initial_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            initial_backoff,
+        }
+    }
+
+    /// Returns the wait time after the given failed attempt, starting at 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF)
+    }
+
+    /// Runs `op` until it succeeds, fails with an error that isn't transient,
+    /// or the attempts are used up. Returns the last error in the latter cases.
+    pub async fn run<T, E, F, Fut>(
+        &self,
+        mut op: F,
+        is_transient: impl Fn(&E) -> bool,
+    ) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Ok(value) => return Ok(value),
+                Err(e) if is_transient(&e) && attempt + 1 < self.max_attempts => {
+                    tokio::time::sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Returns whether a failed RPC may succeed when sent again.
+pub fn is_transient(status: &tonic::Status) -> bool {
+    matches!(
+        status.code(),
+        tonic::Code::Unavailable
+            | tonic::Code::Unknown
+            | tonic::Code::DeadlineExceeded
+            | tonic::Code::Aborted
+            | tonic::Code::ResourceExhausted
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_backoff_doubles() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100));
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy::new(5, Duration::from_secs(1));
+        assert_eq!(policy.backoff(100), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(1));
+        let calls = AtomicU32::new(0);
+
+        let res: Result<u32, tonic::Status> = policy
+            .run(
+                || async {
+                    match calls.fetch_add(1, Ordering::SeqCst) {
+                        0 | 1 => Err(tonic::Status::unavailable("down")),
+                        n => Ok(n),
+                    }
+                },
+                is_transient,
+            )
+            .await;
+
+        assert_eq!(res.unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+        let calls = AtomicU32::new(0);
+
+        let res: Result<(), tonic::Status> = policy
+            .run(
+                || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err(tonic::Status::unavailable("down"))
+                },
+                is_transient,
+            )
+            .await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::Unavailable);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_permanent_errors() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+        let calls = AtomicU32::new(0);
+
+        let res: Result<(), tonic::Status> = policy
+            .run(
+                || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err(tonic::Status::not_found("Job not found"))
+                },
+                is_transient,
+            )
+            .await;
+
+        assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
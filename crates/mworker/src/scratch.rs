@@ -0,0 +1,197 @@
+use melon_common::log;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Name of the environment variable that points a job to its scratch directory.
+pub const SCRATCH_ENV: &str = "MELON_SCRATCH";
+
+/// File name of a script that was sent along with its job, if its path has none
+const SCRIPT_FILE_NAME: &str = "script";
+
+/// Returns the default scratch base directory `<tmp>/melon/scratch`.
+pub fn default_scratch_dir() -> PathBuf {
+    std::env::temp_dir().join("melon").join("scratch")
+}
+
+/// Returns the path of the scratch directory of a job, `job-<id>` below the base directory.
+pub fn scratch_path(base: &Path, job_id: u64) -> PathBuf {
+    base.join(format!("job-{}", job_id))
+}
+
+/// Removes the scratch directories left behind by an earlier run of the worker.
+///
+/// Only called on startup, when no job can be using them.
+pub fn remove_stale_scratch_dirs(base: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(base)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with("job-") {
+            log!(
+                info,
+                "Remove stale scratch directory {}",
+                entry.path().display()
+            );
+            std::fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// A job's scratch directory, removed again once the job is done.
+///
+/// Call [ScratchDir::remove] when the job has finished. If the guard is dropped
+/// without it, e.g. because the job failed early, the directory is removed on drop.
+#[derive(Debug)]
+pub struct ScratchDir {
+    path: Option<PathBuf>,
+}
+
+impl ScratchDir {
+    /// Creates an empty scratch directory for a job.
+    ///
+    /// Leftovers of an earlier job with the same id are removed first.
+    pub async fn create(base: &Path, job_id: u64) -> std::io::Result<Self> {
+        let path = scratch_path(base, job_id);
+        match tokio::fs::remove_dir_all(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        tokio::fs::create_dir_all(&path).await?;
+        Ok(Self { path: Some(path) })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_deref().expect("Scratch directory was removed")
+    }
+
+    /// Writes a script that was sent along with the job into the directory, executable.
+    ///
+    /// The file is named like the script path of the job, e.g. `wrap` for `mbatch --wrap`.
+    pub async fn write_script(
+        &self,
+        script_path: &str,
+        contents: &[u8],
+    ) -> std::io::Result<PathBuf> {
+        let name = Path::new(script_path)
+            .file_name()
+            .map_or_else(|| SCRIPT_FILE_NAME.into(), |name| name.to_owned());
+        let path = self.path().join(name);
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .mode(0o755)
+            .open(&path)
+            .await?;
+        file.write_all(contents).await?;
+        file.flush().await?;
+        Ok(path)
+    }
+
+    /// Removes the directory with everything the job left in it.
+    pub async fn remove(mut self) {
+        if let Some(path) = self.path.take() {
+            if let Err(e) = tokio::fs::remove_dir_all(&path).await {
+                log!(
+                    error,
+                    "Could not remove scratch directory {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            if let Err(e) = std::fs::remove_dir_all(&path) {
+                log!(
+                    error,
+                    "Could not remove scratch directory {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scratch_path() {
+        assert_eq!(
+            scratch_path(Path::new("/scratch"), 42),
+            PathBuf::from("/scratch/job-42")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_script() {
+        let base = TempDir::new().unwrap();
+        let scratch = ScratchDir::create(base.path(), 1).await.unwrap();
+
+        let path = scratch
+            .write_script("wrap", b"#!/bin/sh\necho hi\n")
+            .await
+            .unwrap();
+
+        assert_eq!(path, scratch.path().join("wrap"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"#!/bin/sh\necho hi\n");
+        assert!(melon_common::script::is_executable(&path).unwrap());
+        let path = scratch.write_script("", b"#!/bin/sh\n").await.unwrap();
+        assert_eq!(path, scratch.path().join("script"));
+    }
+
+    #[tokio::test]
+    async fn test_scratch_dir_is_removed() {
+        let base = TempDir::new().unwrap();
+        let scratch = ScratchDir::create(base.path(), 1).await.unwrap();
+        let path = scratch.path().to_path_buf();
+        std::fs::write(path.join("data"), b"intermediate results").unwrap();
+
+        scratch.remove().await;
+
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_scratch_dir_is_removed_on_drop() {
+        let base = TempDir::new().unwrap();
+        let scratch = ScratchDir::create(base.path(), 1).await.unwrap();
+        let path = scratch.path().to_path_buf();
+
+        drop(scratch);
+
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_scratch_dir_starts_empty() {
+        let base = TempDir::new().unwrap();
+        let stale = scratch_path(base.path(), 1);
+        std::fs::create_dir_all(&stale).unwrap();
+        std::fs::write(stale.join("data"), b"from an earlier run").unwrap();
+
+        let scratch = ScratchDir::create(base.path(), 1).await.unwrap();
+
+        assert_eq!(std::fs::read_dir(scratch.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_remove_stale_scratch_dirs() {
+        let base = TempDir::new().unwrap();
+        std::fs::create_dir_all(scratch_path(base.path(), 1)).unwrap();
+        std::fs::create_dir_all(base.path().join("other")).unwrap();
+
+        remove_stale_scratch_dirs(base.path()).unwrap();
+
+        assert!(!scratch_path(base.path(), 1).exists());
+        assert!(base.path().join("other").exists());
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+use sysinfo::{Pid, Process, System};
+
+/// Measures the peak memory and CPU time of a job by sampling its process tree.
+///
+/// Only needed without cgroups, which account for both exactly.
+/// The CPU time is an estimate, integrated from the CPU usage at every sample.
+#[derive(Debug, Default)]
+pub struct UsageSampler {
+    max_rss_bytes: u64,
+    cpu_seconds: f64,
+    samples: u64,
+}
+
+impl UsageSampler {
+    /// Adds a sample of the process tree rooted at `root` and returns its current memory in bytes.
+    ///
+    /// `interval` is the time since the previous sample, the processes must have been refreshed.
+    pub fn sample(&mut self, system: &System, root: Pid, interval: Duration) -> u64 {
+        let processes = process_tree(system, root);
+        let memory = processes.iter().map(|process| process.memory()).sum();
+        let cpu_usage: f32 = processes.iter().map(|process| process.cpu_usage()).sum();
+
+        self.max_rss_bytes = self.max_rss_bytes.max(memory);
+        self.cpu_seconds += cpu_usage as f64 / 100.0 * interval.as_secs_f64();
+        self.samples += 1;
+        memory
+    }
+
+    /// The highest memory usage seen in bytes, if any sample was taken.
+    pub fn max_rss_bytes(&self) -> Option<u64> {
+        (self.samples > 0).then_some(self.max_rss_bytes)
+    }
+
+    /// The estimated CPU time in seconds, if any sample was taken.
+    pub fn cpu_seconds(&self) -> Option<f64> {
+        (self.samples > 0).then_some(self.cpu_seconds)
+    }
+}
+
+/// Returns a process and all of its descendants.
+fn process_tree(system: &System, root: Pid) -> Vec<&Process> {
+    system
+        .processes()
+        .values()
+        .filter(|process| {
+            let mut current = Some(process.pid());
+            while let Some(pid) = current {
+                if pid == root {
+                    return true;
+                }
+                current = system.process(pid).and_then(|p| p.parent());
+            }
+            false
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_samples() {
+        let usage = UsageSampler::default();
+        assert_eq!(usage.max_rss_bytes(), None);
+        assert_eq!(usage.cpu_seconds(), None);
+    }
+
+    #[test]
+    fn test_sample_own_process() {
+        let mut system = System::new();
+        system.refresh_processes();
+        let mut usage = UsageSampler::default();
+
+        let memory = usage.sample(
+            &system,
+            Pid::from_u32(std::process::id()),
+            Duration::from_secs(1),
+        );
+
+        assert!(memory > 0);
+        assert_eq!(usage.max_rss_bytes(), Some(memory));
+        assert!(usage.cpu_seconds().unwrap() >= 0.0);
+    }
+
+    #[test]
+    fn test_sample_unknown_process() {
+        let system = System::new();
+        let mut usage = UsageSampler::default();
+
+        let memory = usage.sample(&system, Pid::from_u32(u32::MAX), Duration::from_secs(1));
+
+        assert_eq!(memory, 0);
+        assert_eq!(usage.max_rss_bytes(), Some(0));
+    }
+}
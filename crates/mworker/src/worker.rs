@@ -1,31 +1,59 @@
+use crate::address::{discover_local_ip, endpoint_url, listen_addr};
+#[cfg(not(feature = "cgroups"))]
+use crate::affinity::pin_to_cores;
 use crate::arg::Args;
-use crate::core_mask::CoreMask;
+use crate::core_mask::{CoreMask, CpuSet};
+use crate::error::WorkerError;
+use crate::gpu;
+use crate::identity::{hand_over, lookup_user, run_as};
+use crate::interactive::{
+    attach_terminal, spawn_input_relay, spawn_output_relay, AttachSession, PendingAttach,
+    ATTACH_TIMEOUT, OUTPUT_BUFFER,
+};
+use crate::output::{
+    default_log_dir, finish_output_capture, link_output, output_path, read_output_tail,
+    spawn_output_capture, write_output_message, OutputCap, OutputReader, TruncatedOutputs,
+    ERROR_TAIL_BYTES,
+};
+use crate::retry::{is_transient, RetryPolicy};
+use crate::scratch::{default_scratch_dir, remove_stale_scratch_dirs, ScratchDir, SCRATCH_ENV};
+use crate::usage::UsageSampler;
 #[cfg(feature = "cgroups")]
 use cgroups::CGroups;
 use dashmap::DashMap;
-use melon_common::proto::melon_scheduler_client::MelonSchedulerClient;
+use melon_common::auth;
 use melon_common::proto::melon_worker_server::{MelonWorker, MelonWorkerServer};
-use melon_common::proto::{self, NodeInfo, NodeResources};
-use melon_common::{log, JobResult, JobStatus};
+use melon_common::proto::{self, NodeInfo, NodeResources, OutputStream};
+use melon_common::script::{is_executable, read_shebang};
+use melon_common::utils::expand_output_path;
+use melon_common::{log, JobResult, JobStatus, WarnSignal};
+use std::collections::VecDeque;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
 use std::time::Duration;
-use sysinfo::System;
-use tokio::io::{AsyncReadExt, BufReader};
-use tokio::process::Command;
-use tokio::sync::{mpsc, watch, Mutex, Notify};
+use sysinfo::{Pid, System};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot, watch, Mutex, Notify};
 use tokio::task::JoinHandle;
 use tokio::time::{interval, Instant};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::transport::Server;
 
+/// How often the memory usage of a job is checked on builds without cgroups.
+const MEMORY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone)]
 pub struct Worker {
     /// The unique worker ID assigned by the master node
     id: Option<String>,
 
-    /// Internal server port
-    port: u16,
+    /// Address the scheduler reaches the worker server at
+    advertise_addr: SocketAddr,
+
+    /// Address the worker server listens on
+    bind_addr: SocketAddr,
 
     /// Endpoint of the master node/scheduler
     endpoint: String,
@@ -68,6 +96,12 @@ pub struct Worker {
     /// Value: Channel to send deadline extensions
     deadline_notifiers: Arc<DashMap<u64, mpsc::Sender<Duration>>>,
 
+    /// Map of cancellation notifiers for running jobs
+    ///
+    /// Key: Job ID
+    /// Value: Channel to ask the job to terminate
+    cancel_notifiers: Arc<DashMap<u64, mpsc::Sender<()>>>,
+
     /// CoreMask for managing CPU core allocation
     ///
     /// Represents the available CPU cores on the worker node.
@@ -80,8 +114,84 @@ pub struct Worker {
     /// Map of job-specific core masks
     ///
     /// Key: Job ID
-    /// Value: Bitmask representing the cores allocated to the job
-    job_masks: Arc<DashMap<u64, u64>>,
+    /// Value: Set of the cores allocated to the job
+    job_masks: Arc<DashMap<u64, CpuSet>>,
+
+    /// Directory where the captured stdout and stderr of jobs are written to
+    log_dir: PathBuf,
+
+    /// Directory below which every job gets its own scratch directory
+    scratch_dir: PathBuf,
+
+    /// How much of a job's output is kept, along with the output that was cut
+    output_cap: OutputCap,
+
+    /// Results of finished jobs that have not been delivered to the scheduler yet
+    pending_results: Arc<Mutex<VecDeque<JobResult>>>,
+
+    /// Retry policy for delivering job results
+    submit_retry: RetryPolicy,
+
+    /// Notifier to signal the running jobs to terminate on shutdown
+    job_shutdown: watch::Sender<()>,
+
+    /// How long jobs get to exit after SIGTERM before they are killed, unless they set their own
+    grace_period: Duration,
+
+    /// Signal jobs get once they used a share of their time limit, unless they set their own
+    warn_signal: Option<WarnSignal>,
+
+    /// Most jobs that run at once, unlimited if unset
+    max_jobs: Option<u32>,
+
+    /// Run jobs as the users who submitted them
+    run_as_user: bool,
+
+    /// Lowest uid jobs may run as, root is always refused
+    min_uid: u32,
+
+    /// Token the scheduler sends with its requests, any caller is trusted if unset
+    token: Option<String>,
+
+    /// Features of the node that jobs can ask for
+    labels: Vec<String>,
+
+    /// Directory the job cgroups are created in
+    #[cfg(feature = "cgroups")]
+    cgroup_path: PathBuf,
+
+    /// Most swap a job may use in bytes, unlimited if unset
+    #[cfg(feature = "cgroups")]
+    swap_limit: Option<u64>,
+
+    /// Share of CPU time jobs get under contention, the kernel default if unset
+    #[cfg(feature = "cgroups")]
+    cpu_weight: Option<u64>,
+
+    /// Interactive jobs that wait for their client to attach
+    ///
+    /// Key: Job ID
+    /// Value: Token of the client and channel to hand it to the job
+    attach_sessions: Arc<DashMap<u64, PendingAttach>>,
+
+    /// Channels that running jobs wait on for their steps
+    ///
+    /// Key: Job ID
+    /// Value: Sender every step of the job holds a clone of while it runs
+    job_steps: Arc<DashMap<u64, mpsc::Sender<()>>>,
+
+    /// Parents of the running steps
+    ///
+    /// Key: Job ID of the step
+    /// Value: Job ID of its parent
+    step_parents: Arc<DashMap<u64, u64>>,
+
+    /// Cgroups of running jobs, their steps are added to them
+    ///
+    /// Key: Job ID
+    /// Value: Cgroup of the job
+    #[cfg(feature = "cgroups")]
+    job_cgroups: Arc<DashMap<u64, Arc<CGroups>>>,
 }
 
 impl Drop for Worker {
@@ -112,9 +222,44 @@ enum ConnectionStatus {
 
 impl Worker {
     #[tracing::instrument(level = "info", name = "Build new worker...", skip(args))]
-    pub fn new(args: &Args) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(args: &Args) -> Result<Self, WorkerError> {
         let endpoint = format!("http://{}", args.api_endpoint);
         let (server_notifier, _server_notifier_rx) = watch::channel(());
+        let (job_shutdown, _job_shutdown_rx) = watch::channel(());
+
+        // the v1 file layout differs, every job would fail to set up its cgroup
+        #[cfg(feature = "cgroups")]
+        cgroups::require_v2()?;
+
+        // SAFETY: geteuid has no preconditions and can't fail
+        if args.run_as_user && unsafe { libc::geteuid() } != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "Running jobs as their user needs a worker that runs as root",
+            )
+            .into());
+        }
+        let token = std::env::var(auth::WORKER_TOKEN_ENV)
+            .ok()
+            .filter(|token| !token.is_empty());
+        if token.is_none() {
+            // whoever reaches the worker could run jobs as any user otherwise
+            if args.run_as_user {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    format!(
+                        "Running jobs as their user needs a worker token in {}",
+                        auth::WORKER_TOKEN_ENV
+                    ),
+                )
+                .into());
+            }
+            log!(
+                warn,
+                "No worker token in {}, anyone who can reach the worker can run jobs on it",
+                auth::WORKER_TOKEN_ENV
+            );
+        }
 
         let total_cores = num_cpus::get(); // cpuset considers logical cores
         let core_mask = Arc::new(Mutex::new(CoreMask::new(total_cores as u32)));
@@ -122,10 +267,59 @@ impl Worker {
 
         log!(info, "Set up worker with {} logical cores", total_cores);
 
+        let advertise_ip = match args.advertise_addr {
+            Some(ip) => ip,
+            None => discover_local_ip(args.api_endpoint)?,
+        };
+        let advertise_addr = SocketAddr::new(advertise_ip, args.port);
+        let bind_addr = match args.bind {
+            Some(ip) => SocketAddr::new(ip, args.port),
+            None => listen_addr(advertise_addr),
+        };
+        log!(
+            info,
+            "Listen on {}, advertise worker at {}",
+            bind_addr,
+            advertise_addr
+        );
+        if bind_addr.ip().is_loopback() && !advertise_ip.is_loopback() {
+            log!(
+                warn,
+                "Only listen on {}, the scheduler can't reach the worker at {} without --bind",
+                bind_addr.ip(),
+                advertise_addr
+            );
+        }
+
+        let log_dir = args.log_dir.clone().unwrap_or_else(default_log_dir);
+        std::fs::create_dir_all(&log_dir)?;
+        log!(info, "Write job output to {}", log_dir.display());
+
+        let scratch_dir = args.scratch_dir.clone().unwrap_or_else(default_scratch_dir);
+        std::fs::create_dir_all(&scratch_dir)?;
+        remove_stale_scratch_dirs(&scratch_dir)?;
+        log!(
+            info,
+            "Create job scratch directories in {}",
+            scratch_dir.display()
+        );
+
+        if cfg!(not(feature = "cgroups"))
+            && (args.cgroup_path.is_some()
+                || args.swap_limit.is_some()
+                || args.cpu_weight.is_some())
+        {
+            log!(
+                warn,
+                "The cgroup path, swap limit and CPU weight need a build with cgroups, ignore them"
+            );
+        }
+
         Ok(Self {
             id: None,
             status: ConnectionStatus::Disconnected,
-            port: args.port,
+            advertise_addr,
+            bind_addr,
             endpoint,
             heartbeat_handle: None,
             heartbeat_notifier: Arc::new(Notify::new()),
@@ -134,13 +328,47 @@ impl Worker {
             polling_handle: None,
             polling_notifier: Arc::new(Notify::new()),
             deadline_notifiers: Arc::new(DashMap::new()),
+            cancel_notifiers: Arc::new(DashMap::new()),
             core_mask,
             job_masks,
+            log_dir,
+            scratch_dir,
+            output_cap: OutputCap {
+                max_bytes: args.max_output_bytes,
+                truncated: TruncatedOutputs::default(),
+            },
+            pending_results: Arc::new(Mutex::new(VecDeque::new())),
+            submit_retry: RetryPolicy::new(
+                args.submit_attempts,
+                Duration::from_millis(args.submit_backoff_ms),
+            ),
+            job_shutdown,
+            grace_period: Duration::from_secs(args.grace_secs),
+            warn_signal: args.warn_signal.clone(),
+            max_jobs: args.max_jobs,
+            run_as_user: args.run_as_user,
+            min_uid: args.min_uid,
+            token,
+            labels: args.labels.clone(),
+            #[cfg(feature = "cgroups")]
+            cgroup_path: args
+                .cgroup_path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(cgroups::BASE_CGROUP_PATH)),
+            #[cfg(feature = "cgroups")]
+            swap_limit: args.swap_limit,
+            #[cfg(feature = "cgroups")]
+            cpu_weight: args.cpu_weight,
+            attach_sessions: Arc::new(DashMap::new()),
+            job_steps: Arc::new(DashMap::new()),
+            step_parents: Arc::new(DashMap::new()),
+            #[cfg(feature = "cgroups")]
+            job_cgroups: Arc::new(DashMap::new()),
         })
     }
 
     #[tracing::instrument(level = "info", name = "Start polling" skip(self))]
-    pub async fn start_polling(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn start_polling(&mut self) -> Result<(), WorkerError> {
         let worker = self.clone();
         let notifier = self.polling_notifier.clone();
 
@@ -173,13 +401,9 @@ impl Worker {
     ///
     /// If there are any finished jobs, submit the job result to the
     /// master node and remove the job from our internal data structure.
-    ///
-    /// # TODOS
-    ///
-    /// - [ ] handle timeouts when sending the result to the master
+    /// Results that can't be delivered are kept and sent again on the next poll.
     #[tracing::instrument(level = "debug", name = "Poll jobs" skip(self))]
-    async fn poll_jobs(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let endpoint = self.endpoint.clone();
+    async fn poll_jobs(&self) -> Result<(), WorkerError> {
         let jobs = self.running_jobs.clone();
         let mut completed_jobs = Vec::new();
         for entry in jobs.iter_mut() {
@@ -192,48 +416,126 @@ impl Worker {
         }
 
         for &job_id in &completed_jobs {
-            if let Some((_, handle)) = jobs.remove(&job_id) {
-                match handle.await {
-                    Ok(result) => {
-                        log!(info, "Received job result {:?}", result);
-
-                        // send the update to the server
-                        let mut client = MelonSchedulerClient::connect(endpoint.clone()).await?;
-                        let request = tonic::Request::new(result.into());
-                        // FIXME: handle timeouts and disconnects
-                        let _res = client.submit_job_result(request).await?;
-                    }
-                    Err(e) => {
-                        log!(error, "Job execution failed: {}", e);
-                        let status = JobStatus::Failed;
-                        let result = JobResult::new(job_id, status);
-                        let mut client = MelonSchedulerClient::connect(endpoint.clone()).await?;
-                        let request = tonic::Request::new(result.into());
-                        // FIXME: handle timeouts and disconnects
-                        let _res = client.submit_job_result(request).await?;
-                    }
+            self.collect_result(job_id).await;
+        }
+
+        self.flush_results().await
+    }
+
+    /// Waits for a job's task and buffers its result for delivery to the scheduler.
+    async fn collect_result(&self, job_id: u64) {
+        // keep the job visible to heartbeats while it moves to the buffer
+        let mut pending_results = self.pending_results.lock().await;
+        if let Some((_, handle)) = self.running_jobs.remove(&job_id) {
+            let result = match handle.await {
+                Ok(result) => {
+                    log!(info, "Received job result {:?}", result);
+                    result
                 }
-            }
+                Err(e) => {
+                    log!(error, "Job execution failed: {}", e);
+                    JobResult::new(job_id, JobStatus::Failed)
+                }
+            };
+            pending_results.push_back(result);
         }
+        drop(pending_results);
 
         // remove the notifiers
-        for &job_id in &completed_jobs {
-            if self.deadline_notifiers.remove(&job_id).is_some() {
-                log!(info, "Remove deadline notifier for {}", job_id);
+        if self.deadline_notifiers.remove(&job_id).is_some() {
+            log!(info, "Remove deadline notifier for {}", job_id);
+        }
+        self.cancel_notifiers.remove(&job_id);
+        self.job_steps.remove(&job_id);
+        self.step_parents.remove(&job_id);
+    }
+
+    /// Shuts the worker down without leaving job processes behind.
+    ///
+    /// Running jobs are terminated, their results are reported to the scheduler
+    /// and the server is stopped afterwards.
+    #[tracing::instrument(level = "info", name = "Shut down worker" skip(self))]
+    pub async fn shutdown(&self) {
+        self.heartbeat_notifier.notify_one();
+        self.polling_notifier.notify_one();
+
+        let job_ids: Vec<u64> = self.running_jobs.iter().map(|entry| *entry.key()).collect();
+        log!(info, "Terminating {} running jobs", job_ids.len());
+        let _ = self.job_shutdown.send(());
+        for job_id in job_ids {
+            self.collect_result(job_id).await;
+        }
+
+        if let Err(e) = self.flush_results().await {
+            log!(error, "Could not report all job results: {}", e);
+        }
+
+        let _ = self.server_notifier.send(());
+    }
+
+    /// Sends all buffered job results to the scheduler, oldest first.
+    ///
+    /// Every result is retried with exponential backoff. If it still can't be delivered,
+    /// it stays in the buffer together with all later results.
+    #[tracing::instrument(level = "debug", name = "Flush job results" skip(self))]
+    async fn flush_results(&self) -> Result<(), WorkerError> {
+        let mut pending_results = self.pending_results.lock().await;
+        while let Some(result) = pending_results.front().cloned() {
+            let job_id = result.id;
+            let res = self
+                .submit_retry
+                .run(|| self.submit_job_result(result.clone()), is_transient)
+                .await;
+
+            match res {
+                Ok(()) => {}
+                Err(status) if is_transient(&status) => {
+                    log!(
+                        warn,
+                        "Could not deliver result of job {}, keep {} results for the next poll",
+                        job_id,
+                        pending_results.len()
+                    );
+                    return Err(status.into());
+                }
+                Err(status) => {
+                    // sending it again won't help
+                    log!(
+                        error,
+                        "Scheduler rejected result of job {}: {}",
+                        job_id,
+                        status
+                    );
+                }
             }
+            pending_results.pop_front();
         }
 
         Ok(())
     }
 
+    async fn submit_job_result(&self, result: JobResult) -> Result<(), tonic::Status> {
+        let mut client = auth::connect(self.endpoint.clone())
+            .await
+            .map_err(|e| tonic::Status::unavailable(format!("Could not connect: {}", e)))?;
+        let mut result: proto::JobResult = result.into();
+        // tells the scheduler which part of a multi-node job ended
+        result.node_id = self.id.clone().unwrap_or_default();
+        client
+            .submit_job_result(tonic::Request::new(result))
+            .await?;
+        Ok(())
+    }
+
     #[tracing::instrument(level = "info", name = "Register node at daemon" skip(self))]
-    pub async fn register_node(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn register_node(&mut self) -> Result<(), WorkerError> {
         log!(info, "Register node at master at {}", self.endpoint);
-        let mut client = MelonSchedulerClient::connect(self.endpoint.clone().to_string()).await?;
+        let mut client = auth::connect(self.endpoint.clone()).await?;
         let resources = get_node_resources();
         let req = NodeInfo {
-            address: format!("http://[::1]:{}", self.port),
+            address: endpoint_url(self.advertise_addr),
             resources: Some(resources),
+            labels: self.labels.clone(),
         };
         let request = tonic::Request::new(req);
         let res = client.register_node(request).await?;
@@ -244,7 +546,7 @@ impl Worker {
     }
 
     #[tracing::instrument(level = "debug", name = "Start hearbeat loop" skip(self))]
-    pub async fn start_heartbeats(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn start_heartbeats(&mut self) -> Result<(), WorkerError> {
         let worker = self.clone();
         let notifier = self.heartbeat_notifier.clone();
         let handle = tokio::spawn(async move {
@@ -274,21 +576,62 @@ impl Worker {
     }
 
     #[tracing::instrument(level = "debug", name = "Send heartbeat" skip(self))]
-    async fn send_heartbeat(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut client = MelonSchedulerClient::connect(self.endpoint.clone().to_string()).await?;
+    async fn send_heartbeat(&self) -> Result<(), WorkerError> {
+        let mut client = auth::connect(self.endpoint.clone()).await?;
         let node_id = self.id.clone().unwrap();
-        let req = proto::Heartbeat { node_id };
+        let usage = self.current_usage().await;
+        let req = proto::Heartbeat {
+            node_id,
+            usage: Some(usage),
+        };
         let req = tonic::Request::new(req);
         let _ = client.send_heartbeat(req).await?;
         Ok(())
     }
 
+    /// Collects the jobs and resources in use, so the scheduler can correct its view of the node.
+    ///
+    /// Finished jobs are included until their result is delivered.
+    async fn current_usage(&self) -> proto::NodeUsage {
+        let mut running_job_ids: Vec<u64> = {
+            let pending_results = self.pending_results.lock().await;
+            let mut ids: Vec<u64> = self.running_jobs.iter().map(|entry| *entry.key()).collect();
+            ids.extend(pending_results.iter().map(|result| result.id));
+            ids
+        };
+        running_job_ids.sort_unstable();
+
+        let allocated_cpus = self.core_mask.lock().await.get_allocated_cores().len();
+
+        let mut system = System::new();
+        system.refresh_memory();
+
+        proto::NodeUsage {
+            running_job_ids,
+            allocated_cpus,
+            free_memory: system.available_memory(),
+            max_jobs: self.max_jobs,
+            active_jobs: self.active_jobs() as u32,
+        }
+    }
+
+    /// Counts the jobs whose task is still running, finished ones stay in `running_jobs` until the next poll.
+    ///
+    /// Steps run within the slot of their parent and are not counted.
+    fn active_jobs(&self) -> usize {
+        self.running_jobs
+            .iter()
+            .filter(|entry| !entry.value().is_finished())
+            .filter(|entry| !self.step_parents.contains_key(entry.key()))
+            .count()
+    }
+
     #[tracing::instrument(level = "info", name = "Start worker server" skip(self))]
-    pub async fn start_server(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn start_server(&mut self) -> Result<(), WorkerError> {
         let worker = self.clone();
         let mut shutdown_rx = self.server_notifier.subscribe();
 
-        let address: SocketAddr = format!("[::1]:{}", worker.port).parse().unwrap();
+        let address = worker.bind_addr;
         let server = Server::builder()
             .add_service(MelonWorkerServer::new(worker))
             .serve_with_shutdown(address, async {
@@ -301,24 +644,90 @@ impl Worker {
         Ok(())
     }
 
+    /// Checks that a request comes from the scheduler, by the worker token it sent.
+    #[allow(clippy::result_large_err)] // handed to the caller as is
+    fn check_token<T>(&self, request: &tonic::Request<T>) -> Result<(), tonic::Status> {
+        match &self.token {
+            Some(token) if auth::bearer_token(request) != Some(token.as_str()) => Err(
+                tonic::Status::unauthenticated("Missing or invalid worker token"),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails a job that can't be started, the reason also goes to its error output.
+    async fn fail_before_start(&self, job_id: u64, message: String) -> JoinHandle<JobResult> {
+        log!(error, "Job {}: {}", job_id, message);
+        let stderr_path = output_path(&self.log_dir, job_id, OutputStream::Stderr);
+        if let Err(e) = write_output_message(&stderr_path, &format!("{}\n", message)).await {
+            log!(
+                error,
+                "Could not write error output of job {}: {}",
+                job_id,
+                e
+            );
+        }
+        tokio::spawn(async move {
+            JobResult::new(job_id, JobStatus::Failed).with_exit(None, Some(message))
+        })
+    }
+
     /// Spawn a thread to work on a given job
     ///
     /// # Notes
     ///
-    /// Returns the thread handler to the calling scope.
+    /// Returns the thread handler to the calling scope. Jobs that can't be started on this
+    /// node get a handle that reports them as failed, an error means the job was not taken.
     #[tracing::instrument(level = "info", name = "Spawn new job" skip(self, job))]
     pub async fn spawn_job(
         &self,
         job: &proto::JobAssignment,
-    ) -> Result<JoinHandle<JobResult>, Box<dyn std::error::Error>> {
+    ) -> Result<JoinHandle<JobResult>, WorkerError> {
+        let resources = job
+            .req_res
+            .ok_or_else(|| WorkerError::SpawnFailed("Job has no requested resources".into()))?;
+        if job.interactive && job.attach_token.is_empty() {
+            return Err(WorkerError::SpawnFailed(
+                "Interactive job has no attach token".into(),
+            ));
+        }
+        // steps run within the allocation of their parent, which waits for them to finish
+        let step_of = match job.parent_job_id {
+            Some(parent_id) => match self.job_steps.get(&parent_id) {
+                Some(steps_open) => Some((parent_id, steps_open.clone())),
+                None => {
+                    return Err(WorkerError::SpawnFailed(format!(
+                        "Job {} does not run on this node",
+                        parent_id
+                    )))
+                }
+            },
+            None => None,
+        };
+
         // spawn a new thread that works on the job
         let job_id = job.job_id;
         let (tx, mut rx) = mpsc::channel::<Duration>(10);
         self.deadline_notifiers.insert(job_id, tx);
-        let initial_time_mins = job.req_res.expect("Could not get resources").time as u64;
-        let pth = job.script_path.clone();
+        let (cancel_tx, mut cancel_rx) = mpsc::channel::<()>(1);
+        self.cancel_notifiers.insert(job_id, cancel_tx);
+        let grace_period = job
+            .grace_secs
+            .map(|secs| Duration::from_secs(secs as u64))
+            .unwrap_or(self.grace_period);
+        let warn_signal = match job.warn_signal.as_deref().map(str::parse::<WarnSignal>) {
+            Some(Ok(warn_signal)) => Some(warn_signal),
+            Some(Err(e)) => {
+                log!(warn, "Ignore the warning signal of job {}: {}", job_id, e);
+                None
+            }
+            None => self.warn_signal.clone(),
+        };
+        let initial_time_mins = resources.time as u64;
+        let mut pth = job.script_path.clone();
         let args = job.script_args.clone();
-        let resources = job.req_res.unwrap();
+        let env = job.env.clone();
+        let work_dir = job.work_dir.clone();
         let cores_needed = resources.cpu_count;
 
         log!(
@@ -330,18 +739,150 @@ impl Worker {
             cores_needed
         );
 
-        let allocated_mask = {
+        // fail right away instead of letting the exec fail with a cryptic error,
+        // the submitting host may see a different filesystem than this node
+        if job.script_contents.is_none() && !Path::new(&pth).is_file() {
+            let message = format!("Script {} does not exist on this node", pth);
+            return Ok(self.fail_before_start(job_id, message).await);
+        }
+        if let Some(dir) = work_dir.as_deref().filter(|dir| !Path::new(dir).is_dir()) {
+            let message = format!("Working directory {} does not exist on this node", dir);
+            return Ok(self.fail_before_start(job_id, message).await);
+        }
+        let job_user = if self.run_as_user {
+            match lookup_user(&job.user, self.min_uid) {
+                Ok(user) => Some(user),
+                Err(message) => return Ok(self.fail_before_start(job_id, message).await),
+            }
+        } else {
+            None
+        };
+        let stdout_path = output_path(&self.log_dir, job_id, OutputStream::Stdout);
+        let stderr_path = output_path(&self.log_dir, job_id, OutputStream::Stderr);
+        for (log_path, template) in [
+            (&stdout_path, &job.output_path),
+            (&stderr_path, &job.error_path),
+        ] {
+            let Some(template) = template else {
+                continue;
+            };
+            let linked = match expand_output_path(template, job_id, &job.user) {
+                Ok(target) => link_output(log_path, Path::new(&target))
+                    .await
+                    .map_err(|e| format!("Could not create output file {}: {}", target, e)),
+                Err(e) => Err(e),
+            };
+            if let Err(message) = linked {
+                return Ok(self.fail_before_start(job_id, message).await);
+            }
+        }
+
+        // removed once the job is done, or right away if it can't be started
+        let scratch = match ScratchDir::create(&self.scratch_dir, job_id).await {
+            Ok(scratch) => scratch,
+            Err(e) => {
+                log!(
+                    error,
+                    "Could not create scratch directory for job {}: {}",
+                    job_id,
+                    e
+                );
+                let message = format!("Could not create the scratch directory: {}", e);
+                return Ok(tokio::spawn(async move {
+                    JobResult::new(job_id, JobStatus::Failed).with_exit(None, Some(message))
+                }));
+            }
+        };
+        // a script sent along with the job is run from its scratch directory
+        if let Some(contents) = &job.script_contents {
+            match scratch.write_script(&pth, contents).await {
+                Ok(path) => pth = path.to_string_lossy().into_owned(),
+                Err(e) => {
+                    let message =
+                        format!("Could not write the script to the scratch directory: {}", e);
+                    return Ok(self.fail_before_start(job_id, message).await);
+                }
+            }
+        }
+        if let Some(user) = &job_user {
+            if let Err(e) = hand_over(scratch.path(), user) {
+                let message = format!(
+                    "Could not hand the scratch directory to {}: {}",
+                    user.name, e
+                );
+                return Ok(self.fail_before_start(job_id, message).await);
+            }
+        }
+
+        let allocated_mask = if let Some((parent_id, _)) = &step_of {
+            // a parent that shares the cores of other jobs has no mask of its own
+            let parent_mask = self.job_masks.get(parent_id).map(|mask| mask.clone());
+            match parent_mask {
+                Some(mask) => Some(mask),
+                None => self.core_mask.lock().await.share(cores_needed),
+            }
+        } else {
             let mut core_mask = self.core_mask.lock().await;
-            core_mask.allocate(cores_needed).ok_or_else(|| {
-                log!(error, "Resources are exhausted!");
-                tonic::Status::resource_exhausted("Not enough cores available")
-            })?
+            match core_mask.allocate(cores_needed) {
+                Some(mask) => {
+                    self.job_masks.insert(job_id, mask.clone());
+                    Some(mask)
+                }
+                // the scheduler overcommits CPUs, the job runs on cores of other jobs
+                None => core_mask.share(cores_needed).inspect(|mask| {
+                    log!(info, "Job {} shares cores {}", job_id, mask);
+                }),
+            }
+        };
+        let Some(allocated_mask) = allocated_mask else {
+            log!(error, "Resources are exhausted!");
+            // the scratch directory is removed on drop
+            self.deadline_notifiers.remove(&job_id);
+            self.cancel_notifiers.remove(&job_id);
+            return Err(WorkerError::ResourcesExhausted(
+                "Not enough cores available".into(),
+            ));
+        };
+        let (step_guard, steps_done) = match step_of {
+            Some((parent_id, steps_open)) => {
+                self.step_parents.insert(job_id, parent_id);
+                (Some(steps_open), None)
+            }
+            None => {
+                let (steps_open, steps_done) = mpsc::channel::<()>(1);
+                self.job_steps.insert(job_id, steps_open);
+                (None, Some(steps_done))
+            }
         };
-        // store allocated mask
-        self.job_masks.insert(job_id, allocated_mask);
+
+        // an interactive job starts once its client attached
+        let attach_rx = job.interactive.then(|| {
+            let (tx, rx) = oneshot::channel();
+            self.attach_sessions.insert(
+                job_id,
+                PendingAttach {
+                    token: job.attach_token.clone(),
+                    session: tx,
+                },
+            );
+            rx
+        });
+        let attach_sessions = self.attach_sessions.clone();
 
         let core_mask = self.core_mask.clone();
         let job_masks = self.job_masks.clone();
+        let output_cap = self.output_cap.clone();
+        let (job_steps, step_parents, cancel_notifiers) = (
+            self.job_steps.clone(),
+            self.step_parents.clone(),
+            self.cancel_notifiers.clone(),
+        );
+        #[cfg(feature = "cgroups")]
+        let (job_cgroups, step_parent) = (self.job_cgroups.clone(), job.parent_job_id);
+        let mut shutdown_rx = self.job_shutdown.subscribe();
+        #[cfg(feature = "cgroups")]
+        let (cgroup_path, swap_limit, cpu_weight) =
+            (self.cgroup_path.clone(), self.swap_limit, self.cpu_weight);
         let handle = tokio::spawn(async move {
             let span = tracing::span!(tracing::Level::INFO, "Spawn jobs result listener");
             let _guard = span.enter();
@@ -349,137 +890,486 @@ impl Worker {
             // let cgroup = Arc::new(Mutex::new(None));
             // let cgroup_clone = Arc::clone(&cgroup);
 
-            let mut child = match Command::new(&pth)
+            let session = match attach_rx {
+                Some(attach_rx) => {
+                    let attached = tokio::select! {
+                        Ok(session) = attach_rx => Ok(session),
+                        _ = tokio::time::sleep(ATTACH_TIMEOUT) => {
+                            let message = format!("No client attached within {} seconds", ATTACH_TIMEOUT.as_secs());
+                            Err(JobResult::new(job_id, JobStatus::Failed).with_exit(None, Some(message)))
+                        },
+                        Some(()) = cancel_rx.recv() => Err(JobResult::new(job_id, JobStatus::Cancelled)),
+                        Ok(()) = shutdown_rx.changed() => {
+                            let message = "Worker shut down before a client attached".to_string();
+                            Err(JobResult::new(job_id, JobStatus::Failed).with_exit(None, Some(message)))
+                        },
+                    };
+                    attach_sessions.remove(&job_id);
+                    match attached {
+                        Ok(session) => Some(session),
+                        Err(result) => {
+                            log!(info, "Interactive job {} ends before it started", job_id);
+                            free_cores(&job_masks, &core_mask, job_id).await;
+                            return result;
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            let mut command = script_command(&pth);
+            command
                 .args(&args)
+                .envs(&env)
+                .env("MELON_JOB_ID", job_id.to_string())
+                .env("MELON_CPUS", cores_needed.to_string())
+                .env(SCRATCH_ENV, scratch.path())
                 .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-            {
+                .stderr(Stdio::piped());
+            if let Some(dir) = &work_dir {
+                command.current_dir(dir);
+            }
+            let terminal = match session.as_ref().and_then(|session| session.terminal) {
+                Some((rows, cols)) => match attach_terminal(&mut command, rows, cols) {
+                    Ok(terminal) => Some(terminal),
+                    Err(e) => {
+                        log!(
+                            warn,
+                            "Could not open a terminal for job {}, use pipes: {}",
+                            job_id,
+                            e
+                        );
+                        None
+                    }
+                },
+                None => None,
+            };
+            if session.is_some() && terminal.is_none() {
+                command.stdin(Stdio::piped());
+            }
+            if let Some(user) = &job_user {
+                run_as(&mut command, user);
+            }
+            // cgroups pin the job to its cores through the cpuset controller instead
+            #[cfg(not(feature = "cgroups"))]
+            if let Err(e) = pin_to_cores(&mut command, &allocated_mask) {
+                log!(
+                    warn,
+                    "Could not pin job {} to cores {}: {}",
+                    job_id,
+                    allocated_mask,
+                    e
+                );
+            }
+
+            let mut child = match command.spawn() {
                 Ok(child) => child,
                 Err(e) => {
                     log!(error, "Could not spawn command {}", e);
-                    return JobResult::new(job_id, JobStatus::Failed);
+                    free_cores(&job_masks, &core_mask, job_id).await;
+                    let message = format!("Could not start {}: {}", pth, e);
+                    return JobResult::new(job_id, JobStatus::Failed)
+                        .with_exit(None, Some(message));
                 }
             };
+            // our copies of a terminal would keep its output open after the process exited
+            drop(command);
 
-            #[cfg(feature = "cgroups")]
             let child_pid = match child.id() {
                 Some(id) => id,
-                None => return JobResult::new(job_id, JobStatus::Failed),
+                None => {
+                    free_cores(&job_masks, &core_mask, job_id).await;
+                    let message = "Job process exited before it could be tracked".to_string();
+                    return JobResult::new(job_id, JobStatus::Failed)
+                        .with_exit(None, Some(message));
+                }
             };
 
             #[cfg(feature = "cgroups")]
             let core_string = CoreMask::mask_to_string(allocated_mask);
 
+            // a step joins the cgroup of its parent
             #[cfg(feature = "cgroups")]
-            let cgroup = match CGroups::build()
-                .name(&format!("melon_{}", child_pid))
-                .with_cpu(&core_string)
-                .with_memory(resources.memory)
-                .build()
-            {
-                Ok(group) => group,
-                Err(e) => {
-                    log!(
-                        error,
-                        "Could not build cgroup for job {} on process id {} due to error {}",
-                        job_id,
-                        child_pid,
-                        e.to_string()
-                    );
-                    return JobResult::new(job_id, JobStatus::Failed);
+            let parent_cgroup = step_parent
+                .and_then(|parent_id| job_cgroups.get(&parent_id).map(|cgroup| cgroup.clone()));
+            #[cfg(feature = "cgroups")]
+            let cgroup = match parent_cgroup {
+                Some(cgroup) => cgroup,
+                None => {
+                    let mut builder = CGroups::build()
+                        .name(&format!("melon_{}", child_pid))
+                        .with_base_path(cgroup_path)
+                        // processes the job left behind would keep the cgroup alive
+                        .with_force_remove(true)
+                        .with_cpu(&core_string)
+                        .with_memory(resources.memory);
+                    if let Some(swap) = swap_limit {
+                        builder = builder.with_swap(swap);
+                    }
+                    if let Some(weight) = cpu_weight {
+                        builder = builder.with_cpu_weight(weight);
+                    }
+
+                    let cgroup = match builder.build() {
+                        Ok(group) => group,
+                        Err(e) => {
+                            log!(
+                                error,
+                                "Could not build cgroup for job {} on process id {} due to error {}",
+                                job_id,
+                                child_pid,
+                                e.to_string()
+                            );
+                            // the job must not run without its limits
+                            let _ = child.kill().await;
+                            free_cores(&job_masks, &core_mask, job_id).await;
+                            let message = format!("Could not set up the job's cgroup: {}", e);
+                            return JobResult::new(job_id, JobStatus::Failed)
+                                .with_exit(None, Some(message));
+                        }
+                    };
+
+                    if let Err(e) = cgroup.create() {
+                        log!(
+                            error,
+                            "Could not create cgroup for job {} on process id {} due to error {}",
+                            job_id,
+                            child_pid,
+                            e.to_string()
+                        );
+                        let _ = child.kill().await;
+                        free_cores(&job_masks, &core_mask, job_id).await;
+                        let message = format!("Could not create the job's cgroup: {}", e);
+                        return JobResult::new(job_id, JobStatus::Failed)
+                            .with_exit(None, Some(message));
+                    }
+                    Arc::new(cgroup)
                 }
             };
 
             #[cfg(feature = "cgroups")]
-            if let Err(e) = cgroup.create() {
+            if let Err(e) = cgroup.add_process(child_pid) {
                 log!(
                     error,
-                    "Could not create cgroup for job {} on process id {} due to error {}",
-                    job_id,
+                    "Could not add process id {} of job {} to cgroup due to error {}",
                     child_pid,
+                    job_id,
                     e.to_string()
                 );
-                return JobResult::new(job_id, JobStatus::Failed);
+                let _ = child.kill().await;
+                free_cores(&job_masks, &core_mask, job_id).await;
+                let message = format!("Could not add the job to its cgroup: {}", e);
+                return JobResult::new(job_id, JobStatus::Failed).with_exit(None, Some(message));
+            }
+            #[cfg(feature = "cgroups")]
+            if step_parent.is_none() {
+                job_cgroups.insert(job_id, cgroup.clone());
             }
 
-            let mut deadline = Instant::now() + Duration::from_secs(initial_time_mins * 60);
-            let mut stdout = BufReader::new(child.stdout.take().unwrap());
-            let mut stderr = BufReader::new(child.stderr.take().unwrap());
+            // without cgroups the worker has to measure and limit the memory usage itself
+            let memory_limit = resources.memory;
+            let sample_usage = !cfg!(feature = "cgroups");
+            let mut memory_poll = interval(MEMORY_POLL_INTERVAL);
+            let mut system = System::new();
+            let mut usage = UsageSampler::default();
 
-            let mut stdout_buf = String::new();
-            let mut stderr_buf = String::new();
+            let started = Instant::now();
+            let mut time_limit = Duration::from_secs(initial_time_mins * 60);
+            let mut deadline = started + time_limit;
+            // the warning is sent once, until then extensions move it along with the deadline
+            let mut warn_at = warn_signal
+                .as_ref()
+                .map(|warn_signal| started + warn_signal.due_after(time_limit));
 
-            loop {
+            // stream the output to the log files, or to the client of an interactive job,
+            // while the process is running
+            let (disconnect_tx, mut disconnect_rx) = mpsc::channel::<()>(1);
+            let (captures, attached) = match session {
+                None => {
+                    let captures = vec![
+                        spawn_output_capture(
+                            child.stdout.take().unwrap(),
+                            stdout_path,
+                            output_cap.clone(),
+                        ),
+                        spawn_output_capture(
+                            child.stderr.take().unwrap(),
+                            stderr_path.clone(),
+                            output_cap,
+                        ),
+                    ];
+                    (captures, None)
+                }
+                Some(AttachSession { input, output, .. }) => {
+                    let (captures, input) = match terminal {
+                        Some((reader, writer)) => (
+                            vec![spawn_output_relay(
+                                tokio::fs::File::from_std(reader),
+                                OutputStream::Stdout,
+                                output.clone(),
+                            )],
+                            spawn_input_relay(
+                                input,
+                                Some(tokio::fs::File::from_std(writer)),
+                                output.clone(),
+                                disconnect_tx,
+                            ),
+                        ),
+                        None => (
+                            vec![
+                                spawn_output_relay(
+                                    child.stdout.take().unwrap(),
+                                    OutputStream::Stdout,
+                                    output.clone(),
+                                ),
+                                spawn_output_relay(
+                                    child.stderr.take().unwrap(),
+                                    OutputStream::Stderr,
+                                    output.clone(),
+                                ),
+                            ],
+                            spawn_input_relay(
+                                input,
+                                child.stdin.take(),
+                                output.clone(),
+                                disconnect_tx,
+                            ),
+                        ),
+                    };
+                    (captures, Some((output, input)))
+                }
+            };
+
+            let mut exit_code = None;
+            let mut failed_exit = None;
+            let mut message = None;
+            let status = loop {
                 tokio::select! {
                     status_result = child.wait() => {
                         log!(info, "Got child result!");
-                        // read the segments
-                        stdout.read_to_string(&mut stdout_buf).await.unwrap_or_else(|e| {
-                            log!(error, "Failed to read stdout: {}", e);
-                            0
-                        });
-                        stderr.read_to_string(&mut stderr_buf).await.unwrap_or_else(|e| {
-                            log!(error, "Failed to read stderr: {}", e);
-                            0
-                        });
-
-
-                        {
-                            // free up core mask
-                            if let Some((_, mask)) = job_masks.remove(&job_id) {
-                                let mut core_mask = core_mask.lock().await;
-                                core_mask.free(mask);
-                            }
-                        }
-
+                        exit_code = status_result.as_ref().ok().and_then(|status| status.code());
                         match status_result {
+                            Ok(status) if status.success() => {
+                                log!(info, "Job was a success");
+                                break JobStatus::Completed;
+                            }
                             Ok(status) => {
-                                if status.success() {
-                                    // capture the output
-                                    log!(info, "Job was a success");
-                                    return JobResult::new(job_id, JobStatus::Completed);
-                                } else {
-                                    // capture error output
-                                    let error_msg = format!("Process exited with status: {}. Stderr: {}", status, stderr_buf);
-                                    log!(info, "Job was not successfull: {}", error_msg);
-                                    return JobResult::new(job_id, JobStatus::Failed);
+                                #[cfg(feature = "cgroups")]
+                                if cgroup.oom_kill_count().unwrap_or(0) > 0 {
+                                    log!(info, "Job {} was killed for exceeding its memory", job_id);
+                                    message = Some(format!("Job exceeded its memory limit of {} bytes", memory_limit));
+                                    break JobStatus::OutOfMemory;
                                 }
-                            },
-                            Err(_) => {
+                                failed_exit = Some(status);
+                                break JobStatus::Failed;
+                            }
+                            Err(e) => {
                                 log!(error, "Something wrong with the result!");
-                                return JobResult::new(job_id, JobStatus::Failed);
+                                message = Some(format!("Could not wait for the job process: {}", e));
+                                break JobStatus::Failed;
+                            }
+                        }
+                    },
+                    _ = tokio::time::sleep_until(warn_at.unwrap_or(deadline)), if warn_at.is_some() => {
+                        warn_at = None;
+                        if let Some(warn_signal) = &warn_signal {
+                            log!(info, "Job {} used {}% of its time, send SIG{}", job_id, warn_signal.percent, warn_signal.signal);
+                            if let Err(e) = send_signal(child_pid, &warn_signal.signal) {
+                                log!(warn, "Could not send SIG{} to job {}: {}", warn_signal.signal, job_id, e);
                             }
                         }
                     },
                     _ = tokio::time::sleep_until(deadline) => {
                         log!(info, "Deadline hit! Start cancel");
                         // reached timeout deadline
-                        if let Err(e) = child.kill().await {
-                            log!(error, "Failed to kill process: {}", e);
+                        terminate_process(&mut child, child_pid, grace_period).await;
+                        message = Some("Job reached its time limit".to_string());
+                        break JobStatus::Timeout;
+                    },
+                    Some(()) = cancel_rx.recv() => {
+                        log!(info, "Cancel job {}", job_id);
+                        terminate_process(&mut child, child_pid, grace_period).await;
+                        break JobStatus::Cancelled;
+                    },
+                    _ = memory_poll.tick(), if sample_usage => {
+                        system.refresh_processes();
+                        let used = usage.sample(&system, Pid::from_u32(child_pid), MEMORY_POLL_INTERVAL);
+                        if memory_limit > 0 && used > memory_limit {
+                            log!(info, "Job {} uses {} bytes of memory, exceeding its limit of {}", job_id, used, memory_limit);
+                            if let Err(e) = child.kill().await {
+                                log!(error, "Failed to kill process: {}", e);
+                            }
+                            message = Some(format!("Job used {} bytes of memory, exceeding its limit of {}", used, memory_limit));
+                            break JobStatus::OutOfMemory;
                         }
-                        return JobResult::new(job_id, JobStatus::Timeout);
+                    },
+                    Some(()) = disconnect_rx.recv(), if attached.is_some() => {
+                        log!(info, "Client of job {} is gone, terminate it", job_id);
+                        terminate_process(&mut child, child_pid, grace_period).await;
+                        message = Some("Client disconnected".to_string());
+                        break JobStatus::Cancelled;
+                    },
+                    Ok(()) = shutdown_rx.changed() => {
+                        log!(info, "Worker shuts down, terminate job {}", job_id);
+                        terminate_process(&mut child, child_pid, grace_period).await;
+                        message = Some("Worker shut down while the job was running".to_string());
+                        break JobStatus::Failed;
                     },
                     Some(extension) = rx.recv() => {
                         // extend the deadline
                         log!(info, "Receive deadline extension for job by {} minutes", extension.as_secs() / 60);
                         deadline += extension;
+                        time_limit += extension;
+                        if let (Some(warn_signal), Some(at)) = (&warn_signal, warn_at.as_mut()) {
+                            *at = started + warn_signal.due_after(time_limit);
+                        }
+                    }
+                }
+            };
+
+            for capture in captures {
+                finish_output_capture(capture, job_id).await;
+            }
+            if let Some((output, input)) = attached {
+                input.abort();
+                let exited = proto::AttachOutput {
+                    exited: true,
+                    exit_code,
+                    ..Default::default()
+                };
+                let _ = output.send(Ok(exited)).await;
+            }
+
+            // the job keeps its allocation until its steps are done, they end early
+            // if the job didn't end on its own
+            if let Some(mut steps_done) = steps_done {
+                if matches!(
+                    status,
+                    JobStatus::Cancelled | JobStatus::Timeout | JobStatus::OutOfMemory
+                ) {
+                    let steps: Vec<_> = step_parents
+                        .iter()
+                        .filter(|entry| *entry.value() == job_id)
+                        .filter_map(|entry| cancel_notifiers.get(entry.key()).map(|tx| tx.clone()))
+                        .collect();
+                    for cancel_tx in steps {
+                        let _ = cancel_tx.send(()).await;
                     }
                 }
+                job_steps.remove(&job_id);
+                // nothing is ever sent, the channel closes once the last step dropped its sender
+                let _ = steps_done.recv().await;
+                #[cfg(feature = "cgroups")]
+                job_cgroups.remove(&job_id);
             }
+            drop(step_guard);
+
+            free_cores(&job_masks, &core_mask, job_id).await;
+            scratch.remove().await;
+
+            if let Some(exit) = failed_exit {
+                // the end of the error output, the whole of it can be huge
+                let stderr = read_output_tail(&stderr_path, ERROR_TAIL_BYTES)
+                    .await
+                    .unwrap_or_default();
+                let stderr = String::from_utf8_lossy(&stderr);
+                let error_msg = if stderr.trim().is_empty() {
+                    format!("Process ended with {}", exit)
+                } else {
+                    format!(
+                        "Process ended with {}, stderr:\n{}",
+                        exit,
+                        stderr.trim_end()
+                    )
+                };
+                log!(info, "Job was not successfull: {}", error_msg);
+                message = Some(error_msg);
+            }
+
+            // the usage of a step is part of its parent's cgroup
+            #[cfg(feature = "cgroups")]
+            let (max_rss_bytes, cpu_seconds) = if step_parent.is_some() {
+                (None, None)
+            } else {
+                (
+                    cgroup.memory_peak().ok(),
+                    cgroup
+                        .cpu_usage_usec()
+                        .ok()
+                        .map(|usec| usec as f64 / 1_000_000.0),
+                )
+            };
+            // the forced removal on drop waits for the processes left in the cgroup to die
+            #[cfg(feature = "cgroups")]
+            tokio::task::spawn_blocking(move || drop(cgroup));
+            #[cfg(not(feature = "cgroups"))]
+            let (max_rss_bytes, cpu_seconds) = (usage.max_rss_bytes(), usage.cpu_seconds());
+
+            JobResult::new(job_id, status)
+                .with_usage(max_rss_bytes, cpu_seconds)
+                .with_exit(exit_code, message)
         });
 
         Ok(handle)
     }
 }
 
+/// Returns the cores of a job to the mask.
+///
+/// Steps and jobs that share the cores of others have no mask of their own to return.
+async fn free_cores(job_masks: &DashMap<u64, CpuSet>, core_mask: &Mutex<CoreMask>, job_id: u64) {
+    if let Some((_, mask)) = job_masks.remove(&job_id) {
+        core_mask.lock().await.free(mask);
+    }
+}
+
+/// Sends a signal given by its name without the `SIG` prefix, e.g. `USR1`.
+#[cfg(target_os = "linux")]
+fn send_signal(pid: u32, name: &str) -> Result<(), String> {
+    use nix::sys::signal::{kill, Signal};
+    use std::str::FromStr;
+
+    let signal = Signal::from_str(&format!("SIG{}", name)).map_err(|e| e.to_string())?;
+    kill(nix::unistd::Pid::from_raw(pid as i32), signal).map_err(|e| e.to_string())
+}
+
+/// Signals by name need `nix`, which is only used on Linux.
+#[cfg(not(target_os = "linux"))]
+fn send_signal(_pid: u32, _name: &str) -> Result<(), String> {
+    Err("Signals are only supported on Linux".to_string())
+}
+
+/// Asks a process to exit with SIGTERM and kills it once the grace period is over.
+///
+/// Without a grace period the process is killed right away.
+async fn terminate_process(child: &mut Child, pid: u32, grace: Duration) {
+    if !grace.is_zero() {
+        // SAFETY: kill only sends a signal, the pid belongs to our own child that wasn't reaped yet
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGTERM);
+        }
+        if tokio::time::timeout(grace, child.wait()).await.is_ok() {
+            return;
+        }
+        log!(warn, "Process {} ignored SIGTERM, killing it", pid);
+    }
+    if let Err(e) = child.kill().await {
+        log!(error, "Failed to kill process: {}", e);
+    }
+}
+
 fn get_node_resources() -> NodeResources {
     let mut system = System::new_all();
     system.refresh_all();
 
     let cpu_count = system.cpus().len() as u32;
     let memory = system.total_memory() * 1024;
-    NodeResources { cpu_count, memory }
+    let gpu_count = gpu::detect_gpu_count();
+    NodeResources {
+        cpu_count,
+        memory,
+        gpu_count,
+    }
 }
 
 #[tonic::async_trait]
@@ -490,10 +1380,20 @@ impl MelonWorker for Worker {
         &self,
         request: tonic::Request<proto::JobAssignment>,
     ) -> Result<tonic::Response<()>, tonic::Status> {
-        let handle = self
-            .spawn_job(request.get_ref())
-            .await
-            .expect("Could not spawn job task");
+        self.check_token(&request)?;
+        // the scheduler tries another node, steps don't take a job slot
+        let is_step = request.get_ref().parent_job_id.is_some();
+        if let Some(max_jobs) = self.max_jobs.filter(|_| !is_step) {
+            if self.active_jobs() >= max_jobs as usize {
+                return Err(tonic::Status::resource_exhausted(format!(
+                    "Already running {} jobs",
+                    max_jobs
+                )));
+            }
+        }
+
+        // the scheduler keeps the job pending and tries another node
+        let handle = self.spawn_job(request.get_ref()).await?;
         self.running_jobs.insert(request.get_ref().job_id, handle);
 
         let res = tonic::Response::new(());
@@ -505,12 +1405,20 @@ impl MelonWorker for Worker {
         &self,
         request: tonic::Request<proto::CancelJobRequest>,
     ) -> Result<tonic::Response<()>, tonic::Status> {
+        self.check_token(&request)?;
         let req = request.get_ref();
         let id = req.job_id;
         if let Some((_, handle)) = self.running_jobs.remove(&id) {
-            // if job is not finished, cancel the job first
-            if !handle.is_finished() {
-                handle.abort();
+            self.deadline_notifiers.remove(&id);
+            self.job_steps.remove(&id);
+            self.step_parents.remove(&id);
+
+            // if job is not finished, let it terminate its process within the grace period,
+            // the job task frees its cores once the process is gone
+            if let Some((_, cancel_tx)) = self.cancel_notifiers.remove(&id) {
+                if !handle.is_finished() && cancel_tx.send(()).await.is_ok() {
+                    return Ok(tonic::Response::new(()));
+                }
             }
 
             // free the cores
@@ -523,11 +1431,116 @@ impl MelonWorker for Worker {
 
         Err(tonic::Status::not_found("Not found!"))
     }
+    type GetJobOutputStream = ReceiverStream<Result<proto::JobOutput, tonic::Status>>;
+
+    #[tracing::instrument(level = "info", name = "Get job output request" skip(self,request))]
+    async fn get_job_output(
+        &self,
+        request: tonic::Request<proto::JobOutputRequest>,
+    ) -> Result<tonic::Response<Self::GetJobOutputStream>, tonic::Status> {
+        self.check_token(&request)?;
+        let req = request.get_ref();
+        let path = output_path(&self.log_dir, req.job_id, req.stream());
+        let truncated_bytes = self.output_cap.truncated.get(&path).map_or(0, |cut| *cut);
+
+        let mut reader = match OutputReader::open(&path, req.offset, req.tail_bytes).await {
+            Ok(reader) => reader,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(tonic::Status::not_found(format!(
+                    "No output found for job {}",
+                    req.job_id
+                )));
+            }
+            Err(e) => {
+                return Err(tonic::Status::internal(format!(
+                    "Could not read job output: {}",
+                    e
+                )));
+            }
+        };
+
+        // chunks are only read as fast as the client takes them
+        let (tx, rx) = mpsc::channel(OUTPUT_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                let frame = match reader.next_chunk().await {
+                    Ok(Some((offset, content))) => Ok(proto::JobOutput {
+                        content,
+                        offset,
+                        truncated_bytes,
+                    }),
+                    Ok(None) => break,
+                    Err(e) => Err(tonic::Status::internal(format!(
+                        "Could not read job output: {}",
+                        e
+                    ))),
+                };
+                let failed = frame.is_err();
+                // the client may have gone away
+                if tx.send(frame).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(tonic::Response::new(ReceiverStream::new(rx)))
+    }
+
+    type AttachStream = ReceiverStream<Result<proto::AttachOutput, tonic::Status>>;
+
+    /// Connects a client to the process of an interactive job that waits for it
+    ///
+    /// Clients attach directly, the attach token of the job takes the place of the worker token.
+    #[tracing::instrument(level = "info", name = "Get attach request" skip(self,request))]
+    async fn attach(
+        &self,
+        request: tonic::Request<tonic::Streaming<proto::AttachInput>>,
+    ) -> Result<tonic::Response<Self::AttachStream>, tonic::Status> {
+        let mut input = request.into_inner();
+        let first = input
+            .message()
+            .await?
+            .ok_or_else(|| tonic::Status::invalid_argument("Missing the job to attach to"))?;
+        let job_id = first.job_id;
+
+        if !self.attach_sessions.contains_key(&job_id) {
+            return Err(tonic::Status::not_found(format!(
+                "Job {} does not wait for a client",
+                job_id
+            )));
+        }
+        // a wrong token leaves the job waiting for its client
+        let Some((_, pending)) = self
+            .attach_sessions
+            .remove_if(&job_id, |_, pending| pending.token == first.attach_token)
+        else {
+            return Err(tonic::Status::permission_denied(format!(
+                "Wrong attach token for job {}",
+                job_id
+            )));
+        };
+
+        let (tx, rx) = mpsc::channel(OUTPUT_BUFFER);
+        let clamp = |value: u32| value.min(u16::MAX as u32) as u16;
+        let session = AttachSession {
+            input,
+            output: tx,
+            terminal: first.tty.then(|| (clamp(first.rows), clamp(first.cols))),
+        };
+        if pending.session.send(session).is_err() {
+            return Err(tonic::Status::not_found(format!(
+                "Job {} no longer waits for a client",
+                job_id
+            )));
+        }
+        Ok(tonic::Response::new(ReceiverStream::new(rx)))
+    }
+
     #[tracing::instrument(level = "info", name = "Get job extension request" skip(self,request))]
     async fn extend_job(
         &self,
         request: tonic::Request<proto::ExtendJobRequest>,
     ) -> Result<tonic::Response<()>, tonic::Status> {
+        self.check_token(&request)?;
         let req = request.get_ref();
         let id = req.job_id;
         let time_in_mins = req.extension_mins;
@@ -547,3 +1560,395 @@ impl MelonWorker for Worker {
         }
     }
 }
+
+/// Builds the command that starts a job script.
+///
+/// Scripts without an execute bit are started through the interpreter of their `#!` line.
+fn script_command(path: &str) -> Command {
+    let interpreter = match is_executable(Path::new(path)) {
+        Ok(false) => read_shebang(Path::new(path)).ok().flatten(),
+        _ => None,
+    };
+    match interpreter {
+        Some(interpreter) => {
+            let mut command = Command::new(&interpreter[0]);
+            command.args(&interpreter[1..]).arg(path);
+            command
+        }
+        None => Command::new(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::scratch_path;
+    use clap::Parser;
+    use melon_common::proto::melon_worker_client::MelonWorkerClient;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_terminate_process_within_grace_period() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id().unwrap();
+
+        terminate_process(&mut child, pid, Duration::from_secs(5)).await;
+
+        let status = child
+            .try_wait()
+            .unwrap()
+            .expect("process should have exited");
+        assert!(!status.success());
+    }
+
+    #[tokio::test]
+    async fn test_kill_process_that_ignores_sigterm() {
+        let mut child = Command::new("sh")
+            .args(["-c", "trap '' TERM; sleep 30"])
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        // give the shell time to install the trap
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let start = Instant::now();
+        terminate_process(&mut child, pid, Duration::from_millis(300)).await;
+
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert!(child.wait().await.is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_send_warning_signal() {
+        let mut child = Command::new("sh")
+            .args(["-c", "trap 'exit 7' USR1; sleep 30 & wait"])
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        // give the shell time to install the trap
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        send_signal(pid, "USR1").unwrap();
+
+        let status = tokio::time::timeout(Duration::from_secs(5), child.wait())
+            .await
+            .expect("process should have exited")
+            .unwrap();
+        assert_eq!(status.code(), Some(7));
+        assert!(send_signal(pid, "NOPE").is_err());
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    /// Arguments of a worker that keeps its logs and scratch directories in `dir`.
+    fn test_args(dir: &TempDir, extra_args: &[&str]) -> Args {
+        let logs = dir.path().join("logs");
+        let scratch = dir.path().join("scratch");
+        let mut args = vec![
+            "mworker",
+            "--advertise_addr",
+            "127.0.0.1",
+            "--log_dir",
+            logs.to_str().unwrap(),
+            "--scratch_dir",
+            scratch.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra_args);
+        Args::parse_from(args)
+    }
+
+    /// Sets up a worker that keeps its logs and scratch directories in `dir`.
+    fn test_worker(dir: &TempDir, extra_args: &[&str]) -> Worker {
+        Worker::new(&test_args(dir, extra_args)).unwrap()
+    }
+
+    /// Connects to the server of a worker once it is up.
+    async fn connect_when_up(port: u16) -> MelonWorkerClient<tonic::transport::Channel> {
+        let endpoint = format!("http://127.0.0.1:{}", port);
+        for _ in 0..50 {
+            if let Ok(client) = MelonWorkerClient::connect(endpoint.clone()).await {
+                return client;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Worker server did not come up");
+    }
+
+    #[tokio::test]
+    async fn test_assign_job_beyond_max_jobs_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let worker = test_worker(&dir, &["--max_jobs", "1"]);
+        let job = tokio::spawn(async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            JobResult::new(1, JobStatus::Completed)
+        });
+        worker.running_jobs.insert(1, job);
+
+        let status = worker
+            .assign_job(tonic::Request::new(proto::JobAssignment {
+                job_id: 2,
+                script_path: "/bin/true".to_string(),
+                req_res: Some(proto::RequestedResources {
+                    cpu_count: 1,
+                    memory: 1024,
+                    time: 1,
+                    gpu_count: 0,
+                    node_count: 1,
+                }),
+                ..Default::default()
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(worker.current_usage().await.active_jobs, 1);
+    }
+
+    #[tokio::test]
+    async fn test_script_sent_along_runs_from_the_scratch_directory() {
+        let dir = TempDir::new().unwrap();
+        let worker = test_worker(&dir, &[]);
+
+        let handle = worker
+            .spawn_job(&proto::JobAssignment {
+                job_id: 1,
+                script_path: "wrap".to_string(),
+                script_contents: Some(b"#!/bin/sh\necho \"$0\"\n".to_vec()),
+                req_res: Some(proto::RequestedResources {
+                    cpu_count: 1,
+                    memory: 1024 * 1024 * 1024,
+                    time: 1,
+                    gpu_count: 0,
+                    node_count: 1,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(handle.await.unwrap().status, JobStatus::Completed);
+        let stdout_path = output_path(&dir.path().join("logs"), 1, OutputStream::Stdout);
+        let script = scratch_path(&dir.path().join("scratch"), 1).join("wrap");
+        assert_eq!(
+            std::fs::read_to_string(stdout_path).unwrap(),
+            format!("{}\n", script.display())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_worker_token_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let mut worker = test_worker(&dir, &[]);
+        worker.token = Some("secret".to_string());
+        let cancel = |token: Option<&str>| {
+            let mut request = tonic::Request::new(proto::CancelJobRequest {
+                job_id: 1,
+                user: "alice".to_string(),
+            });
+            if let Some(token) = token {
+                let value = format!("Bearer {}", token).parse().unwrap();
+                request.metadata_mut().insert("authorization", value);
+            }
+            request
+        };
+
+        for token in [None, Some("guessed")] {
+            let status = worker.cancel_job(cancel(token)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        }
+        let status = worker
+            .assign_job(tonic::Request::new(proto::JobAssignment {
+                job_id: 1,
+                script_path: "/bin/true".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert!(worker.running_jobs.is_empty());
+
+        // the scheduler's requests get through
+        let status = worker.cancel_job(cancel(Some("secret"))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_run_as_user_needs_a_worker_token() {
+        // without root, the worker is refused for that already
+        // SAFETY: geteuid has no preconditions and can't fail
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        let err = Worker::new(&test_args(&dir, &["--run_as_user"])).unwrap_err();
+        assert!(err.to_string().contains(auth::WORKER_TOKEN_ENV), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_failed_start_frees_the_cores() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let worker = test_worker(&dir, &[]);
+        // not executable and without a shebang, so the process can't be spawned
+        let script = dir.path().join("job.sh");
+        std::fs::write(&script, "echo hi\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let handle = worker
+            .spawn_job(&proto::JobAssignment {
+                job_id: 1,
+                script_path: script.to_str().unwrap().to_string(),
+                req_res: Some(proto::RequestedResources {
+                    cpu_count: 1,
+                    memory: 1024 * 1024 * 1024,
+                    time: 1,
+                    gpu_count: 0,
+                    node_count: 1,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(handle.await.unwrap().status, JobStatus::Failed);
+        assert!(worker.job_masks.is_empty());
+        assert!(worker
+            .core_mask
+            .lock()
+            .await
+            .get_allocated_cores()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_assign_job_without_free_cores_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let worker = test_worker(&dir, &[]);
+        let assignment = |req_res| proto::JobAssignment {
+            job_id: 1,
+            script_path: "/bin/true".to_string(),
+            req_res,
+            ..Default::default()
+        };
+
+        let status = worker
+            .assign_job(tonic::Request::new(assignment(Some(
+                proto::RequestedResources {
+                    cpu_count: u32::MAX,
+                    memory: 1024,
+                    time: 1,
+                    gpu_count: 0,
+                    node_count: 1,
+                },
+            ))))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(worker.running_jobs.is_empty());
+        assert!(worker.deadline_notifiers.is_empty());
+        assert!(worker.cancel_notifiers.is_empty());
+        assert!(!scratch_path(&dir.path().join("scratch"), 1).exists());
+
+        let status = worker
+            .assign_job(tonic::Request::new(assignment(None)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_attach_to_interactive_job() {
+        let dir = TempDir::new().unwrap();
+        let port = free_port();
+        let mut worker = test_worker(&dir, &["--port", &port.to_string(), "--bind", "127.0.0.1"]);
+        worker
+            .assign_job(tonic::Request::new(proto::JobAssignment {
+                job_id: 1,
+                script_path: "/bin/cat".to_string(),
+                req_res: Some(proto::RequestedResources {
+                    cpu_count: 1,
+                    memory: 1024,
+                    time: 1,
+                    gpu_count: 0,
+                    node_count: 1,
+                }),
+                interactive: true,
+                attach_token: "secret".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        let server_notifier = worker.server_notifier.clone();
+        let server = tokio::spawn(async move { worker.start_server().await.unwrap() });
+        let mut client = connect_when_up(port).await;
+        let attach = |token: &str, stdin: &[u8]| {
+            let first = proto::AttachInput {
+                job_id: 1,
+                attach_token: token.to_string(),
+                ..Default::default()
+            };
+            let input = proto::AttachInput {
+                stdin: stdin.to_vec(),
+                stdin_closed: true,
+                ..Default::default()
+            };
+            tokio_stream::iter(vec![first, input])
+        };
+
+        let status = client.attach(attach("wrong", b"")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        // the job still waits for its client
+        let mut output = client
+            .attach(attach("secret", b"hello\n"))
+            .await
+            .unwrap()
+            .into_inner();
+        let mut stdout = Vec::new();
+        let mut exit = None;
+        while let Some(message) = output.message().await.unwrap() {
+            if message.exited {
+                exit = Some(message.exit_code);
+            }
+            stdout.extend(message.content);
+        }
+        assert_eq!(stdout, b"hello\n");
+        assert_eq!(exit, Some(Some(0)));
+
+        server_notifier.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_listens_on_all_interfaces() {
+        let dir = TempDir::new().unwrap();
+        let port = free_port();
+        let mut worker = test_worker(&dir, &["--port", &port.to_string(), "--bind", "0.0.0.0"]);
+        assert_eq!(worker.bind_addr, SocketAddr::from(([0, 0, 0, 0], port)));
+
+        let server_notifier = worker.server_notifier.clone();
+        let server = tokio::spawn(async move { worker.start_server().await.unwrap() });
+
+        // the server is reachable through any local address
+        let mut client = connect_when_up(port).await;
+        let status = client
+            .cancel_job(proto::CancelJobRequest {
+                job_id: 1,
+                user: "alice".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        server_notifier.send(()).unwrap();
+        server.await.unwrap();
+    }
+}
@@ -1,7 +1,7 @@
 #!/bin/bash
 set -e
 
-for crate in melond mbatch mqueue mcancel mextend mshow; do
+for crate in melond mbatch mqueue mcancel mextend mdrain mnodes mhold mrelease mshow mtop madmin mreserve mstat mrun mrerun mstep; do
     echo "Building $crate..."
     cargo build --release --manifest-path crates/$crate/Cargo.toml
 done
@@ -9,7 +9,7 @@ done
 cargo build --release --manifest-path crates/mworker/Cargo.toml --features cgroups
 
 echo "Installing binaries to /usr/local/bin. You may be prompted for your password."
-for crate in melond mbatch mworker mqueue mcancel mextend mshow; do
+for crate in melond mbatch mworker mqueue mcancel mextend mdrain mnodes mhold mrelease mshow mtop madmin mreserve mstat mrun mrerun mstep; do
     echo "Installing $crate to /usr/local/bin..."
     sudo cp target/release/$crate /usr/local/bin/
     sudo chmod +x /usr/local/bin/$crate